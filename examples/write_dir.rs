@@ -3,7 +3,10 @@
 use anyhow::Context;
 use clap::{Parser, ValueEnum};
 use std::io::prelude::*;
-use zip::{result::ZipError, write::SimpleFileOptions};
+use zip::{
+    result::ZipError,
+    write::{ExtensionOptions, SimpleFileOptions},
+};
 
 use std::fs::File;
 use std::path::{Path, PathBuf};
@@ -125,6 +128,9 @@ where
     let options = SimpleFileOptions::default()
         .compression_method(method)
         .unix_permissions(0o755);
+    // Already-compressed formats are stored and everything else uses the requested method, at the
+    // highest level for text if that's deflate. All of them keep the permissions above.
+    let file_options = ExtensionOptions::with_base(options);
 
     let prefix = Path::new(prefix);
     let mut buffer = Vec::new();
@@ -140,7 +146,7 @@ where
         // Some unzip tools unzip files with directory paths correctly, some do not!
         if path.is_file() {
             println!("adding file {path:?} as {name:?} ...");
-            zip.start_file(path_as_string, options)?;
+            zip.start_file(path_as_string, file_options.options_for(path))?;
             let mut f = File::open(path)?;
 
             f.read_to_end(&mut buffer)?;
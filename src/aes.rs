@@ -68,8 +68,9 @@ pub struct AesReader<R> {
 
 impl<R: Read> AesReader<R> {
     pub const fn new(reader: R, aes_mode: AesMode, compressed_size: u64) -> AesReader<R> {
+        // A file too short to hold the salt and codes fails once they're read
         let data_length = compressed_size
-            - (PWD_VERIFY_LENGTH + AUTH_CODE_LENGTH + aes_mode.salt_length()) as u64;
+            .saturating_sub((PWD_VERIFY_LENGTH + AUTH_CODE_LENGTH + aes_mode.salt_length()) as u64);
 
         Self {
             reader,
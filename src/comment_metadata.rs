@@ -0,0 +1,187 @@
+//! Key-value metadata stored in the archive comment as a JSON object of strings
+
+use crate::result::{ZipError, ZipResult};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Encodes `metadata` as a JSON object with its keys in order, and checks that it fits in an
+/// archive comment.
+pub(crate) fn encode(metadata: &BTreeMap<String, String>) -> ZipResult<Vec<u8>> {
+    let mut json = String::from("{");
+    for (i, (key, value)) in metadata.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        push_string(&mut json, key);
+        json.push(':');
+        push_string(&mut json, value);
+    }
+    json.push('}');
+    if json.len() > u16::MAX as usize {
+        return Err(ZipError::InvalidArchive(
+            "Comment metadata is too large for an archive comment",
+        ));
+    }
+    Ok(json.into_bytes())
+}
+
+fn push_string(json: &mut String, s: &str) {
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+/// Decodes a comment written by [`encode`], or any other JSON object whose values are all
+/// strings. Returns `None` if the comment is anything else.
+pub(crate) fn decode(comment: &[u8]) -> Option<BTreeMap<String, String>> {
+    let json = std::str::from_utf8(comment).ok()?;
+    let mut parser = Parser {
+        rest: json.trim_start(),
+    };
+    let mut metadata = BTreeMap::new();
+    parser.expect('{')?;
+    if !parser.eat('}') {
+        loop {
+            let key = parser.string()?;
+            parser.expect(':')?;
+            let value = parser.string()?;
+            metadata.insert(key, value);
+            if parser.eat('}') {
+                break;
+            }
+            parser.expect(',')?;
+        }
+    }
+    parser.rest.trim().is_empty().then_some(metadata)
+}
+
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl Parser<'_> {
+    fn eat(&mut self, c: char) -> bool {
+        self.rest = self.rest.trim_start();
+        match self.rest.strip_prefix(c) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Option<()> {
+        self.eat(c).then_some(())
+    }
+
+    fn string(&mut self) -> Option<String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        let mut chars = self.rest.char_indices();
+        loop {
+            let (i, c) = chars.next()?;
+            match c {
+                '"' => {
+                    self.rest = &self.rest[i + 1..];
+                    return Some(s);
+                }
+                '\\' => {
+                    let (_, escape) = chars.next()?;
+                    s.push(match escape {
+                        '"' => '"',
+                        '\\' => '\\',
+                        '/' => '/',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'u' => {
+                            let high = hex4(&mut chars)?;
+                            if (0xd800..0xdc00).contains(&high) {
+                                // A surrogate pair, which must be followed by its low half
+                                if chars.next()?.1 != '\\' || chars.next()?.1 != 'u' {
+                                    return None;
+                                }
+                                let low = hex4(&mut chars)?;
+                                if !(0xdc00..0xe000).contains(&low) {
+                                    return None;
+                                }
+                                char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00))?
+                            } else {
+                                char::from_u32(high)?
+                            }
+                        }
+                        _ => return None,
+                    });
+                }
+                c if (c as u32) < 0x20 => return None,
+                c => s.push(c),
+            }
+        }
+    }
+}
+
+fn hex4(chars: &mut std::str::CharIndices<'_>) -> Option<u32> {
+    let mut value = 0;
+    for _ in 0..4 {
+        value = value * 16 + chars.next()?.1.to_digit(16)?;
+    }
+    Some(value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode, encode};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn round_trip() {
+        let mut metadata = BTreeMap::new();
+        metadata.insert("commit".to_string(), "0123abc".to_string());
+        metadata.insert(
+            "quote \"\\".to_string(),
+            "line\nbreak\u{1}\u{1F600}".to_string(),
+        );
+        let encoded = encode(&metadata).unwrap();
+        assert_eq!(decode(&encoded), Some(metadata));
+        assert_eq!(decode(b"{}"), Some(BTreeMap::new()));
+    }
+
+    #[test]
+    fn decode_other_json() {
+        let metadata = decode(r#" { "a" : "é😀\/" , "b":"" } "#.as_bytes()).unwrap();
+        assert_eq!(metadata["a"], "\u{e9}\u{1F600}/");
+        assert_eq!(metadata["b"], "");
+        for comment in [
+            &b""[..],
+            b"Created by a build server",
+            br#"{"a": 1}"#,
+            br#"{"a": "b",}"#,
+            br#"{"a": "b"} trailing"#,
+            br#"{"a": "\ud83d"}"#,
+        ] {
+            assert_eq!(decode(comment), None);
+        }
+    }
+
+    #[test]
+    fn too_large() {
+        let mut metadata = BTreeMap::new();
+        metadata.insert("key".to_string(), "x".repeat(u16::MAX as usize));
+        assert!(encode(&metadata).is_err());
+    }
+}
@@ -0,0 +1,209 @@
+//! Compression methods implemented outside this crate
+
+use crate::compression::CompressionMethod;
+use crate::result::{ZipError, ZipResult};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+/// A compression method that this crate doesn't implement, such as PPMd or WavPack, provided by
+/// another crate and added to a [`CompressionRegistry`].
+pub trait CustomCompression: Send + Sync {
+    /// Wraps `compressed`, which reads a file's compressed data, in a reader that decompresses it.
+    fn decoder<'a>(&self, compressed: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>>;
+
+    /// Returns an encoder for one file, given the level set with
+    /// [`crate::write::FileOptions::compression_level`].
+    fn encoder(&self, level: Option<i64>) -> io::Result<Box<dyn CustomEncoder>>;
+}
+
+/// Compresses the contents of one file for a [`CustomCompression`].
+pub trait CustomEncoder: Send {
+    /// Compresses `input`, appending whatever compressed data is ready to `output`.
+    fn compress(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<()>;
+
+    /// Appends the rest of the compressed data to `output`, once all the input has been given.
+    fn finish(self: Box<Self>, output: &mut Vec<u8>) -> io::Result<()>;
+}
+
+/// The compression methods, by ID, that an archive is read or written with beyond those this
+/// crate implements. Set one with [`crate::ZipArchive::set_compression_registry`] or
+/// [`crate::ZipWriter::set_compression_registry`], and choose a registered method for writing
+/// with the matching [`CompressionMethod`] constant, such as [`CompressionMethod::PPMD`].
+#[derive(Clone, Default)]
+pub struct CompressionRegistry {
+    methods: HashMap<u16, Arc<dyn CustomCompression>>,
+}
+
+impl CompressionRegistry {
+    /// Creates a registry with no methods.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `method` under the compression method ID `id`, replacing any method already
+    /// registered there. IDs of the methods this crate implements, with the features it was built
+    /// with, can't be registered.
+    pub fn register<M: CustomCompression + 'static>(
+        &mut self,
+        id: u16,
+        method: M,
+    ) -> ZipResult<()> {
+        #[allow(deprecated)]
+        if !matches!(
+            CompressionMethod::from_u16(id),
+            CompressionMethod::Unsupported(_)
+        ) {
+            return Err(ZipError::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The compression method is already implemented",
+            )));
+        }
+        self.methods.insert(id, Arc::new(method));
+        Ok(())
+    }
+
+    /// Returns the method registered for `method`, if it's one this crate doesn't implement.
+    pub(crate) fn get(&self, method: CompressionMethod) -> Option<Arc<dyn CustomCompression>> {
+        #[allow(deprecated)]
+        match method {
+            CompressionMethod::Unsupported(id) => self.methods.get(&id).cloned(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Debug for CompressionRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut ids: Vec<_> = self.methods.keys().collect();
+        ids.sort_unstable();
+        f.debug_struct("CompressionRegistry")
+            .field("methods", &ids)
+            .finish()
+    }
+}
+
+/// Writes the data compressed by a [`CustomEncoder`] to `inner`.
+pub(crate) struct CustomWriter<W> {
+    encoder: Box<dyn CustomEncoder>,
+    inner: W,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> CustomWriter<W> {
+    pub(crate) fn new(encoder: Box<dyn CustomEncoder>, inner: W) -> Self {
+        CustomWriter {
+            encoder,
+            inner,
+            buffer: Vec::new(),
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        self.encoder.finish(&mut self.buffer)?;
+        self.inner.write_all(&self.buffer)?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for CustomWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.encoder.compress(buf, &mut self.buffer)?;
+        self.inner.write_all(&self.buffer)?;
+        self.buffer.clear();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CompressionRegistry, CustomCompression, CustomEncoder};
+    use crate::result::ZipError;
+    use crate::write::SimpleFileOptions;
+    use crate::{CompressionMethod, ZipArchive, ZipWriter};
+    use std::io::{self, Cursor, Read, Write};
+    use std::sync::Arc;
+
+    /// Stores each run of a byte as the byte followed by the run's length
+    struct RunLength;
+
+    struct RunLengthEncoder(Option<(u8, u8)>);
+
+    impl CustomEncoder for RunLengthEncoder {
+        fn compress(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+            for &byte in input {
+                match &mut self.0 {
+                    Some((run_byte, count)) if *run_byte == byte && *count < u8::MAX => *count += 1,
+                    run => {
+                        if let Some((run_byte, count)) = run.replace((byte, 1)) {
+                            output.extend_from_slice(&[run_byte, count]);
+                        }
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        fn finish(self: Box<Self>, output: &mut Vec<u8>) -> io::Result<()> {
+            if let Some((byte, count)) = self.0 {
+                output.extend_from_slice(&[byte, count]);
+            }
+            Ok(())
+        }
+    }
+
+    impl CustomCompression for RunLength {
+        fn decoder<'a>(
+            &self,
+            mut compressed: Box<dyn Read + 'a>,
+        ) -> io::Result<Box<dyn Read + 'a>> {
+            let mut runs = Vec::new();
+            compressed.read_to_end(&mut runs)?;
+            let data: Vec<u8> = runs
+                .chunks(2)
+                .flat_map(|run| vec![run[0]; run[1] as usize])
+                .collect();
+            Ok(Box::new(Cursor::new(data)))
+        }
+
+        fn encoder(&self, _: Option<i64>) -> io::Result<Box<dyn CustomEncoder>> {
+            Ok(Box::new(RunLengthEncoder(None)))
+        }
+    }
+
+    #[test]
+    fn custom_method_round_trip() {
+        let mut registry = CompressionRegistry::new();
+        registry.register(0x8001, RunLength).unwrap();
+        assert!(registry.register(0, RunLength).is_err());
+        let registry = Arc::new(registry);
+        #[allow(deprecated)]
+        let method = CompressionMethod::Unsupported(0x8001);
+        let data: Vec<u8> = (0..1000u32).map(|i| (i / 300) as u8).collect();
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_compression_registry(registry.clone());
+        let options = SimpleFileOptions::default().compression_method(method);
+        writer.start_file("runs", options).unwrap();
+        writer.write_all(&data).unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        assert!(matches!(
+            archive.by_name("runs"),
+            Err(ZipError::UnsupportedArchive(_))
+        ));
+        archive.set_compression_registry(registry);
+        let mut file = archive.by_name("runs").unwrap();
+        assert_eq!(file.compression(), method);
+        assert!(file.compressed_size() < 20);
+        let mut read = Vec::new();
+        file.read_to_end(&mut read).unwrap();
+        assert_eq!(read, data);
+    }
+}
@@ -0,0 +1,319 @@
+//! Archives produced by other ZIP implementations, and checks that the enabled features of this
+//! crate handle them
+//!
+//! Every fixture holds the same few files, written by Info-ZIP `zip` 3.0, OpenJDK 17's `jar` tool
+//! (`java.util.zip`) or CPython 3.11's `zipfile` module, with each producer's usual choice of
+//! compression, extra fields, data descriptors and Zip64 records. [`check_reader`] reads all of
+//! them, and [`check_writer`] checks that archives written with each enabled compression method
+//! and encryption read back the same way.
+
+use crate::compression::CompressionMethod;
+use crate::read::ZipArchive;
+use crate::result::{ZipError, ZipResult};
+use crate::types::DateTime;
+use crate::write::{SimpleFileOptions, ZipWriter};
+use std::fmt;
+use std::io::{self, Cursor, Read, Write};
+
+const HELLO: &[u8] = b"Hello, world!\n";
+const LOREM: &[u8] = include_bytes!("conformance/lorem.txt");
+
+/// The files in every fixture except the streamed ones
+const TREE: &[(&str, &[u8])] = &[
+    ("hello.txt", HELLO),
+    ("empty.txt", b""),
+    ("dir/lorem.txt", LOREM),
+];
+
+/// Info-ZIP names an entry read from standard input `-`
+const STDIN: &[(&str, &[u8])] = &[("-", LOREM)];
+
+/// An archive written by another ZIP implementation
+#[derive(Clone, Copy, Debug)]
+pub struct Fixture {
+    /// A short unique name
+    pub name: &'static str,
+    /// The program that wrote the archive
+    pub producer: &'static str,
+    /// The archive's contents
+    pub bytes: &'static [u8],
+    /// The password the entries are encrypted with, if they are
+    pub password: Option<&'static [u8]>,
+    /// The compression methods used by the entries
+    pub methods: &'static [CompressionMethod],
+    /// Files the archive must contain, with their contents. Other entries, such as directories
+    /// and the manifest added by `jar`, must only be readable.
+    pub files: &'static [(&'static str, &'static [u8])],
+}
+
+impl Fixture {
+    /// Opens the fixture
+    pub fn archive(&self) -> ZipResult<ZipArchive<Cursor<&'static [u8]>>> {
+        ZipArchive::new(Cursor::new(self.bytes))
+    }
+}
+
+/// Returns every fixture.
+pub fn fixtures() -> &'static [Fixture] {
+    const INFOZIP: &str = "Info-ZIP zip 3.0";
+    const JAR: &str = "OpenJDK 17 jar";
+    const PYTHON: &str = "CPython 3.11 zipfile";
+    const DEFLATE_AND_STORE: &[CompressionMethod] =
+        &[CompressionMethod::STORE, CompressionMethod::DEFLATE];
+    &[
+        Fixture {
+            name: "infozip_deflate",
+            producer: INFOZIP,
+            bytes: include_bytes!("conformance/infozip_deflate.zip"),
+            password: None,
+            methods: DEFLATE_AND_STORE,
+            files: TREE,
+        },
+        Fixture {
+            name: "infozip_stored",
+            producer: INFOZIP,
+            bytes: include_bytes!("conformance/infozip_stored.zip"),
+            password: None,
+            methods: &[CompressionMethod::STORE],
+            files: TREE,
+        },
+        Fixture {
+            name: "infozip_streamed",
+            producer: INFOZIP,
+            bytes: include_bytes!("conformance/infozip_streamed.zip"),
+            password: None,
+            methods: &[CompressionMethod::DEFLATE],
+            files: STDIN,
+        },
+        Fixture {
+            name: "infozip_zipcrypto",
+            producer: INFOZIP,
+            bytes: include_bytes!("conformance/infozip_zipcrypto.zip"),
+            password: Some(b"conformance"),
+            methods: DEFLATE_AND_STORE,
+            files: TREE,
+        },
+        Fixture {
+            name: "jar",
+            producer: JAR,
+            bytes: include_bytes!("conformance/jar.zip"),
+            password: None,
+            methods: DEFLATE_AND_STORE,
+            files: TREE,
+        },
+        Fixture {
+            name: "python_deflate",
+            producer: PYTHON,
+            bytes: include_bytes!("conformance/python_deflate.zip"),
+            password: None,
+            methods: DEFLATE_AND_STORE,
+            files: TREE,
+        },
+        Fixture {
+            name: "python_stored",
+            producer: PYTHON,
+            bytes: include_bytes!("conformance/python_stored.zip"),
+            password: None,
+            methods: &[CompressionMethod::STORE],
+            files: TREE,
+        },
+        Fixture {
+            name: "python_streamed",
+            producer: PYTHON,
+            bytes: include_bytes!("conformance/python_streamed.zip"),
+            password: None,
+            methods: DEFLATE_AND_STORE,
+            files: TREE,
+        },
+        Fixture {
+            name: "python_zip64",
+            producer: PYTHON,
+            bytes: include_bytes!("conformance/python_zip64.zip"),
+            password: None,
+            methods: DEFLATE_AND_STORE,
+            files: TREE,
+        },
+        Fixture {
+            name: "python_bzip2",
+            producer: PYTHON,
+            bytes: include_bytes!("conformance/python_bzip2.zip"),
+            password: None,
+            methods: &[CompressionMethod::STORE, CompressionMethod::BZIP2],
+            files: TREE,
+        },
+        Fixture {
+            name: "python_lzma",
+            producer: PYTHON,
+            bytes: include_bytes!("conformance/python_lzma.zip"),
+            password: None,
+            methods: &[CompressionMethod::STORE, CompressionMethod::LZMA],
+            files: TREE,
+        },
+    ]
+}
+
+/// An archive that couldn't be read correctly
+#[derive(Debug)]
+pub struct Failure {
+    /// The fixture's name, or for [`check_writer`], the options the archive was written with
+    pub archive: String,
+    /// What went wrong
+    pub error: ZipError,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.archive, self.error)
+    }
+}
+
+/// Reads every entry of `archive`, which also verifies their checksums, and compares the contents
+/// of `files`.
+fn check_archive<R: Read + io::Seek>(
+    archive: &mut ZipArchive<R>,
+    password: Option<&[u8]>,
+    files: &[(&str, &[u8])],
+) -> ZipResult<()> {
+    let mut contents = Vec::with_capacity(archive.len());
+    for i in 0..archive.len() {
+        let mut file = match password {
+            Some(password) => archive.by_index_decrypt(i, password)?,
+            None => archive.by_index(i)?,
+        };
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        contents.push((file.name().to_string(), data));
+    }
+    for (name, expected) in files {
+        match contents.iter().find(|(found, _)| found == name) {
+            None => return Err(ZipError::FileNotFound),
+            Some((_, data)) if data != expected => {
+                return Err(ZipError::Io(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{name} doesn't have the expected contents"),
+                )))
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Checks that every fixture can be read, and that its files have the expected contents.
+///
+/// Fixtures that use a compression method whose feature isn't enabled fail with
+/// [`ZipError::UnsupportedArchive`]; their [`Fixture::methods`] show which features they need.
+pub fn check_reader() -> Result<(), Vec<Failure>> {
+    let failures: Vec<Failure> = fixtures()
+        .iter()
+        .filter_map(|fixture| {
+            let result = fixture.archive().and_then(|mut archive| {
+                check_archive(&mut archive, fixture.password, fixture.files)
+            });
+            result.err().map(|error| Failure {
+                archive: fixture.name.to_string(),
+                error,
+            })
+        })
+        .collect();
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures)
+    }
+}
+
+/// Writes the fixtures' files with every compression method this crate can write with the
+/// enabled features, with each kind of encryption, and with Zip64 headers, and checks that the
+/// archives read back correctly.
+pub fn check_writer() -> Result<(), Vec<Failure>> {
+    let options = SimpleFileOptions::default()
+        .last_modified_time(DateTime::default())
+        .unix_permissions(0o644);
+    let mut variants: Vec<(String, SimpleFileOptions, Option<&[u8]>)> = vec![(
+        "Stored".to_string(),
+        options.compression_method(CompressionMethod::Stored),
+        None,
+    )];
+    #[allow(deprecated)]
+    let methods: &[CompressionMethod] = &[
+        #[cfg(feature = "_deflate-any")]
+        CompressionMethod::Deflated,
+        #[cfg(feature = "bzip2")]
+        CompressionMethod::Bzip2,
+        #[cfg(feature = "zstd")]
+        CompressionMethod::Zstd,
+    ];
+    for &method in methods {
+        variants.push((method.to_string(), options.compression_method(method), None));
+    }
+    let default_method = variants.last().unwrap().1;
+    variants.push(("Zip64".to_string(), default_method.large_file(true), None));
+    variants.push((
+        "ZipCrypto".to_string(),
+        default_method.with_deprecated_encryption(b"conformance"),
+        Some(b"conformance"),
+    ));
+    #[cfg(feature = "aes-crypto")]
+    variants.push((
+        "AES-256".to_string(),
+        default_method.with_aes_encryption(crate::AesMode::Aes256, "conformance"),
+        Some(b"conformance"),
+    ));
+
+    let failures: Vec<Failure> = variants
+        .into_iter()
+        .filter_map(|(name, options, password)| {
+            let result = write_tree(options)
+                .and_then(|mut archive| check_archive(&mut archive, password, TREE));
+            result.err().map(|error| Failure {
+                archive: name,
+                error,
+            })
+        })
+        .collect();
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures)
+    }
+}
+
+fn write_tree(options: SimpleFileOptions) -> ZipResult<ZipArchive<Cursor<Vec<u8>>>> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    writer.add_directory("dir/", options)?;
+    for (name, contents) in TREE {
+        writer.start_file(*name, options)?;
+        writer.write_all(contents)?;
+    }
+    writer.finish_into_readable()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_reader, check_writer, fixtures};
+    use std::collections::HashSet;
+
+    #[test]
+    fn fixture_names_are_unique() {
+        let names: HashSet<_> = fixtures().iter().map(|fixture| fixture.name).collect();
+        assert_eq!(names.len(), fixtures().len());
+    }
+
+    #[cfg(all(feature = "_deflate-any", feature = "bzip2", feature = "lzma"))]
+    #[test]
+    fn reader() {
+        if let Err(failures) = check_reader() {
+            let failures: Vec<_> = failures.iter().map(ToString::to_string).collect();
+            panic!("{failures:#?}");
+        }
+    }
+
+    #[test]
+    fn writer() {
+        if let Err(failures) = check_writer() {
+            let failures: Vec<_> = failures.iter().map(ToString::to_string).collect();
+            panic!("{failures:#?}");
+        }
+    }
+}
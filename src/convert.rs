@@ -0,0 +1,563 @@
+//! Converting between ZIP archives and tar streams
+//!
+//! [`zip_to_tar`] and [`tar_to_zip`] carry each entry's contents, Unix permissions, owner,
+//! modification time and symlink target across, streaming contents rather than buffering whole
+//! entries. Tar streams are written in the POSIX ustar format, with pax extended headers for names,
+//! link targets, sizes and owners that don't fit its fields, and pax and GNU long names are
+//! understood when reading.
+
+use crate::extra_fields::ExtendedTimestamp;
+use crate::read::ZipArchive;
+use crate::result::{ZipError, ZipResult};
+use crate::spec;
+use crate::types::DateTime;
+use crate::write::{SimpleFileOptions, ZipWriter};
+use std::io::{self, Read, Seek, Write};
+use std::time::{Duration, UNIX_EPOCH};
+
+/// The size of tar headers, and the unit entries' contents are padded to
+const BLOCK: usize = 512;
+
+/// Name of the pax extended headers that are written, as GNU tar names them
+const PAX_HEADER_NAME: &str = "././@PaxHeader";
+
+/// The largest pax extended header that's read, to bound memory use
+const MAX_PAX_HEADER: u64 = 1024 * 1024;
+
+/// Typeflags of the tar entries that are converted
+const REGULAR: u8 = b'0';
+const OLD_REGULAR: u8 = b'\0';
+const CONTIGUOUS: u8 = b'7';
+const SYMLINK: u8 = b'2';
+const DIRECTORY: u8 = b'5';
+const PAX_EXTENDED: u8 = b'x';
+const GNU_LONG_NAME: u8 = b'L';
+const GNU_LONG_LINK: u8 = b'K';
+
+/// The fields of a tar header that are carried to and from a ZIP entry
+#[derive(Debug, Default)]
+struct TarEntry {
+    path: String,
+    link: String,
+    kind: u8,
+    mode: u32,
+    uid: u64,
+    gid: u64,
+    mtime: u64,
+    size: u64,
+}
+
+impl TarEntry {
+    /// Returns the ustar header for the entry, with `path` split into `prefix` and `name`, and any
+    /// number too large for its field clamped to the largest it holds.
+    fn to_block(&self, prefix: &str, name: &str) -> [u8; BLOCK] {
+        let mut block = [0; BLOCK];
+        put_str(&mut block[0..100], name);
+        put_octal(&mut block[100..108], self.mode.into());
+        put_octal(&mut block[108..116], self.uid);
+        put_octal(&mut block[116..124], self.gid);
+        put_octal(&mut block[124..136], self.size);
+        put_octal(&mut block[136..148], self.mtime);
+        block[156] = self.kind;
+        put_str(&mut block[157..257], &self.link);
+        block[257..265].copy_from_slice(b"ustar\x0000");
+        put_str(&mut block[345..500], prefix);
+        let checksum = format!("{:06o}\0 ", checksum(&block));
+        block[148..156].copy_from_slice(checksum.as_bytes());
+        block
+    }
+
+    /// Parses a ustar, or older, tar header.
+    fn from_block(block: &[u8; BLOCK]) -> ZipResult<Self> {
+        if parse_octal(&block[148..156])? != u64::from(checksum(block)) {
+            return Err(ZipError::InvalidArchive("Invalid tar header checksum"));
+        }
+        let mut path = parse_str(&block[0..100]);
+        if &block[257..262] == b"ustar" {
+            let prefix = parse_str(&block[345..500]);
+            if !prefix.is_empty() {
+                path = format!("{prefix}/{path}");
+            }
+        }
+        Ok(TarEntry {
+            path,
+            link: parse_str(&block[157..257]),
+            kind: block[156],
+            mode: parse_octal(&block[100..108])? as u32,
+            uid: parse_octal(&block[108..116])?,
+            gid: parse_octal(&block[116..124])?,
+            mtime: parse_octal(&block[136..148])?,
+            size: parse_octal(&block[124..136])?,
+        })
+    }
+
+    /// Writes the entry's header, preceded by a pax extended header if any of its fields don't
+    /// fit in ustar's.
+    fn write_header<W: Write>(&self, tar: &mut W) -> io::Result<()> {
+        let mut pax = Vec::new();
+        let (prefix, name) = split_path(&self.path).unwrap_or_else(|| {
+            pax_record(&mut pax, "path", &self.path);
+            ("", &self.path)
+        });
+        if self.link.len() > 100 {
+            pax_record(&mut pax, "linkpath", &self.link);
+        }
+        for (key, value, len) in [
+            ("size", self.size, 12),
+            ("mtime", self.mtime, 12),
+            ("uid", self.uid, 8),
+            ("gid", self.gid, 8),
+        ] {
+            if value > max_octal(len) {
+                pax_record(&mut pax, key, &value.to_string());
+            }
+        }
+        if !pax.is_empty() {
+            let header = TarEntry {
+                kind: PAX_EXTENDED,
+                mode: 0o644,
+                mtime: self.mtime,
+                size: pax.len() as u64,
+                ..TarEntry::default()
+            };
+            tar.write_all(&header.to_block("", PAX_HEADER_NAME))?;
+            tar.write_all(&pax)?;
+            tar.write_all(&[0; BLOCK][..padding(pax.len() as u64)])?;
+        }
+        tar.write_all(&self.to_block(prefix, name))
+    }
+}
+
+/// Returns the header checksum: the sum of its bytes, with the checksum field taken as spaces.
+fn checksum(block: &[u8; BLOCK]) -> u32 {
+    block[..148]
+        .iter()
+        .chain(&[b' '; 8])
+        .chain(&block[156..])
+        .map(|&byte| u32::from(byte))
+        .sum()
+}
+
+/// Splits `path` into a ustar prefix and name, or returns `None` if it can't fit.
+fn split_path(path: &str) -> Option<(&str, &str)> {
+    if path.len() <= 100 {
+        return Some(("", path));
+    }
+    // The prefix should be as long as possible, so the name is as short as possible
+    let split = path
+        .match_indices('/')
+        .map(|(index, _)| index)
+        .find(|&index| index > 0 && path.len() - index - 1 <= 100)?;
+    (split <= 155 && split + 1 < path.len()).then(|| (&path[..split], &path[split + 1..]))
+}
+
+/// Appends a pax extended header record, which starts with its own length in decimal.
+fn pax_record(pax: &mut Vec<u8>, key: &str, value: &str) {
+    let record = format!(" {key}={value}\n");
+    let mut len = record.len() + 1;
+    while len.to_string().len() + record.len() != len {
+        len = len.to_string().len() + record.len();
+    }
+    pax.extend_from_slice(len.to_string().as_bytes());
+    pax.extend_from_slice(record.as_bytes());
+}
+
+/// Parses pax extended header records into their keys and values.
+fn parse_pax(mut pax: &[u8]) -> ZipResult<Vec<(String, String)>> {
+    let invalid = || ZipError::InvalidArchive("Invalid pax extended header");
+    let mut records = Vec::new();
+    while !pax.is_empty() {
+        let space = pax
+            .iter()
+            .position(|&byte| byte == b' ')
+            .ok_or_else(invalid)?;
+        let len: usize = std::str::from_utf8(&pax[..space])
+            .ok()
+            .and_then(|len| len.parse().ok())
+            .filter(|&len| len > space && len <= pax.len())
+            .ok_or_else(invalid)?;
+        let record = pax[space + 1..len]
+            .strip_suffix(b"\n")
+            .ok_or_else(invalid)?;
+        let equals = record
+            .iter()
+            .position(|&byte| byte == b'=')
+            .ok_or_else(invalid)?;
+        records.push((
+            String::from_utf8_lossy(&record[..equals]).into_owned(),
+            String::from_utf8_lossy(&record[equals + 1..]).into_owned(),
+        ));
+        pax = &pax[len..];
+    }
+    Ok(records)
+}
+
+/// Returns the largest number an octal field of `len` bytes holds, with its terminating NUL.
+const fn max_octal(len: usize) -> u64 {
+    (1 << (3 * (len - 1))) - 1
+}
+
+/// Writes `value` to a NUL-terminated octal field, clamped to the largest it holds.
+fn put_octal(field: &mut [u8], value: u64) {
+    let digits = field.len() - 1;
+    let value = value.min(max_octal(field.len()));
+    field[..digits].copy_from_slice(format!("{value:0digits$o}").as_bytes());
+    field[digits] = 0;
+}
+
+/// Copies as much of `value` into a field as fits.
+fn put_str(field: &mut [u8], value: &str) {
+    let len = value.len().min(field.len());
+    field[..len].copy_from_slice(&value.as_bytes()[..len]);
+}
+
+/// Parses a numeric field, in octal or in the base-256 encoding GNU tar uses for large numbers.
+fn parse_octal(field: &[u8]) -> ZipResult<u64> {
+    let invalid = || ZipError::InvalidArchive("Invalid number in tar header");
+    if field[0] & 0x80 != 0 {
+        return field[1..]
+            .iter()
+            .try_fold(u64::from(field[0] & 0x7f), |value, &byte| {
+                value.checked_mul(256).map(|value| value | u64::from(byte))
+            })
+            .ok_or_else(invalid);
+    }
+    let digits = std::str::from_utf8(field)
+        .map_err(|_| invalid())?
+        .trim_matches(['\0', ' ']);
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(digits, 8).map_err(|_| invalid())
+}
+
+/// Returns a NUL-terminated text field.
+fn parse_str(field: &[u8]) -> String {
+    let len = field
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..len]).into_owned()
+}
+
+/// Returns how many bytes pad `size` bytes of contents to a whole block.
+fn padding(size: u64) -> usize {
+    (BLOCK - (size % BLOCK as u64) as usize) % BLOCK
+}
+
+/// Reads and discards `len` bytes.
+fn skip<R: Read>(tar: &mut R, len: u64) -> ZipResult<()> {
+    if io::copy(&mut tar.take(len), &mut io::sink())? < len {
+        return Err(ZipError::InvalidArchive("Truncated tar entry"));
+    }
+    Ok(())
+}
+
+/// Reads the contents of an entry holding metadata, such as a pax extended header, and their
+/// padding.
+fn read_contents<R: Read>(tar: &mut R, size: u64) -> ZipResult<Vec<u8>> {
+    if size > MAX_PAX_HEADER {
+        return Err(ZipError::InvalidArchive("Tar extended header is too large"));
+    }
+    let mut contents = vec![0; size as usize];
+    tar.read_exact(&mut contents)?;
+    skip(tar, padding(size) as u64)?;
+    Ok(contents)
+}
+
+/// Returns the seconds since the Unix epoch of an MS-DOS time, taken to be in UTC.
+fn unix_time(time: DateTime) -> u64 {
+    let (year, month) = match time.month() {
+        1 | 2 => (i64::from(time.year()) - 1, i64::from(time.month()) + 9),
+        month => (i64::from(time.year()), i64::from(month) - 3),
+    };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * month + 2) / 5 + i64::from(time.day()) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    let seconds =
+        i64::from(time.hour()) * 3600 + i64::from(time.minute()) * 60 + i64::from(time.second());
+    (days * 86400 + seconds).max(0) as u64
+}
+
+/// Returns the MS-DOS time, in UTC, of a time in seconds since the Unix epoch, if it's in the
+/// range MS-DOS times cover.
+fn dos_time(unix_time: u64) -> Option<DateTime> {
+    let days = i64::try_from(unix_time / 86400).ok()? + 719_468;
+    let seconds = unix_time % 86400;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let (year, month) = match month {
+        10.. => (era * 400 + year_of_era + 1, month - 9),
+        _ => (era * 400 + year_of_era, month + 3),
+    };
+    DateTime::from_date_and_time(
+        u16::try_from(year).ok()?,
+        month as u8,
+        day as u8,
+        (seconds / 3600) as u8,
+        (seconds / 60 % 60) as u8,
+        (seconds % 60) as u8,
+    )
+    .ok()
+}
+
+/// Writes every entry of `archive` to `tar` as a tar stream, in order, ending it with the two
+/// empty blocks that mark the end of a tar archive.
+///
+/// Directories and symlinks become tar directories and symlinks. Entries keep their Unix
+/// permissions and owner if the archive records them, and otherwise get 755 for directories and
+/// 644 for files, owned by root. The modification time is taken from the extended timestamp if
+/// there is one, and otherwise from the MS-DOS time, which is taken to be in UTC.
+///
+/// Contents are streamed through as they're decompressed, so an entry's data is rejected only
+/// once it's been written if it doesn't match the size the central directory records.
+pub fn zip_to_tar<R, W>(archive: &mut ZipArchive<R>, mut tar: W) -> ZipResult<()>
+where
+    R: Read + Seek,
+    W: Write,
+{
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        let owner = file.unix_owner();
+        let mut entry = TarEntry {
+            path: file.name().to_owned(),
+            kind: REGULAR,
+            mode: 0o644,
+            uid: owner.map_or(0, |owner| owner.uid().into()),
+            gid: owner.map_or(0, |owner| owner.gid().into()),
+            mtime: file
+                .extended_timestamp()
+                .and_then(|timestamp| timestamp.mod_time().copied())
+                .map_or_else(|| unix_time(file.last_modified()), u64::from),
+            ..TarEntry::default()
+        };
+        if file.is_dir() {
+            entry.kind = DIRECTORY;
+            entry.mode = 0o755;
+        } else if file.is_symlink() {
+            entry.kind = SYMLINK;
+            entry.mode = 0o777;
+            file.read_to_string(&mut entry.link)?;
+        } else {
+            entry.size = file.size();
+        }
+        if let Some(mode) = file.unix_mode() {
+            entry.mode = mode & 0o7777;
+        }
+        entry.write_header(&mut tar)?;
+        if entry.kind == REGULAR {
+            if io::copy(&mut file, &mut tar)? != entry.size {
+                return Err(ZipError::InvalidArchive(
+                    "File size doesn't match the central directory",
+                ));
+            }
+            tar.write_all(&[0; BLOCK][..padding(entry.size)])?;
+        }
+    }
+    tar.write_all(&[0; 2 * BLOCK])?;
+    Ok(())
+}
+
+/// Adds every entry of the tar stream `tar` to `zip`, in order, until the end of the stream or
+/// the empty block that marks the end of the archive.
+///
+/// Files, directories and symlinks become ZIP entries with the same Unix permissions and owner,
+/// and with their modification time stored both in an extended timestamp and as an MS-DOS time
+/// in UTC. Files are compressed with the default method of [`SimpleFileOptions`], and their
+/// contents are streamed through without being buffered. Hard links, devices and FIFOs have no
+/// ZIP equivalent and are left out.
+pub fn tar_to_zip<R, W>(mut tar: R, zip: &mut ZipWriter<W>) -> ZipResult<()>
+where
+    R: Read,
+    W: Write + Seek,
+{
+    let mut pax = Vec::new();
+    let mut long_path = None;
+    let mut long_link = None;
+    loop {
+        let mut block = [0; BLOCK];
+        let mut filled = 0;
+        while filled < BLOCK {
+            match tar.read(&mut block[filled..]) {
+                Ok(0) => break,
+                Ok(len) => filled += len,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        match filled {
+            0 => break,
+            BLOCK if block.iter().all(|&byte| byte == 0) => break,
+            BLOCK => {}
+            _ => return Err(ZipError::InvalidArchive("Truncated tar header")),
+        }
+        let mut entry = TarEntry::from_block(&block)?;
+        match entry.kind {
+            PAX_EXTENDED => {
+                pax = parse_pax(&read_contents(&mut tar, entry.size)?)?;
+                continue;
+            }
+            GNU_LONG_NAME | GNU_LONG_LINK => {
+                let name = parse_str(&read_contents(&mut tar, entry.size)?);
+                match entry.kind {
+                    GNU_LONG_NAME => long_path = Some(name),
+                    _ => long_link = Some(name),
+                }
+                continue;
+            }
+            _ => {}
+        }
+        entry.path = long_path.take().unwrap_or(entry.path);
+        entry.link = long_link.take().unwrap_or(entry.link);
+        for (key, value) in pax.drain(..) {
+            let number = || {
+                // pax times may have a fraction of a second
+                value
+                    .split('.')
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .ok_or(ZipError::InvalidArchive("Invalid pax extended header"))
+            };
+            match key.as_str() {
+                "path" => entry.path = value,
+                "linkpath" => entry.link = value,
+                "size" => entry.size = number()?,
+                "mtime" => entry.mtime = number()?,
+                "uid" => entry.uid = number()?,
+                "gid" => entry.gid = number()?,
+                _ => {}
+            }
+        }
+
+        let mut options = SimpleFileOptions::default().unix_permissions(entry.mode & 0o7777);
+        if let (Ok(uid), Ok(gid)) = (u32::try_from(entry.uid), u32::try_from(entry.gid)) {
+            options = options.unix_owner(uid, gid);
+        }
+        let modified = Duration::from_secs(entry.mtime);
+        if let Ok(timestamp) = ExtendedTimestamp::new(Some(UNIX_EPOCH + modified), None, None) {
+            options = options.extended_timestamp(timestamp);
+        }
+        if let Some(time) = dos_time(entry.mtime) {
+            options = options.last_modified_time(time);
+        }
+        match entry.kind {
+            REGULAR | OLD_REGULAR | CONTIGUOUS if !entry.path.ends_with('/') => {
+                let options = options.large_file(entry.size > spec::ZIP64_BYTES_THR);
+                zip.start_file(entry.path, options)?;
+                if io::copy(&mut (&mut tar).take(entry.size), zip)? < entry.size {
+                    return Err(ZipError::InvalidArchive("Truncated tar entry"));
+                }
+            }
+            // Old tar writers mark directories only by the trailing slash
+            REGULAR | OLD_REGULAR | DIRECTORY => {
+                zip.add_directory(entry.path, options)?;
+                skip(&mut tar, entry.size)?;
+            }
+            SYMLINK => {
+                zip.add_symlink(entry.path, entry.link, options)?;
+                skip(&mut tar, entry.size)?;
+            }
+            // pax global headers, hard links, devices and FIFOs
+            _ => skip(&mut tar, entry.size)?,
+        }
+        skip(&mut tar, padding(entry.size) as u64)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{dos_time, tar_to_zip, unix_time, zip_to_tar, BLOCK};
+    use crate::extra_fields::ExtendedTimestamp;
+    use crate::write::SimpleFileOptions;
+    use crate::{DateTime, ZipArchive, ZipWriter};
+    use std::io::{Cursor, Read, Write};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn dos_times() {
+        let time = DateTime::from_date_and_time(2024, 2, 29, 13, 14, 15).unwrap();
+        assert_eq!(unix_time(time), 1_709_212_455);
+        assert_eq!(
+            dos_time(1_709_212_455).map(|time| (time.datepart(), time.timepart())),
+            Some((time.datepart(), time.timepart()))
+        );
+        assert!(dos_time(0).is_none());
+    }
+
+    #[test]
+    fn round_trip() {
+        let long_name = format!("{}/file", "d".repeat(200));
+        let modified = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let timestamp = ExtendedTimestamp::new(Some(modified), None, None).unwrap();
+        let options = SimpleFileOptions::default()
+            .unix_permissions(0o750)
+            .unix_owner(1000, 100)
+            .extended_timestamp(timestamp);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.add_directory("dir", options).unwrap();
+        writer.start_file("dir/file", options).unwrap();
+        writer.write_all(&[b'x'; 1000]).unwrap();
+        writer.add_symlink("link", "dir/file", options).unwrap();
+        writer.start_file(long_name.as_str(), options).unwrap();
+        writer.write_all(b"contents").unwrap();
+        let mut src = writer.finish_into_readable().unwrap();
+
+        let mut tar = Vec::new();
+        zip_to_tar(&mut src, &mut tar).unwrap();
+        assert_eq!(tar.len() % BLOCK, 0);
+        assert_eq!(&tar[257..262], b"ustar");
+        assert_eq!(&tar[..4], b"dir/");
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        tar_to_zip(Cursor::new(tar), &mut writer).unwrap();
+        let mut dst = ZipArchive::new(writer.finish().unwrap()).unwrap();
+        assert_eq!(dst.len(), 4);
+        for index in 0..dst.len() {
+            let mut file = dst.by_index(index).unwrap();
+            let expected = src.by_name(file.name()).unwrap();
+            assert_eq!(file.unix_mode(), expected.unix_mode(), "{}", file.name());
+            assert_eq!(file.unix_owner(), expected.unix_owner());
+            assert_eq!(
+                file.extended_timestamp().unwrap().mod_time(),
+                Some(&1_700_000_000)
+            );
+            assert_eq!(file.size(), expected.size());
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents).unwrap();
+            if file.name() == long_name {
+                assert_eq!(contents, b"contents");
+            }
+        }
+        assert!(dst.by_name("dir/").unwrap().is_dir());
+        assert!(dst.by_name("link").unwrap().is_symlink());
+        let mut target = String::new();
+        dst.by_name("link")
+            .unwrap()
+            .read_to_string(&mut target)
+            .unwrap();
+        assert_eq!(target, "dir/file");
+    }
+
+    #[test]
+    fn bad_checksum() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("file", SimpleFileOptions::default())
+            .unwrap();
+        let mut src = writer.finish_into_readable().unwrap();
+        let mut tar = Vec::new();
+        zip_to_tar(&mut src, &mut tar).unwrap();
+        tar[0] = b'g';
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        assert!(tar_to_zip(Cursor::new(tar), &mut writer).is_err());
+    }
+}
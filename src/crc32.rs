@@ -5,6 +5,8 @@ use std::io::prelude::*;
 
 use crc32fast::Hasher;
 
+use crate::result::ZipError;
+
 /// Reader that validates the CRC32 when it reaches the EOF.
 pub struct Crc32Reader<R> {
     inner: R,
@@ -42,7 +44,11 @@ impl<R: Read> Read for Crc32Reader<R> {
 
         let count = match self.inner.read(buf) {
             Ok(0) if invalid_check => {
-                return Err(io::Error::new(io::ErrorKind::Other, "Invalid checksum"))
+                return Err(ZipError::ChecksumMismatch {
+                    expected: self.check,
+                    actual: self.hasher.clone().finalize(),
+                }
+                .into())
             }
             Ok(n) => n,
             Err(e) => return Err(e),
@@ -72,6 +78,21 @@ mod test {
             .contains("Invalid checksum"));
     }
 
+    #[test]
+    fn test_mismatch() {
+        let data: &[u8] = b"1234";
+        let mut reader = Crc32Reader::new(data, 1, false);
+        let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(matches!(
+            ZipError::from(err),
+            ZipError::ChecksumMismatch {
+                expected: 1,
+                actual: 0x9be3e0a3
+            }
+        ));
+    }
+
     #[test]
     fn test_byte_by_byte() {
         let data: &[u8] = b"1234";
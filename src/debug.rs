@@ -0,0 +1,511 @@
+//! Field-by-field dumps of an archive's structure, for debugging and bug reports
+
+use crate::compression::CompressionMethod;
+use crate::read::{SegmentKind, ZipArchive};
+use crate::result::ZipResult;
+use crate::types::DateTime;
+use std::fmt;
+use std::io::{Read, Seek, SeekFrom};
+
+/// A decoded field of a [`Record`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Field {
+    /// The field's position in the underlying reader
+    pub offset: u64,
+    /// The field's size in bytes
+    pub len: u64,
+    /// What the field holds
+    pub name: String,
+    /// The field's value, decoded where its meaning is known
+    pub value: String,
+}
+
+/// A header, record or region of an archive
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Record {
+    /// The record's position in the underlying reader
+    pub offset: u64,
+    /// The record's size in bytes
+    pub len: u64,
+    /// What the record is, such as `LOCAL HEADER #1`
+    pub title: String,
+    /// The record's fields, in file order. Regions that aren't headers, such as entry data, have
+    /// none.
+    pub fields: Vec<Field>,
+}
+
+/// Every record of an archive in file order, as returned by [`dump`]
+///
+/// The [`fmt::Display`] implementation prints one line per field, with its offset and size in
+/// hexadecimal, similar to Info-ZIP's `zipdetails`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Dump {
+    /// The records
+    pub records: Vec<Record>,
+}
+
+impl fmt::Display for Dump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, record) in self.records.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            writeln!(
+                f,
+                "{:08X} {:04X} {}",
+                record.offset, record.len, record.title
+            )?;
+            for field in &record.fields {
+                writeln!(
+                    f,
+                    "{:08X} {:04X}   {:<26}{}",
+                    field.offset, field.len, field.name, field.value
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Decodes every header of `archive`, along with the regions between them.
+///
+/// Headers are read as they are on disk, so fields the rest of the crate ignores or rejects, and
+/// headers that contradict the central directory, still appear in the dump. Entry data isn't
+/// read or decrypted.
+pub fn dump<R: Read + Seek>(archive: &mut ZipArchive<R>) -> ZipResult<Dump> {
+    let layout = archive.layout()?;
+    let mut records = Vec::with_capacity(layout.segments.len());
+    let mut local_headers = 0;
+    for segment in layout.segments {
+        let offset = segment.range.start;
+        let len = segment.len();
+        let title = match segment.kind {
+            SegmentKind::Prefix => "PREFIX DATA",
+            SegmentKind::LocalHeader => "LOCAL HEADER",
+            SegmentKind::EntryData => "PAYLOAD",
+            SegmentKind::DataDescriptor => "DATA DESCRIPTOR",
+            SegmentKind::Gap => "UNUSED",
+            SegmentKind::ApkSigningBlock => "APK SIGNING BLOCK",
+            SegmentKind::CentralDirectory => "CENTRAL DIRECTORY",
+            SegmentKind::Zip64EndOfCentralDirectory => "ZIP64 END CENTRAL DIR RECORD",
+            SegmentKind::EndOfCentralDirectory => "END CENTRAL HEADER",
+            SegmentKind::Comment => "ARCHIVE COMMENT",
+        };
+        let mut record = Record {
+            offset,
+            len,
+            title: title.to_string(),
+            fields: Vec::new(),
+        };
+        let read = |archive: &mut ZipArchive<R>, len: u64| -> ZipResult<Vec<u8>> {
+            let mut bytes = Vec::new();
+            archive.reader.seek(SeekFrom::Start(offset))?;
+            (&mut archive.reader).take(len).read_to_end(&mut bytes)?;
+            Ok(bytes)
+        };
+        match segment.kind {
+            SegmentKind::LocalHeader => {
+                local_headers += 1;
+                record.title = format!("LOCAL HEADER #{local_headers}");
+                if let Some(index) = segment.entry {
+                    record.title += &format!(" (entry {index})");
+                }
+                let mut parser = Parser::new(read(archive, len)?, offset);
+                parser.local_header();
+                record.fields = parser.fields;
+            }
+            SegmentKind::DataDescriptor => {
+                let mut parser = Parser::new(read(archive, len)?, offset);
+                parser.data_descriptor();
+                record.fields = parser.fields;
+            }
+            SegmentKind::CentralDirectory => {
+                let mut parser = Parser::new(read(archive, len)?, offset);
+                let mut headers = 0;
+                while parser.remaining() > 0 {
+                    headers += 1;
+                    let start = parser.position();
+                    parser.central_header();
+                    let fields = std::mem::take(&mut parser.fields);
+                    records.push(Record {
+                        offset: start,
+                        len: parser.position() - start,
+                        title: format!("CENTRAL HEADER #{headers}"),
+                        fields,
+                    });
+                }
+                continue;
+            }
+            SegmentKind::Zip64EndOfCentralDirectory => {
+                let mut parser = Parser::new(read(archive, len)?, offset);
+                parser.zip64_end_of_central_directory();
+                record.fields = parser.fields;
+            }
+            SegmentKind::EndOfCentralDirectory => {
+                let mut parser = Parser::new(read(archive, len)?, offset);
+                parser.end_of_central_directory();
+                record.fields = parser.fields;
+            }
+            SegmentKind::Comment => {
+                let mut parser = Parser::new(read(archive, len)?, offset);
+                parser.text("Comment", len as usize);
+                record.fields = parser.fields;
+            }
+            _ => {}
+        }
+        records.push(record);
+    }
+    Ok(Dump { records })
+}
+
+/// Reads little-endian fields from a header, recording each one. Once the bytes run out, every
+/// further read returns `None` and a single truncation note is recorded.
+struct Parser {
+    bytes: Vec<u8>,
+    base: u64,
+    pos: usize,
+    truncated: bool,
+    fields: Vec<Field>,
+}
+
+impl Parser {
+    fn new(bytes: Vec<u8>, base: u64) -> Parser {
+        Parser {
+            bytes,
+            base,
+            pos: 0,
+            truncated: false,
+            fields: Vec::new(),
+        }
+    }
+
+    fn position(&self) -> u64 {
+        self.base + self.pos as u64
+    }
+
+    fn remaining(&self) -> usize {
+        if self.truncated {
+            0
+        } else {
+            self.bytes.len() - self.pos
+        }
+    }
+
+    fn field<T>(
+        &mut self,
+        name: &str,
+        len: usize,
+        decode: impl FnOnce(&[u8]) -> (T, String),
+    ) -> Option<T> {
+        if self.remaining() < len {
+            if !self.truncated {
+                self.fields.push(Field {
+                    offset: self.position(),
+                    len: self.remaining() as u64,
+                    name: name.to_string(),
+                    value: "(truncated)".to_string(),
+                });
+                self.truncated = true;
+            }
+            return None;
+        }
+        let bytes = &self.bytes[self.pos..self.pos + len];
+        let (value, text) = decode(bytes);
+        self.fields.push(Field {
+            offset: self.position(),
+            len: len as u64,
+            name: name.to_string(),
+            value: text,
+        });
+        self.pos += len;
+        Some(value)
+    }
+
+    fn u16(&mut self, name: &str) -> Option<u16> {
+        self.field(name, 2, |b| {
+            let value = u16::from_le_bytes([b[0], b[1]]);
+            (value, value.to_string())
+        })
+    }
+
+    fn u32(&mut self, name: &str) -> Option<u32> {
+        self.field(name, 4, |b| {
+            let value = u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+            (value, value.to_string())
+        })
+    }
+
+    fn u64(&mut self, name: &str) -> Option<u64> {
+        self.field(name, 8, |b| {
+            let value = u64::from_le_bytes(b.try_into().unwrap());
+            (value, value.to_string())
+        })
+    }
+
+    fn hex16(&mut self, name: &str) -> Option<u16> {
+        self.field(name, 2, |b| {
+            let value = u16::from_le_bytes([b[0], b[1]]);
+            (value, format!("0x{value:04X}"))
+        })
+    }
+
+    fn hex32(&mut self, name: &str) -> Option<u32> {
+        self.field(name, 4, |b| {
+            let value = u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+            (value, format!("0x{value:08X}"))
+        })
+    }
+
+    fn text(&mut self, name: &str, len: usize) -> Option<()> {
+        self.field(name, len, |b| {
+            ((), format!("'{}'", String::from_utf8_lossy(b)))
+        })
+    }
+
+    fn version(&mut self, name: &str) -> Option<u16> {
+        self.field(name, 2, |b| {
+            let value = u16::from_le_bytes([b[0], b[1]]);
+            let spec = value & 0xff;
+            (value, format!("{value} '{}.{}'", spec / 10, spec % 10))
+        })
+    }
+
+    fn flags(&mut self) -> Option<u16> {
+        self.field("General purpose flags", 2, |b| {
+            let value = u16::from_le_bytes([b[0], b[1]]);
+            let mut text = format!("0x{value:04X}");
+            for (bit, meaning) in [
+                (0, "encrypted"),
+                (3, "data descriptor"),
+                (6, "strong encryption"),
+                (11, "UTF-8 names"),
+                (13, "masked local header"),
+            ] {
+                if value & (1 << bit) != 0 {
+                    text += &format!(" [bit {bit}: {meaning}]");
+                }
+            }
+            (value, text)
+        })
+    }
+
+    fn compression_method(&mut self) -> Option<u16> {
+        self.field("Compression method", 2, |b| {
+            let value = u16::from_le_bytes([b[0], b[1]]);
+            #[allow(deprecated)]
+            let method = CompressionMethod::from_u16(value);
+            (value, format!("{value} '{method}'"))
+        })
+    }
+
+    fn modified(&mut self) -> Option<()> {
+        let time = self.hex16("Modification time")?;
+        let date = self.hex16("Modification date")?;
+        let datetime = DateTime::from_msdos(date, time);
+        if let Some(field) = self.fields.last_mut() {
+            field.value += &format!(
+                " '{:04}-{:02}-{:02} {:02}:{:02}:{:02}'",
+                datetime.year(),
+                datetime.month(),
+                datetime.day(),
+                datetime.hour(),
+                datetime.minute(),
+                datetime.second()
+            );
+        }
+        Some(())
+    }
+
+    fn extra_fields(&mut self, len: usize) -> Option<()> {
+        let end = self.pos + len;
+        while self.pos + 4 <= end {
+            let id = self.field("Extra field id", 2, |b| {
+                let id = u16::from_le_bytes([b[0], b[1]]);
+                (id, format!("0x{id:04X} '{}'", extra_field_name(id)))
+            })?;
+            let field_len = self.u16("  Length")? as usize;
+            if id == 0x9901 && field_len == 7 {
+                self.field("  AES vendor version", 2, |b| {
+                    let version = u16::from_le_bytes([b[0], b[1]]);
+                    (version, format!("{version} 'AE-{version}'"))
+                })?;
+                self.text("  AES vendor id", 2)?;
+                self.field("  AES strength", 1, |b| {
+                    let bits = match b[0] {
+                        1 => "128",
+                        2 => "192",
+                        3 => "256",
+                        _ => "unknown",
+                    };
+                    (b[0], format!("{} '{bits}-bit'", b[0]))
+                })?;
+                self.compression_method()?;
+            } else {
+                self.field("  Data", field_len.min(end - self.pos), |b| ((), hex(b)))?;
+            }
+        }
+        if self.pos < end {
+            self.field("Extra field padding", end - self.pos, |b| ((), hex(b)))?;
+        }
+        Some(())
+    }
+
+    fn local_header(&mut self) -> Option<()> {
+        self.hex32("Signature")?;
+        self.version("Version needed")?;
+        self.flags()?;
+        self.compression_method()?;
+        self.modified()?;
+        self.hex32("CRC-32")?;
+        self.u32("Compressed size")?;
+        self.u32("Uncompressed size")?;
+        let name_len = self.u16("Filename length")? as usize;
+        let extra_len = self.u16("Extra field length")? as usize;
+        self.text("Filename", name_len)?;
+        self.extra_fields(extra_len)
+    }
+
+    fn data_descriptor(&mut self) -> Option<()> {
+        let len = self.remaining();
+        if len == 16 || len == 24 {
+            self.hex32("Signature")?;
+        }
+        self.hex32("CRC-32")?;
+        if len >= 20 {
+            self.u64("Compressed size")?;
+            self.u64("Uncompressed size")?;
+        } else {
+            self.u32("Compressed size")?;
+            self.u32("Uncompressed size")?;
+        }
+        Some(())
+    }
+
+    fn central_header(&mut self) -> Option<()> {
+        self.hex32("Signature")?;
+        self.version("Version made by")?;
+        self.version("Version needed")?;
+        self.flags()?;
+        self.compression_method()?;
+        self.modified()?;
+        self.hex32("CRC-32")?;
+        self.u32("Compressed size")?;
+        self.u32("Uncompressed size")?;
+        let name_len = self.u16("Filename length")? as usize;
+        let extra_len = self.u16("Extra field length")? as usize;
+        let comment_len = self.u16("Comment length")? as usize;
+        self.u16("Disk number start")?;
+        self.hex16("Internal attributes")?;
+        self.hex32("External attributes")?;
+        self.u32("Local header offset")?;
+        self.text("Filename", name_len)?;
+        self.extra_fields(extra_len)?;
+        if comment_len > 0 {
+            self.text("Comment", comment_len)?;
+        }
+        Some(())
+    }
+
+    fn zip64_end_of_central_directory(&mut self) -> Option<()> {
+        self.hex32("Signature")?;
+        let size = self.u64("Record size")? as usize;
+        self.version("Version made by")?;
+        self.version("Version needed")?;
+        self.u32("Disk number")?;
+        self.u32("Central directory disk")?;
+        self.u64("Entries on this disk")?;
+        self.u64("Total entries")?;
+        self.u64("Central directory size")?;
+        self.u64("Central directory offset")?;
+        let extensible = size.saturating_sub(44);
+        if extensible > 0 {
+            self.field("Extensible data", extensible, |b| ((), hex(b)))?;
+        }
+        self.hex32("Locator signature")?;
+        self.u32("Central directory disk")?;
+        self.u64("Zip64 end record offset")?;
+        self.u32("Total disks")?;
+        Some(())
+    }
+
+    fn end_of_central_directory(&mut self) -> Option<()> {
+        self.hex32("Signature")?;
+        self.u16("Disk number")?;
+        self.u16("Central directory disk")?;
+        self.u16("Entries on this disk")?;
+        self.u16("Total entries")?;
+        self.u32("Central directory size")?;
+        self.u32("Central directory offset")?;
+        self.u16("Comment length")?;
+        Some(())
+    }
+}
+
+fn extra_field_name(id: u16) -> &'static str {
+    match id {
+        0x0001 => "Zip64",
+        0x000a => "NTFS timestamps",
+        0x000d => "Unix",
+        0x5455 => "Extended timestamp",
+        0x5855 => "Info-ZIP Unix (old)",
+        0x6375 => "Unicode comment",
+        0x7075 => "Unicode path",
+        0x7875 => "Unix uid/gid",
+        0x9901 => "AES encryption",
+        0xcafe => "Java executable",
+        _ => "unknown",
+    }
+}
+
+/// Formats up to 32 bytes as hexadecimal
+fn hex(bytes: &[u8]) -> String {
+    let mut text: String = bytes
+        .iter()
+        .take(32)
+        .map(|byte| format!("{byte:02X}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if bytes.len() > 32 {
+        text += " ...";
+    }
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use super::dump;
+    use crate::write::SimpleFileOptions;
+    use crate::{CompressionMethod, ZipWriter};
+    use std::io::Write;
+
+    #[test]
+    fn dump_archive() {
+        let mut writer = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file("hello.txt", options).unwrap();
+        writer.write_all(b"hello").unwrap();
+        writer.set_comment("archive comment");
+        let mut archive = writer.finish_into_readable().unwrap();
+
+        let dump = dump(&mut archive).unwrap();
+        let titles: Vec<_> = dump.records.iter().map(|r| r.title.as_str()).collect();
+        assert_eq!(
+            titles,
+            [
+                "LOCAL HEADER #1 (entry 0)",
+                "PAYLOAD",
+                "CENTRAL HEADER #1",
+                "END CENTRAL HEADER",
+                "ARCHIVE COMMENT"
+            ]
+        );
+        let text = dump.to_string();
+        assert!(text.contains("00000000 0004   Signature                 0x04034B50"));
+        assert!(text.contains("Filename                  'hello.txt'"));
+        assert!(text.contains("'archive comment'"));
+        let fields = &dump.records[2].fields;
+        assert_eq!(fields.last().unwrap().value, "'hello.txt'");
+        let end = dump.records[2].offset + dump.records[2].len;
+        assert_eq!(end, dump.records[3].offset);
+    }
+}
@@ -0,0 +1,167 @@
+//! Comparing the entries of two archives
+//!
+//! [`diff`] reports which entries were added, removed or changed between two versions of an
+//! archive, such as to upload only what changed between two builds.
+
+use crate::read::ZipArchive;
+use crate::result::ZipResult;
+use std::io::{self, Read, Seek};
+
+/// Options for [`diff`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DiffOptions {
+    compare_modified_times: bool,
+    compare_contents: bool,
+}
+
+impl DiffOptions {
+    /// Set whether entries whose last-modified times differ count as changed. Rebuilding an
+    /// archive usually changes every time, so the default is false.
+    #[must_use]
+    pub const fn compare_modified_times(mut self, compare_modified_times: bool) -> Self {
+        self.compare_modified_times = compare_modified_times;
+        self
+    }
+
+    /// Set whether the contents of entries whose CRC-32 and size match are decompressed and
+    /// compared, rather than trusting the checksum. Entries whose metadata already differs are
+    /// never read.
+    ///
+    /// The default is false.
+    #[must_use]
+    pub const fn compare_contents(mut self, compare_contents: bool) -> Self {
+        self.compare_contents = compare_contents;
+        self
+    }
+}
+
+/// The differences between two archives, as returned by [`diff`]. Each list of names is in the
+/// central directory order of the archive it comes from.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ArchiveDiff {
+    /// Entries only in the new archive
+    pub added: Vec<String>,
+    /// Entries only in the old archive
+    pub removed: Vec<String>,
+    /// Entries in both archives whose CRC-32, size, last-modified time or contents differ, as
+    /// set by the [`DiffOptions`]
+    pub changed: Vec<String>,
+}
+
+impl ArchiveDiff {
+    /// Returns true if the archives have the same entries and none of them changed.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Compares the entries of `old` and `new` by name.
+///
+/// Entries in both archives count as changed if their CRC-32 or uncompressed size differ, or if
+/// `options` ask for more and those differ too. Encrypted entries fail the comparison if their
+/// contents need to be read.
+pub fn diff<A, B>(
+    old: &mut ZipArchive<A>,
+    new: &mut ZipArchive<B>,
+    options: DiffOptions,
+) -> ZipResult<ArchiveDiff>
+where
+    A: Read + Seek,
+    B: Read + Seek,
+{
+    let mut result = ArchiveDiff::default();
+    let new_entries: Vec<_> = new.entries_lazy().collect();
+    for old_entry in old.entries_lazy() {
+        let Some(new_index) = new.index_for_name(old_entry.name()) else {
+            result.removed.push(old_entry.name().to_owned());
+            continue;
+        };
+        let new_entry = &new_entries[new_index];
+        let mut changed =
+            old_entry.crc32() != new_entry.crc32() || old_entry.size() != new_entry.size();
+        if options.compare_modified_times {
+            let (old_time, new_time) = (old_entry.last_modified(), new_entry.last_modified());
+            changed |= (old_time.datepart(), old_time.timepart())
+                != (new_time.datepart(), new_time.timepart());
+        }
+        if !changed && options.compare_contents {
+            let mut old_file = old.by_index(old_entry.index())?;
+            changed = !same_contents(&mut old_file, &mut new.by_index(new_index)?)?;
+        }
+        if changed {
+            result.changed.push(old_entry.name().to_owned());
+        }
+    }
+    result.added = new_entries
+        .iter()
+        .filter(|new_entry| old.index_for_name(new_entry.name()).is_none())
+        .map(|new_entry| new_entry.name().to_owned())
+        .collect();
+    Ok(result)
+}
+
+/// Reads `a` and `b` to their ends, returning whether they hold the same bytes.
+fn same_contents(a: &mut impl Read, b: &mut impl Read) -> io::Result<bool> {
+    let mut a_buf = [0; 8 * 1024];
+    let mut b_buf = [0; 8 * 1024];
+    loop {
+        let count = a.read(&mut a_buf)?;
+        if count == 0 {
+            return Ok(b.read(&mut b_buf[..1])? == 0);
+        }
+        match b.read_exact(&mut b_buf[..count]) {
+            Ok(()) if a_buf[..count] == b_buf[..count] => {}
+            Ok(()) => return Ok(false),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(false),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{diff, DiffOptions};
+    use crate::write::SimpleFileOptions;
+    use crate::{DateTime, ZipArchive, ZipWriter};
+    use std::io::{Cursor, Write};
+
+    fn archive(files: &[(&str, &[u8], u16)]) -> ZipArchive<Cursor<Vec<u8>>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for &(name, contents, day) in files {
+            let time = DateTime::from_msdos(0x0020 | day, 0);
+            let options = SimpleFileOptions::default().last_modified_time(time);
+            writer.start_file(name, options).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        writer.finish_into_readable().unwrap()
+    }
+
+    #[test]
+    fn diffs() {
+        let mut old = archive(&[
+            ("same", b"same", 1),
+            ("touched", b"touched", 1),
+            ("edited", b"before", 1),
+            ("removed", b"", 1),
+        ]);
+        let mut new = archive(&[
+            ("added", b"", 1),
+            ("edited", b"after!", 1),
+            ("touched", b"touched", 2),
+            ("same", b"same", 1),
+        ]);
+
+        let result = diff(&mut old, &mut new, DiffOptions::default()).unwrap();
+        assert_eq!(result.added, ["added"]);
+        assert_eq!(result.removed, ["removed"]);
+        assert_eq!(result.changed, ["edited"]);
+
+        let options = DiffOptions::default()
+            .compare_modified_times(true)
+            .compare_contents(true);
+        let result = diff(&mut old, &mut new, options).unwrap();
+        assert_eq!(result.changed, ["touched", "edited"]);
+        let mut copy = old.clone();
+        assert!(diff(&mut old, &mut copy, options).unwrap().is_empty());
+    }
+}
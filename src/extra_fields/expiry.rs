@@ -0,0 +1,118 @@
+use crate::result::{ZipError, ZipResult};
+use crate::unstable::LittleEndianReadExt;
+use std::io::Read;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// When a cached entry was created and how long it stays fresh, for archives used as caches.
+///
+/// This crate's own extra field (0x4c54, "TL"), which isn't registered with PKWARE, so other
+/// implementations ignore it. It holds a version byte of 1, then the creation time in seconds
+/// since the Unix epoch and the time-to-live in seconds, each as a little-endian u64.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Expiry {
+    created: u64,
+    ttl: u64,
+}
+
+impl Expiry {
+    /// The header ID of the extra field
+    pub const HEADER_ID: u16 = 0x4c54;
+
+    /// creates the extra field's contents for an entry created at `created` that expires after
+    /// `ttl`. Both are rounded down to whole seconds, and times before the Unix epoch are stored
+    /// as the epoch.
+    pub fn new(created: SystemTime, ttl: Duration) -> Self {
+        let created = created
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since_epoch| since_epoch.as_secs());
+        Self {
+            created,
+            ttl: ttl.as_secs(),
+        }
+    }
+
+    /// creates the struct by reading the required bytes from the reader.
+    ///
+    /// This method assumes that the length has already been read, therefore
+    /// it must be passed as an argument
+    pub fn try_from_reader<R>(reader: &mut R, len: u16) -> ZipResult<Self>
+    where
+        R: Read,
+    {
+        if len != 17 {
+            return Err(ZipError::InvalidArchive(
+                "Expiry extra field has an unsupported length",
+            ));
+        }
+        let mut version = [0u8];
+        reader.read_exact(&mut version)?;
+        if version[0] != 1 {
+            return Err(ZipError::UnsupportedArchive(
+                "unsupported version of the expiry extra field",
+            ));
+        }
+        let created = reader.read_u64_le()?;
+        let ttl = reader.read_u64_le()?;
+        Ok(Self { created, ttl })
+    }
+
+    /// returns when the entry was created
+    pub fn created(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.created)
+    }
+
+    /// returns how long the entry stays fresh after it was created
+    pub const fn ttl(&self) -> Duration {
+        Duration::from_secs(self.ttl)
+    }
+
+    /// returns when the entry expires, or `None` if that's too far in the future to represent
+    pub fn expires_at(&self) -> Option<SystemTime> {
+        self.created().checked_add(self.ttl())
+    }
+
+    /// returns whether the entry had expired by `now`
+    pub fn is_expired_at(&self, now: SystemTime) -> bool {
+        self.expires_at()
+            .is_some_and(|expires_at| expires_at <= now)
+    }
+
+    /// returns whether the entry has expired according to the system clock
+    pub fn is_expired(&self) -> bool {
+        self.is_expired_at(SystemTime::now())
+    }
+
+    /// serializes the extra field, including its header
+    pub(crate) fn to_bytes(self) -> [u8; 21] {
+        let mut bytes = [0u8; 21];
+        bytes[0..2].copy_from_slice(&Self::HEADER_ID.to_le_bytes());
+        bytes[2..4].copy_from_slice(&17u16.to_le_bytes());
+        bytes[4] = 1;
+        bytes[5..13].copy_from_slice(&self.created.to_le_bytes());
+        bytes[13..21].copy_from_slice(&self.ttl.to_le_bytes());
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Expiry;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn expiry() {
+        let created = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let expiry = Expiry::new(created, Duration::from_secs(60));
+        let bytes = expiry.to_bytes();
+        let parsed = Expiry::try_from_reader(&mut &bytes[4..], 17).unwrap();
+        assert_eq!(parsed, expiry);
+        assert_eq!(parsed.created(), created);
+        assert!(!parsed.is_expired_at(created + Duration::from_secs(59)));
+        assert!(parsed.is_expired_at(created + Duration::from_secs(60)));
+
+        let forever = Expiry::new(created, Duration::MAX);
+        assert_eq!(forever.expires_at(), None);
+        assert!(!forever.is_expired());
+    }
+}
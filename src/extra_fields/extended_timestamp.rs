@@ -1,10 +1,11 @@
-use crate::result::{ZipError, ZipResult};
+use crate::result::{DateTimeRangeError, ZipError, ZipResult};
 use crate::unstable::LittleEndianReadExt;
 use std::io::Read;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// extended timestamp, as described in <https://libzip.org/specifications/extrafld.txt>
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ExtendedTimestamp {
     mod_time: Option<u32>,
     ac_time: Option<u32>,
@@ -12,6 +13,25 @@ pub struct ExtendedTimestamp {
 }
 
 impl ExtendedTimestamp {
+    /// The header ID of the extra field
+    pub const HEADER_ID: u16 = 0x5455;
+
+    /// creates an extended timestamp holding the given times, rounded down to whole seconds.
+    ///
+    /// Fails if any of them is before the Unix epoch or after 2106-02-07, which don't fit in the
+    /// field's 32-bit timestamps.
+    pub fn new(
+        modified: Option<SystemTime>,
+        accessed: Option<SystemTime>,
+        created: Option<SystemTime>,
+    ) -> Result<Self, DateTimeRangeError> {
+        Ok(Self {
+            mod_time: modified.map(to_timestamp).transpose()?,
+            ac_time: accessed.map(to_timestamp).transpose()?,
+            cr_time: created.map(to_timestamp).transpose()?,
+        })
+    }
+
     /// creates an extended timestamp struct by reading the required bytes from the reader.
     ///
     /// This method assumes that the length has already been read, therefore
@@ -84,4 +104,82 @@ impl ExtendedTimestamp {
     pub fn cr_time(&self) -> Option<&u32> {
         self.cr_time.as_ref()
     }
+
+    /// returns the last modification time
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.mod_time.map(from_timestamp)
+    }
+
+    /// returns the last access time
+    pub fn accessed(&self) -> Option<SystemTime> {
+        self.ac_time.map(from_timestamp)
+    }
+
+    /// returns the creation time
+    pub fn created(&self) -> Option<SystemTime> {
+        self.cr_time.map(from_timestamp)
+    }
+
+    fn flags(&self) -> u8 {
+        self.mod_time.map_or(0, |_| 0b001)
+            | self.ac_time.map_or(0, |_| 0b010)
+            | self.cr_time.map_or(0, |_| 0b100)
+    }
+
+    /// serializes the extra field, including its header
+    pub(crate) fn to_bytes(self) -> Vec<u8> {
+        let times: Vec<u32> = [self.mod_time, self.ac_time, self.cr_time]
+            .into_iter()
+            .flatten()
+            .collect();
+        let len = 1 + 4 * times.len() as u16;
+        let mut bytes = Vec::with_capacity(4 + len as usize);
+        bytes.extend_from_slice(&Self::HEADER_ID.to_le_bytes());
+        bytes.extend_from_slice(&len.to_le_bytes());
+        bytes.push(self.flags());
+        for time in times {
+            bytes.extend_from_slice(&time.to_le_bytes());
+        }
+        bytes
+    }
+}
+
+fn to_timestamp(time: SystemTime) -> Result<u32, DateTimeRangeError> {
+    let since_epoch = time
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| DateTimeRangeError)?;
+    u32::try_from(since_epoch.as_secs()).map_err(|_| DateTimeRangeError)
+}
+
+fn from_timestamp(timestamp: u32) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(timestamp.into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::ExtendedTimestamp;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn round_trip() {
+        let modified = UNIX_EPOCH + Duration::from_secs(1714635025);
+        let created = UNIX_EPOCH + Duration::from_secs(1000);
+        let timestamp = ExtendedTimestamp::new(Some(modified), None, Some(created)).unwrap();
+
+        let bytes = timestamp.to_bytes();
+        assert_eq!(bytes.len(), 4 + 9);
+        let parsed = ExtendedTimestamp::try_from_reader(&mut &bytes[4..], 9).unwrap();
+        assert_eq!(parsed, timestamp);
+        assert_eq!(parsed.modified(), Some(modified));
+        assert_eq!(parsed.accessed(), None);
+        assert_eq!(parsed.created(), Some(created));
+    }
+
+    #[test]
+    fn out_of_range() {
+        let too_late = UNIX_EPOCH + Duration::from_secs(u32::MAX as u64 + 1);
+        assert!(ExtendedTimestamp::new(Some(too_late), None, None).is_err());
+        let too_early = UNIX_EPOCH - Duration::from_secs(1);
+        assert!(ExtendedTimestamp::new(None, Some(too_early), None).is_err());
+    }
 }
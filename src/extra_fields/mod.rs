@@ -16,13 +16,25 @@ pub struct CentralHeaderVersion;
 impl ExtraFieldVersion for LocalHeaderVersion {}
 impl ExtraFieldVersion for CentralHeaderVersion {}
 
+mod expiry;
 mod extended_timestamp;
+mod unix_owner;
 
+pub use expiry::*;
 pub use extended_timestamp::*;
+pub use unix_owner::*;
 
 /// contains one extra field
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum ExtraField {
     /// extended timestamp, as described in <https://libzip.org/specifications/extrafld.txt>
     ExtendedTimestamp(ExtendedTimestamp),
+
+    /// Info-ZIP New Unix extra field with the owner's UID and GID, as described in
+    /// <https://libzip.org/specifications/extrafld.txt>
+    UnixOwner(UnixOwner),
+
+    /// creation time and time-to-live of a cached entry
+    Expiry(Expiry),
 }
@@ -0,0 +1,116 @@
+use crate::result::{ZipError, ZipResult};
+use std::io::Read;
+
+/// Info-ZIP New Unix extra field (0x7875), holding the UID and GID of the file's owner, as
+/// described in <https://libzip.org/specifications/extrafld.txt>
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnixOwner {
+    uid: u32,
+    gid: u32,
+}
+
+impl UnixOwner {
+    /// The header ID of the extra field
+    pub const HEADER_ID: u16 = 0x7875;
+
+    /// creates the extra field's contents for the given UID and GID
+    pub const fn new(uid: u32, gid: u32) -> Self {
+        Self { uid, gid }
+    }
+
+    /// creates the struct by reading the required bytes from the reader.
+    ///
+    /// This method assumes that the length has already been read, therefore
+    /// it must be passed as an argument
+    pub fn try_from_reader<R>(reader: &mut R, len: u16) -> ZipResult<Self>
+    where
+        R: Read,
+    {
+        let mut data = vec![0u8; len as usize];
+        reader.read_exact(&mut data)?;
+        let (&version, rest) = data
+            .split_first()
+            .ok_or(ZipError::InvalidArchive("Unix owner extra field is empty"))?;
+        if version != 1 {
+            return Err(ZipError::UnsupportedArchive(
+                "unsupported version of the Unix owner extra field",
+            ));
+        }
+        let (uid, rest) = read_id(rest)?;
+        let (gid, _) = read_id(rest)?;
+        Ok(Self { uid, gid })
+    }
+
+    /// returns the user ID
+    pub const fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    /// returns the group ID
+    pub const fn gid(&self) -> u32 {
+        self.gid
+    }
+
+    /// serializes the extra field, including its header, with 4-byte IDs as Info-ZIP writes them
+    pub(crate) fn to_bytes(self) -> [u8; 15] {
+        let mut bytes = [0u8; 15];
+        bytes[0..2].copy_from_slice(&Self::HEADER_ID.to_le_bytes());
+        bytes[2..4].copy_from_slice(&11u16.to_le_bytes());
+        bytes[4] = 1;
+        bytes[5] = 4;
+        bytes[6..10].copy_from_slice(&self.uid.to_le_bytes());
+        bytes[10] = 4;
+        bytes[11..15].copy_from_slice(&self.gid.to_le_bytes());
+        bytes
+    }
+}
+
+/// Reads a size-prefixed little-endian ID, returning it and the bytes after it.
+fn read_id(data: &[u8]) -> ZipResult<(u32, &[u8])> {
+    const TRUNCATED: ZipError = ZipError::InvalidArchive("Unix owner extra field is truncated");
+    let (&size, rest) = data.split_first().ok_or(TRUNCATED)?;
+    let size = size as usize;
+    if rest.len() < size {
+        return Err(TRUNCATED);
+    }
+    let (id, rest) = rest.split_at(size);
+    let mut value = 0u64;
+    for (i, &byte) in id.iter().enumerate() {
+        if i >= 8 {
+            if byte != 0 {
+                return Err(ZipError::UnsupportedArchive("Unix owner ID is too large"));
+            }
+            continue;
+        }
+        value |= (byte as u64) << (8 * i);
+    }
+    let value = u32::try_from(value)
+        .map_err(|_| ZipError::UnsupportedArchive("Unix owner ID is too large"))?;
+    Ok((value, rest))
+}
+
+#[cfg(test)]
+mod test {
+    use super::UnixOwner;
+
+    #[test]
+    fn round_trip() {
+        let bytes = UnixOwner::new(1000, 0x1234_5678).to_bytes();
+        let owner = UnixOwner::try_from_reader(&mut &bytes[4..], 11).unwrap();
+        assert_eq!(owner, UnixOwner::new(1000, 0x1234_5678));
+    }
+
+    #[test]
+    fn variable_sizes() {
+        // 2-byte UID and 8-byte GID
+        let data = [1, 2, 0xe8, 0x03, 8, 100, 0, 0, 0, 0, 0, 0, 0];
+        let owner = UnixOwner::try_from_reader(&mut &data[..], data.len() as u16).unwrap();
+        assert_eq!((owner.uid(), owner.gid()), (1000, 100));
+
+        let too_large = [1, 1, 0, 5, 0, 0, 0, 0, 1];
+        assert!(UnixOwner::try_from_reader(&mut &too_large[..], 9).is_err());
+        let truncated = [1, 4, 0, 0];
+        assert!(UnixOwner::try_from_reader(&mut &truncated[..], 4).is_err());
+    }
+}
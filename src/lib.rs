@@ -21,13 +21,22 @@
 //! | Deflate64 | ✅ | |
 //! | Bzip2 | ✅ | ✅ |
 //! | LZMA | ✅ | |
+//! | XZ | ✅ | |
 //! | AES encryption | ✅ | ✅ |
 //! | ZipCrypto deprecated encryption | ✅ | ✅ |
 //!
+//! Reading doesn't panic on malformed input: [`ZipArchive`], [`read::read_zipfile_from_stream`]
+//! and the files they return report truncated or corrupted archives as errors instead, so
+//! services can open archives they don't trust. This is checked by the fuzz targets in `fuzz/` and
+//! by replaying damaged copies of the test archives; a panic while reading is a bug.
+//!
 //!
 #![warn(missing_docs)]
 #![allow(unexpected_cfgs)] // Needed for cfg(fuzzing) on nightly as of 2024-05-06
-pub use crate::compression::{CompressionMethod, SUPPORTED_COMPRESSION_METHODS};
+pub use crate::compression::{
+    CompressionMethod, CompressionRegistry, CustomCompression, CustomEncoder,
+    SUPPORTED_COMPRESSION_METHODS,
+};
 pub use crate::read::ZipArchive;
 pub use crate::types::{AesMode, DateTime};
 pub use crate::write::ZipWriter;
@@ -36,13 +45,25 @@ pub use crate::write::ZipWriter;
 mod aes;
 #[cfg(feature = "aes-crypto")]
 mod aes_ctr;
+mod comment_metadata;
 mod compression;
+#[cfg(feature = "conformance")]
+pub mod conformance;
+pub mod convert;
 mod cp437;
 mod crc32;
+pub mod debug;
+pub mod diff;
 pub mod extra_fields;
+pub mod metrics;
+pub mod progress;
 pub mod read;
+pub mod recover;
 pub mod result;
 mod spec;
+#[cfg(test)]
+mod test_util;
+pub mod transform;
 mod types;
 pub mod write;
 mod zipcrypto;
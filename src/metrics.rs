@@ -0,0 +1,113 @@
+//! Counting the bytes archives read and write, to export as metrics
+
+use std::fmt;
+
+/// Receives byte counts from the archives and writers it's installed on with
+/// [`crate::ZipArchive::set_metrics`] and [`crate::ZipWriter::set_metrics`].
+///
+/// One instance can be shared by many archives and writers, on many threads, so implementations
+/// usually add to atomic counters, such as those of a Prometheus client. Every method does nothing
+/// by default.
+pub trait Metrics: Send + Sync {
+    /// Called when a file read from an archive has been read to the end, with the size of its
+    /// compressed data. Files dropped before the end, or opened with
+    /// [`crate::ZipArchive::by_index_raw`], aren't counted.
+    fn compressed_bytes_read(&self, _count: u64) {}
+
+    /// Called after each read from a file in an archive, with the number of decompressed bytes
+    /// it produced.
+    fn decompressed_bytes_read(&self, _count: u64) {}
+
+    /// Called after each write to a [`crate::ZipWriter`], with the number of uncompressed bytes
+    /// it took.
+    fn uncompressed_bytes_written(&self, _count: u64) {}
+
+    /// Called when a file being written is finished, with the size of its compressed data.
+    /// Entries copied without recompressing them aren't counted.
+    fn compressed_bytes_written(&self, _count: u64) {}
+}
+
+impl fmt::Debug for dyn Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Metrics").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Metrics;
+    use crate::write::SimpleFileOptions;
+    use crate::ZipWriter;
+    use std::io::{Cursor, Read, Write};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Default)]
+    struct Counters {
+        compressed_read: AtomicU64,
+        decompressed_read: AtomicU64,
+        uncompressed_written: AtomicU64,
+        compressed_written: AtomicU64,
+    }
+
+    impl Metrics for Counters {
+        fn compressed_bytes_read(&self, count: u64) {
+            self.compressed_read.fetch_add(count, Ordering::Relaxed);
+        }
+
+        fn decompressed_bytes_read(&self, count: u64) {
+            self.decompressed_read.fetch_add(count, Ordering::Relaxed);
+        }
+
+        fn uncompressed_bytes_written(&self, count: u64) {
+            self.uncompressed_written
+                .fetch_add(count, Ordering::Relaxed);
+        }
+
+        fn compressed_bytes_written(&self, count: u64) {
+            self.compressed_written.fetch_add(count, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn counts() {
+        let counters = Arc::new(Counters::default());
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_metrics(counters.clone());
+        for name in ["a", "b"] {
+            writer
+                .start_file(name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(&[0; 10_000]).unwrap();
+        }
+        let mut archive = writer.finish_into_readable().unwrap();
+        assert_eq!(
+            counters.uncompressed_written.load(Ordering::Relaxed),
+            20_000
+        );
+        let compressed_written = counters.compressed_written.load(Ordering::Relaxed);
+        let compressed_sizes: u64 = (0..2)
+            .map(|i| archive.by_index_raw(i).unwrap().compressed_size())
+            .sum();
+        assert_eq!(compressed_written, compressed_sizes);
+
+        archive.set_metrics(counters.clone());
+        let mut contents = Vec::new();
+        archive
+            .by_name("a")
+            .unwrap()
+            .read_to_end(&mut contents)
+            .unwrap();
+        // Only part of b is read, so its compressed size isn't counted
+        archive
+            .by_name("b")
+            .unwrap()
+            .read_exact(&mut [0; 10])
+            .unwrap();
+        assert_eq!(counters.decompressed_read.load(Ordering::Relaxed), 10_010);
+        assert_eq!(
+            counters.compressed_read.load(Ordering::Relaxed),
+            archive.by_index_raw(0).unwrap().compressed_size()
+        );
+    }
+}
@@ -0,0 +1,89 @@
+//! Progress reporting for long-running operations
+
+/// How far an operation has got, as passed to [`Progress::update`]
+///
+/// All sizes count uncompressed bytes, the bytes written to a [`crate::ZipWriter`] or extracted
+/// from an archive, whatever compression method the entries use.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ProgressStatus<'a> {
+    /// The index of the entry being processed
+    pub entry_index: usize,
+    /// The name of the entry being processed
+    pub entry_name: &'a str,
+    /// The number of bytes of this entry processed so far
+    pub entry_bytes: u64,
+    /// The number of bytes of all entries processed so far
+    pub bytes: u64,
+    /// The number of entries that will be processed, if known
+    pub total_entries: Option<usize>,
+    /// The number of bytes that will be processed, if known
+    pub total_bytes: Option<u64>,
+}
+
+/// Receives progress updates from [`crate::ZipWriter::set_progress`] and
+/// [`crate::ZipArchive::extract_with_progress`].
+///
+/// This is implemented for closures that take a [`ProgressStatus`].
+pub trait Progress {
+    /// Called when an entry is started, and after each block of its data is processed.
+    fn update(&mut self, status: &ProgressStatus<'_>);
+}
+
+impl<F: FnMut(&ProgressStatus<'_>)> Progress for F {
+    fn update(&mut self, status: &ProgressStatus<'_>) {
+        self(status)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ProgressStatus;
+    use crate::test_util::TempDir;
+    use crate::write::SimpleFileOptions;
+    use crate::{CompressionMethod, ZipWriter};
+    use std::io::{Cursor, Write};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn write_and_extract() {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let updates = written.clone();
+        writer.set_progress(move |status: &ProgressStatus<'_>| {
+            updates.lock().unwrap().push((
+                status.entry_index,
+                status.entry_name.to_string(),
+                status.entry_bytes,
+                status.bytes,
+            ));
+        });
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file("a", options).unwrap();
+        writer.write_all(b"first").unwrap();
+        writer.start_file("b", options).unwrap();
+        writer.write_all(b"second").unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+        assert_eq!(
+            *written.lock().unwrap(),
+            vec![
+                (0, "a".to_string(), 0, 0),
+                (0, "a".to_string(), 5, 5),
+                (1, "b".to_string(), 0, 5),
+                (1, "b".to_string(), 6, 11),
+            ]
+        );
+
+        let temp = TempDir::new("extract-progress");
+        let directory = temp.path();
+        let mut extracted = Vec::new();
+        archive
+            .extract_with_progress(directory, &mut |status: &ProgressStatus<'_>| {
+                assert_eq!(status.total_entries, Some(2));
+                assert_eq!(status.total_bytes, Some(11));
+                extracted.push((status.entry_index, status.entry_bytes, status.bytes));
+            })
+            .unwrap();
+        assert_eq!(extracted, vec![(0, 0, 0), (0, 5, 5), (1, 0, 5), (1, 6, 11)]);
+    }
+}
@@ -2,27 +2,28 @@
 
 #[cfg(feature = "aes-crypto")]
 use crate::aes::{AesReader, AesReaderValid};
-use crate::compression::CompressionMethod;
+use crate::comment_metadata;
+use crate::compression::{CompressionMethod, CompressionRegistry, CustomCompression};
 use crate::cp437::FromCp437;
 use crate::crc32::Crc32Reader;
-use crate::extra_fields::{ExtendedTimestamp, ExtraField};
+use crate::extra_fields::{Expiry, ExtendedTimestamp, ExtraField, UnixOwner};
+use crate::metrics::Metrics;
+use crate::progress::{Progress, ProgressStatus};
 use crate::read::zip_archive::Shared;
 use crate::result::{ZipError, ZipResult};
 use crate::spec;
-use crate::types::{AesMode, AesVendorVersion, DateTime, System, ZipFileData};
+use crate::types::{ffi, AesMode, AesVendorVersion, DateTime, System, ZipFileData};
 use crate::zipcrypto::{ZipCryptoReader, ZipCryptoReaderValid, ZipCryptoValidator};
 use indexmap::IndexMap;
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::io::{self, copy, prelude::*, sink};
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
+use std::str::Utf8Error;
 use std::sync::{Arc, OnceLock};
 
-#[cfg(any(
-    feature = "deflate",
-    feature = "deflate-zlib",
-    feature = "deflate-zlib-ng"
-))]
+#[cfg(feature = "_deflate-flate2")]
 use flate2::read::DeflateDecoder;
 
 #[cfg(feature = "deflate64")]
@@ -37,26 +38,97 @@ use zstd::stream::read::Decoder as ZstdDecoder;
 /// Provides high level API for reading from a stream.
 pub(crate) mod stream;
 
+mod analyze;
+pub use analyze::{AnalysisFinding, AnalysisReport, AnalyzeOptions, FindingKind};
+
+mod apk_signing;
+pub(crate) use apk_signing::{find_apk_signing_block, is_apk_signing_block};
+
+mod archive_index;
+pub use archive_index::ArchiveIndex;
+
+mod backend;
+pub use backend::{Backend, BackendReader};
+
+mod entry_meta;
+pub use entry_meta::EntryMetaRef;
+
+mod extract;
+pub(crate) use extract::glob_matches;
+pub use extract::{extract_from_stream, ExtractOptions, OverwritePolicy};
+
+mod layout;
+pub use layout::{ArchiveLayout, LayoutSegment, SegmentKind};
+
+mod lazy;
+pub use lazy::LazyZipArchive;
+
+mod limits;
+pub use limits::ReadLimits;
+pub(crate) use limits::{EntryLimit, LimitTracker};
+
+mod lookup;
+use lookup::NameIndex;
+
+mod metadata;
+pub use metadata::ZipEntryMetadata;
+
+mod parallel;
+pub use parallel::ParallelOptions;
+
+mod parse_options;
+pub use parse_options::ParseOptions;
+
+mod pipeline;
+pub use pipeline::{EntryPipeline, Stage};
+
+mod plan;
+pub use plan::{ExtractPlan, PlanProblem, PlannedAction, PlannedEntry};
+
+mod recovery;
+pub use recovery::RecoveryOptions;
+
+mod seekable;
+pub use seekable::SeekableZipFile;
+
+mod spanned;
+pub use spanned::SpannedReader;
+
+mod stats;
+pub use stats::{ArchiveStats, MethodStats};
+
+mod verify;
+pub use verify::{ProblemKind, VerifyOptions, VerifyProblem, VerifyReport};
+
 #[cfg(feature = "lzma")]
 pub(crate) mod lzma;
+#[cfg(feature = "xz")]
+pub(crate) mod xz;
 
 // Put the struct declaration in a private module to convince rustdoc to display ZipArchive nicely
 pub(crate) mod zip_archive {
-    use std::sync::Arc;
+    use std::sync::{Arc, OnceLock};
 
     /// Extract immutable data from `ZipArchive` to make it cheap to clone
     #[derive(Debug)]
     pub(crate) struct Shared {
         pub(crate) files: super::IndexMap<Box<str>, super::ZipFileData>,
         pub(super) offset: u64,
-        pub(super) dir_start: u64,
+        pub(crate) dir_start: u64,
+        pub(super) name_index: OnceLock<super::NameIndex>,
     }
 
     /// ZIP archive reader
     ///
-    /// At the moment, this type is cheap to clone if this is the case for the
-    /// reader it uses. However, this is not guaranteed by this crate and it may
-    /// change in the future.
+    /// Cloning shares the parsed central directory, so this type is as cheap to clone as the
+    /// reader it uses. Each clone reads through its own reader, so clones can read different
+    /// entries on different threads at once; with a [`crate::read::BackendReader`] over a file
+    /// or buffer, they share one handle and read it by offset, without reopening it.
+    ///
+    /// Entry indices follow the order of the central directory, and so does every method that
+    /// iterates over entries, such as [`ZipArchive::file_names`]. This order is part of the API
+    /// and won't change between versions. Use [`ZipArchive::sorted_by_name`] or
+    /// [`ZipArchive::sorted_by_offset`] for other orders.
     ///
     /// ```no_run
     /// use std::io::prelude::*;
@@ -74,14 +146,19 @@ pub(crate) mod zip_archive {
     /// ```
     #[derive(Clone, Debug)]
     pub struct ZipArchive<R> {
-        pub(super) reader: R,
+        pub(crate) reader: R,
         pub(super) shared: Arc<Shared>,
         pub(super) comment: Arc<[u8]>,
+        pub(super) registry: Option<Arc<super::CompressionRegistry>>,
+        pub(super) limits: Option<Arc<super::LimitTracker>>,
+        pub(super) metrics: Option<Arc<dyn crate::metrics::Metrics>>,
     }
 }
 
 #[cfg(feature = "lzma")]
 use crate::read::lzma::LzmaDecoder;
+#[cfg(feature = "xz")]
+use crate::read::xz::XzDecoder;
 use crate::result::ZipError::{InvalidPassword, UnsupportedArchive};
 use crate::spec::path_to_string;
 use crate::unstable::LittleEndianReadExt;
@@ -149,12 +226,18 @@ pub(crate) enum ZipFileReader<'a> {
     Zstd(Crc32Reader<ZstdDecoder<'a, io::BufReader<CryptoReader<'a>>>>),
     #[cfg(feature = "lzma")]
     Lzma(Crc32Reader<Box<LzmaDecoder<CryptoReader<'a>>>>),
+    #[cfg(feature = "xz")]
+    Xz(Crc32Reader<XzDecoder<CryptoReader<'a>>>),
+    Custom(Crc32Reader<Box<dyn Read + 'a>>),
 }
 
 impl<'a> Read for ZipFileReader<'a> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         match self {
-            ZipFileReader::NoReader => panic!("ZipFileReader was in an invalid state"),
+            ZipFileReader::NoReader => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "ZipFileReader was in an invalid state",
+            )),
             ZipFileReader::Raw(r) => r.read(buf),
             ZipFileReader::Stored(r) => r.read(buf),
             #[cfg(feature = "_deflate-any")]
@@ -167,6 +250,9 @@ impl<'a> Read for ZipFileReader<'a> {
             ZipFileReader::Zstd(r) => r.read(buf),
             #[cfg(feature = "lzma")]
             ZipFileReader::Lzma(r) => r.read(buf),
+            #[cfg(feature = "xz")]
+            ZipFileReader::Xz(r) => r.read(buf),
+            ZipFileReader::Custom(r) => r.read(buf),
         }
     }
 }
@@ -175,7 +261,7 @@ impl<'a> ZipFileReader<'a> {
     /// Consumes this decoder, returning the underlying reader.
     pub fn drain(self) {
         let mut inner = match self {
-            ZipFileReader::NoReader => panic!("ZipFileReader was in an invalid state"),
+            ZipFileReader::NoReader => return,
             ZipFileReader::Raw(r) => r,
             ZipFileReader::Stored(r) => r.into_inner().into_inner(),
             #[cfg(feature = "_deflate-any")]
@@ -195,6 +281,17 @@ impl<'a> ZipFileReader<'a> {
                 }
                 return;
             }
+            #[cfg(feature = "xz")]
+            ZipFileReader::Xz(r) => match r.into_inner().into_inner() {
+                Some(inner) => inner.into_inner(),
+                // Already decompressed, so the compressed data has been consumed
+                None => return,
+            },
+            ZipFileReader::Custom(r) => {
+                // A custom decoder doesn't give its reader back, so it reads the rest instead
+                let _ = copy(&mut r.into_inner(), &mut sink());
+                return;
+            }
         };
         let _ = copy(&mut inner, &mut sink());
     }
@@ -205,12 +302,27 @@ pub struct ZipFile<'a> {
     pub(crate) data: Cow<'a, ZipFileData>,
     pub(crate) crypto_reader: Option<CryptoReader<'a>>,
     pub(crate) reader: ZipFileReader<'a>,
+    pub(crate) custom_method: Option<Arc<dyn CustomCompression>>,
+    pub(crate) limit: Option<EntryLimit>,
+    /// Taken when the file has been read to the end, so its compressed size is counted once
+    pub(crate) metrics: Option<Arc<dyn Metrics>>,
 }
 
 pub(crate) fn find_content<'a>(
     data: &ZipFileData,
     reader: &'a mut (impl Read + Seek),
 ) -> ZipResult<io::Take<&'a mut dyn Read>> {
+    let data_start = find_data_start(data, reader)?;
+    reader.seek(io::SeekFrom::Start(data_start))?;
+    Ok((reader as &mut dyn Read).take(data.compressed_size))
+}
+
+/// Checks the local header of `data` and returns where its data starts, reading the header's
+/// lengths if they haven't been read yet.
+pub(crate) fn find_data_start(
+    data: &ZipFileData,
+    reader: &mut (impl Read + Seek),
+) -> ZipResult<u64> {
     // Parse local header
     reader.seek(io::SeekFrom::Start(data.header_start))?;
     let signature = reader.read_u32_le()?;
@@ -223,21 +335,44 @@ pub(crate) fn find_content<'a>(
             let file_name_length = reader.read_u16_le()? as u64;
             let extra_field_length = reader.read_u16_le()? as u64;
             let magic_and_header = 4 + 22 + 2 + 2;
-            let data_start =
-                data.header_start + magic_and_header + file_name_length + extra_field_length;
+            let data_start = data
+                .header_start
+                .checked_add(magic_and_header + file_name_length + extra_field_length)
+                .ok_or(ZipError::InvalidArchive(
+                    "File data is beyond the end of the archive",
+                ))?;
             data.data_start.get_or_init(|| data_start);
             data_start
         }
         Some(start) => *start,
     };
+    Ok(data_start)
+}
 
-    reader.seek(io::SeekFrom::Start(data_start))?;
-    Ok((reader as &mut dyn Read).take(data.compressed_size))
+/// Returns where the entry described by `data` ends, including its data descriptor if it has one.
+pub(crate) fn find_entry_end(
+    data: &ZipFileData,
+    reader: &mut (impl Read + Seek),
+) -> ZipResult<u64> {
+    let data_end = find_data_start(data, reader)?.saturating_add(data.compressed_size);
+    if !data.using_data_descriptor {
+        return Ok(data_end);
+    }
+    reader.seek(io::SeekFrom::Start(data_end))?;
+    let signature = match reader.read_u32_le() {
+        Ok(signature) => signature,
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => 0,
+        Err(e) => return Err(e.into()),
+    };
+    let mut len = if data.large_file { 20 } else { 12 };
+    if signature == spec::DATA_DESCRIPTOR_SIGNATURE {
+        len += 4;
+    }
+    Ok(data_end + len)
 }
 
 #[allow(clippy::too_many_arguments)]
 pub(crate) fn make_crypto_reader<'a>(
-    compression_method: CompressionMethod,
     crc32: u32,
     last_modified_time: DateTime,
     using_data_descriptor: bool,
@@ -246,13 +381,6 @@ pub(crate) fn make_crypto_reader<'a>(
     aes_info: Option<(AesMode, AesVendorVersion, CompressionMethod)>,
     #[cfg(feature = "aes-crypto")] compressed_size: u64,
 ) -> ZipResult<CryptoReader<'a>> {
-    #[allow(deprecated)]
-    {
-        if let CompressionMethod::Unsupported(_) = compression_method {
-            return unsupported_zip_error("Compression method not supported");
-        }
-    }
-
     let reader = match (password, aes_info) {
         #[cfg(not(feature = "aes-crypto"))]
         (Some(_), Some(_)) => {
@@ -279,12 +407,36 @@ pub(crate) fn make_crypto_reader<'a>(
     Ok(reader)
 }
 
-pub(crate) fn make_reader(
+/// Fails unless the data of a file compressed with `compression_method` can be decompressed,
+/// either by this crate or by `custom_method`.
+pub(crate) fn check_supported(
+    compression_method: CompressionMethod,
+    custom_method: Option<&Arc<dyn CustomCompression>>,
+) -> ZipResult<()> {
+    #[allow(deprecated)]
+    match compression_method {
+        CompressionMethod::Unsupported(_) if custom_method.is_none() => {
+            unsupported_zip_error("Compression method not supported")
+        }
+        _ => Ok(()),
+    }
+}
+
+pub(crate) fn make_reader<'a>(
     compression_method: CompressionMethod,
     crc32: u32,
-    reader: CryptoReader,
-) -> ZipResult<ZipFileReader> {
+    #[cfg(feature = "xz")] uncompressed_size: u64,
+    reader: CryptoReader<'a>,
+    custom_method: Option<&Arc<dyn CustomCompression>>,
+) -> ZipResult<ZipFileReader<'a>> {
     let ae2_encrypted = reader.is_ae2_encrypted();
+    if let Some(method) = custom_method {
+        return Ok(ZipFileReader::Custom(Crc32Reader::new(
+            method.decoder(Box::new(reader))?,
+            crc32,
+            ae2_encrypted,
+        )));
+    }
 
     match compression_method {
         CompressionMethod::Stored => Ok(ZipFileReader::Stored(Crc32Reader::new(
@@ -321,7 +473,7 @@ pub(crate) fn make_reader(
         }
         #[cfg(feature = "zstd")]
         CompressionMethod::Zstd => {
-            let zstd_reader = ZstdDecoder::new(reader).unwrap();
+            let zstd_reader = ZstdDecoder::new(reader)?;
             Ok(ZipFileReader::Zstd(Crc32Reader::new(
                 zstd_reader,
                 crc32,
@@ -337,6 +489,15 @@ pub(crate) fn make_reader(
                 ae2_encrypted,
             )))
         }
+        #[cfg(feature = "xz")]
+        CompressionMethod::Xz => {
+            let reader = XzDecoder::new(reader, uncompressed_size);
+            Ok(ZipFileReader::Xz(Crc32Reader::new(
+                reader,
+                crc32,
+                ae2_encrypted,
+            )))
+        }
         _ => Err(UnsupportedArchive("Compression method not supported")),
     }
 }
@@ -351,11 +512,18 @@ pub(crate) struct CentralDirectoryInfo {
 
 impl<R> ZipArchive<R> {
     pub(crate) fn from_finalized_writer(
-        files: IndexMap<Box<str>, ZipFileData>,
+        mut files: IndexMap<Box<str>, ZipFileData>,
         comment: Vec<u8>,
         reader: R,
         central_start: u64,
     ) -> ZipResult<Self> {
+        for file in files.values_mut() {
+            // The writer records AES-encrypted files as using the AES method, but when reading
+            // them the method from the AES extra field is used, as it is when parsing an archive.
+            if let Some((_, _, compression_method)) = file.aes_mode {
+                file.compression_method = compression_method;
+            }
+        }
         let initial_offset = match files.first() {
             Some((_, file)) => file.header_start,
             None => 0,
@@ -364,11 +532,15 @@ impl<R> ZipArchive<R> {
             files,
             offset: initial_offset,
             dir_start: central_start,
+            name_index: OnceLock::new(),
         });
         Ok(Self {
             reader,
             shared,
             comment: comment.into_boxed_slice().into(),
+            registry: None,
+            limits: None,
+            metrics: None,
         })
     }
 
@@ -384,6 +556,29 @@ impl<R> ZipArchive<R> {
         }
         Some(total)
     }
+
+    /// Returns the indices of all entries, ordered by file name.
+    ///
+    /// Names are compared byte-wise as UTF-8, so the result doesn't depend on the locale. Entries
+    /// with equal names keep their central directory order.
+    pub fn sorted_by_name(&self) -> Vec<usize> {
+        let files = &self.shared.files;
+        let mut indices: Vec<usize> = (0..files.len()).collect();
+        indices.sort_by(|&a, &b| files[a].file_name.cmp(&files[b].file_name));
+        indices
+    }
+
+    /// Returns the indices of all entries, ordered by the offset of their local file header.
+    ///
+    /// This is the order that reads the archive front to back, which can differ from the central
+    /// directory order when the archive has been edited. Entries sharing a local header keep their
+    /// central directory order.
+    pub fn sorted_by_offset(&self) -> Vec<usize> {
+        let files = &self.shared.files;
+        let mut indices: Vec<usize> = (0..files.len()).collect();
+        indices.sort_by_key(|&i| files[i].header_start);
+        indices
+    }
 }
 
 impl<R: Read + Seek> ZipArchive<R> {
@@ -476,16 +671,14 @@ impl<R: Read + Seek> ZipArchive<R> {
 
     fn get_directory_info_zip64(
         reader: &mut R,
-        footer: &spec::CentralDirectoryEnd,
         cde_start_pos: u64,
     ) -> ZipResult<Vec<ZipResult<CentralDirectoryInfo>>> {
         // See if there's a ZIP64 footer. The ZIP64 locator if present will
-        // have its signature 20 bytes in front of the standard footer. The
-        // standard footer, in turn, is 22+N bytes large, where N is the
-        // comment length. Therefore:
-        reader.seek(io::SeekFrom::End(
-            -(20 + 22 + footer.zip_file_comment.len() as i64),
-        ))?;
+        // have its signature 20 bytes in front of the standard footer, which
+        // isn't necessarily at the end of the file. Therefore:
+        reader.seek(io::SeekFrom::Start(cde_start_pos.checked_sub(20).ok_or(
+            ZipError::InvalidArchive("File cannot contain ZIP64 central directory end"),
+        )?))?;
         let locator64 = spec::Zip64CentralDirectoryEndLocator::parse(reader)?;
 
         // We need to reassess `archive_offset`. We know where the ZIP64
@@ -552,10 +745,54 @@ impl<R: Read + Seek> ZipArchive<R> {
         reader: &mut R,
         footer: &spec::CentralDirectoryEnd,
         cde_start_pos: u64,
+        legacy_decoder: Option<fn(&[u8]) -> String>,
+        reject_duplicates: bool,
     ) -> ZipResult<Shared> {
+        let (dir_info, files) =
+            Self::find_directory(reader, footer, cde_start_pos, |reader, dir_info| {
+                // If the parsed number of files is greater than the offset then
+                // something fishy is going on and we shouldn't trust number_of_files.
+                let file_capacity = if dir_info.number_of_files > dir_info.directory_start as usize
+                {
+                    0
+                } else {
+                    dir_info.number_of_files
+                };
+                let mut files = IndexMap::with_capacity(file_capacity);
+                reader.seek(io::SeekFrom::Start(dir_info.directory_start))?;
+                for _ in 0..dir_info.number_of_files {
+                    let file = central_header_to_zip_file(
+                        reader,
+                        dir_info.archive_offset,
+                        legacy_decoder,
+                    )?;
+                    let name = file.file_name.clone();
+                    if files.insert(name, file).is_some() && reject_duplicates {
+                        return Err(ZipError::InvalidArchive("Duplicate file name"));
+                    }
+                }
+                Ok(files)
+            })?;
+        Ok(Shared {
+            files,
+            offset: dir_info.archive_offset,
+            dir_start: dir_info.directory_start,
+            name_index: OnceLock::new(),
+        })
+    }
+
+    /// Find the central directory from the ZIP64 and ZIP32 footers, reading each candidate with
+    /// `read_directory`. Of the candidates read without error, the one that starts last is used,
+    /// and the reader is left at its start.
+    pub(crate) fn find_directory<T>(
+        reader: &mut R,
+        footer: &spec::CentralDirectoryEnd,
+        cde_start_pos: u64,
+        mut read_directory: impl FnMut(&mut R, &CentralDirectoryInfo) -> ZipResult<T>,
+    ) -> ZipResult<(CentralDirectoryInfo, T)> {
         // Check if file has a zip64 footer
-        let mut results = Self::get_directory_info_zip64(reader, footer, cde_start_pos)
-            .unwrap_or_else(|e| vec![Err(e)]);
+        let mut results =
+            Self::get_directory_info_zip64(reader, cde_start_pos).unwrap_or_else(|e| vec![Err(e)]);
         let zip32_result = Self::get_directory_info_zip32(footer, cde_start_pos);
         let mut invalid_errors = Vec::new();
         let mut unsupported_errors = Vec::new();
@@ -596,28 +833,11 @@ impl<R: Read + Seek> ZipArchive<R> {
             .into_iter()
             .map(|result| {
                 result.and_then(|dir_info| {
-                    // If the parsed number of files is greater than the offset then
-                    // something fishy is going on and we shouldn't trust number_of_files.
-                    let file_capacity =
-                        if dir_info.number_of_files > dir_info.directory_start as usize {
-                            0
-                        } else {
-                            dir_info.number_of_files
-                        };
-                    let mut files = IndexMap::with_capacity(file_capacity);
-                    reader.seek(io::SeekFrom::Start(dir_info.directory_start))?;
-                    for _ in 0..dir_info.number_of_files {
-                        let file = central_header_to_zip_file(reader, dir_info.archive_offset)?;
-                        files.insert(file.file_name.clone(), file);
-                    }
+                    let directory = read_directory(reader, &dir_info)?;
                     if dir_info.disk_number != dir_info.disk_with_central_directory {
                         unsupported_zip_error("Support for multi-disk files is not implemented")
                     } else {
-                        Ok(Shared {
-                            files,
-                            offset: dir_info.archive_offset,
-                            dir_start: dir_info.directory_start,
-                        })
+                        Ok((dir_info, directory))
                     }
                 })
             })
@@ -634,61 +854,78 @@ impl<R: Read + Seek> ZipArchive<R> {
                 .next()
                 .unwrap_or_else(|| invalid_errors.into_iter().next().unwrap()));
         }
-        let shared = ok_results
+        let found = ok_results
             .into_iter()
-            .max_by_key(|shared| shared.dir_start)
+            .max_by_key(|(dir_info, _)| dir_info.directory_start)
             .unwrap();
-        reader.seek(io::SeekFrom::Start(shared.dir_start))?;
-        Ok(shared)
+        reader.seek(io::SeekFrom::Start(found.0.directory_start))?;
+        Ok(found)
     }
 
     /// Read a ZIP archive, collecting the files it contains
     ///
     /// This uses the central directory record of the ZIP file, and ignores local file headers
-    pub fn new(mut reader: R) -> ZipResult<ZipArchive<R>> {
-        let (footer, cde_start_pos) = spec::CentralDirectoryEnd::find_and_parse(&mut reader)?;
-        let shared = Self::get_metadata(&mut reader, &footer, cde_start_pos)?;
-        Ok(ZipArchive {
-            reader,
-            shared: shared.into(),
-            comment: footer.zip_file_comment.into(),
-        })
+    pub fn new(reader: R) -> ZipResult<ZipArchive<R>> {
+        Self::with_options(reader, ParseOptions::default())
+    }
+
+    /// Decompress files with the methods in `registry`, as well as those this crate implements.
+    pub fn set_compression_registry(&mut self, registry: Arc<CompressionRegistry>) {
+        self.registry = Some(registry);
+    }
+
+    /// Reports the bytes read from this archive's files to `metrics`.
+    pub fn set_metrics(&mut self, metrics: Arc<dyn Metrics>) {
+        self.metrics = Some(metrics);
     }
+
     /// Extract a Zip archive into a directory, overwriting files if they
     /// already exist. Paths are sanitized with [`ZipFile::enclosed_name`].
     ///
     /// Extraction is not atomic; If an error is encountered, some of the files
-    /// may be left on disk.
+    /// may be left on disk. [`ExtractOptions::atomic`] at least keeps any file from being left
+    /// partly written.
+    ///
+    /// Entries that are symlinks are extracted as regular files that contain the link's target;
+    /// see [`ExtractOptions::symlinks`].
     pub fn extract<P: AsRef<Path>>(&mut self, directory: P) -> ZipResult<()> {
-        use std::fs;
+        self.extract_with_options(directory, ExtractOptions::default())
+    }
 
-        for i in 0..self.len() {
+    /// Extract a Zip archive into a directory like [`ZipArchive::extract`], reporting the
+    /// progress to `progress` as each file is started and as its decompressed contents are
+    /// written.
+    ///
+    /// The total size is known unless an entry's sizes are stored in a data descriptor.
+    pub fn extract_with_progress<P: AsRef<Path>>(
+        &mut self,
+        directory: P,
+        progress: &mut dyn Progress,
+    ) -> ZipResult<()> {
+        let total_entries = self.len();
+        let total_bytes = self
+            .decompressed_size()
+            .and_then(|size| u64::try_from(size).ok());
+        let mut bytes = 0;
+        for i in 0..total_entries {
             let mut file = self.by_index(i)?;
-            let filepath = file
-                .enclosed_name()
-                .ok_or(ZipError::InvalidArchive("Invalid file path"))?;
-
-            let outpath = directory.as_ref().join(filepath);
-
-            if file.is_dir() {
-                fs::create_dir_all(&outpath)?;
-            } else {
-                if let Some(p) = outpath.parent() {
-                    if !p.exists() {
-                        fs::create_dir_all(p)?;
-                    }
-                }
-                let mut outfile = fs::File::create(&outpath)?;
-                io::copy(&mut file, &mut outfile)?;
-            }
-            // Get and Set permissions
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::PermissionsExt;
-                if let Some(mode) = file.unix_mode() {
-                    fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))?;
-                }
-            }
+            let name = file.name().to_string();
+            let mut entry_bytes = 0;
+            let mut report = |count: u64| {
+                entry_bytes += count;
+                bytes += count;
+                progress.update(&ProgressStatus {
+                    entry_index: i,
+                    entry_name: &name,
+                    entry_bytes,
+                    bytes,
+                    total_entries: Some(total_entries),
+                    total_bytes,
+                });
+            };
+            report(0);
+            let options = ExtractOptions::default();
+            extract_entry(&mut file, directory.as_ref(), &options, &mut report)?;
         }
         Ok(())
     }
@@ -716,6 +953,17 @@ impl<R: Read + Seek> ZipArchive<R> {
         &self.comment
     }
 
+    /// Get the comment of the zip archive as UTF-8 text.
+    pub fn comment_str(&self) -> Result<&str, Utf8Error> {
+        std::str::from_utf8(&self.comment)
+    }
+
+    /// Get the metadata in the archive comment, if it's a JSON object whose values are all
+    /// strings, as written by [`crate::ZipWriter::set_comment_metadata`].
+    pub fn comment_metadata(&self) -> Option<BTreeMap<String, String>> {
+        comment_metadata::decode(&self.comment)
+    }
+
     /// Returns an iterator over all the file and directory names in this archive.
     pub fn file_names(&self) -> impl Iterator<Item = &str> {
         self.shared.files.keys().map(|s| s.as_ref())
@@ -813,45 +1061,72 @@ impl<R: Read + Seek> ZipArchive<R> {
             crypto_reader: None,
             reader: ZipFileReader::Raw(find_content(data, reader)?),
             data: Cow::Borrowed(data),
+            custom_method: None,
+            limit: None,
+            metrics: None,
         })
     }
 
     fn by_index_with_optional_password(
         &mut self,
         file_number: usize,
-        mut password: Option<&[u8]>,
+        password: Option<&[u8]>,
     ) -> ZipResult<ZipFile<'_>> {
         let (_, data) = self
             .shared
             .files
             .get_index(file_number)
             .ok_or(ZipError::FileNotFound)?;
-
-        match (password, data.encrypted) {
-            (None, true) => return Err(ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED)),
-            (Some(_), false) => password = None, //Password supplied, but none needed! Discard.
-            _ => {}
-        }
-        let limit_reader = find_content(data, &mut self.reader)?;
-
-        let crypto_reader = make_crypto_reader(
-            data.compression_method,
-            data.crc32,
-            data.last_modified_time,
-            data.using_data_descriptor,
-            limit_reader,
+        let custom_method = self
+            .registry
+            .as_ref()
+            .and_then(|registry| registry.get(data.compression_method));
+        open_file(
+            data,
+            &mut self.reader,
             password,
-            data.aes_mode,
-            #[cfg(feature = "aes-crypto")]
-            data.compressed_size,
-        )?;
-        Ok(ZipFile {
-            crypto_reader: Some(crypto_reader),
-            reader: ZipFileReader::NoReader,
-            data: Cow::Borrowed(data),
+            custom_method,
+            self.limits.as_ref(),
+            self.metrics.clone(),
+        )
+    }
+
+    /// Returns an iterator over handles to every entry in this archive, in central directory order.
+    ///
+    /// Each [`LazyEntry`] carries the entry's metadata, but doesn't seek to or decrypt the entry
+    /// until [`LazyEntry::open`] is called. This makes it cheap to filter entries on their metadata
+    /// before reading the ones that are needed.
+    pub fn entries_lazy(&self) -> impl ExactSizeIterator<Item = LazyEntry> {
+        let shared = self.shared.clone();
+        (0..shared.files.len()).map(move |index| LazyEntry {
+            shared: shared.clone(),
+            index,
         })
     }
 
+    /// Finds the entry whose local header or compressed data contains the byte at `offset`, which
+    /// is a position in the underlying reader.
+    ///
+    /// Returns `None` if the offset falls between entries, in a data descriptor, or in the central
+    /// directory. If several entries share the same data, the one with the lowest index is
+    /// returned.
+    pub fn entry_at_data_offset(&mut self, offset: u64) -> ZipResult<Option<usize>> {
+        let mut candidate: Option<(usize, &ZipFileData)> = None;
+        for (index, file) in self.shared.files.values().enumerate() {
+            if file.header_start <= offset
+                && candidate.map_or(true, |(_, best)| file.header_start > best.header_start)
+            {
+                candidate = Some((index, file));
+            }
+        }
+        let Some((index, file)) = candidate else {
+            return Ok(None);
+        };
+        let data_end =
+            find_data_start(file, &mut self.reader)?.saturating_add(file.compressed_size);
+        Ok((offset < data_end).then_some(index))
+    }
+
     /// Unwrap and return the inner reader object
     ///
     /// The position of the reader is undefined.
@@ -860,14 +1135,223 @@ impl<R: Read + Seek> ZipArchive<R> {
     }
 }
 
+impl<T: AsRef<[u8]>> ZipArchive<io::Cursor<T>> {
+    /// Get the data of an entry as it's stored in the archive, still compressed and encrypted,
+    /// without copying it. For a stored entry, this is its contents.
+    ///
+    /// This suits archives that are in memory or memory-mapped, for example by wrapping a
+    /// `memmap2::Mmap` in a [`std::io::Cursor`].
+    pub fn raw_data(&self, file_number: usize) -> ZipResult<&[u8]> {
+        let (_, data) = self
+            .shared
+            .files
+            .get_index(file_number)
+            .ok_or(ZipError::FileNotFound)?;
+        let bytes = self.reader.get_ref().as_ref();
+        let data_start = find_data_start(data, &mut io::Cursor::new(bytes))?;
+        data_start
+            .checked_add(data.compressed_size)
+            .and_then(|data_end| bytes.get(data_start as usize..data_end as usize))
+            .ok_or(ZipError::InvalidArchive(
+                "File data is beyond the end of the archive",
+            ))
+    }
+}
+
+/// Extracts one entry into `directory`, passing the size of each block of data written to
+/// `on_data`.
+pub(crate) fn extract_entry(
+    file: &mut ZipFile,
+    directory: &Path,
+    options: &ExtractOptions,
+    on_data: &mut dyn FnMut(u64),
+) -> ZipResult<()> {
+    use std::fs;
+
+    let filepath = file
+        .enclosed_name()
+        .ok_or(ZipError::InvalidArchive("Invalid file path"))?;
+
+    let outpath = directory.join(&filepath);
+    // Nothing may be written through a symlink that leads outside `directory`
+    let parent = outpath.parent().unwrap_or(directory);
+    extract::check_within(directory, if file.is_dir() { &outpath } else { parent })?;
+
+    if file.is_symlink() && options.creates_symlinks() {
+        let mut target = String::new();
+        file.read_to_string(&mut target)?;
+        let target = PathBuf::from(target);
+        extract::check_symlink_target(&filepath, &target)?;
+        fs::create_dir_all(parent)?;
+        extract::check_symlink_resolves_within(directory, parent, &target)?;
+        let Some(outpath) = extract::resolve_existing(outpath, options.on_existing())? else {
+            return Ok(());
+        };
+        let link_path = match options.is_atomic() {
+            true => extract::temporary_sibling(&outpath),
+            // Replace an earlier file or link, as extracting a file would
+            false => {
+                if fs::symlink_metadata(&outpath).is_ok_and(|metadata| !metadata.is_dir()) {
+                    fs::remove_file(&outpath)?;
+                }
+                outpath.clone()
+            }
+        };
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&target, &link_path)?;
+        #[cfg(windows)]
+        {
+            let target_is_dir =
+                target.to_string_lossy().ends_with('/') || outpath.with_file_name(&target).is_dir();
+            if target_is_dir {
+                std::os::windows::fs::symlink_dir(&target, &link_path)?;
+            } else {
+                std::os::windows::fs::symlink_file(&target, &link_path)?;
+            }
+        }
+        if link_path != outpath {
+            fs::rename(&link_path, &outpath)?;
+        }
+        // The link's own permissions can't be set portably, and setting them would follow it
+        return Ok(());
+    }
+
+    if file.is_dir() {
+        fs::create_dir_all(&outpath)?;
+        set_unix_mode(file, &outpath)?;
+        return Ok(());
+    }
+    if let Some(p) = outpath.parent() {
+        if !p.exists() {
+            fs::create_dir_all(p)?;
+        }
+    }
+    let Some(outpath) = extract::resolve_existing(outpath, options.on_existing())? else {
+        return Ok(());
+    };
+    if options.is_atomic() {
+        let temporary = extract::temporary_sibling(&outpath);
+        let written = write_extracted_file(file, &temporary, options, on_data)
+            .and_then(|()| Ok(set_unix_mode(file, &temporary)?));
+        if let Err(e) = written {
+            let _ = fs::remove_file(&temporary);
+            return Err(e);
+        }
+        // Renaming replaces a link left by an earlier extraction rather than writing through it
+        fs::rename(&temporary, &outpath)?;
+    } else {
+        if fs::symlink_metadata(&outpath).is_ok_and(|metadata| metadata.is_symlink()) {
+            // Don't write through a link left by an earlier extraction
+            fs::remove_file(&outpath)?;
+        }
+        write_extracted_file(file, &outpath, options, on_data)?;
+        set_unix_mode(file, &outpath)?;
+    }
+    Ok(())
+}
+
+/// Writes the contents of `file` to a new file at `path`.
+fn write_extracted_file(
+    file: &mut ZipFile,
+    path: &Path,
+    options: &ExtractOptions,
+    on_data: &mut dyn FnMut(u64),
+) -> ZipResult<()> {
+    let mut outfile = ReportingWriter {
+        inner: std::fs::File::create(path)?,
+        on_data,
+    };
+    let preallocate = options.preallocates() && file.size() > 0;
+    if preallocate {
+        outfile.inner.set_len(file.size())?;
+    }
+    let written = io::copy(file, &mut outfile)?;
+    if preallocate && written < file.size() {
+        outfile.inner.set_len(written)?;
+    }
+    Ok(())
+}
+
+/// Gives the extracted file or directory at `path` the Unix mode of `file`, on Unix.
+fn set_unix_mode(file: &ZipFile, path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Some(mode) = file.unix_mode() {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = (file, path);
+    Ok(())
+}
+
+struct ReportingWriter<'a, W> {
+    inner: W,
+    on_data: &'a mut dyn FnMut(u64),
+}
+
+impl<W: Write> Write for ReportingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let count = self.inner.write(buf)?;
+        (self.on_data)(count as u64);
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
 const fn unsupported_zip_error<T>(detail: &'static str) -> ZipResult<T> {
     Err(ZipError::UnsupportedArchive(detail))
 }
 
+/// Open the entry described by `data` for reading its decompressed contents from `reader`.
+pub(crate) fn open_file<'a>(
+    data: &'a ZipFileData,
+    reader: &'a mut (impl Read + Seek),
+    mut password: Option<&[u8]>,
+    custom_method: Option<Arc<dyn CustomCompression>>,
+    limits: Option<&Arc<LimitTracker>>,
+    metrics: Option<Arc<dyn Metrics>>,
+) -> ZipResult<ZipFile<'a>> {
+    match (password, data.encrypted) {
+        (None, true) => return Err(ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED)),
+        (Some(_), false) => password = None, //Password supplied, but none needed! Discard.
+        _ => {}
+    }
+    check_supported(data.compression_method, custom_method.as_ref())?;
+    let limit = limits
+        .map(|limits| limits.entry(data.compressed_size, data.uncompressed_size))
+        .transpose()?;
+    let limit_reader = find_content(data, reader)?;
+
+    let crypto_reader = make_crypto_reader(
+        data.crc32,
+        data.last_modified_time,
+        data.using_data_descriptor,
+        limit_reader,
+        password,
+        data.aes_mode,
+        #[cfg(feature = "aes-crypto")]
+        data.compressed_size,
+    )?;
+    Ok(ZipFile {
+        crypto_reader: Some(crypto_reader),
+        reader: ZipFileReader::NoReader,
+        data: Cow::Borrowed(data),
+        custom_method,
+        limit,
+        metrics,
+    })
+}
+
 /// Parse a central directory entry to collect the information for the file.
 pub(crate) fn central_header_to_zip_file<R: Read + Seek>(
     reader: &mut R,
     archive_offset: u64,
+    legacy_decoder: Option<fn(&[u8]) -> String>,
 ) -> ZipResult<ZipFileData> {
     let central_header_start = reader.stream_position()?;
 
@@ -876,15 +1360,24 @@ pub(crate) fn central_header_to_zip_file<R: Read + Seek>(
     if signature != spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE {
         Err(ZipError::InvalidArchive("Invalid Central Directory header"))
     } else {
-        central_header_to_zip_file_inner(reader, archive_offset, central_header_start)
+        central_header_to_zip_file_inner(
+            reader,
+            archive_offset,
+            central_header_start,
+            legacy_decoder,
+        )
     }
 }
 
 /// Parse a central directory entry to collect the information for the file.
+///
+/// Names and comments not flagged as UTF-8 are decoded with `legacy_decoder`, or as code page 437
+/// without one.
 fn central_header_to_zip_file_inner<R: Read>(
     reader: &mut R,
     archive_offset: u64,
     central_header_start: u64,
+    legacy_decoder: Option<fn(&[u8]) -> String>,
 ) -> ZipResult<ZipFileData> {
     let version_made_by = reader.read_u16_le()?;
     let _version_to_extract = reader.read_u16_le()?;
@@ -901,7 +1394,7 @@ fn central_header_to_zip_file_inner<R: Read>(
     let file_name_length = reader.read_u16_le()? as usize;
     let extra_field_length = reader.read_u16_le()? as usize;
     let file_comment_length = reader.read_u16_le()? as usize;
-    let _disk_number = reader.read_u16_le()?;
+    let disk_number = reader.read_u16_le()?;
     let _internal_file_attributes = reader.read_u16_le()?;
     let external_file_attributes = reader.read_u32_le()?;
     let offset = reader.read_u32_le()? as u64;
@@ -912,13 +1405,15 @@ fn central_header_to_zip_file_inner<R: Read>(
     let mut file_comment_raw = vec![0; file_comment_length];
     reader.read_exact(&mut file_comment_raw)?;
 
-    let file_name: Box<str> = match is_utf8 {
-        true => String::from_utf8_lossy(&file_name_raw).into(),
-        false => file_name_raw.from_cp437().into(),
+    let file_name: Box<str> = match (is_utf8, legacy_decoder) {
+        (true, _) => String::from_utf8_lossy(&file_name_raw).into(),
+        (false, Some(decode)) => decode(&file_name_raw).into(),
+        (false, None) => file_name_raw.from_cp437().into(),
     };
-    let file_comment: Box<str> = match is_utf8 {
-        true => String::from_utf8_lossy(&file_comment_raw).into(),
-        false => file_comment_raw.from_cp437().into(),
+    let file_comment: Box<str> = match (is_utf8, legacy_decoder) {
+        (true, _) => String::from_utf8_lossy(&file_comment_raw).into(),
+        (false, Some(decode)) => decode(&file_comment_raw).into(),
+        (false, None) => file_comment_raw.from_cp437().into(),
     };
 
     // Construct the result
@@ -942,6 +1437,7 @@ fn central_header_to_zip_file_inner<R: Read>(
         central_extra_field: None,
         file_comment,
         header_start: offset,
+        disk_number: disk_number.into(),
         extra_data_start: None,
         central_header_start,
         data_start: OnceLock::new(),
@@ -952,7 +1448,7 @@ fn central_header_to_zip_file_inner<R: Read>(
         extra_fields: Vec::new(),
     };
 
-    match parse_extra_field(&mut result) {
+    match parse_extra_field(&mut result, &file_comment_raw) {
         Ok(..) | Err(ZipError::Io(..)) => {}
         Err(e) => return Err(e),
     }
@@ -973,7 +1469,8 @@ fn central_header_to_zip_file_inner<R: Read>(
     Ok(result)
 }
 
-fn parse_extra_field(file: &mut ZipFileData) -> ZipResult<()> {
+/// Parses the extra field of `file`, whose comment as stored in its header is `file_comment_raw`.
+fn parse_extra_field(file: &mut ZipFileData, file_comment_raw: &[u8]) -> ZipResult<()> {
     let Some(extra_field) = &file.extra_field else {
         return Ok(());
     };
@@ -1000,6 +1497,10 @@ fn parse_extra_field(file: &mut ZipFileData) -> ZipResult<()> {
                     file.header_start = reader.read_u64_le()?;
                     len_left -= 8;
                 }
+                if file.disk_number == u16::MAX as u32 {
+                    file.disk_number = reader.read_u32_le()?;
+                    len_left -= 4;
+                }
             }
             0x9901 => {
                 // AES
@@ -1038,7 +1539,7 @@ fn parse_extra_field(file: &mut ZipFileData) -> ZipResult<()> {
                 };
                 file.compression_method = compression_method;
             }
-            0x5455 => {
+            ExtendedTimestamp::HEADER_ID => {
                 // extended timestamp
                 // https://libzip.org/specifications/extrafld.txt
 
@@ -1049,6 +1550,41 @@ fn parse_extra_field(file: &mut ZipFileData) -> ZipResult<()> {
                 // the reader for ExtendedTimestamp consumes `len` bytes
                 len_left = 0;
             }
+            UnixOwner::HEADER_ID => {
+                file.extra_fields
+                    .push(ExtraField::UnixOwner(UnixOwner::try_from_reader(
+                        &mut reader,
+                        len,
+                    )?));
+                len_left = 0;
+            }
+            Expiry::HEADER_ID => {
+                file.extra_fields
+                    .push(ExtraField::Expiry(Expiry::try_from_reader(
+                        &mut reader,
+                        len,
+                    )?));
+                len_left = 0;
+            }
+            0x7075 if len >= 5 => {
+                // Info-ZIP Unicode Path: the name in UTF-8, which only applies while the name
+                // in the header is still the one it was made from
+                if let Some(unicode_name) =
+                    read_unicode_extra_field(&mut reader, len, &file.file_name_raw)?
+                {
+                    file.file_name = unicode_name.into();
+                }
+                len_left = 0;
+            }
+            0x6375 if len >= 5 => {
+                // Info-ZIP Unicode Comment: likewise, the comment in UTF-8
+                if let Some(unicode_comment) =
+                    read_unicode_extra_field(&mut reader, len, file_comment_raw)?
+                {
+                    file.file_comment = unicode_comment.into();
+                }
+                len_left = 0;
+            }
             _ => {
                 // Other fields are ignored
             }
@@ -1062,20 +1598,53 @@ fn parse_extra_field(file: &mut ZipFileData) -> ZipResult<()> {
     Ok(())
 }
 
+/// Reads the `len` bytes of an Info-ZIP Unicode extra field, and returns the UTF-8 text it holds
+/// if it was made from `header_value`, the field of the header it replaces.
+fn read_unicode_extra_field<R: Read>(
+    reader: &mut R,
+    len: u16,
+    header_value: &[u8],
+) -> ZipResult<Option<String>> {
+    let mut version = [0u8];
+    reader.read_exact(&mut version)?;
+    let crc32 = reader.read_u32_le()?;
+    let mut unicode = vec![0; len as usize - 5];
+    reader.read_exact(&mut unicode)?;
+    if version[0] != 1 || crc32 != crc32fast::hash(header_value) {
+        return Ok(None);
+    }
+    Ok(String::from_utf8(unicode).ok())
+}
+
 /// Methods for retrieving information on zip files
 impl<'a> ZipFile<'a> {
     fn get_reader(&mut self) -> ZipResult<&mut ZipFileReader<'a>> {
         if let ZipFileReader::NoReader = self.reader {
             let data = &self.data;
-            let crypto_reader = self.crypto_reader.take().expect("Invalid reader state");
-            self.reader = make_reader(data.compression_method, data.crc32, crypto_reader)?;
+            // A previous attempt that failed has already consumed the crypto reader
+            let crypto_reader = self
+                .crypto_reader
+                .take()
+                .ok_or(ZipError::Io(io::Error::new(
+                    io::ErrorKind::Other,
+                    "ZipFileReader was in an invalid state",
+                )))?;
+            self.reader = make_reader(
+                data.compression_method,
+                data.crc32,
+                #[cfg(feature = "xz")]
+                data.uncompressed_size,
+                crypto_reader,
+                self.custom_method.as_ref(),
+            )?;
         }
         Ok(&mut self.reader)
     }
 
     pub(crate) fn get_raw_reader(&mut self) -> &mut dyn Read {
-        if let ZipFileReader::NoReader = self.reader {
-            let crypto_reader = self.crypto_reader.take().expect("Invalid reader state");
+        if let (ZipFileReader::NoReader, Some(crypto_reader)) =
+            (&self.reader, self.crypto_reader.take())
+        {
             self.reader = ZipFileReader::Raw(crypto_reader.into_inner())
         }
         &mut self.reader
@@ -1190,6 +1759,13 @@ impl<'a> ZipFile<'a> {
         !self.is_dir()
     }
 
+    /// Returns whether the file is a symlink, according to its Unix mode. Its contents are the
+    /// link's target.
+    pub fn is_symlink(&self) -> bool {
+        self.unix_mode()
+            .is_some_and(|mode| mode & ffi::S_IFMT == ffi::S_IFLNK)
+    }
+
     /// Get unix mode for the file
     pub fn unix_mode(&self) -> Option<u32> {
         self.data.unix_mode()
@@ -1223,11 +1799,206 @@ impl<'a> ZipFile<'a> {
     pub fn extra_data_fields(&self) -> impl Iterator<Item = &ExtraField> {
         self.data.extra_fields.iter()
     }
+
+    /// Get the UID and GID of the file's owner, from the Info-ZIP New Unix extra field
+    pub fn unix_owner(&self) -> Option<UnixOwner> {
+        self.data.unix_owner()
+    }
+
+    /// Get the times from the Info-ZIP extended timestamp extra field
+    pub fn extended_timestamp(&self) -> Option<ExtendedTimestamp> {
+        self.data.extended_timestamp()
+    }
+
+    /// Get the creation time and time-to-live set with [`crate::write::FileOptions::expiry`]
+    pub fn expiry(&self) -> Option<Expiry> {
+        self.data.expiry()
+    }
+
+    /// Returns whether the file has an expiry that has passed, according to the system clock
+    pub fn is_expired(&self) -> bool {
+        self.expiry().is_some_and(|expiry| expiry.is_expired())
+    }
+}
+
+/// A handle to an entry of a [`ZipArchive`] whose data hasn't been opened yet.
+///
+/// Returned by [`ZipArchive::entries_lazy`]. The handle keeps the archive's metadata alive, so it
+/// can outlive the borrow of the archive it came from.
+#[derive(Clone, Debug)]
+pub struct LazyEntry {
+    shared: Arc<Shared>,
+    index: usize,
+}
+
+impl LazyEntry {
+    fn data(&self) -> &ZipFileData {
+        &self.shared.files[self.index]
+    }
+
+    /// Get the index of this entry in the archive
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Get the name of the file
+    ///
+    /// See [`ZipFile::name`] for why this name shouldn't be used directly when extracting.
+    pub fn name(&self) -> &str {
+        &self.data().file_name
+    }
+
+    /// Get the name of the file, in the raw (internal) byte representation.
+    pub fn name_raw(&self) -> &[u8] {
+        &self.data().file_name_raw
+    }
+
+    /// Ensure the file path is safe to use as a [`Path`]. See [`ZipFile::enclosed_name`].
+    pub fn enclosed_name(&self) -> Option<PathBuf> {
+        self.data().enclosed_name()
+    }
+
+    /// Get the comment of the file
+    pub fn comment(&self) -> &str {
+        &self.data().file_comment
+    }
+
+    /// Get the compression method used to store the file
+    pub fn compression(&self) -> CompressionMethod {
+        self.data().compression_method
+    }
+
+    /// Get the size of the file, in bytes, in the archive
+    pub fn compressed_size(&self) -> u64 {
+        self.data().compressed_size
+    }
+
+    /// Get the size of the file, in bytes, when uncompressed
+    pub fn size(&self) -> u64 {
+        self.data().uncompressed_size
+    }
+
+    /// Get the time the file was last modified
+    pub fn last_modified(&self) -> DateTime {
+        self.data().last_modified_time
+    }
+
+    /// Returns whether the file is actually a directory
+    pub fn is_dir(&self) -> bool {
+        self.name().ends_with('/') || self.name().ends_with('\\')
+    }
+
+    /// Returns whether the file is a regular file
+    pub fn is_file(&self) -> bool {
+        !self.is_dir()
+    }
+
+    /// Returns whether the file is encrypted, and so needs [`LazyEntry::open_decrypt`]
+    pub fn encrypted(&self) -> bool {
+        self.data().encrypted
+    }
+
+    /// Get unix mode for the file
+    pub fn unix_mode(&self) -> Option<u32> {
+        self.data().unix_mode()
+    }
+
+    /// Get the CRC32 hash of the original file
+    pub fn crc32(&self) -> u32 {
+        self.data().crc32
+    }
+
+    /// Get the starting offset of the zip header for this file
+    pub fn header_start(&self) -> u64 {
+        self.data().header_start
+    }
+
+    /// iterate through all extra fields
+    pub fn extra_data_fields(&self) -> impl Iterator<Item = &ExtraField> {
+        self.data().extra_fields.iter()
+    }
+
+    /// Get the UID and GID of the file's owner, from the Info-ZIP New Unix extra field
+    pub fn unix_owner(&self) -> Option<UnixOwner> {
+        self.data().unix_owner()
+    }
+
+    /// Get the times from the Info-ZIP extended timestamp extra field
+    pub fn extended_timestamp(&self) -> Option<ExtendedTimestamp> {
+        self.data().extended_timestamp()
+    }
+
+    /// Get the creation time and time-to-live set with [`crate::write::FileOptions::expiry`]
+    pub fn expiry(&self) -> Option<Expiry> {
+        self.data().expiry()
+    }
+
+    /// Returns whether the file has an expiry that has passed, according to the system clock
+    pub fn is_expired(&self) -> bool {
+        self.expiry().is_some_and(|expiry| expiry.is_expired())
+    }
+
+    fn check_archive<R>(&self, archive: &ZipArchive<R>) -> ZipResult<()> {
+        if Arc::ptr_eq(&self.shared, &archive.shared) {
+            Ok(())
+        } else {
+            Err(ZipError::InvalidArchive(
+                "Entry handle belongs to a different archive",
+            ))
+        }
+    }
+
+    /// Open the entry's data for reading.
+    ///
+    /// `archive` must be the archive this handle came from, or a clone of it.
+    pub fn open<'a, R: Read + Seek>(
+        &self,
+        archive: &'a mut ZipArchive<R>,
+    ) -> ZipResult<ZipFile<'a>> {
+        self.check_archive(archive)?;
+        archive.by_index(self.index)
+    }
+
+    /// Open the entry's data for reading, decrypting it with the given password.
+    ///
+    /// `archive` must be the archive this handle came from, or a clone of it.
+    pub fn open_decrypt<'a, R: Read + Seek>(
+        &self,
+        archive: &'a mut ZipArchive<R>,
+        password: &[u8],
+    ) -> ZipResult<ZipFile<'a>> {
+        self.check_archive(archive)?;
+        archive.by_index_decrypt(self.index, password)
+    }
+
+    /// Open the entry's data without decompressing it.
+    ///
+    /// `archive` must be the archive this handle came from, or a clone of it.
+    pub fn open_raw<'a, R: Read + Seek>(
+        &self,
+        archive: &'a mut ZipArchive<R>,
+    ) -> ZipResult<ZipFile<'a>> {
+        self.check_archive(archive)?;
+        archive.by_index_raw(self.index)
+    }
 }
 
 impl<'a> Read for ZipFile<'a> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.get_reader()?.read(buf)
+        let count = self.get_reader()?.read(buf)?;
+        if let Some(limit) = &mut self.limit {
+            limit.record(count)?;
+        }
+        if count > 0 {
+            if let Some(metrics) = &self.metrics {
+                metrics.decompressed_bytes_read(count as u64);
+            }
+        } else if !buf.is_empty() {
+            if let Some(metrics) = self.metrics.take() {
+                metrics.compressed_bytes_read(self.data.compressed_size);
+            }
+        }
+        Ok(count)
     }
 }
 
@@ -1239,11 +2010,9 @@ impl<'a> Drop for ZipFile<'a> {
             // Get the inner `Take` reader so all decryption, decompression and CRC calculation is skipped.
             match &mut self.reader {
                 ZipFileReader::NoReader => {
-                    let innerreader = self.crypto_reader.take();
-                    let _ = copy(
-                        &mut innerreader.expect("Invalid reader state").into_inner(),
-                        &mut sink(),
-                    );
+                    if let Some(innerreader) = self.crypto_reader.take() {
+                        let _ = copy(&mut innerreader.into_inner(), &mut sink());
+                    }
                 }
                 reader => {
                     let innerreader = std::mem::replace(reader, ZipFileReader::NoReader);
@@ -1271,6 +2040,29 @@ impl<'a> Drop for ZipFile<'a> {
 /// * `data_start`: set to 0
 /// * `external_attributes`: `unix_mode()`: will return None
 pub fn read_zipfile_from_stream<'a, R: Read>(reader: &'a mut R) -> ZipResult<Option<ZipFile<'_>>> {
+    let Some(result) = read_local_header_from_stream(reader)? else {
+        return Ok(None);
+    };
+    if result.using_data_descriptor {
+        return unsupported_zip_error("The file length is not available in the local header");
+    }
+    stream_zipfile(result, reader).map(Some)
+}
+
+/// Reads a local file header from the start of the stream, returning `None` if the start of the
+/// central directory is found instead.
+pub(crate) fn read_local_header_from_stream<R: Read>(
+    reader: &mut R,
+) -> ZipResult<Option<ZipFileData>> {
+    let result = read_local_header(reader)?;
+    if result.as_ref().is_some_and(|result| result.encrypted) {
+        return unsupported_zip_error("Encrypted files are not supported");
+    }
+    Ok(result)
+}
+
+/// Like [`read_local_header_from_stream`], but also accepts encrypted files.
+pub(crate) fn read_local_header<R: Read>(reader: &mut R) -> ZipResult<Option<ZipFileData>> {
     let signature = reader.read_u32_le()?;
 
     match signature {
@@ -1323,6 +2115,7 @@ pub fn read_zipfile_from_stream<'a, R: Read>(reader: &'a mut R) -> ZipResult<Opt
         // header_start and data start are not available, but also don't matter, since seeking is
         // not available.
         header_start: 0,
+        disk_number: 0,
         extra_data_start: None,
         data_start: OnceLock::new(),
         central_header_start: 0,
@@ -1336,24 +2129,103 @@ pub fn read_zipfile_from_stream<'a, R: Read>(reader: &'a mut R) -> ZipResult<Opt
         extra_fields: Vec::new(),
     };
 
-    match parse_extra_field(&mut result) {
+    match parse_extra_field(&mut result, &[]) {
         Ok(..) | Err(ZipError::Io(..)) => {}
         Err(e) => return Err(e),
     }
 
-    if encrypted {
-        return unsupported_zip_error("Encrypted files are not supported");
+    Ok(Some(result))
+}
+
+/// Reads the data of a file whose local header has the data descriptor flag set, up to and
+/// including the data descriptor, and fills in the CRC and sizes from the descriptor.
+///
+/// The end of the data is found by looking for the optional data descriptor signature, followed
+/// by a compressed size that matches the number of bytes read. Since the sizes aren't known in
+/// advance, the compressed data is returned in memory, and reading fails once more than
+/// `max_size` bytes of it have been read.
+pub(crate) fn read_data_descriptor_entry<R: BufRead>(
+    reader: &mut R,
+    file: &mut ZipFileData,
+    max_size: u64,
+) -> ZipResult<Vec<u8>> {
+    // A ZIP64 extra field in the local header means the descriptor uses 8-byte sizes
+    let zip64 = file
+        .extra_field
+        .as_deref()
+        .is_some_and(|extra| has_extra_field(extra, 0x0001));
+    let descriptor_len = if zip64 { 24 } else { 16 };
+    let mut data = Vec::new();
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Err(ZipError::InvalidArchive("Data descriptor not found"));
+        }
+        let mut consumed = 0;
+        let mut found = false;
+        for &byte in buf {
+            data.push(byte);
+            consumed += 1;
+            let Some(start) = data.len().checked_sub(descriptor_len) else {
+                continue;
+            };
+            if start as u64 > max_size {
+                return Err(ZipError::UnsupportedArchive(
+                    "File's data is larger than the buffering limit allows",
+                ));
+            }
+            let descriptor = &data[start..];
+            if descriptor[..4] != spec::DATA_DESCRIPTOR_SIGNATURE.to_le_bytes() {
+                continue;
+            }
+            let mut fields = &descriptor[4..];
+            let crc32 = fields.read_u32_le()?;
+            let (compressed_size, uncompressed_size) = if zip64 {
+                (fields.read_u64_le()?, fields.read_u64_le()?)
+            } else {
+                (fields.read_u32_le()? as u64, fields.read_u32_le()? as u64)
+            };
+            if compressed_size == start as u64 {
+                file.crc32 = crc32;
+                file.compressed_size = compressed_size;
+                file.uncompressed_size = uncompressed_size;
+                data.truncate(start);
+                found = true;
+                break;
+            }
+        }
+        reader.consume(consumed);
+        if found {
+            return Ok(data);
+        }
     }
-    if using_data_descriptor {
-        return unsupported_zip_error("The file length is not available in the local header");
+}
+
+fn has_extra_field(mut extra: &[u8], wanted: u16) -> bool {
+    while extra.len() >= 4 {
+        let kind = u16::from_le_bytes([extra[0], extra[1]]);
+        let len = u16::from_le_bytes([extra[2], extra[3]]) as usize;
+        if kind == wanted {
+            return true;
+        }
+        extra = extra.get(4 + len..).unwrap_or_default();
     }
+    false
+}
 
-    let limit_reader = (reader as &'a mut dyn Read).take(result.compressed_size);
+/// Creates a [`ZipFile`] that reads the data of `result` from the start of `reader`.
+pub(crate) fn stream_zipfile<'a>(
+    result: ZipFileData,
+    reader: &'a mut dyn Read,
+) -> ZipResult<ZipFile<'a>> {
+    let limit_reader = reader.take(result.compressed_size);
 
     let result_crc32 = result.crc32;
     let result_compression_method = result.compression_method;
+    #[cfg(feature = "xz")]
+    let result_uncompressed_size = result.uncompressed_size;
+    check_supported(result_compression_method, None)?;
     let crypto_reader = make_crypto_reader(
-        result_compression_method,
         result_crc32,
         result.last_modified_time,
         result.using_data_descriptor,
@@ -1364,11 +2236,21 @@ pub fn read_zipfile_from_stream<'a, R: Read>(reader: &'a mut R) -> ZipResult<Opt
         result.compressed_size,
     )?;
 
-    Ok(Some(ZipFile {
+    Ok(ZipFile {
         data: Cow::Owned(result),
         crypto_reader: None,
-        reader: make_reader(result_compression_method, result_crc32, crypto_reader)?,
-    }))
+        reader: make_reader(
+            result_compression_method,
+            result_crc32,
+            #[cfg(feature = "xz")]
+            result_uncompressed_size,
+            crypto_reader,
+            None,
+        )?,
+        custom_method: None,
+        limit: None,
+        metrics: None,
+    })
 }
 
 #[cfg(test)]
@@ -1376,6 +2258,115 @@ mod test {
     use crate::ZipArchive;
     use std::io::Cursor;
 
+    #[test]
+    fn sorted_indices() {
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        for name in ["b", "c", "a"] {
+            writer.start_file(name, options).unwrap();
+        }
+        // A shallow copy shares its local header with the original
+        writer.shallow_copy_file("b", "0").unwrap();
+        let archive = writer.finish_into_readable().unwrap();
+        let names: Vec<_> = archive.file_names().collect();
+        assert_eq!(names, ["b", "c", "a", "0"]);
+        assert_eq!(archive.sorted_by_name(), [3, 2, 0, 1]);
+        assert_eq!(archive.sorted_by_offset(), [0, 3, 1, 2]);
+    }
+
+    #[test]
+    fn entry_at_data_offset() {
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::io::Write;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options =
+            SimpleFileOptions::default().compression_method(crate::CompressionMethod::Stored);
+        writer.start_file("first", options).unwrap();
+        writer.write_all(&[1; 100]).unwrap();
+        writer.start_file("second", options).unwrap();
+        writer.write_all(&[2; 100]).unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+
+        let second = archive.by_index(1).unwrap();
+        let (second_header, second_data) = (second.header_start(), second.data_start());
+        drop(second);
+        assert_eq!(archive.entry_at_data_offset(0).unwrap(), Some(0));
+        assert_eq!(
+            archive.entry_at_data_offset(second_header - 1).unwrap(),
+            Some(0)
+        );
+        assert_eq!(
+            archive.entry_at_data_offset(second_header).unwrap(),
+            Some(1)
+        );
+        assert_eq!(
+            archive.entry_at_data_offset(second_data + 99).unwrap(),
+            Some(1)
+        );
+        assert_eq!(
+            archive.entry_at_data_offset(second_data + 100).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn entries_lazy() {
+        use std::io::Read;
+
+        let mut v = Vec::new();
+        v.extend_from_slice(include_bytes!("../tests/data/mimetype.zip"));
+        let mut archive = ZipArchive::new(Cursor::new(v)).unwrap();
+        let entries: Vec<_> = archive.entries_lazy().collect();
+        assert_eq!(entries.len(), archive.len());
+        let entry = entries.iter().find(|e| e.name() == "mimetype").unwrap();
+        assert!(entry.is_file());
+        assert_eq!(entry.size(), 39);
+
+        let mut content = String::new();
+        entry
+            .open(&mut archive)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "application/vnd.oasis.opendocument.text");
+
+        let mut other = ZipArchive::new(Cursor::new(
+            include_bytes!("../tests/data/mimetype.zip").to_vec(),
+        ))
+        .unwrap();
+        assert!(entry.open(&mut other).is_err());
+        // A clone shares its metadata with the original, so handles can be used on it
+        let mut cloned = archive.clone();
+        assert!(entry.open(&mut cloned).is_ok());
+    }
+
+    #[test]
+    fn raw_data() {
+        use crate::write::SimpleFileOptions;
+        use crate::{CompressionMethod, ZipWriter};
+        use std::io::Write;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file("stored", options).unwrap();
+        writer.write_all(b"contents").unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let archive = ZipArchive::new(Cursor::new(&bytes[..])).unwrap();
+        let data = archive.raw_data(0).unwrap();
+        assert_eq!(data, b"contents");
+        assert!(bytes.as_ptr_range().contains(&data.as_ptr()));
+        assert!(matches!(
+            archive.raw_data(1),
+            Err(crate::result::ZipError::FileNotFound)
+        ));
+    }
+
     #[test]
     fn invalid_offset() {
         use super::ZipArchive;
@@ -0,0 +1,287 @@
+//! Looking for the structures zip bombs use, without decompressing whole entries
+
+use crate::read::{find_entry_end, ZipArchive};
+use crate::result::ZipResult;
+use std::io::{Cursor, Read, Seek};
+
+/// Options for [`ZipArchive::analyze`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AnalyzeOptions {
+    max_ratio: u64,
+    max_depth: usize,
+    max_nested_size: u64,
+}
+
+impl Default for AnalyzeOptions {
+    fn default() -> Self {
+        AnalyzeOptions {
+            max_ratio: 100,
+            max_depth: 2,
+            max_nested_size: 64 * 1024 * 1024,
+        }
+    }
+}
+
+impl AnalyzeOptions {
+    /// Set how many times larger than its compressed data an entry may claim to be before it's
+    /// reported. Deflate rarely exceeds 100 outside of crafted or highly repetitive data.
+    ///
+    /// The default is 100.
+    #[must_use]
+    pub const fn max_ratio(mut self, max_ratio: u64) -> Self {
+        self.max_ratio = max_ratio;
+        self
+    }
+
+    /// Set how deeply archives may be nested in each other before it's reported. Archives nested
+    /// deeper aren't analyzed.
+    ///
+    /// The default is 2, an archive in an archive in the one being analyzed.
+    #[must_use]
+    pub const fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Set the largest nested archive that's decompressed into memory to analyze in turn. Larger
+    /// ones are still counted towards the depth, but what's in them isn't analyzed.
+    ///
+    /// The default is 64 MiB.
+    #[must_use]
+    pub const fn max_nested_size(mut self, max_nested_size: u64) -> Self {
+        self.max_nested_size = max_nested_size;
+        self
+    }
+}
+
+/// What is suspicious about an entry, in an [`AnalysisFinding`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FindingKind {
+    /// The entry's local header or data lies within another entry's, as in zip bombs that
+    /// reference the same compressed data many times
+    SharedData {
+        /// The index of the entry it overlaps, in the same archive
+        other: usize,
+    },
+    /// The entry claims to decompress to more times its compressed size than allowed
+    HighRatio {
+        /// The uncompressed size divided by the compressed size
+        ratio: u64,
+    },
+    /// The entry is an archive nested deeper than allowed
+    DeepNesting {
+        /// How many archives it's in, counting the one being analyzed
+        depth: usize,
+    },
+}
+
+/// Something suspicious about one entry, found by [`ZipArchive::analyze`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnalysisFinding {
+    /// The entry's index, preceded by the indices of the nested archives it's in, outermost first
+    pub path: Vec<usize>,
+    /// The entry's name
+    pub name: String,
+    /// What is suspicious
+    pub kind: FindingKind,
+}
+
+/// Everything [`ZipArchive::analyze`] found
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AnalysisReport {
+    /// The findings, ordered by path
+    pub findings: Vec<AnalysisFinding>,
+}
+
+impl AnalysisReport {
+    /// Returns true if nothing suspicious was found.
+    pub fn is_ok(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+impl<R: Read + Seek> ZipArchive<R> {
+    /// Looks for the structures of zip bombs, so the archive can be rejected before it's
+    /// extracted: entries that share compressed data, entries that claim an extreme compression
+    /// ratio, and archives nested in each other too deeply.
+    ///
+    /// Ratios are worked out from the sizes the central directory records. Only the first bytes
+    /// of each entry are decompressed, to find nested archives, and those no larger than
+    /// [`AnalyzeOptions::max_nested_size`] are then decompressed in full to analyze them in turn.
+    /// Entries that can't be read, such as encrypted ones, are only checked from their headers.
+    pub fn analyze(&mut self, options: AnalyzeOptions) -> ZipResult<AnalysisReport> {
+        let mut report = AnalysisReport::default();
+        analyze_into(self, options, &mut Vec::new(), &mut report)?;
+        Ok(report)
+    }
+}
+
+/// Adds the findings for `archive`, which is nested at `path`, to `report`.
+fn analyze_into<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    options: AnalyzeOptions,
+    path: &mut Vec<usize>,
+    report: &mut AnalysisReport,
+) -> ZipResult<()> {
+    let shared = archive.shared.clone();
+    let mut ranges = Vec::with_capacity(shared.files.len());
+    for (index, data) in shared.files.values().enumerate() {
+        ranges.push((
+            data.header_start,
+            find_entry_end(data, &mut archive.reader)?,
+            index,
+        ));
+    }
+    // Each entry that starts before the furthest end so far overlaps the entry with that end
+    ranges.sort_unstable();
+    let mut shared_data = vec![None; ranges.len()];
+    let mut furthest: Option<(u64, usize)> = None;
+    for (start, end, index) in ranges {
+        match furthest {
+            Some((furthest_end, other)) if start < furthest_end => {
+                shared_data[index] = Some(other);
+                if end > furthest_end {
+                    furthest = Some((end, index));
+                }
+            }
+            _ => furthest = Some((end, index)),
+        }
+    }
+
+    for (index, data) in shared.files.values().enumerate() {
+        path.push(index);
+        let mut finding = |kind| {
+            report.findings.push(AnalysisFinding {
+                path: path.clone(),
+                name: data.file_name.to_string(),
+                kind,
+            })
+        };
+        if let Some(other) = shared_data[index] {
+            finding(FindingKind::SharedData { other });
+        }
+        let ratio = data.uncompressed_size / data.compressed_size.max(1);
+        if ratio > options.max_ratio {
+            finding(FindingKind::HighRatio { ratio });
+        }
+        // Data that other entries share was already reported, and may nest without end
+        if shared_data[index].is_none() && is_archive(archive, index) {
+            let depth = path.len();
+            if depth > options.max_depth {
+                finding(FindingKind::DeepNesting { depth });
+            } else if data.uncompressed_size <= options.max_nested_size {
+                let mut bytes = Vec::new();
+                let nested = archive
+                    .by_index(index)
+                    .and_then(|file| {
+                        file.take(options.max_nested_size)
+                            .read_to_end(&mut bytes)
+                            .map_err(Into::into)
+                    })
+                    .and_then(|_| ZipArchive::new(Cursor::new(bytes)));
+                if let Ok(mut nested) = nested {
+                    analyze_into(&mut nested, options, path, report)?;
+                }
+            }
+        }
+        path.pop();
+    }
+    Ok(())
+}
+
+/// Returns whether entry `index` starts like a ZIP archive, with a local header or, if it's
+/// empty, an end of central directory record.
+fn is_archive<R: Read + Seek>(archive: &mut ZipArchive<R>, index: usize) -> bool {
+    let mut magic = [0; 4];
+    archive
+        .by_index(index)
+        .and_then(|mut file| file.read_exact(&mut magic).map_err(Into::into))
+        .is_ok_and(|_| matches!(&magic, b"PK\x03\x04" | b"PK\x05\x06"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AnalyzeOptions, FindingKind};
+    use crate::write::SimpleFileOptions;
+    use crate::{CompressionMethod, ZipArchive, ZipWriter};
+    use std::io::{Cursor, Write};
+
+    /// An archive holding one entry with `contents`
+    fn archive(name: &str, contents: &[u8]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file(name, options).unwrap();
+        writer.write_all(contents).unwrap();
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn nesting() {
+        let inner = archive("inner", b"contents");
+        let middle = archive("inner.zip", &inner);
+        let outer = archive("middle.zip", &middle);
+        let mut archive = ZipArchive::new(Cursor::new(outer)).unwrap();
+        assert!(archive.analyze(AnalyzeOptions::default()).unwrap().is_ok());
+
+        let report = archive
+            .analyze(AnalyzeOptions::default().max_depth(1))
+            .unwrap();
+        assert_eq!(report.findings.len(), 1);
+        assert_eq!(report.findings[0].path, [0, 0]);
+        assert_eq!(report.findings[0].name, "inner.zip");
+        assert_eq!(
+            report.findings[0].kind,
+            FindingKind::DeepNesting { depth: 2 }
+        );
+        let report = archive
+            .analyze(AnalyzeOptions::default().max_depth(1).max_nested_size(10))
+            .unwrap();
+        assert!(report.is_ok());
+    }
+
+    #[test]
+    fn shared_data() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        for name in ["a", "b"] {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(b"contents").unwrap();
+        }
+        let mut bytes = writer.finish().unwrap().into_inner();
+        // Point the second entry at the first one's local header
+        let central_header = bytes
+            .windows(4)
+            .rposition(|window| window == b"PK\x01\x02")
+            .unwrap();
+        bytes[central_header + 42..central_header + 46].fill(0);
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let report = archive.analyze(AnalyzeOptions::default()).unwrap();
+        assert_eq!(report.findings.len(), 1);
+        assert_eq!(report.findings[0].path, [1]);
+        assert_eq!(
+            report.findings[0].kind,
+            FindingKind::SharedData { other: 0 }
+        );
+    }
+
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn ratio() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("zeros", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(&[0; 1 << 20]).unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+        let report = archive.analyze(AnalyzeOptions::default()).unwrap();
+        assert_eq!(report.findings.len(), 1);
+        assert!(matches!(
+            report.findings[0].kind,
+            FindingKind::HighRatio { ratio } if ratio > 100
+        ));
+        let options = AnalyzeOptions::default().max_ratio(u64::MAX);
+        assert!(archive.analyze(options).unwrap().is_ok());
+    }
+}
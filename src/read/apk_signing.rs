@@ -0,0 +1,138 @@
+//! Finding the APK Signing Block that Android's signature schemes put before the central directory
+
+use crate::read::ZipArchive;
+use crate::result::ZipResult;
+use crate::unstable::LittleEndianReadExt;
+use std::io::{Read, Seek, SeekFrom};
+
+/// The magic number at the end of an APK Signing Block
+const MAGIC: &[u8; 16] = b"APK Sig Block 42";
+
+/// The size of the block's two size fields and its magic number
+const MIN_SIZE: u64 = 8 + 8 + 16;
+
+/// Returns where the APK Signing Block that ends at `dir_start` starts, if there is one.
+pub(crate) fn find_apk_signing_block(
+    reader: &mut (impl Read + Seek),
+    dir_start: u64,
+) -> ZipResult<Option<u64>> {
+    let Some(footer_start) = dir_start.checked_sub(8 + 16) else {
+        return Ok(None);
+    };
+    reader.seek(SeekFrom::Start(footer_start))?;
+    let size = reader.read_u64_le()?;
+    let mut magic = [0; 16];
+    reader.read_exact(&mut magic)?;
+    // The size counts everything but the leading size field
+    let block_start = size
+        .checked_add(8)
+        .and_then(|block_size| dir_start.checked_sub(block_size));
+    let Some(block_start) = block_start.filter(|_| &magic == MAGIC && size + 8 >= MIN_SIZE) else {
+        return Ok(None);
+    };
+    reader.seek(SeekFrom::Start(block_start))?;
+    Ok((reader.read_u64_le()? == size).then_some(block_start))
+}
+
+/// Returns whether `block` is laid out as an APK Signing Block.
+pub(crate) fn is_apk_signing_block(block: &[u8]) -> bool {
+    let len = block.len() as u64;
+    let size_at = |at: usize| u64::from_le_bytes(block[at..at + 8].try_into().unwrap());
+    len >= MIN_SIZE
+        && size_at(0) == len - 8
+        && size_at(block.len() - 24) == len - 8
+        && block.ends_with(MAGIC)
+}
+
+impl<R: Read + Seek> ZipArchive<R> {
+    /// Returns the APK Signing Block between the last entry and the central directory, which
+    /// Android's v2 and later signature schemes add to APKs, if the archive has one.
+    ///
+    /// The block is returned whole, from its leading size field to its magic number.
+    pub fn apk_signing_block(&mut self) -> ZipResult<Option<Vec<u8>>> {
+        let dir_start = self.shared.dir_start;
+        let Some(block_start) = find_apk_signing_block(&mut self.reader, dir_start)? else {
+            return Ok(None);
+        };
+        let mut block = vec![0; (dir_start - block_start) as usize];
+        self.reader.seek(SeekFrom::Start(block_start))?;
+        self.reader.read_exact(&mut block)?;
+        Ok(Some(block))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::read::SegmentKind;
+    use crate::write::SimpleFileOptions;
+    use crate::{ZipArchive, ZipWriter};
+    use std::io::{Cursor, Read, Write};
+
+    /// A block holding one ID-value pair
+    fn block(value: &[u8]) -> Vec<u8> {
+        let mut pairs = Vec::new();
+        pairs.extend_from_slice(&(4 + value.len() as u64).to_le_bytes());
+        pairs.extend_from_slice(&0x7109871a_u32.to_le_bytes());
+        pairs.extend_from_slice(value);
+        let size = (pairs.len() + 8 + 16) as u64;
+        let mut block = size.to_le_bytes().to_vec();
+        block.extend(pairs);
+        block.extend_from_slice(&size.to_le_bytes());
+        block.extend_from_slice(b"APK Sig Block 42");
+        block
+    }
+
+    fn contents(archive: &mut ZipArchive<Cursor<Vec<u8>>>, name: &str) -> String {
+        let mut contents = String::new();
+        archive
+            .by_name(name)
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        contents
+    }
+
+    #[test]
+    fn keep_and_replace() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("a", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"first").unwrap();
+        assert!(writer
+            .set_apk_signing_block(Some(b"not a block".to_vec()))
+            .is_err());
+        writer
+            .set_apk_signing_block(Some(block(b"signed")))
+            .unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+        assert_eq!(archive.apk_signing_block().unwrap(), Some(block(b"signed")));
+        let layout = archive.layout().unwrap();
+        assert_eq!(
+            layout.total(SegmentKind::ApkSigningBlock),
+            block(b"signed").len() as u64
+        );
+        assert_eq!(layout.wasted(), 0);
+
+        let mut writer = ZipWriter::new_append(Cursor::new(bytes)).unwrap();
+        assert_eq!(writer.apk_signing_block(), Some(&block(b"signed")[..]));
+        writer
+            .start_file("b", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"second").unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+        let mut archive = ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+        assert_eq!(archive.apk_signing_block().unwrap(), Some(block(b"signed")));
+        assert_eq!(contents(&mut archive, "a"), "first");
+        assert_eq!(contents(&mut archive, "b"), "second");
+        assert_eq!(archive.layout().unwrap().wasted(), 0);
+
+        let mut writer = ZipWriter::new_append(Cursor::new(bytes)).unwrap();
+        writer.set_apk_signing_block(None).unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+        assert_eq!(archive.apk_signing_block().unwrap(), None);
+        assert_eq!(contents(&mut archive, "b"), "second");
+    }
+}
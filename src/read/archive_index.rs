@@ -0,0 +1,189 @@
+//! Saving an archive's central directory, to reopen the archive without searching for it
+
+use crate::read::zip_archive::Shared;
+use crate::read::{central_header_to_zip_file, ZipArchive};
+use crate::result::{ZipError, ZipResult};
+use crate::spec;
+use crate::unstable::{LittleEndianReadExt, LittleEndianWriteExt};
+use indexmap::IndexMap;
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::sync::OnceLock;
+
+/// Identifies the serialized form of an [`ArchiveIndex`], and its version
+const MAGIC: &[u8; 8] = b"zipidx\x00\x01";
+
+/// A copy of an archive's central directory, from [`ZipArchive::index`], that reopens the
+/// archive with [`ZipArchive::with_index`] without searching for the end of central directory
+/// record or reading the directory from the archive. This speeds up opening the same large
+/// archive repeatedly, such as from an asset server, especially over a slow reader.
+///
+/// The index can be saved alongside the archive with [`ArchiveIndex::serialize`]. It records the
+/// archive's length, and reopening fails if that changed, but otherwise it's up to the caller to
+/// only use an index with the archive it was made from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArchiveIndex {
+    archive_len: u64,
+    archive_offset: u64,
+    directory_start: u64,
+    comment: Box<[u8]>,
+    directory: Box<[u8]>,
+}
+
+impl ArchiveIndex {
+    /// Write the index in a compact binary form, which [`ArchiveIndex::deserialize`] reads.
+    pub fn serialize<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_u64_le(self.archive_len)?;
+        writer.write_u64_le(self.archive_offset)?;
+        writer.write_u64_le(self.directory_start)?;
+        writer.write_u64_le(self.comment.len() as u64)?;
+        writer.write_all(&self.comment)?;
+        writer.write_u64_le(self.directory.len() as u64)?;
+        writer.write_all(&self.directory)
+    }
+
+    /// Read an index written by [`ArchiveIndex::serialize`].
+    pub fn deserialize<R: Read>(mut reader: R) -> ZipResult<Self> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(ZipError::InvalidArchive("Not a supported archive index"));
+        }
+        let archive_len = reader.read_u64_le()?;
+        let archive_offset = reader.read_u64_le()?;
+        let directory_start = reader.read_u64_le()?;
+        let comment = read_bytes(&mut reader)?;
+        let directory = read_bytes(&mut reader)?;
+        Ok(ArchiveIndex {
+            archive_len,
+            archive_offset,
+            directory_start,
+            comment,
+            directory,
+        })
+    }
+}
+
+/// Reads a length-prefixed byte string, without trusting the length enough to allocate it upfront.
+fn read_bytes(reader: &mut impl Read) -> ZipResult<Box<[u8]>> {
+    let len = reader.read_u64_le()?;
+    let mut bytes = Vec::new();
+    reader.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(ZipError::Io(io::ErrorKind::UnexpectedEof.into()));
+    }
+    Ok(bytes.into())
+}
+
+impl<R: Read + Seek> ZipArchive<R> {
+    /// Copy this archive's central directory into an [`ArchiveIndex`], to reopen it later with
+    /// [`ZipArchive::with_index`].
+    pub fn index(&mut self) -> ZipResult<ArchiveIndex> {
+        let archive_len = self.reader.seek(SeekFrom::End(0))?;
+        self.reader.seek(SeekFrom::Start(self.shared.dir_start))?;
+        let mut reader = io::BufReader::new(&mut self.reader);
+        let mut directory = Vec::new();
+        // The records run up to the ZIP64 or ZIP32 end of central directory record
+        while let Ok(spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE) = reader.read_u32_le() {
+            let mut record = [0; 42];
+            reader.read_exact(&mut record)?;
+            let length = |at: usize| u16::from_le_bytes([record[at], record[at + 1]]) as u64;
+            // The name, extra field and comment lengths
+            let variable_size = length(24) + length(26) + length(28);
+            directory.write_u32_le(spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE)?;
+            directory.extend_from_slice(&record);
+            let copied = io::copy(&mut (&mut reader).take(variable_size), &mut directory)?;
+            if copied != variable_size {
+                return Err(ZipError::InvalidArchive("Invalid Central Directory header"));
+            }
+        }
+        Ok(ArchiveIndex {
+            archive_len,
+            archive_offset: self.shared.offset,
+            directory_start: self.shared.dir_start,
+            comment: self.comment.to_vec().into(),
+            directory: directory.into(),
+        })
+    }
+
+    /// Open an archive with an [`ArchiveIndex`] made from it by [`ZipArchive::index`], parsing
+    /// the central directory from the index instead of finding and reading it in `reader`.
+    ///
+    /// Fails if `reader` isn't the length the indexed archive was.
+    pub fn with_index(mut reader: R, index: &ArchiveIndex) -> ZipResult<ZipArchive<R>> {
+        if reader.seek(SeekFrom::End(0))? != index.archive_len {
+            return Err(ZipError::InvalidArchive(
+                "Archive index was made from a different archive",
+            ));
+        }
+        let mut directory = Cursor::new(&*index.directory);
+        let mut files = IndexMap::new();
+        while directory.position() < index.directory.len() as u64 {
+            let mut file = central_header_to_zip_file(&mut directory, index.archive_offset, None)?;
+            // The record was parsed from the index, not from its place in the archive
+            file.central_header_start += index.directory_start;
+            files.insert(file.file_name.clone(), file);
+        }
+        Ok(ZipArchive {
+            reader,
+            shared: Shared {
+                files,
+                offset: index.archive_offset,
+                dir_start: index.directory_start,
+                name_index: OnceLock::new(),
+            }
+            .into(),
+            comment: index.comment.clone().into(),
+            registry: None,
+            limits: None,
+            metrics: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ArchiveIndex;
+    use crate::write::SimpleFileOptions;
+    use crate::{ZipArchive, ZipWriter};
+    use std::io::{Cursor, Read, Write};
+
+    #[test]
+    fn round_trip() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for name in ["a", "dir/b", "c"] {
+            writer
+                .start_file(name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(name.as_bytes()).unwrap();
+        }
+        writer.set_raw_comment(b"comment".to_vec());
+        let mut bytes = b"preface".to_vec();
+        bytes.extend(writer.finish().unwrap().into_inner());
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+        let mut serialized = Vec::new();
+        archive.index().unwrap().serialize(&mut serialized).unwrap();
+        let index = ArchiveIndex::deserialize(&*serialized).unwrap();
+        assert_eq!(index, archive.index().unwrap());
+
+        let mut reopened = ZipArchive::with_index(Cursor::new(bytes.clone()), &index).unwrap();
+        assert!(reopened.file_names().eq(archive.file_names()));
+        assert_eq!(reopened.offset(), archive.offset());
+        assert_eq!(reopened.comment(), b"comment");
+        let mut contents = String::new();
+        let mut file = reopened.by_name("dir/b").unwrap();
+        file.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "dir/b");
+        assert_eq!(
+            file.central_header_start(),
+            archive.by_name("dir/b").unwrap().central_header_start()
+        );
+        drop(file);
+
+        bytes.push(0);
+        assert!(ZipArchive::with_index(Cursor::new(bytes), &index).is_err());
+        assert!(ArchiveIndex::deserialize(&serialized[..serialized.len() - 1]).is_err());
+        assert!(ArchiveIndex::deserialize(&b"not an index"[..]).is_err());
+    }
+}
@@ -0,0 +1,263 @@
+//! Reading archives from storage that's read by offset, rather than through a cursor
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::Arc;
+
+/// Storage that an archive can be read from at any offset, such as a file, a buffer in memory or
+/// an object in a remote store fetched with range requests.
+///
+/// Reads don't share a position, so one backend can serve several readers at once. Wrap it in a
+/// [`BackendReader`] to read the archive with [`crate::ZipArchive`]; only the byte ranges that
+/// the archive's directory and the opened entries need are read.
+pub trait Backend {
+    /// Returns the total size of the archive, in bytes.
+    fn size(&self) -> io::Result<u64>;
+
+    /// Reads bytes starting at `offset` into `buf`, returning how many were read. Like
+    /// [`Read::read`], this may read fewer bytes than requested, and returns 0 at the end.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize>;
+}
+
+impl Backend for [u8] {
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.len() as u64)
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let start = usize::try_from(offset).map_or(self.len(), |offset| offset.min(self.len()));
+        let mut remaining = &self[start..];
+        remaining.read(buf)
+    }
+}
+
+impl Backend for Vec<u8> {
+    fn size(&self) -> io::Result<u64> {
+        self.as_slice().size()
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        self.as_slice().read_at(offset, buf)
+    }
+}
+
+#[cfg(any(unix, windows))]
+impl Backend for File {
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.metadata()?.len())
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        #[cfg(unix)]
+        return std::os::unix::fs::FileExt::read_at(self, buf, offset);
+        // This moves the file's cursor, but nothing here relies on it
+        #[cfg(windows)]
+        return std::os::windows::fs::FileExt::seek_read(self, buf, offset);
+    }
+}
+
+impl<B: Backend + ?Sized> Backend for Arc<B> {
+    fn size(&self) -> io::Result<u64> {
+        (**self).size()
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        (**self).read_at(offset, buf)
+    }
+}
+
+impl<B: Backend + ?Sized> Backend for &B {
+    fn size(&self) -> io::Result<u64> {
+        (**self).size()
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        (**self).read_at(offset, buf)
+    }
+}
+
+/// Reads a [`Backend`] through a position of its own, so that it can be passed to
+/// [`crate::ZipArchive::new`].
+///
+/// Clones share the backend but not the position, which makes them suitable for
+/// [`crate::ZipArchive::extract_parallel`].
+///
+/// ```
+/// use std::io::{Cursor, Read, Write};
+/// use std::sync::Arc;
+/// use zip::read::{Backend, BackendReader};
+///
+/// # fn main() -> zip::result::ZipResult<()> {
+/// let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+/// zip.start_file("a.txt", zip::write::SimpleFileOptions::default())?;
+/// zip.write_all(b"Hello")?;
+/// let bytes = zip.finish()?.into_inner();
+///
+/// let backend: Arc<dyn Backend + Send + Sync> = Arc::new(bytes);
+/// let mut archive = zip::ZipArchive::new(BackendReader::new(backend)?)?;
+/// let mut contents = String::new();
+/// archive.by_name("a.txt")?.read_to_string(&mut contents)?;
+/// assert_eq!(contents, "Hello");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct BackendReader<B> {
+    backend: B,
+    len: u64,
+    position: u64,
+}
+
+impl<B: Backend> BackendReader<B> {
+    /// Reads `backend` from its start.
+    pub fn new(backend: B) -> io::Result<Self> {
+        let len = backend.size()?;
+        Ok(BackendReader {
+            backend,
+            len,
+            position: 0,
+        })
+    }
+
+    /// Returns a reference to the backend.
+    pub const fn get_ref(&self) -> &B {
+        &self.backend
+    }
+
+    /// Unwrap and return the backend
+    pub fn into_inner(self) -> B {
+        self.backend
+    }
+}
+
+impl<B: Backend> Read for BackendReader<B> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.len.saturating_sub(self.position);
+        let limit = buf.len().min(remaining.try_into().unwrap_or(usize::MAX));
+        let count = self.backend.read_at(self.position, &mut buf[..limit])?;
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl<B: Backend> Seek for BackendReader<B> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position = position.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Backend, BackendReader};
+    use crate::write::SimpleFileOptions;
+    use crate::{ZipArchive, ZipWriter};
+    use std::io::{Cursor, Read, Write};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// Counts the bytes read, as a remote backend would pay for them
+    struct Counting {
+        bytes: Vec<u8>,
+        read: AtomicU64,
+    }
+
+    impl Backend for Counting {
+        fn size(&self) -> std::io::Result<u64> {
+            self.bytes.size()
+        }
+
+        fn read_at(&self, offset: u64, buf: &mut [u8]) -> std::io::Result<usize> {
+            let count = self.bytes.read_at(offset, buf)?;
+            self.read.fetch_add(count as u64, Ordering::Relaxed);
+            Ok(count)
+        }
+    }
+
+    #[test]
+    fn reads_only_what_is_needed() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        writer.start_file("small", options).unwrap();
+        writer.write_all(b"contents").unwrap();
+        writer.start_file("large", options).unwrap();
+        writer.write_all(&vec![1; 1 << 20]).unwrap();
+        let backend = Counting {
+            bytes: writer.finish().unwrap().into_inner(),
+            read: AtomicU64::new(0),
+        };
+        let total = backend.bytes.len() as u64;
+
+        let mut archive = ZipArchive::new(BackendReader::new(&backend).unwrap()).unwrap();
+        let mut contents = String::new();
+        archive
+            .by_name("small")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "contents");
+        assert!(backend.read.load(Ordering::Relaxed) < total);
+    }
+
+    #[test]
+    fn concurrent_clones() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for i in 0..8u8 {
+            writer
+                .start_file(format!("file{i}"), SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(&vec![i; 10_000]).unwrap();
+        }
+        let bytes = writer.finish().unwrap().into_inner();
+        let archive = ZipArchive::new(BackendReader::new(bytes.as_slice()).unwrap()).unwrap();
+
+        std::thread::scope(|scope| {
+            for i in 0..8u8 {
+                let mut archive = archive.clone();
+                scope.spawn(move || {
+                    let mut contents = Vec::new();
+                    archive
+                        .by_name(&format!("file{i}"))
+                        .unwrap()
+                        .read_to_end(&mut contents)
+                        .unwrap();
+                    assert_eq!(contents, vec![i; 10_000]);
+                });
+            }
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file() {
+        use crate::test_util::TempDir;
+
+        let temp = TempDir::new("backend");
+        let path = temp.path().join("archive.zip");
+        let mut writer = ZipWriter::new(std::fs::File::create(&path).unwrap());
+        writer
+            .start_file("file", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"contents").unwrap();
+        writer.finish().unwrap();
+
+        let file = std::fs::File::open(&path).unwrap();
+        let mut archive = ZipArchive::new(BackendReader::new(&file).unwrap()).unwrap();
+        let mut contents = String::new();
+        archive
+            .by_index(0)
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "contents");
+    }
+}
@@ -0,0 +1,300 @@
+//! Lists an archive's entries straight from its central directory
+
+use crate::compression::CompressionMethod;
+use crate::read::ZipArchive;
+use crate::result::{ZipError, ZipResult};
+use crate::spec;
+use crate::types::DateTime;
+use std::io::{self, BufReader, Read, Seek};
+
+/// The metadata of one central directory record, borrowed from the buffer it was read into by
+/// [`ZipArchive::for_each_entry_meta`].
+#[derive(Clone, Copy, Debug)]
+pub struct EntryMetaRef<'a> {
+    index: usize,
+    version_made_by: u16,
+    flags: u16,
+    compression_method: u16,
+    last_modified_time: DateTime,
+    crc32: u32,
+    compressed_size: u64,
+    uncompressed_size: u64,
+    external_attributes: u32,
+    header_start: u64,
+    name_raw: &'a [u8],
+    extra_data: &'a [u8],
+    comment_raw: &'a [u8],
+}
+
+impl<'a> EntryMetaRef<'a> {
+    /// Get the index of this entry in the central directory
+    pub const fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Get the name of the file, if it's valid UTF-8.
+    ///
+    /// Unlike [`crate::read::ZipFile::name`], this doesn't decode names in code page 437, which
+    /// would need an allocation; use [`EntryMetaRef::name_raw`] for those.
+    pub fn name(&self) -> Option<&'a str> {
+        std::str::from_utf8(self.name_raw).ok()
+    }
+
+    /// Get the name of the file, in the raw (internal) byte representation.
+    pub const fn name_raw(&self) -> &'a [u8] {
+        self.name_raw
+    }
+
+    /// Returns whether the file name is flagged as UTF-8 rather than code page 437
+    pub const fn is_utf8(&self) -> bool {
+        self.flags & (1 << 11) != 0
+    }
+
+    /// Get the extra data of the central directory record
+    pub const fn extra_data(&self) -> &'a [u8] {
+        self.extra_data
+    }
+
+    /// Get the comment of the file, in the raw (internal) byte representation.
+    pub const fn comment_raw(&self) -> &'a [u8] {
+        self.comment_raw
+    }
+
+    /// Returns whether the file is encrypted
+    pub const fn encrypted(&self) -> bool {
+        self.flags & 1 != 0
+    }
+
+    /// Returns whether the file is actually a directory
+    pub fn is_dir(&self) -> bool {
+        matches!(self.name_raw.last(), Some(b'/' | b'\\'))
+    }
+
+    /// Get the compression method used to store the file
+    pub fn compression(&self) -> CompressionMethod {
+        #[allow(deprecated)]
+        CompressionMethod::from_u16(self.compression_method)
+    }
+
+    /// Get the size of the file, in bytes, in the archive
+    pub const fn compressed_size(&self) -> u64 {
+        self.compressed_size
+    }
+
+    /// Get the size of the file, in bytes, when uncompressed
+    pub const fn size(&self) -> u64 {
+        self.uncompressed_size
+    }
+
+    /// Get the CRC32 hash of the original file
+    pub const fn crc32(&self) -> u32 {
+        self.crc32
+    }
+
+    /// Get the time the file was last modified
+    pub const fn last_modified(&self) -> DateTime {
+        self.last_modified_time
+    }
+
+    /// Get the version of the file
+    pub const fn version_made_by(&self) -> (u8, u8) {
+        let version = self.version_made_by as u8;
+        (version / 10, version % 10)
+    }
+
+    /// Get the external file attributes, whose meaning depends on the system that made the file
+    pub const fn external_attributes(&self) -> u32 {
+        self.external_attributes
+    }
+
+    /// Get the starting offset of the file's local header, including any data prepended to the
+    /// archive
+    pub const fn header_start(&self) -> u64 {
+        self.header_start
+    }
+}
+
+impl<R: Read + Seek> ZipArchive<R> {
+    /// Calls `f` with the metadata of each entry in the archive read from `reader`, in central
+    /// directory order, without building a [`ZipArchive`].
+    ///
+    /// Records are read into one buffer that's reused from entry to entry, and each
+    /// [`EntryMetaRef`] borrows from it, so listing allocates nothing per entry. This suits
+    /// indexing many archives when only their listings are needed.
+    ///
+    /// ```
+    /// use std::io::{Cursor, Write};
+    /// use zip::write::SimpleFileOptions;
+    ///
+    /// # fn main() -> zip::result::ZipResult<()> {
+    /// let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    /// zip.start_file("a.txt", SimpleFileOptions::default())?;
+    /// zip.write_all(b"Hello")?;
+    /// zip.add_directory("dir/", SimpleFileOptions::default())?;
+    /// let mut reader = zip.finish()?;
+    ///
+    /// let mut total = 0;
+    /// zip::ZipArchive::for_each_entry_meta(&mut reader, |entry| {
+    ///     if !entry.is_dir() {
+    ///         total += entry.size();
+    ///     }
+    /// })?;
+    /// assert_eq!(total, 5);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn for_each_entry_meta<F>(reader: &mut R, mut f: F) -> ZipResult<()>
+    where
+        F: FnMut(EntryMetaRef<'_>),
+    {
+        let (footer, cde_start_pos) = spec::CentralDirectoryEnd::find_and_parse(reader)?;
+        let zip64 = Self::get_directory_info_zip64(reader, cde_start_pos)
+            .ok()
+            .and_then(|results| results.into_iter().find_map(Result::ok));
+        let dir_info = match zip64 {
+            Some(dir_info) => dir_info,
+            None => Self::get_directory_info_zip32(&footer, cde_start_pos)?,
+        };
+        if dir_info.disk_number != dir_info.disk_with_central_directory {
+            return Err(ZipError::UnsupportedArchive(
+                "Support for multi-disk files is not implemented",
+            ));
+        }
+
+        reader.seek(io::SeekFrom::Start(dir_info.directory_start))?;
+        let mut reader = BufReader::new(reader);
+        let mut fixed = [0u8; 46];
+        let mut variable = Vec::new();
+        for index in 0..dir_info.number_of_files {
+            reader.read_exact(&mut fixed)?;
+            let u16_at = |i: usize| u16::from_le_bytes([fixed[i], fixed[i + 1]]);
+            let u32_at =
+                |i: usize| u32::from_le_bytes([fixed[i], fixed[i + 1], fixed[i + 2], fixed[i + 3]]);
+            if u32_at(0) != spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE {
+                return Err(ZipError::InvalidArchive("Invalid Central Directory header"));
+            }
+            let name_len = u16_at(28) as usize;
+            let extra_len = u16_at(30) as usize;
+            let comment_len = u16_at(32) as usize;
+            variable.resize(name_len + extra_len + comment_len, 0);
+            reader.read_exact(&mut variable)?;
+            let (name_raw, rest) = variable.split_at(name_len);
+            let (extra_data, comment_raw) = rest.split_at(extra_len);
+
+            let mut entry = EntryMetaRef {
+                index,
+                version_made_by: u16_at(4),
+                flags: u16_at(8),
+                compression_method: u16_at(10),
+                last_modified_time: DateTime::from_msdos(u16_at(14), u16_at(12)),
+                crc32: u32_at(16),
+                compressed_size: u32_at(20).into(),
+                uncompressed_size: u32_at(24).into(),
+                external_attributes: u32_at(38),
+                header_start: u32_at(42).into(),
+                name_raw,
+                extra_data,
+                comment_raw,
+            };
+            apply_zip64_extra_field(&mut entry)?;
+            entry.header_start = entry
+                .header_start
+                .checked_add(dir_info.archive_offset)
+                .ok_or(ZipError::InvalidArchive("Archive header is too large"))?;
+            f(entry);
+        }
+        Ok(())
+    }
+}
+
+/// Replaces the sizes and offset that are saturated in the fixed part of the record with the
+/// values in its Zip64 extended information extra field.
+fn apply_zip64_extra_field(entry: &mut EntryMetaRef<'_>) -> ZipResult<()> {
+    const TRUNCATED: ZipError = ZipError::InvalidArchive("Zip64 extra field is truncated");
+    let mut extra = entry.extra_data;
+    while extra.len() >= 4 {
+        let kind = u16::from_le_bytes([extra[0], extra[1]]);
+        let len = u16::from_le_bytes([extra[2], extra[3]]) as usize;
+        let data = extra.get(4..4 + len).ok_or(TRUNCATED)?;
+        extra = &extra[4 + len..];
+        if kind != 0x0001 {
+            continue;
+        }
+        let mut values = data.chunks_exact(8).map(|value| {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(value);
+            u64::from_le_bytes(bytes)
+        });
+        for field in [
+            &mut entry.uncompressed_size,
+            &mut entry.compressed_size,
+            &mut entry.header_start,
+        ] {
+            if *field == spec::ZIP64_BYTES_THR {
+                *field = values.next().ok_or(TRUNCATED)?;
+            }
+        }
+        break;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::write::SimpleFileOptions;
+    use crate::{ZipArchive, ZipWriter};
+    use std::io::{Cursor, Write};
+
+    fn assert_matches_archive(bytes: Vec<u8>) {
+        let mut reader = Cursor::new(bytes);
+        let mut listed = Vec::new();
+        ZipArchive::for_each_entry_meta(&mut reader, |entry| {
+            listed.push((
+                entry.name().unwrap().to_string(),
+                entry.size(),
+                entry.compressed_size(),
+                entry.crc32(),
+                entry.header_start(),
+                entry.is_dir(),
+            ));
+        })
+        .unwrap();
+
+        let mut archive = ZipArchive::new(reader).unwrap();
+        assert_eq!(listed.len(), archive.len());
+        for (i, listed) in listed.into_iter().enumerate() {
+            let file = archive.by_index_raw(i).unwrap();
+            let expected = (
+                file.name().to_string(),
+                file.size(),
+                file.compressed_size(),
+                file.crc32(),
+                file.header_start(),
+                file.is_dir(),
+            );
+            assert_eq!(listed, expected);
+        }
+    }
+
+    #[test]
+    fn matches_archive() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("small", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"contents").unwrap();
+        writer
+            .start_file("large", SimpleFileOptions::default().large_file(true))
+            .unwrap();
+        writer.write_all(&[0; 100]).unwrap();
+        writer
+            .add_directory("dir/", SimpleFileOptions::default())
+            .unwrap();
+        // Data prepended to the archive shifts every offset
+        let mut bytes = b"#!/bin/sh\n".to_vec();
+        bytes.extend_from_slice(&writer.finish().unwrap().into_inner());
+        assert_matches_archive(bytes);
+
+        assert_matches_archive(include_bytes!("../../tests/data/zip64_demo.zip").to_vec());
+    }
+}
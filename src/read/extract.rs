@@ -0,0 +1,641 @@
+//! Options for extracting an archive into a directory
+
+use crate::read::{extract_entry, read_zipfile_from_stream, ZipArchive};
+use crate::result::{ZipError, ZipResult};
+use std::fs;
+use std::io::{self, Read, Seek};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// What extracting an entry does when something is already at its path, set with
+/// [`ExtractOptions::overwrite_policy`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OverwritePolicy {
+    /// Fail the extraction with an [`io::ErrorKind::AlreadyExists`] error.
+    Error,
+    /// Leave what's there, and don't extract the entry.
+    Skip,
+    /// Replace what's there. A symlink is replaced, not written through.
+    #[default]
+    Overwrite,
+    /// Extract the entry under a new name, adding ` (1)`, ` (2)` and so on before the extension.
+    RenameNew,
+}
+
+/// Options for [`ZipArchive::extract_with_options`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExtractOptions {
+    symlinks: bool,
+    windows_symlinks: bool,
+    preallocate: bool,
+    overwrite_policy: OverwritePolicy,
+    atomic: bool,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        ExtractOptions {
+            symlinks: false,
+            windows_symlinks: false,
+            preallocate: false,
+            overwrite_policy: OverwritePolicy::Overwrite,
+            atomic: false,
+        }
+    }
+}
+
+impl ExtractOptions {
+    /// Set whether entries whose Unix mode marks them as symlinks are extracted as symlinks.
+    /// Otherwise, they're extracted as regular files that contain the link's target.
+    ///
+    /// A symlink whose target is absolute, or leads outside the extraction directory once the
+    /// symlinks already on disk are followed, fails the extraction with
+    /// [`ZipError::InvalidArchive`]. So does an entry whose path leads outside it through a
+    /// symlink, so that nothing is written anywhere else.
+    ///
+    /// The default is false, and symlinks are only created on Windows if
+    /// [`ExtractOptions::windows_symlinks`] is also set.
+    #[must_use]
+    pub const fn symlinks(mut self, symlinks: bool) -> Self {
+        self.symlinks = symlinks;
+        self
+    }
+
+    /// Set whether symlinks are created on Windows, where doing so needs Developer Mode or the
+    /// `SeCreateSymbolicLinkPrivilege` privilege.
+    ///
+    /// The default is false.
+    #[must_use]
+    pub const fn windows_symlinks(mut self, windows_symlinks: bool) -> Self {
+        self.windows_symlinks = windows_symlinks;
+        self
+    }
+
+    /// Set whether each file is set to its uncompressed size before its contents are written,
+    /// which lets the file system lay it out in one piece. Windows reserves the space then, so
+    /// a full disk fails the extraction before the file is written; most Unix file systems only
+    /// record the size. A file whose contents turn out shorter is cut to their size.
+    ///
+    /// The default is false.
+    #[must_use]
+    pub const fn preallocate(mut self, preallocate: bool) -> Self {
+        self.preallocate = preallocate;
+        self
+    }
+
+    /// Set what happens when a file or symlink being extracted is already on disk. Directories
+    /// that already exist are always reused.
+    ///
+    /// The default is [`OverwritePolicy::Overwrite`].
+    #[must_use]
+    pub const fn overwrite_policy(mut self, overwrite_policy: OverwritePolicy) -> Self {
+        self.overwrite_policy = overwrite_policy;
+        self
+    }
+
+    /// Set whether each file and symlink is written to a temporary name in its directory, then
+    /// renamed into place once it's complete. A failed extraction then never leaves a partly
+    /// written file, or replaces an existing file with one, though the entries extracted before
+    /// the failure stay on disk.
+    ///
+    /// The default is false.
+    #[must_use]
+    pub const fn atomic(mut self, atomic: bool) -> Self {
+        self.atomic = atomic;
+        self
+    }
+
+    pub(crate) const fn on_existing(&self) -> OverwritePolicy {
+        self.overwrite_policy
+    }
+
+    pub(crate) const fn is_atomic(&self) -> bool {
+        self.atomic
+    }
+
+    pub(crate) const fn preallocates(&self) -> bool {
+        self.preallocate
+    }
+
+    pub(crate) const fn creates_symlinks(&self) -> bool {
+        self.symlinks && (cfg!(unix) || (cfg!(windows) && self.windows_symlinks))
+    }
+}
+
+impl<R: Read + Seek> ZipArchive<R> {
+    /// Extract a Zip archive into a directory like [`ZipArchive::extract`], with the given
+    /// options.
+    pub fn extract_with_options<P: AsRef<Path>>(
+        &mut self,
+        directory: P,
+        options: ExtractOptions,
+    ) -> ZipResult<()> {
+        for i in 0..self.len() {
+            let mut file = self.by_index(i)?;
+            extract_entry(&mut file, directory.as_ref(), &options, &mut |_| {})?;
+        }
+        Ok(())
+    }
+
+    /// Extract the entries whose names `predicate` accepts into a directory, like
+    /// [`ZipArchive::extract`]. The predicate is given each name before the entry is opened, so
+    /// entries it rejects are never read.
+    pub fn extract_matching<P, F>(&mut self, directory: P, predicate: F) -> ZipResult<()>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&str) -> bool,
+    {
+        self.extract_matching_with_options(directory, predicate, ExtractOptions::default())
+    }
+
+    /// Extract the entries whose names `predicate` accepts into a directory like
+    /// [`ZipArchive::extract_matching`], with the given options.
+    pub fn extract_matching_with_options<P, F>(
+        &mut self,
+        directory: P,
+        mut predicate: F,
+        options: ExtractOptions,
+    ) -> ZipResult<()>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&str) -> bool,
+    {
+        for i in 0..self.len() {
+            if !self.name_for_index(i).is_some_and(&mut predicate) {
+                continue;
+            }
+            let mut file = self.by_index(i)?;
+            extract_entry(&mut file, directory.as_ref(), &options, &mut |_| {})?;
+        }
+        Ok(())
+    }
+
+    /// Extract the entries whose names match the glob `pattern` into a directory, like
+    /// [`ZipArchive::extract_matching`].
+    ///
+    /// In the pattern, `*` matches any run of characters and `?` any one character, neither
+    /// crossing a `/`, and a `**` component matches any number of whole components, including
+    /// none. So `assets/**/*.png` matches both `assets/a.png` and `assets/icons/b/c.png`.
+    /// A directory's trailing `/` is ignored when matching.
+    pub fn extract_glob<P: AsRef<Path>>(&mut self, directory: P, pattern: &str) -> ZipResult<()> {
+        self.extract_glob_with_options(directory, pattern, ExtractOptions::default())
+    }
+
+    /// Extract the entries whose names match the glob `pattern` into a directory like
+    /// [`ZipArchive::extract_glob`], with the given options.
+    pub fn extract_glob_with_options<P: AsRef<Path>>(
+        &mut self,
+        directory: P,
+        pattern: &str,
+        options: ExtractOptions,
+    ) -> ZipResult<()> {
+        let pattern: Vec<&str> = pattern.split('/').collect();
+        self.extract_matching_with_options(
+            directory,
+            |name| {
+                let name: Vec<&str> = name.trim_end_matches('/').split('/').collect();
+                glob_matches(&pattern, &name)
+            },
+            options,
+        )
+    }
+}
+
+/// Matches a name against a glob pattern, each split into its `/`-separated components.
+pub(crate) fn glob_matches(pattern: &[&str], name: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((&"**", rest)) => (0..=name.len()).any(|skip| glob_matches(rest, &name[skip..])),
+        Some((component, rest)) => match name.split_first() {
+            Some((name_component, name_rest)) => {
+                component_matches(component.as_bytes(), name_component.as_bytes())
+                    && glob_matches(rest, name_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+/// Matches one component of a name against one component of a glob pattern.
+fn component_matches(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|skip| component_matches(rest, &name[skip..])),
+        Some((b'?', rest)) => {
+            // Skip one whole UTF-8 character
+            let len = name
+                .iter()
+                .skip(1)
+                .take_while(|&&byte| byte & 0xc0 == 0x80)
+                .count();
+            !name.is_empty() && component_matches(rest, &name[len + 1..])
+        }
+        Some((byte, rest)) => name.first() == Some(byte) && component_matches(rest, &name[1..]),
+    }
+}
+
+/// Extract the archive read from `reader` into a directory as it arrives, without seeking, such
+/// as from the body of an HTTP response. Paths are sanitized as by [`ZipArchive::extract`], and
+/// the entries are read from their local headers, so the archive's central directory is never
+/// consulted. Returns the number of entries extracted.
+///
+/// Entries whose sizes are stored in a data descriptor, and encrypted entries, can't be read this
+/// way and fail the extraction. As with [`ZipArchive::extract`], some of the files may be left on
+/// disk when that happens.
+pub fn extract_from_stream<R: Read, P: AsRef<Path>>(
+    reader: &mut R,
+    directory: P,
+    options: ExtractOptions,
+) -> ZipResult<usize> {
+    let mut count = 0;
+    while let Some(mut file) = read_zipfile_from_stream(reader)? {
+        extract_entry(&mut file, directory.as_ref(), &options, &mut |_| {})?;
+        // Skip anything extract_entry didn't read, such as a directory's data
+        io::copy(&mut file, &mut io::sink())?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Applies `policy` to an entry to be extracted to `path`, returning where it's extracted to, or
+/// `None` if it's skipped.
+pub(crate) fn resolve_existing(
+    path: PathBuf,
+    policy: OverwritePolicy,
+) -> ZipResult<Option<PathBuf>> {
+    let exists = |path: &Path| fs::symlink_metadata(path).is_ok();
+    if !exists(&path) {
+        return Ok(Some(path));
+    }
+    match policy {
+        OverwritePolicy::Error => Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", path.display()),
+        )
+        .into()),
+        OverwritePolicy::Skip => Ok(None),
+        OverwritePolicy::Overwrite => Ok(Some(path)),
+        OverwritePolicy::RenameNew => {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let extension = match path.extension() {
+                Some(extension) => format!(".{}", extension.to_string_lossy()),
+                None => String::new(),
+            };
+            let renamed = (1..)
+                .map(|n| path.with_file_name(format!("{stem} ({n}){extension}")))
+                .find(|renamed| !exists(renamed))
+                .unwrap();
+            Ok(Some(renamed))
+        }
+    }
+}
+
+/// A path in the same directory as `path` to write to before renaming into place, unique within
+/// this process.
+pub(crate) fn temporary_sibling(path: &Path) -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let unique = NEXT.fetch_add(1, Ordering::Relaxed);
+    path.with_file_name(format!(".{name}.{}-{unique}.tmp", std::process::id()))
+}
+
+/// Checks that a symlink at `link`, relative to the extraction directory, can't lead outside it.
+pub(crate) fn check_symlink_target(link: &Path, target: &Path) -> ZipResult<()> {
+    let mut depth = link.components().count().saturating_sub(1);
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir => {
+                depth = depth.checked_sub(1).ok_or(ZipError::InvalidArchive(
+                    "Symlink target is outside the extraction directory",
+                ))?;
+            }
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(ZipError::InvalidArchive(
+                    "Symlink target is an absolute path",
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks that `path`, under the extraction directory `directory`, doesn't lead outside it
+/// through a symlink on disk, such as one created earlier in the same extraction. Only the
+/// deepest part of `path` that exists is resolved, as the rest is created as directories.
+pub(crate) fn check_within(directory: &Path, path: &Path) -> ZipResult<()> {
+    fs::create_dir_all(directory)?;
+    let directory = directory.canonicalize()?;
+    let Some(existing) = path
+        .ancestors()
+        .find(|ancestor| fs::symlink_metadata(ancestor).is_ok())
+    else {
+        return Ok(());
+    };
+    if !existing.canonicalize()?.starts_with(&directory) {
+        return Err(ZipError::InvalidArchive(
+            "Path leads outside the extraction directory through a symlink",
+        ));
+    }
+    Ok(())
+}
+
+/// Checks that a symlink to `target` in `parent`, under the extraction directory `directory`,
+/// resolves to somewhere inside it, following the symlinks on disk that the target goes through.
+///
+/// A target that goes up out of a directory that doesn't exist yet is rejected, since what that
+/// directory turns out to be isn't known.
+pub(crate) fn check_symlink_resolves_within(
+    directory: &Path,
+    parent: &Path,
+    target: &Path,
+) -> ZipResult<()> {
+    let directory = directory.canonicalize()?;
+    let mut resolved = parent.canonicalize()?;
+    let mut exists = true;
+    for component in target.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if exists => {
+                resolved.pop();
+            }
+            Component::ParentDir => {
+                return Err(ZipError::InvalidArchive(
+                    "Symlink target goes through a directory that doesn't exist",
+                ))
+            }
+            Component::Normal(name) => {
+                resolved.push(name);
+                exists = exists && fs::symlink_metadata(&resolved).is_ok();
+                if exists {
+                    resolved = resolved.canonicalize()?;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(ZipError::InvalidArchive(
+                    "Symlink target is an absolute path",
+                ))
+            }
+        }
+    }
+    if !resolved.starts_with(&directory) {
+        return Err(ZipError::InvalidArchive(
+            "Symlink target is outside the extraction directory",
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_symlink_target, glob_matches, ExtractOptions, OverwritePolicy};
+    use crate::read::ZipArchive;
+    use crate::result::ZipError;
+    use crate::test_util::TempDir;
+    use std::io;
+    use std::path::Path;
+
+    #[test]
+    fn symlink_targets() {
+        let check = |link: &str, target: &str| {
+            check_symlink_target(Path::new(link), Path::new(target)).is_ok()
+        };
+        assert!(check("link", "file"));
+        assert!(check("dir/link", "../file"));
+        assert!(check("dir/link", "./sub/../../file"));
+        assert!(!check("link", "../file"));
+        assert!(!check("dir/link", "../../file"));
+        assert!(!check("dir/link", "sub/../../../file"));
+        assert!(!check("link", "/etc/passwd"));
+    }
+
+    #[test]
+    fn globs() {
+        let check = |pattern: &str, name: &str| {
+            let pattern: Vec<&str> = pattern.split('/').collect();
+            let name: Vec<&str> = name.split('/').collect();
+            glob_matches(&pattern, &name)
+        };
+        assert!(check("assets/**/*.png", "assets/a.png"));
+        assert!(check("assets/**/*.png", "assets/icons/b/c.png"));
+        assert!(!check("assets/**/*.png", "assets/icons/c.jpg"));
+        assert!(!check("assets/*.png", "assets/icons/c.png"));
+        assert!(check("**", "any/thing"));
+        assert!(check("f?le.\u{e9}*", "file.\u{e9}t\u{e9}"));
+        assert!(check("f?le", "f\u{e9}le"));
+        assert!(!check("f?le", "fle"));
+    }
+
+    #[test]
+    fn preallocate() {
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::fs;
+        use std::io::{Cursor, Write};
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("file", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(&[7; 100_000]).unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+
+        let temp = TempDir::new("extract-preallocate");
+        let directory = temp.path();
+        let options = ExtractOptions::default().preallocate(true);
+        archive.extract_with_options(directory, options).unwrap();
+        assert_eq!(fs::read(directory.join("file")).unwrap(), [7; 100_000]);
+    }
+
+    #[test]
+    fn overwrite_policies() {
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::fs;
+        use std::io::{Cursor, Write};
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("dir/file.txt", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"new").unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+
+        let temp = TempDir::new("extract-overwrite");
+        let directory = temp.path();
+        let file = directory.join("dir/file.txt");
+        fs::create_dir_all(directory.join("dir")).unwrap();
+        let extract = |archive: &mut ZipArchive<_>, policy, atomic| {
+            fs::write(&file, "old").unwrap();
+            let options = ExtractOptions::default()
+                .overwrite_policy(policy)
+                .atomic(atomic);
+            archive.extract_with_options(directory, options)
+        };
+
+        let error = extract(&mut archive, OverwritePolicy::Error, false).unwrap_err();
+        assert!(matches!(error, ZipError::Io(e) if e.kind() == io::ErrorKind::AlreadyExists));
+        extract(&mut archive, OverwritePolicy::Skip, false).unwrap();
+        assert_eq!(fs::read(&file).unwrap(), b"old");
+        for atomic in [false, true] {
+            extract(&mut archive, OverwritePolicy::Overwrite, atomic).unwrap();
+            assert_eq!(fs::read(&file).unwrap(), b"new");
+        }
+        extract(&mut archive, OverwritePolicy::RenameNew, true).unwrap();
+        extract(&mut archive, OverwritePolicy::RenameNew, false).unwrap();
+        assert_eq!(fs::read(&file).unwrap(), b"old");
+        assert_eq!(
+            fs::read(directory.join("dir/file (1).txt")).unwrap(),
+            b"new"
+        );
+        assert_eq!(
+            fs::read(directory.join("dir/file (2).txt")).unwrap(),
+            b"new"
+        );
+        assert_eq!(fs::read_dir(directory.join("dir")).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn extract_glob() {
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::fs;
+
+        let mut writer = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        for name in [
+            "assets/a.png",
+            "assets/icons/b.png",
+            "assets/c.txt",
+            "d.png",
+        ] {
+            writer.start_file(name, options).unwrap();
+        }
+        let mut archive = writer.finish_into_readable().unwrap();
+
+        let temp = TempDir::new("extract-glob");
+        let directory = temp.path();
+        archive.extract_glob(directory, "assets/**/*.png").unwrap();
+        assert!(directory.join("assets/a.png").is_file());
+        assert!(directory.join("assets/icons/b.png").is_file());
+        assert!(!directory.join("assets/c.txt").exists());
+        assert!(!directory.join("d.png").exists());
+
+        // Options apply to the matching entries
+        fs::write(directory.join("assets/a.png"), b"kept").unwrap();
+        let options = ExtractOptions::default().overwrite_policy(OverwritePolicy::Skip);
+        archive
+            .extract_glob_with_options(directory, "assets/*.png", options)
+            .unwrap();
+        assert_eq!(fs::read(directory.join("assets/a.png")).unwrap(), b"kept");
+    }
+
+    #[test]
+    fn extract_from_stream() {
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::fs;
+        use std::io::{Cursor, Write};
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        writer.add_directory("dir/", options).unwrap();
+        writer.start_file("dir/file", options).unwrap();
+        writer.write_all(b"contents").unwrap();
+        writer.start_file("../escape", options).unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let temp = TempDir::new("extract-stream");
+        let directory = temp.path();
+        let options = ExtractOptions::default();
+        assert!(super::extract_from_stream(&mut &bytes[..], directory, options).is_err());
+        assert_eq!(fs::read(directory.join("dir/file")).unwrap(), b"contents");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn extract_symlinks() {
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::fs;
+        use std::io::{Cursor, Write};
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        writer.start_file("dir/file", options).unwrap();
+        writer.write_all(b"contents").unwrap();
+        writer.add_symlink("link", "dir/file", options).unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+        assert!(archive.by_index(1).unwrap().is_symlink());
+
+        let temp = TempDir::new("extract-symlinks");
+        let directory = temp.path();
+        let symlinks = ExtractOptions::default().symlinks(true);
+        archive.extract_with_options(directory, symlinks).unwrap();
+        let link = directory.join("link");
+        assert_eq!(fs::read_link(&link).unwrap(), Path::new("dir/file"));
+        assert_eq!(fs::read(&link).unwrap(), b"contents");
+
+        archive.extract(directory).unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().is_file());
+        assert_eq!(fs::read(&link).unwrap(), b"dir/file");
+        fs::remove_dir_all(directory).unwrap();
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .add_symlink("escape", "../outside", SimpleFileOptions::default())
+            .unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+        assert!(archive.extract_with_options(directory, symlinks).is_err());
+        assert!(fs::symlink_metadata(directory.join("escape")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn chained_symlinks() {
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::fs;
+        use std::io::{Cursor, Write};
+
+        let temp = TempDir::new("extract-chain");
+        let root = temp.path();
+        let directory = root.join("dest");
+        let options = SimpleFileOptions::default();
+        let symlinks = ExtractOptions::default().symlinks(true);
+
+        // `up` stays inside, but `x` goes up out of what `up` leads to
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.add_symlink("d1/d2/up", "../..", options).unwrap();
+        writer.add_symlink("d1/x", "d2/up/..", options).unwrap();
+        writer.start_file("d1/x/evil.txt", options).unwrap();
+        writer.write_all(b"evil").unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+        assert!(archive.extract_with_options(&directory, symlinks).is_err());
+        assert!(fs::symlink_metadata(root.join("evil.txt")).is_err());
+        assert!(fs::symlink_metadata(directory.join("d1/x")).is_err());
+        fs::remove_dir_all(&directory).unwrap();
+
+        // Links are followed however many of them a target goes through
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.add_symlink("d1/a/up", "..", options).unwrap();
+        writer.add_symlink("d1/b", "a/up/../..", options).unwrap();
+        writer.start_file("d1/b/evil.txt", options).unwrap();
+        writer.write_all(b"evil").unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+        assert!(archive.extract_with_options(&directory, symlinks).is_err());
+        assert!(fs::symlink_metadata(root.join("evil.txt")).is_err());
+
+        // Nor is anything written through a link that was already in the directory
+        fs::remove_dir_all(&directory).unwrap();
+        fs::create_dir_all(&directory).unwrap();
+        std::os::unix::fs::symlink(root, directory.join("out")).unwrap();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("out/evil.txt", options).unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+        assert!(archive.extract(&directory).is_err());
+        assert!(fs::symlink_metadata(root.join("evil.txt")).is_err());
+    }
+}
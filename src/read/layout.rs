@@ -0,0 +1,234 @@
+//! Maps out the regions of an archive file
+
+use crate::read::{find_apk_signing_block, find_data_start, find_entry_end, ZipArchive};
+use crate::result::ZipResult;
+use crate::spec;
+use std::io::{self, Read, Seek};
+use std::ops::Range;
+
+/// What a [`LayoutSegment`] contains
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SegmentKind {
+    /// Data prepended to the archive, such as a self-extractor stub
+    Prefix,
+    /// An entry's local file header, including its name and extra fields
+    LocalHeader,
+    /// An entry's compressed data
+    EntryData,
+    /// The data descriptor that follows an entry's data
+    DataDescriptor,
+    /// Bytes that no part of the archive refers to, such as data left behind by removed entries
+    Gap,
+    /// The APK Signing Block that Android's signature schemes put before the central directory
+    ApkSigningBlock,
+    /// The central directory
+    CentralDirectory,
+    /// The zip64 end of central directory record and locator
+    Zip64EndOfCentralDirectory,
+    /// The end of central directory record, not including the archive comment
+    EndOfCentralDirectory,
+    /// The archive comment
+    Comment,
+}
+
+/// A contiguous region of an archive file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LayoutSegment {
+    /// What the region contains
+    pub kind: SegmentKind,
+    /// The region's position in the underlying reader
+    pub range: Range<u64>,
+    /// The index of the entry the region belongs to, for entry headers, data and descriptors
+    pub entry: Option<usize>,
+}
+
+impl LayoutSegment {
+    /// The size of the region in bytes
+    pub fn len(&self) -> u64 {
+        self.range.end - self.range.start
+    }
+
+    /// Whether the region is empty
+    pub fn is_empty(&self) -> bool {
+        self.range.is_empty()
+    }
+}
+
+/// The regions of an archive file, as returned by [`ZipArchive::layout`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ArchiveLayout {
+    /// The regions, ordered by position. Regions of entries that share data overlap.
+    pub segments: Vec<LayoutSegment>,
+}
+
+impl ArchiveLayout {
+    /// The total size of every region of the given kind
+    pub fn total(&self, kind: SegmentKind) -> u64 {
+        self.segments
+            .iter()
+            .filter(|segment| segment.kind == kind)
+            .map(LayoutSegment::len)
+            .sum()
+    }
+
+    /// The number of bytes that aren't part of the archive, which rewriting it would reclaim
+    pub fn wasted(&self) -> u64 {
+        self.total(SegmentKind::Gap)
+    }
+}
+
+impl<R: Read + Seek> ZipArchive<R> {
+    /// Maps out the headers, entry data, unused gaps and directory records of this archive, in
+    /// file order.
+    ///
+    /// This reads each entry's local header, and the end of the file.
+    pub fn layout(&mut self) -> ZipResult<ArchiveLayout> {
+        let mut segments = Vec::new();
+        let offset = self.shared.offset;
+        if offset > 0 {
+            segments.push(LayoutSegment {
+                kind: SegmentKind::Prefix,
+                range: 0..offset,
+                entry: None,
+            });
+        }
+
+        let mut entries: Vec<_> = self.shared.files.values().enumerate().collect();
+        entries.sort_by_key(|(_, file)| file.header_start);
+        let mut position = offset;
+        for (index, file) in entries {
+            push_gap(&mut segments, position, file.header_start);
+            let data_start = find_data_start(file, &mut self.reader)?;
+            let data_end = data_start.saturating_add(file.compressed_size);
+            segments.push(LayoutSegment {
+                kind: SegmentKind::LocalHeader,
+                range: file.header_start..data_start,
+                entry: Some(index),
+            });
+            segments.push(LayoutSegment {
+                kind: SegmentKind::EntryData,
+                range: data_start..data_end,
+                entry: Some(index),
+            });
+            let end = find_entry_end(file, &mut self.reader)?;
+            if end > data_end {
+                segments.push(LayoutSegment {
+                    kind: SegmentKind::DataDescriptor,
+                    range: data_end..end,
+                    entry: Some(index),
+                });
+            }
+            position = position.max(end);
+        }
+
+        let (footer, cde_start) = spec::CentralDirectoryEnd::find_and_parse(&mut self.reader)?;
+        let mut directory_end = cde_start;
+        if cde_start >= 20 {
+            self.reader.seek(io::SeekFrom::Start(cde_start - 20))?;
+            if let Ok(locator) = spec::Zip64CentralDirectoryEndLocator::parse(&mut self.reader) {
+                let record_start = locator
+                    .end_of_central_directory_offset
+                    .saturating_add(offset)
+                    .min(cde_start - 20);
+                directory_end = record_start;
+            }
+        }
+        let dir_start = self.shared.dir_start;
+        match find_apk_signing_block(&mut self.reader, dir_start)? {
+            Some(block_start) if block_start >= position => {
+                push_gap(&mut segments, position, block_start);
+                segments.push(LayoutSegment {
+                    kind: SegmentKind::ApkSigningBlock,
+                    range: block_start..dir_start,
+                    entry: None,
+                });
+            }
+            _ => push_gap(&mut segments, position, dir_start),
+        }
+        segments.push(LayoutSegment {
+            kind: SegmentKind::CentralDirectory,
+            range: dir_start..directory_end,
+            entry: None,
+        });
+        if directory_end < cde_start {
+            segments.push(LayoutSegment {
+                kind: SegmentKind::Zip64EndOfCentralDirectory,
+                range: directory_end..cde_start,
+                entry: None,
+            });
+        }
+        let comment_start = cde_start + 22;
+        let comment_end = comment_start + footer.zip_file_comment.len() as u64;
+        segments.push(LayoutSegment {
+            kind: SegmentKind::EndOfCentralDirectory,
+            range: cde_start..comment_start,
+            entry: None,
+        });
+        if comment_end > comment_start {
+            segments.push(LayoutSegment {
+                kind: SegmentKind::Comment,
+                range: comment_start..comment_end,
+                entry: None,
+            });
+        }
+        let file_end = self.reader.seek(io::SeekFrom::End(0))?;
+        push_gap(&mut segments, comment_end, file_end);
+        Ok(ArchiveLayout { segments })
+    }
+}
+
+fn push_gap(segments: &mut Vec<LayoutSegment>, start: u64, end: u64) {
+    if end > start {
+        segments.push(LayoutSegment {
+            kind: SegmentKind::Gap,
+            range: start..end,
+            entry: None,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SegmentKind;
+    use crate::write::SimpleFileOptions;
+    use crate::{CompressionMethod, ZipArchive, ZipWriter};
+    use std::io::{Cursor, Write};
+
+    #[test]
+    fn layout() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file("a", options).unwrap();
+        writer.write_all(b"first").unwrap();
+        writer.start_file("b", options).unwrap();
+        writer.write_all(b"second").unwrap();
+        writer.set_comment("comment");
+        let bytes = writer.finish().unwrap().into_inner();
+        let len = bytes.len() as u64;
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let layout = archive.layout().unwrap();
+        let kinds: Vec<_> = layout.segments.iter().map(|s| (s.kind, s.entry)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (SegmentKind::LocalHeader, Some(0)),
+                (SegmentKind::EntryData, Some(0)),
+                (SegmentKind::LocalHeader, Some(1)),
+                (SegmentKind::EntryData, Some(1)),
+                (SegmentKind::CentralDirectory, None),
+                (SegmentKind::EndOfCentralDirectory, None),
+                (SegmentKind::Comment, None),
+            ]
+        );
+        assert!(layout
+            .segments
+            .windows(2)
+            .all(|pair| pair[0].range.end == pair[1].range.start));
+        assert_eq!(layout.segments.last().unwrap().range.end, len);
+        assert_eq!(layout.total(SegmentKind::EntryData), 11);
+        assert_eq!(layout.total(SegmentKind::Comment), 7);
+        assert_eq!(layout.wasted(), 0);
+    }
+}
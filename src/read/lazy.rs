@@ -0,0 +1,236 @@
+//! Reading archives whose central directory is parsed one record at a time, as entries are used
+
+use crate::read::zip_archive::Shared;
+use crate::read::{
+    central_header_to_zip_file, open_file, CentralDirectoryInfo, ZipArchive, ZipFile,
+};
+use crate::result::{ZipError, ZipResult};
+use crate::spec;
+use crate::types::ZipFileData;
+use indexmap::IndexMap;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::OnceLock;
+
+/// The size of a central directory record before its name, extra field and comment
+const RECORD_FIXED_SIZE: u64 = 46;
+
+/// ZIP archive reader that parses each central directory record on first access, returned by
+/// [`ZipArchive::new_lazy`]
+///
+/// Opening only finds where each record starts, which for archives with hundreds of thousands of
+/// entries is much faster than [`ZipArchive::new`] and keeps a few bytes per entry instead of
+/// its whole metadata. Since names aren't known until their records are parsed, looking up an
+/// entry by name parses records in order until it's found, so this suits archives whose entries
+/// are mostly found by index. Use [`LazyZipArchive::into_archive`] to parse the rest and get a
+/// [`ZipArchive`].
+///
+/// Unlike [`ZipArchive`], which keeps the last of several entries that share a name, every record
+/// of the central directory is an entry here. A corrupt record is only reported when it's parsed.
+#[derive(Clone, Debug)]
+pub struct LazyZipArchive<R> {
+    reader: R,
+    archive_offset: u64,
+    directory_start: u64,
+    record_starts: Vec<u64>,
+    entries: Vec<Option<Box<ZipFileData>>>,
+    comment: Box<[u8]>,
+}
+
+impl<R: Read + Seek> ZipArchive<R> {
+    /// Read a ZIP archive lazily, finding where each central directory record starts without
+    /// parsing any of them. See [`LazyZipArchive`].
+    pub fn new_lazy(mut reader: R) -> ZipResult<LazyZipArchive<R>> {
+        let (footer, cde_start_pos) = spec::CentralDirectoryEnd::find_and_parse(&mut reader)?;
+        let (dir_info, record_starts) =
+            Self::find_directory(&mut reader, &footer, cde_start_pos, find_record_starts)?;
+        Ok(LazyZipArchive {
+            reader,
+            archive_offset: dir_info.archive_offset,
+            directory_start: dir_info.directory_start,
+            entries: vec![None; record_starts.len()],
+            record_starts,
+            comment: footer.zip_file_comment.into(),
+        })
+    }
+}
+
+/// Walks the central directory, reading only the fixed part of each record to skip the rest.
+fn find_record_starts<R: Read + Seek>(
+    reader: &mut R,
+    dir_info: &CentralDirectoryInfo,
+) -> ZipResult<Vec<u64>> {
+    // As in ZipArchive::get_metadata, don't trust a file count larger than the offset
+    let capacity = if dir_info.number_of_files > dir_info.directory_start as usize {
+        0
+    } else {
+        dir_info.number_of_files
+    };
+    let mut record_starts = Vec::with_capacity(capacity);
+    reader.seek(SeekFrom::Start(dir_info.directory_start))?;
+    let mut reader = io::BufReader::new(reader);
+    let mut record_start = dir_info.directory_start;
+    for _ in 0..dir_info.number_of_files {
+        let mut record = [0; RECORD_FIXED_SIZE as usize];
+        reader.read_exact(&mut record)?;
+        if record[..4] != spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE.to_le_bytes() {
+            return Err(ZipError::InvalidArchive("Invalid Central Directory header"));
+        }
+        let length = |at: usize| u16::from_le_bytes([record[at], record[at + 1]]) as u64;
+        // The name, extra field and comment lengths
+        let variable_size = length(28) + length(30) + length(32);
+        reader.seek_relative(variable_size as i64)?;
+        record_starts.push(record_start);
+        record_start += RECORD_FIXED_SIZE + variable_size;
+    }
+    Ok(record_starts)
+}
+
+impl<R: Read + Seek> LazyZipArchive<R> {
+    /// Number of files contained in this zip.
+    pub fn len(&self) -> usize {
+        self.record_starts.len()
+    }
+
+    /// Whether this zip archive contains no files
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the offset from the beginning of the underlying reader that this zip begins at, in
+    /// bytes, as with [`ZipArchive::offset`].
+    pub fn offset(&self) -> u64 {
+        self.archive_offset
+    }
+
+    /// Get the comment of the zip archive.
+    pub fn comment(&self) -> &[u8] {
+        &self.comment
+    }
+
+    /// Parses the central directory record of entry `index`, unless that's already done.
+    fn load(&mut self, index: usize) -> ZipResult<()> {
+        let entry = self.entries.get_mut(index).ok_or(ZipError::FileNotFound)?;
+        if entry.is_none() {
+            self.reader
+                .seek(SeekFrom::Start(self.record_starts[index]))?;
+            let data = central_header_to_zip_file(&mut self.reader, self.archive_offset, None)?;
+            *entry = Some(Box::new(data));
+        }
+        Ok(())
+    }
+
+    /// Get the name of the entry at `index`, parsing its record if that isn't done yet.
+    pub fn name(&mut self, index: usize) -> ZipResult<&str> {
+        self.load(index)?;
+        Ok(&self.entries[index].as_ref().unwrap().file_name)
+    }
+
+    /// Get the index of the first entry named `name`, if there is one.
+    ///
+    /// Records are parsed in central directory order until a match is found, so looking up a
+    /// name that isn't in the archive parses every record.
+    pub fn index_for_name(&mut self, name: &str) -> ZipResult<Option<usize>> {
+        for index in 0..self.len() {
+            if self.name(index)? == name {
+                return Ok(Some(index));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Get a contained file by index, parsing its record if that isn't done yet.
+    pub fn by_index(&mut self, index: usize) -> ZipResult<ZipFile<'_>> {
+        self.by_index_with_optional_password(index, None)
+    }
+
+    /// Get a contained file by index, decrypting it with `password`, as with
+    /// [`ZipArchive::by_index_decrypt`].
+    pub fn by_index_decrypt(&mut self, index: usize, password: &[u8]) -> ZipResult<ZipFile<'_>> {
+        self.by_index_with_optional_password(index, Some(password))
+    }
+
+    /// Search for a file entry by name, as with [`LazyZipArchive::index_for_name`].
+    pub fn by_name(&mut self, name: &str) -> ZipResult<ZipFile<'_>> {
+        let index = self.index_for_name(name)?.ok_or(ZipError::FileNotFound)?;
+        self.by_index(index)
+    }
+
+    fn by_index_with_optional_password(
+        &mut self,
+        index: usize,
+        password: Option<&[u8]>,
+    ) -> ZipResult<ZipFile<'_>> {
+        self.load(index)?;
+        let data = self.entries[index].as_deref().unwrap();
+        open_file(data, &mut self.reader, password, None, None, None)
+    }
+
+    /// Parse the records not yet parsed, and return a [`ZipArchive`] with all the entries.
+    /// Entries that share a name are then merged as [`ZipArchive::new`] does.
+    pub fn into_archive(mut self) -> ZipResult<ZipArchive<R>> {
+        let mut files = IndexMap::with_capacity(self.len());
+        for index in 0..self.len() {
+            self.load(index)?;
+            let data = *self.entries[index].take().unwrap();
+            files.insert(data.file_name.clone(), data);
+        }
+        Ok(ZipArchive {
+            reader: self.reader,
+            shared: Shared {
+                files,
+                offset: self.archive_offset,
+                dir_start: self.directory_start,
+                name_index: OnceLock::new(),
+            }
+            .into(),
+            comment: self.comment.into(),
+            registry: None,
+            limits: None,
+            metrics: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::write::SimpleFileOptions;
+    use crate::{ZipArchive, ZipWriter};
+    use std::io::{Cursor, Read, Write};
+
+    #[test]
+    fn lazy() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for i in 0..100 {
+            writer
+                .start_file(format!("file{i}"), SimpleFileOptions::default())
+                .unwrap();
+            write!(writer, "contents of {i}").unwrap();
+        }
+        writer.set_raw_comment(b"comment"[..].into());
+        let mut bytes = b"preface".to_vec();
+        bytes.extend(writer.finish().unwrap().into_inner());
+
+        let mut archive = ZipArchive::new_lazy(Cursor::new(bytes.clone())).unwrap();
+        assert_eq!(archive.len(), 100);
+        assert_eq!(archive.offset(), 7);
+        assert_eq!(archive.comment(), b"comment");
+        assert!(archive.entries.iter().all(Option::is_none));
+
+        let mut contents = String::new();
+        archive
+            .by_index(42)
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "contents of 42");
+        assert_eq!(archive.entries.iter().filter(|e| e.is_some()).count(), 1);
+        assert_eq!(archive.index_for_name("file7").unwrap(), Some(7));
+        assert_eq!(archive.index_for_name("missing").unwrap(), None);
+        assert!(archive.by_index(100).is_err());
+
+        let archive = archive.into_archive().unwrap();
+        let eager = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        assert!(archive.file_names().eq(eager.file_names()));
+        assert_eq!(archive.offset(), eager.offset());
+    }
+}
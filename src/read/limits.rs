@@ -0,0 +1,201 @@
+//! Limits on how much reading an archive may decompress
+
+use crate::result::{ZipError, ZipResult};
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Limits on what reading an archive may cost, which guard services that accept archives from
+/// untrusted sources against decompression bombs. Set them with
+/// [`crate::read::ParseOptions::limits`]; each is off by default.
+///
+/// An archive with more entries than allowed fails to open. The size limits are enforced as
+/// files are read, whatever sizes the archive claims, and a read that would exceed one fails
+/// with [`io::ErrorKind::Unsupported`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReadLimits {
+    max_entries: Option<usize>,
+    max_file_size: Option<u64>,
+    max_total_size: Option<u64>,
+    max_ratio: Option<u64>,
+}
+
+impl ReadLimits {
+    /// Set the most entries the archive may have.
+    #[must_use]
+    pub const fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = Some(max_entries);
+        self
+    }
+
+    /// Set the most bytes any one file may decompress to.
+    #[must_use]
+    pub const fn max_file_size(mut self, max_file_size: u64) -> Self {
+        self.max_file_size = Some(max_file_size);
+        self
+    }
+
+    /// Set the most bytes all the files read from the archive, together, may decompress to.
+    #[must_use]
+    pub const fn max_total_size(mut self, max_total_size: u64) -> Self {
+        self.max_total_size = Some(max_total_size);
+        self
+    }
+
+    /// Set the most times larger than its compressed data a file may decompress to. Deflate
+    /// rarely exceeds 100 outside of crafted or highly repetitive data.
+    #[must_use]
+    pub const fn max_ratio(mut self, max_ratio: u64) -> Self {
+        self.max_ratio = Some(max_ratio);
+        self
+    }
+
+    pub(crate) fn check_entries(&self, entries: usize) -> ZipResult<()> {
+        match self.max_entries {
+            Some(max_entries) if entries > max_entries => Err(ZipError::UnsupportedArchive(
+                "Archive has more entries than the limit allows",
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Counts what the files read from one archive have decompressed to
+#[derive(Debug)]
+pub(crate) struct LimitTracker {
+    limits: ReadLimits,
+    total_size: AtomicU64,
+}
+
+impl LimitTracker {
+    /// Returns a tracker, unless `limits` leaves nothing to track.
+    pub(crate) fn new(limits: ReadLimits) -> Option<Arc<Self>> {
+        let untracked = ReadLimits {
+            max_entries: limits.max_entries,
+            ..ReadLimits::default()
+        };
+        (limits != untracked).then(|| {
+            Arc::new(LimitTracker {
+                limits,
+                total_size: AtomicU64::new(0),
+            })
+        })
+    }
+
+    /// Starts tracking a file that's about to be read, failing if it claims to be too large.
+    pub(crate) fn entry(
+        self: &Arc<Self>,
+        compressed_size: u64,
+        uncompressed_size: u64,
+    ) -> ZipResult<EntryLimit> {
+        let max_size =
+            self.limits
+                .max_file_size
+                .unwrap_or(u64::MAX)
+                .min(match self.limits.max_ratio {
+                    Some(max_ratio) => compressed_size.saturating_mul(max_ratio),
+                    None => u64::MAX,
+                });
+        if uncompressed_size > max_size {
+            return Err(ZipError::UnsupportedArchive(
+                "File is larger than the limit allows",
+            ));
+        }
+        Ok(EntryLimit {
+            tracker: self.clone(),
+            max_size,
+            size: 0,
+        })
+    }
+}
+
+/// Counts what one file has decompressed to
+pub(crate) struct EntryLimit {
+    tracker: Arc<LimitTracker>,
+    max_size: u64,
+    size: u64,
+}
+
+impl EntryLimit {
+    /// Records that `count` more bytes were decompressed, failing if that exceeds a limit.
+    pub(crate) fn record(&mut self, count: usize) -> io::Result<()> {
+        self.size += count as u64;
+        if self.size > self.max_size {
+            return Err(
+                ZipError::UnsupportedArchive("File is larger than the limit allows").into(),
+            );
+        }
+        let total_size = self
+            .tracker
+            .total_size
+            .fetch_add(count as u64, Ordering::Relaxed)
+            + count as u64;
+        if self
+            .tracker
+            .limits
+            .max_total_size
+            .is_some_and(|max_total_size| total_size > max_total_size)
+        {
+            return Err(ZipError::UnsupportedArchive(
+                "Archive's files are larger in total than the limit allows",
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ReadLimits;
+    use crate::read::ParseOptions;
+    use crate::write::SimpleFileOptions;
+    use crate::{ZipArchive, ZipWriter};
+    use std::io::{self, Cursor, Read, Write};
+
+    fn archive() -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for name in ["a", "b", "c"] {
+            writer
+                .start_file(name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(&[0; 10_000]).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn open(limits: ReadLimits) -> ZipArchive<Cursor<Vec<u8>>> {
+        let options = ParseOptions::default().limits(limits);
+        ZipArchive::with_options(Cursor::new(archive()), options).unwrap()
+    }
+
+    fn read(archive: &mut ZipArchive<Cursor<Vec<u8>>>, name: &str) -> io::Result<usize> {
+        let mut contents = Vec::new();
+        archive.by_name(name)?.read_to_end(&mut contents)
+    }
+
+    #[test]
+    fn limits() {
+        let options = ParseOptions::default().limits(ReadLimits::default().max_entries(2));
+        assert!(ZipArchive::with_options(Cursor::new(archive()), options).is_err());
+
+        let mut archive = open(ReadLimits::default().max_file_size(10_000));
+        assert_eq!(read(&mut archive, "a").unwrap(), 10_000);
+        let mut archive = open(ReadLimits::default().max_file_size(9_999));
+        assert!(read(&mut archive, "a").is_err());
+
+        let mut archive = open(ReadLimits::default().max_total_size(25_000));
+        assert!(read(&mut archive, "a").is_ok());
+        assert!(read(&mut archive, "b").is_ok());
+        assert!(read(&mut archive, "c").is_err());
+    }
+
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn ratio() {
+        let mut archive = open(ReadLimits::default().max_ratio(2));
+        assert!(read(&mut archive, "a").is_err());
+        let mut archive = open(ReadLimits::default().max_ratio(1000));
+        assert!(read(&mut archive, "a").is_ok());
+    }
+}
@@ -0,0 +1,112 @@
+//! Looking up entries by names that differ from the stored ones in case or path separators
+
+use crate::read::{ZipArchive, ZipFile};
+use crate::result::{ZipError, ZipResult};
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+
+/// Indices of the entries by caseless and by normalized name, built on the first lookup that
+/// needs them. Where several entries share a key, the first in the central directory wins.
+#[derive(Debug)]
+pub(crate) struct NameIndex {
+    caseless: HashMap<String, usize>,
+    normalized: HashMap<String, usize>,
+}
+
+impl NameIndex {
+    fn new<'a>(names: impl Iterator<Item = &'a str>) -> Self {
+        let mut index = NameIndex {
+            caseless: HashMap::new(),
+            normalized: HashMap::new(),
+        };
+        for (i, name) in names.enumerate() {
+            index.caseless.entry(name.to_lowercase()).or_insert(i);
+            index.normalized.entry(normalize(name)).or_insert(i);
+        }
+        index
+    }
+}
+
+/// Joins the components of `name` with `/`, whichever separator it uses, leaving out empty and
+/// `.` components.
+fn normalize(name: &str) -> String {
+    name.split(['/', '\\'])
+        .filter(|component| !matches!(*component, "" | "."))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+impl<R: Read + Seek> ZipArchive<R> {
+    fn name_index(&self) -> &NameIndex {
+        self.shared
+            .name_index
+            .get_or_init(|| NameIndex::new(self.shared.files.keys().map(AsRef::as_ref)))
+    }
+
+    /// Get the index of the file entry whose name matches `name` ignoring case, if there is one.
+    ///
+    /// The first lookup builds an index over all the names, which clones of this archive share.
+    /// If several names differ only in case, this finds the first in the central directory.
+    pub fn index_for_name_caseless(&self, name: &str) -> Option<usize> {
+        self.name_index()
+            .caseless
+            .get(&name.to_lowercase())
+            .copied()
+    }
+
+    /// Search for a file entry by name, ignoring case, as with
+    /// [`ZipArchive::index_for_name_caseless`].
+    pub fn by_name_caseless(&mut self, name: &str) -> ZipResult<ZipFile<'_>> {
+        let index = self
+            .index_for_name_caseless(name)
+            .ok_or(ZipError::FileNotFound)?;
+        self.by_index(index)
+    }
+
+    /// Get the index of the file entry whose name is the same path as `name`, if there is one.
+    ///
+    /// Names are compared with `/` and `\` treated alike and with empty and `.` components left
+    /// out, so `dir\file`, `./dir/file` and `dir//file` all find `dir/file`, and `dir` finds
+    /// `dir/`. Like [`ZipArchive::index_for_name_caseless`], the first lookup builds an index,
+    /// and the first matching entry in the central directory is found.
+    pub fn index_for_name_normalized(&self, name: &str) -> Option<usize> {
+        self.name_index().normalized.get(&normalize(name)).copied()
+    }
+
+    /// Search for a file entry by path, as with [`ZipArchive::index_for_name_normalized`].
+    pub fn by_name_normalized(&mut self, name: &str) -> ZipResult<ZipFile<'_>> {
+        let index = self
+            .index_for_name_normalized(name)
+            .ok_or(ZipError::FileNotFound)?;
+        self.by_index(index)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::write::SimpleFileOptions;
+    use crate::{ZipArchive, ZipWriter};
+    use std::io::Cursor;
+
+    #[test]
+    fn lookups() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        writer.start_file("Dir/File.TXT", options).unwrap();
+        writer.start_file("dir/file.txt", options).unwrap();
+        writer.add_directory("other", options).unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+
+        assert_eq!(archive.index_for_name_caseless("DIR/FILE.txt"), Some(0));
+        assert_eq!(archive.index_for_name_caseless("dir\\file.txt"), None);
+        assert_eq!(
+            archive.index_for_name_normalized(".\\dir//file.txt"),
+            Some(1)
+        );
+        assert_eq!(archive.index_for_name_normalized("DIR/FILE.TXT"), None);
+        assert_eq!(archive.index_for_name_normalized("other"), Some(2));
+        assert_eq!(archive.by_name_caseless("OTHER/").unwrap().name(), "other/");
+        assert!(archive.by_name_normalized("missing").is_err());
+    }
+}
@@ -1,11 +1,15 @@
 use lzma_rs::decompress::{Options, Stream, UnpackedSize};
 use std::collections::VecDeque;
-use std::io::{copy, Error, Read, Result, Write};
+use std::io::{copy, Error, ErrorKind, Read, Result, Write};
 
 const COMPRESSED_BYTES_TO_BUFFER: usize = 4096;
 
+/// The size of the LZMA properties that follow the ZIP-specific header
+const PROPERTIES_SIZE: u16 = 5;
+
+// Unlike an .lzma file, the properties in a ZIP entry aren't followed by the uncompressed size
 const OPTIONS: Options = Options {
-    unpacked_size: UnpackedSize::ReadFromHeader,
+    unpacked_size: UnpackedSize::UseProvided(None),
     memlimit: None,
     allow_incomplete: true,
 };
@@ -13,39 +17,75 @@ const OPTIONS: Options = Options {
 #[derive(Debug)]
 pub struct LzmaDecoder<R> {
     compressed_reader: R,
-    stream: Stream<VecDeque<u8>>,
+    stream: Option<Stream<VecDeque<u8>>>,
+    // The output left once the stream has been finished
+    remaining: VecDeque<u8>,
+    header_read: bool,
 }
 
 impl<R: Read> LzmaDecoder<R> {
     pub fn new(inner: R) -> Self {
         LzmaDecoder {
             compressed_reader: inner,
-            stream: Stream::new_with_options(&OPTIONS, VecDeque::new()),
+            stream: Some(Stream::new_with_options(&OPTIONS, VecDeque::new())),
+            remaining: VecDeque::new(),
+            header_read: false,
+        }
+    }
+
+    /// Skips the LZMA SDK version and checks the size of the properties, which make up the
+    /// header the ZIP format (APPNOTE 5.8.8) puts before the LZMA properties.
+    fn read_header(&mut self) -> Result<()> {
+        if !self.header_read {
+            let mut header = [0u8; 4];
+            self.compressed_reader.read_exact(&mut header)?;
+            if u16::from_le_bytes([header[2], header[3]]) != PROPERTIES_SIZE {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "Unsupported LZMA properties size",
+                ));
+            }
+            self.header_read = true;
         }
+        Ok(())
     }
 
     pub fn finish(mut self) -> Result<VecDeque<u8>> {
-        copy(&mut self.compressed_reader, &mut self.stream)?;
-        self.stream.finish().map_err(Error::from)
+        self.read_header()?;
+        let Some(mut stream) = self.stream.take() else {
+            return Ok(self.remaining);
+        };
+        copy(&mut self.compressed_reader, &mut stream)?;
+        stream.finish().map_err(Error::from)
     }
 }
 
 impl<R: Read> Read for LzmaDecoder<R> {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-        let mut bytes_read = self.stream.get_output_mut().unwrap().read(buf)?;
+        self.read_header()?;
+        let mut bytes_read = 0;
         while bytes_read < buf.len() {
+            let Some(stream) = self.stream.as_mut() else {
+                bytes_read += self.remaining.read(&mut buf[bytes_read..])?;
+                break;
+            };
+            let output = stream
+                .get_output_mut()
+                .ok_or(Error::new(ErrorKind::Other, "LZMA stream failed earlier"))?;
+            bytes_read += output.read(&mut buf[bytes_read..])?;
+            if bytes_read == buf.len() {
+                break;
+            }
             let mut next_compressed = [0u8; COMPRESSED_BYTES_TO_BUFFER];
             let compressed_bytes_read = self.compressed_reader.read(&mut next_compressed)?;
             if compressed_bytes_read == 0 {
-                break;
+                // The stream holds back the last few bytes of input until it's finished
+                if let Some(stream) = self.stream.take() {
+                    self.remaining = stream.finish().map_err(Error::from)?;
+                }
+            } else {
+                stream.write_all(&next_compressed[..compressed_bytes_read])?;
             }
-            self.stream
-                .write_all(&next_compressed[..compressed_bytes_read])?;
-            bytes_read += self
-                .stream
-                .get_output_mut()
-                .unwrap()
-                .read(&mut buf[bytes_read..])?;
         }
         Ok(bytes_read)
     }
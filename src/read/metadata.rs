@@ -0,0 +1,136 @@
+//! Listing the metadata of every entry as plain owned values
+
+use crate::compression::CompressionMethod;
+use crate::read::ZipArchive;
+use crate::types::{DateTime, ZipFileData};
+
+/// The metadata of one entry, from [`ZipArchive::metadata`]
+///
+/// Every field is a plain owned value, so a listing can be kept after the archive is dropped or
+/// mapped to JSON and other formats field by field.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct ZipEntryMetadata {
+    /// The entry's index
+    pub index: usize,
+    /// The entry's name
+    pub name: String,
+    /// The entry's comment
+    pub comment: String,
+    /// Whether the entry is a directory
+    pub is_dir: bool,
+    /// The size of the entry's data in the archive, in bytes
+    pub compressed_size: u64,
+    /// The size of the entry once extracted, in bytes
+    pub uncompressed_size: u64,
+    /// How the entry's data is compressed
+    pub compression_method: CompressionMethod,
+    /// The CRC-32 of the entry's uncompressed contents
+    pub crc32: u32,
+    /// Whether the entry is encrypted
+    pub encrypted: bool,
+    /// The last-modified time in MS-DOS format
+    pub last_modified: DateTime,
+    /// The last-modified time in seconds since the Unix epoch, from an extended timestamp extra
+    /// field
+    pub modified: Option<u32>,
+    /// The last-accessed time in seconds since the Unix epoch, from an extended timestamp extra
+    /// field
+    pub accessed: Option<u32>,
+    /// The creation time in seconds since the Unix epoch, from an extended timestamp extra field
+    pub created: Option<u32>,
+    /// The Unix mode, with the file type and permissions, if the entry was made on Unix
+    pub unix_mode: Option<u32>,
+    /// The IDs of the blocks in the entry's central directory extra field, in order
+    pub extra_field_ids: Vec<u16>,
+}
+
+impl ZipEntryMetadata {
+    fn new(index: usize, data: &ZipFileData) -> Self {
+        let timestamp = data.extended_timestamp();
+        let extra_field = data.extra_field.as_deref().map_or(&[][..], Vec::as_slice);
+        ZipEntryMetadata {
+            index,
+            name: data.file_name.to_string(),
+            comment: data.file_comment.to_string(),
+            is_dir: data.file_name.ends_with(['/', '\\']),
+            compressed_size: data.compressed_size,
+            uncompressed_size: data.uncompressed_size,
+            compression_method: data.compression_method,
+            crc32: data.crc32,
+            encrypted: data.encrypted,
+            last_modified: data.last_modified_time,
+            modified: timestamp.and_then(|timestamp| timestamp.mod_time().copied()),
+            accessed: timestamp.and_then(|timestamp| timestamp.ac_time().copied()),
+            created: timestamp.and_then(|timestamp| timestamp.cr_time().copied()),
+            unix_mode: data.unix_mode(),
+            extra_field_ids: extra_field_ids(extra_field),
+        }
+    }
+}
+
+/// Returns the IDs of the blocks of an extra field, up to any that's truncated.
+fn extra_field_ids(mut extra_field: &[u8]) -> Vec<u16> {
+    let mut ids = Vec::new();
+    while let [id_low, id_high, len_low, len_high, rest @ ..] = extra_field {
+        ids.push(u16::from_le_bytes([*id_low, *id_high]));
+        let len = u16::from_le_bytes([*len_low, *len_high]) as usize;
+        extra_field = rest.get(len..).unwrap_or_default();
+    }
+    ids
+}
+
+impl<R> ZipArchive<R> {
+    /// Returns the metadata of every entry, in central directory order, read from the central
+    /// directory without touching the reader.
+    pub fn metadata(&self) -> Vec<ZipEntryMetadata> {
+        self.shared
+            .files
+            .values()
+            .enumerate()
+            .map(|(index, data)| ZipEntryMetadata::new(index, data))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::extra_fields::ExtendedTimestamp;
+    use crate::write::SimpleFileOptions;
+    use crate::{CompressionMethod, ZipArchive, ZipWriter};
+    use std::io::{Cursor, Write};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn metadata() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let modified = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let timestamp = ExtendedTimestamp::new(Some(modified), None, None).unwrap();
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Stored)
+            .unix_permissions(0o644)
+            .extended_timestamp(timestamp);
+        writer.add_directory("dir", options).unwrap();
+        writer.start_file("dir/file", options).unwrap();
+        writer.write_all(b"contents").unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let metadata = ZipArchive::new(Cursor::new(bytes)).unwrap().metadata();
+        assert_eq!(metadata.len(), 2);
+        assert!(metadata[0].is_dir);
+        let file = &metadata[1];
+        assert_eq!(file.index, 1);
+        assert_eq!(file.name, "dir/file");
+        assert_eq!(file.comment, "");
+        assert!(!file.is_dir);
+        assert_eq!(file.compressed_size, 8);
+        assert_eq!(file.uncompressed_size, 8);
+        assert_eq!(file.compression_method, CompressionMethod::Stored);
+        assert_eq!(file.crc32, crc32fast::hash(b"contents"));
+        assert!(!file.encrypted);
+        assert_eq!(file.modified, Some(1_700_000_000));
+        assert_eq!(file.accessed, None);
+        assert_eq!(file.unix_mode, Some(0o100644));
+        assert_eq!(file.extra_field_ids, [0x5455]);
+    }
+}
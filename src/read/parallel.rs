@@ -0,0 +1,210 @@
+//! Extracting an archive on several threads
+
+use crate::read::{extract_entry, ExtractOptions, ZipArchive};
+use crate::result::{ZipError, ZipResult};
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// Options for [`ZipArchive::extract_parallel`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ParallelOptions {
+    threads: Option<NonZeroUsize>,
+    extract: ExtractOptions,
+}
+
+impl ParallelOptions {
+    /// Set the maximum number of entries to extract at once.
+    ///
+    /// The default is [`std::thread::available_parallelism`], or 1 if that can't be determined.
+    #[must_use]
+    pub const fn threads(mut self, threads: NonZeroUsize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Set the options each entry is extracted with.
+    #[must_use]
+    pub const fn extract_options(mut self, extract: ExtractOptions) -> Self {
+        self.extract = extract;
+        self
+    }
+
+    fn thread_count(&self) -> usize {
+        self.threads
+            .or_else(|| thread::available_parallelism().ok())
+            .map_or(1, NonZeroUsize::get)
+    }
+}
+
+impl<R: Read + Seek + Clone + Send> ZipArchive<R> {
+    /// Extract a Zip archive into a directory like [`ZipArchive::extract`], decompressing several
+    /// files at once.
+    ///
+    /// Directories are created first, in archive order, and files are then divided among the
+    /// worker threads. Symlinks, and entries whose names appear more than once, are extracted
+    /// last, one at a time in archive order, as tar does: otherwise a symlink could appear on a
+    /// path after another thread checked that writing there stays inside `directory`. Each thread reads through its own clone of this archive, so the clones of
+    /// `R` must be able to seek independently of each other: a [`std::io::Cursor`] over shared
+    /// bytes works, but a [`std::fs::File`] duplicated with [`std::fs::File::try_clone`] doesn't,
+    /// because its duplicates share one position. To extract from a file, open it as a
+    /// [`crate::read::BackendReader`] of an `Arc<File>`, whose clones read the one handle with
+    /// positioned reads (`pread` on Unix), without duplicating it or taking a lock.
+    ///
+    /// Extraction stops at the first error, which is returned once every thread has finished.
+    /// As with [`ZipArchive::extract`], some of the files may be left on disk.
+    pub fn extract_parallel<P: AsRef<Path>>(
+        &self,
+        directory: P,
+        options: ParallelOptions,
+    ) -> ZipResult<()> {
+        let directory = directory.as_ref();
+        let mut files = Vec::new();
+        let mut serial = Vec::new();
+        let mut archive = self.clone();
+        let mut name_counts = HashMap::new();
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            *name_counts.entry(file.mangled_name()).or_insert(0) += 1;
+        }
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i)?;
+            if file.is_dir() {
+                extract_entry(&mut file, directory, &options.extract, &mut |_| {})?;
+            } else if (file.is_symlink() && options.extract.creates_symlinks())
+                || name_counts[&file.mangled_name()] > 1
+            {
+                serial.push(i);
+            } else {
+                files.push(i);
+            }
+        }
+
+        let next = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+        let error: Mutex<Option<ZipError>> = Mutex::new(None);
+        let threads = options.thread_count().min(files.len());
+        let (files, next, failed, error_ref) = (&files, &next, &failed, &error);
+        thread::scope(|scope| {
+            for _ in 0..threads {
+                let mut archive = self.clone();
+                scope.spawn(move || {
+                    while !failed.load(Ordering::Relaxed) {
+                        let Some(&index) = files.get(next.fetch_add(1, Ordering::Relaxed)) else {
+                            break;
+                        };
+                        let result = archive.by_index(index).and_then(|mut file| {
+                            extract_entry(&mut file, directory, &options.extract, &mut |_| {})
+                        });
+                        if let Err(e) = result {
+                            failed.store(true, Ordering::Relaxed);
+                            error_ref.lock().unwrap().get_or_insert(e);
+                        }
+                    }
+                });
+            }
+        });
+        if let Some(e) = error.into_inner().unwrap() {
+            return Err(e);
+        }
+        for i in serial {
+            let mut file = archive.by_index(i)?;
+            extract_entry(&mut file, directory, &options.extract, &mut |_| {})?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ParallelOptions;
+    use crate::test_util::TempDir;
+    use crate::write::SimpleFileOptions;
+    use crate::{CompressionMethod, ZipWriter};
+    use std::fs;
+    use std::io::{Cursor, Write};
+    use std::num::NonZeroUsize;
+    use std::path::Path;
+
+    fn check_extracted(directory: &Path) {
+        for i in 0..20 {
+            let contents = fs::read_to_string(directory.join(format!("dir/sub/{i}"))).unwrap();
+            assert_eq!(contents, format!("contents {i}"));
+        }
+    }
+
+    #[test]
+    fn extract_parallel() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.add_directory("dir/", options).unwrap();
+        for i in 0..20 {
+            writer.start_file(format!("dir/sub/{i}"), options).unwrap();
+            write!(writer, "contents {i}").unwrap();
+        }
+        let archive = writer.finish_into_readable().unwrap();
+
+        let temp = TempDir::new("extract-parallel");
+        let directory = temp.path();
+        let options = ParallelOptions::default().threads(NonZeroUsize::new(4).unwrap());
+        archive.extract_parallel(directory, options).unwrap();
+        check_extracted(directory);
+        fs::remove_dir_all(directory).unwrap();
+
+        #[cfg(any(unix, windows))]
+        {
+            use crate::read::BackendReader;
+            use crate::ZipArchive;
+            use std::sync::Arc;
+
+            // Every thread reads the one file handle
+            fs::create_dir_all(directory).unwrap();
+            let path = directory.join("archive.zip");
+            fs::write(&path, archive.into_inner().into_inner()).unwrap();
+            let file = Arc::new(fs::File::open(&path).unwrap());
+            let archive = ZipArchive::new(BackendReader::new(file).unwrap()).unwrap();
+            archive
+                .extract_parallel(directory.join("extracted"), options)
+                .unwrap();
+            check_extracted(&directory.join("extracted"));
+        }
+    }
+    #[test]
+    fn serial_entries() {
+        use crate::read::ExtractOptions;
+        use crate::write::DuplicateNamePolicy;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_duplicate_name_policy(DuplicateNamePolicy::Allow);
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        for contents in ["first", "second"] {
+            writer.start_file("dup", options).unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+        writer.add_symlink("link", "dup", options).unwrap();
+        for i in 0..10 {
+            writer.start_file(format!("{i}"), options).unwrap();
+        }
+        let archive = writer.finish_into_readable().unwrap();
+
+        let temp = TempDir::new("extract-serial");
+        let directory = temp.path();
+        let extract = ExtractOptions::default().symlinks(true);
+        let options = ParallelOptions::default()
+            .threads(NonZeroUsize::new(4).unwrap())
+            .extract_options(extract);
+        archive.extract_parallel(directory, options).unwrap();
+        // Entries with the same name are extracted in archive order
+        assert_eq!(fs::read(directory.join("dup")).unwrap(), b"second");
+        #[cfg(unix)]
+        {
+            let link = directory.join("link");
+            assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+            assert_eq!(fs::read(link).unwrap(), b"second");
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! Options for how an archive's central directory is found and parsed
+
+use crate::read::verify::check_local_header;
+use crate::read::zip_archive::Shared;
+use crate::read::{find_entry_end, LimitTracker, ReadLimits, ZipArchive};
+use crate::result::{ZipError, ZipResult};
+use crate::spec;
+use std::io::{Read, Seek};
+
+/// Options for [`ZipArchive::with_options`]
+#[derive(Clone, Copy, Debug)]
+pub struct ParseOptions {
+    search_window: u64,
+    limits: ReadLimits,
+    legacy_decoder: Option<fn(&[u8]) -> String>,
+    strict: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            search_window: spec::MAX_HEADER_AND_COMMENT_SIZE,
+            limits: ReadLimits::default(),
+            legacy_decoder: None,
+            strict: false,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Options that reject archives using the tricks malware uses to show different contents to
+    /// different tools, for scanning archives from untrusted sources. Opening fails if:
+    ///
+    /// - several entries have the same name,
+    /// - an entry's local header disagrees with the central directory, as
+    ///   [`ZipArchive::verify`] checks,
+    /// - an extra field's blocks don't exactly fill it,
+    /// - entries overlap, or one's data runs into the central directory.
+    ///
+    /// This reads every local header, so opening takes longer. The other options can be changed
+    /// from here as usual.
+    pub fn strict() -> Self {
+        ParseOptions {
+            strict: true,
+            ..ParseOptions::default()
+        }
+    }
+
+    /// Set how many bytes at the end of the file are searched for the end of central directory
+    /// record. Raising it finds archives that are followed by other data, such as polyglot files
+    /// or archives with a signature appended; searching takes longer the larger it is.
+    ///
+    /// The default is enough for the record with the longest possible comment, at the very end
+    /// of the file.
+    #[must_use]
+    pub const fn search_window(mut self, search_window: u64) -> Self {
+        self.search_window = search_window;
+        self
+    }
+
+    /// Set limits on the entries of the archive and on how much reading them may decompress.
+    #[must_use]
+    pub const fn limits(mut self, limits: ReadLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Set how the names and comments of entries that aren't flagged as UTF-8 are decoded,
+    /// instead of as code page 437 as the specification says. Archives made by tools that used
+    /// the system's code page, such as Shift-JIS or GBK, need a function wrapping a codec like
+    /// `encoding_rs`. A name given in an Info-ZIP Unicode Path extra field is used either way.
+    #[must_use]
+    pub const fn legacy_decoder(mut self, decoder: fn(&[u8]) -> String) -> Self {
+        self.legacy_decoder = Some(decoder);
+        self
+    }
+}
+
+impl<R: Read + Seek> ZipArchive<R> {
+    /// Read a ZIP archive like [`ZipArchive::new`], with the given options.
+    ///
+    /// Data before the archive, such as the stub of a self-extracting archive, is found either
+    /// way: the entries are located relative to where the central directory turns out to be,
+    /// whether the archive records its offsets from the start of the file or from the end of the
+    /// stub.
+    pub fn with_options(mut reader: R, options: ParseOptions) -> ZipResult<ZipArchive<R>> {
+        let (footer, cde_start_pos) =
+            spec::CentralDirectoryEnd::find_and_parse_within(&mut reader, options.search_window)?;
+        let shared = Self::get_metadata(
+            &mut reader,
+            &footer,
+            cde_start_pos,
+            options.legacy_decoder,
+            options.strict,
+        )?;
+        options.limits.check_entries(shared.files.len())?;
+        if options.strict {
+            check_strict(&mut reader, &shared)?;
+        }
+        Ok(ZipArchive {
+            reader,
+            shared: shared.into(),
+            comment: footer.zip_file_comment.into(),
+            registry: None,
+            limits: LimitTracker::new(options.limits),
+            metrics: None,
+        })
+    }
+}
+
+/// Checks the entries of an archive as [`ParseOptions::strict`] describes, apart from their names.
+fn check_strict<R: Read + Seek>(reader: &mut R, shared: &Shared) -> ZipResult<()> {
+    let mut ranges = Vec::with_capacity(shared.files.len());
+    for data in shared.files.values() {
+        let header = check_local_header(data, reader).map_err(ZipError::InvalidArchive)?;
+        if let Some(extra_field) = &data.extra_field {
+            check_extra_field(extra_field)?;
+        }
+        check_extra_field(&header.extra_field)?;
+        let end = find_entry_end(data, reader)?;
+        if end > shared.dir_start {
+            return Err(ZipError::InvalidArchive(
+                "File data overlaps the central directory",
+            ));
+        }
+        ranges.push((data.header_start, end));
+    }
+    ranges.sort_unstable();
+    if ranges.windows(2).any(|pair| pair[0].1 > pair[1].0) {
+        return Err(ZipError::InvalidArchive("Files overlap"));
+    }
+    Ok(())
+}
+
+/// Checks that an extra field is made of whole blocks, each a 2-byte ID and 2-byte length
+/// followed by that many bytes, except for any zeros padding it too short to be a block.
+fn check_extra_field(extra_field: &[u8]) -> ZipResult<()> {
+    let mut rest = extra_field;
+    while !rest.is_empty() {
+        let len = match rest {
+            [_, _, low, high, ..] => u16::from_le_bytes([*low, *high]) as usize,
+            // As ZipWriter aligns files whose padding can't hold a block
+            padding if padding.iter().all(|&byte| byte == 0) => break,
+            _ => return Err(ZipError::InvalidArchive("Extra field block is truncated")),
+        };
+        rest = rest.get(4 + len..).ok_or(ZipError::InvalidArchive(
+            "Extra field block is longer than the extra field",
+        ))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::ParseOptions;
+    use crate::write::SimpleFileOptions;
+    use crate::{CompressionMethod, ZipArchive, ZipWriter};
+    use std::io::{Cursor, Write};
+
+    #[test]
+    fn trailing_data() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("file", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"contents").unwrap();
+        let written = writer.finish().unwrap().into_inner();
+        let zip64 = include_bytes!("../../tests/data/zip64_demo.zip").to_vec();
+
+        for mut bytes in [written, zip64] {
+            bytes.extend_from_slice(&[0; 100_000]);
+            assert!(ZipArchive::new(Cursor::new(bytes.clone())).is_err());
+            let options = ParseOptions::default().search_window(200_000);
+            let mut archive = ZipArchive::with_options(Cursor::new(bytes), options).unwrap();
+            let mut file = archive.by_index(0).unwrap();
+            std::io::copy(&mut file, &mut std::io::sink()).unwrap();
+        }
+    }
+
+    #[test]
+    fn strict() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Stored)
+            .with_alignment(4096);
+        for name in ["aa", "ab"] {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(b"contents").unwrap();
+        }
+        let bytes = writer.finish().unwrap().into_inner();
+        let open = |bytes: &Vec<u8>| {
+            ZipArchive::with_options(Cursor::new(bytes.clone()), ParseOptions::strict())
+        };
+        open(&bytes).unwrap();
+
+        // Give both entries the same name
+        let mut duplicate = bytes.clone();
+        for at in find_all(&bytes, b"ab") {
+            duplicate[at + 1] = b'a';
+        }
+        assert_eq!(
+            ZipArchive::new(Cursor::new(duplicate.clone()))
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(open(&duplicate).is_err());
+
+        // Rename the first entry in its local header only
+        let mut renamed = bytes.clone();
+        renamed[find_all(&bytes, b"aa")[0] + 1] = b'c';
+        ZipArchive::new(Cursor::new(renamed.clone())).unwrap();
+        assert!(open(&renamed).is_err());
+
+        // Make the last entry's data run into the central directory
+        let mut overlong = bytes.clone();
+        let local_header = find_all(&bytes, b"PK\x03\x04")[1];
+        let central_header = find_all(&bytes, b"PK\x01\x02")[1];
+        overlong[local_header + 18] = 200;
+        overlong[central_header + 20] = 200;
+        ZipArchive::new(Cursor::new(overlong.clone())).unwrap();
+        assert!(open(&overlong).is_err());
+    }
+
+    fn find_all(haystack: &[u8], needle: &[u8]) -> Vec<usize> {
+        (0..haystack.len())
+            .filter(|&at| haystack[at..].starts_with(needle))
+            .collect()
+    }
+
+    #[test]
+    fn legacy_decoder() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("caf\u{e9}", SimpleFileOptions::default())
+            .unwrap();
+        let mut bytes = writer.finish().unwrap().into_inner();
+        let central_header = bytes
+            .windows(4)
+            .position(|window| window == b"PK\x01\x02")
+            .unwrap();
+        bytes[central_header + 9] &= !(1 << 3);
+
+        let archive = ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+        assert_eq!(archive.name_for_index(0), Some("caf\u{251c}\u{2310}"));
+        let options =
+            ParseOptions::default().legacy_decoder(|raw| String::from_utf8_lossy(raw).into_owned());
+        let archive = ZipArchive::with_options(Cursor::new(bytes), options).unwrap();
+        assert_eq!(archive.name_for_index(0), Some("caf\u{e9}"));
+    }
+}
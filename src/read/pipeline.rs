@@ -0,0 +1,284 @@
+//! Building an entry's reader one stage at a time
+
+#[cfg(feature = "aes-crypto")]
+use crate::aes::AesReader;
+use crate::compression::CompressionMethod;
+use crate::crc32::Crc32Reader;
+use crate::read::{find_content, ZipArchive};
+use crate::result::{ZipError, ZipResult};
+#[cfg(feature = "aes-crypto")]
+use crate::types::AesVendorVersion;
+use crate::types::ZipFileData;
+use crate::zipcrypto::{ZipCryptoReader, ZipCryptoValidator};
+use std::io::{self, Read, Seek};
+
+#[cfg(feature = "bzip2")]
+use bzip2::read::BzDecoder;
+#[cfg(feature = "deflate64")]
+use deflate64::Deflate64Decoder;
+#[cfg(feature = "_deflate-any")]
+use flate2::read::DeflateDecoder;
+#[cfg(feature = "zstd")]
+use zstd::stream::read::Decoder as ZstdDecoder;
+
+#[cfg(feature = "lzma")]
+use crate::read::lzma::LzmaDecoder;
+#[cfg(feature = "xz")]
+use crate::read::xz::XzDecoder;
+
+/// A stage of an [`EntryPipeline`]: a reader over the output of the stages before it
+pub type Stage<'a> = Box<dyn Read + 'a>;
+
+/// The reader of one entry, assembled from separate stages
+///
+/// [`ZipArchive::entry_pipeline`] starts with the entry's raw data, as stored in the archive. The
+/// standard stages are then added in order, [`decrypt`](EntryPipeline::decrypt),
+/// [`decompress`](EntryPipeline::decompress), [`limit`](EntryPipeline::limit) and
+/// [`verify_crc32`](EntryPipeline::verify_crc32), and [`stage`](EntryPipeline::stage) inserts a
+/// custom stage anywhere between them, such as one that undoes an obfuscation applied on top of
+/// the ZIP format. Reading from the pipeline reads from its last stage.
+///
+/// ```
+/// # fn main() -> zip::result::ZipResult<()> {
+/// use std::io::{BufReader, Cursor, Read, Write};
+///
+/// let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+/// writer.start_file("hello.txt", zip::write::SimpleFileOptions::default())?;
+/// writer.write_all(b"Hello, world!")?;
+/// let mut archive = writer.finish_into_readable()?;
+///
+/// let mut contents = String::new();
+/// archive
+///     .entry_pipeline(0)?
+///     // Any reader can be inserted, such as one that buffers the compressed data
+///     .stage(|raw| Box::new(BufReader::new(raw)))
+///     .decompress()?
+///     .limit()
+///     .verify_crc32()
+///     .read_to_string(&mut contents)?;
+/// assert_eq!(contents, "Hello, world!");
+/// # Ok(())
+/// # }
+/// ```
+pub struct EntryPipeline<'a> {
+    data: &'a ZipFileData,
+    reader: Stage<'a>,
+    decrypted: bool,
+    decompressed: bool,
+    ae2_encrypted: bool,
+}
+
+impl<R: Read + Seek> ZipArchive<R> {
+    /// Starts an [`EntryPipeline`] over the raw data of the entry at `index`.
+    pub fn entry_pipeline(&mut self, index: usize) -> ZipResult<EntryPipeline<'_>> {
+        let (_, data) = self
+            .shared
+            .files
+            .get_index(index)
+            .ok_or(ZipError::FileNotFound)?;
+        let reader = find_content(data, &mut self.reader)?;
+        Ok(EntryPipeline {
+            data,
+            reader: Box::new(reader),
+            decrypted: !data.encrypted,
+            decompressed: false,
+            ae2_encrypted: false,
+        })
+    }
+}
+
+impl<'a> EntryPipeline<'a> {
+    /// The name of the entry
+    pub fn name(&self) -> &str {
+        &self.data.file_name
+    }
+
+    /// The compression method the entry's data uses
+    pub fn compression(&self) -> CompressionMethod {
+        self.data.compression_method
+    }
+
+    /// Whether the entry is encrypted
+    pub fn encrypted(&self) -> bool {
+        self.data.encrypted
+    }
+
+    /// Adds a custom stage, which reads from the stages added so far.
+    #[must_use]
+    pub fn stage<F: FnOnce(Stage<'a>) -> Stage<'a>>(mut self, stage: F) -> Self {
+        self.reader = stage(self.reader);
+        self
+    }
+
+    /// Adds a stage that decrypts the entry with ZipCrypto or AES, depending on how it was
+    /// encrypted, and checks the password. This does nothing if the entry isn't encrypted.
+    pub fn decrypt(mut self, password: &[u8]) -> ZipResult<Self> {
+        if self.decrypted {
+            return Ok(self);
+        }
+        let data = self.data;
+        self.reader = match data.aes_mode {
+            #[cfg(feature = "aes-crypto")]
+            Some((aes_mode, vendor_version, _)) => {
+                self.ae2_encrypted = matches!(vendor_version, AesVendorVersion::Ae2);
+                let reader = AesReader::new(self.reader, aes_mode, data.compressed_size);
+                Box::new(reader.validate(password)?)
+            }
+            #[cfg(not(feature = "aes-crypto"))]
+            Some(_) => {
+                return Err(ZipError::UnsupportedArchive(
+                    "AES encrypted files cannot be decrypted without the aes-crypto feature.",
+                ))
+            }
+            None => {
+                let validator = if data.using_data_descriptor {
+                    ZipCryptoValidator::InfoZipMsdosTime(data.last_modified_time.timepart())
+                } else {
+                    ZipCryptoValidator::PkzipCrc32(data.crc32)
+                };
+                Box::new(ZipCryptoReader::new(self.reader, password).validate(validator)?)
+            }
+        };
+        self.decrypted = true;
+        Ok(self)
+    }
+
+    /// Adds a stage that decompresses the entry. Encrypted entries must be decrypted first.
+    pub fn decompress(mut self) -> ZipResult<Self> {
+        if !self.decrypted {
+            return Err(ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED));
+        }
+        if self.decompressed {
+            return Ok(self);
+        }
+        let reader = self.reader;
+        self.reader = match self.data.compression_method {
+            CompressionMethod::Stored => reader,
+            #[cfg(feature = "_deflate-any")]
+            CompressionMethod::Deflated => Box::new(DeflateDecoder::new(reader)),
+            #[cfg(feature = "deflate64")]
+            CompressionMethod::Deflate64 => Box::new(Deflate64Decoder::new(reader)),
+            #[cfg(feature = "bzip2")]
+            CompressionMethod::Bzip2 => Box::new(BzDecoder::new(reader)),
+            #[cfg(feature = "zstd")]
+            CompressionMethod::Zstd => Box::new(ZstdDecoder::new(reader)?),
+            #[cfg(feature = "lzma")]
+            CompressionMethod::Lzma => Box::new(LzmaDecoder::new(reader)),
+            #[cfg(feature = "xz")]
+            CompressionMethod::Xz => Box::new(XzDecoder::new(reader, self.data.uncompressed_size)),
+            _ => {
+                return Err(ZipError::UnsupportedArchive(
+                    "Compression method not supported",
+                ))
+            }
+        };
+        self.decompressed = true;
+        Ok(self)
+    }
+
+    /// Adds a stage that stops reading once the entry's uncompressed size has been read.
+    #[must_use]
+    pub fn limit(mut self) -> Self {
+        self.reader = Box::new(self.reader.take(self.data.uncompressed_size));
+        self
+    }
+
+    /// Adds a stage that fails with an error at the end of the entry if its contents don't match
+    /// its CRC-32. Entries encrypted with AE-2 don't record a CRC-32, so aren't checked.
+    #[must_use]
+    pub fn verify_crc32(mut self) -> Self {
+        self.reader = Box::new(Crc32Reader::new(
+            self.reader,
+            self.data.crc32,
+            self.ae2_encrypted,
+        ));
+        self
+    }
+
+    /// Returns the last stage.
+    pub fn into_reader(self) -> Stage<'a> {
+        self.reader
+    }
+}
+
+impl Read for EntryPipeline<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::write::SimpleFileOptions;
+    use crate::{CompressionMethod, ZipWriter};
+    use std::io::{self, Cursor, Read, Write};
+
+    struct Xor<R>(R);
+
+    impl<R: Read> Read for Xor<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let count = self.0.read(buf)?;
+            buf[..count].iter_mut().for_each(|byte| *byte ^= 0x5a);
+            Ok(count)
+        }
+    }
+
+    #[test]
+    fn custom_stage() {
+        let obfuscated: Vec<u8> = b"plain text".iter().map(|byte| byte ^ 0x5a).collect();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file("obfuscated", options).unwrap();
+        writer.write_all(&obfuscated).unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+
+        let mut contents = Vec::new();
+        archive
+            .entry_pipeline(0)
+            .unwrap()
+            .decompress()
+            .unwrap()
+            .limit()
+            .verify_crc32()
+            .stage(|reader| Box::new(Xor(reader)))
+            .read_to_end(&mut contents)
+            .unwrap();
+        assert_eq!(contents, b"plain text");
+    }
+
+    #[cfg(feature = "_deflate-any")]
+    #[test]
+    fn decrypt_and_decompress() {
+        use crate::result::ZipError;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .with_deprecated_encryption(b"password");
+        writer.start_file("secret", options).unwrap();
+        writer.write_all(&[b'x'; 1000]).unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+
+        assert!(matches!(
+            archive.entry_pipeline(0).unwrap().decompress(),
+            Err(ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED))
+        ));
+        assert!(matches!(
+            archive.entry_pipeline(0).unwrap().decrypt(b"wrong"),
+            Err(ZipError::InvalidPassword)
+        ));
+        let mut contents = Vec::new();
+        archive
+            .entry_pipeline(0)
+            .unwrap()
+            .decrypt(b"password")
+            .unwrap()
+            .decompress()
+            .unwrap()
+            .limit()
+            .verify_crc32()
+            .read_to_end(&mut contents)
+            .unwrap();
+        assert_eq!(contents, [b'x'; 1000]);
+    }
+}
@@ -0,0 +1,306 @@
+//! Working out what extracting an archive would do, without writing anything
+
+use crate::read::extract::{check_symlink_target, resolve_existing};
+use crate::read::{ExtractOptions, ZipArchive};
+use crate::result::{ZipError, ZipResult};
+use crate::types::ffi;
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+use std::path::{Component, Path, PathBuf};
+
+/// What extracting an entry would do, in a [`PlannedEntry`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PlannedAction {
+    /// Create the directory, unless it exists
+    CreateDirectory,
+    /// Write a file of the given size
+    WriteFile {
+        /// The entry's uncompressed size
+        size: u64,
+    },
+    /// Create a symlink to the given target
+    CreateSymlink {
+        /// The link's target, relative to the link
+        target: PathBuf,
+    },
+    /// Leave what's already at the path, as [`crate::read::OverwritePolicy::Skip`] says
+    Skip,
+}
+
+/// One entry of an [`ExtractPlan`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlannedEntry {
+    /// The entry's index
+    pub index: usize,
+    /// Where the entry would be extracted to, after any renaming the overwrite policy asks for
+    pub path: PathBuf,
+    /// What extracting it would do
+    pub action: PlannedAction,
+}
+
+/// Something that would make extracting an archive fail or go wrong, in an [`ExtractPlan`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PlanProblem {
+    /// The entry's name is absolute or leads outside the destination, so it can't be extracted
+    UnsafePath {
+        /// The entry's index
+        index: usize,
+        /// The entry's name
+        name: String,
+    },
+    /// The symlink's target is absolute or leads outside the destination
+    UnsafeSymlinkTarget {
+        /// The entry's index
+        index: usize,
+        /// The link's target
+        target: PathBuf,
+    },
+    /// Several entries would be extracted to the same path, so the last would win, or a file
+    /// would be extracted where a directory is needed. Only identical paths count, so names that
+    /// differ only in case would still collide on file systems that ignore case.
+    Collision {
+        /// The entries' indices
+        indices: Vec<usize>,
+        /// The path they share, relative to the destination
+        path: PathBuf,
+    },
+    /// Something is already at the entry's path, and the overwrite policy is
+    /// [`crate::read::OverwritePolicy::Error`]
+    AlreadyExists {
+        /// The entry's index
+        index: usize,
+        /// Where the entry would be extracted to
+        path: PathBuf,
+    },
+}
+
+/// What [`ZipArchive::plan_extract`] found extracting the archive would do
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExtractPlan {
+    /// The entries that can be extracted, in central directory order
+    pub entries: Vec<PlannedEntry>,
+    /// What would go wrong, in central directory order except for collisions, which come last
+    pub problems: Vec<PlanProblem>,
+    /// The total size of the files that would be written, not counting the space file systems
+    /// use for directories, links and metadata
+    pub required_space: u64,
+}
+
+impl ExtractPlan {
+    /// Returns true if no problems were found.
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+impl<R: Read + Seek> ZipArchive<R> {
+    /// Works out what [`ZipArchive::extract_with_options`] would do with `options`, writing
+    /// nothing: where each entry would go, which names are unsafe or collide, and how much space
+    /// the files need.
+    ///
+    /// The destination is only looked at to see what's already there, which decides what the
+    /// overwrite policy does. The contents of symlink entries are read to check their targets,
+    /// and nothing else is decompressed.
+    pub fn plan_extract<P: AsRef<Path>>(
+        &mut self,
+        directory: P,
+        options: ExtractOptions,
+    ) -> ZipResult<ExtractPlan> {
+        let directory = directory.as_ref();
+        let mut plan = ExtractPlan::default();
+        let mut paths: HashMap<PathBuf, Vec<usize>> = HashMap::new();
+        for index in 0..self.len() {
+            let data = &self.shared.files[index];
+            let Some(relative) = data.enclosed_name() else {
+                plan.problems.push(PlanProblem::UnsafePath {
+                    index,
+                    name: data.file_name.to_string(),
+                });
+                continue;
+            };
+            let is_dir = data.file_name.ends_with(['/', '\\']);
+            let is_symlink = options.creates_symlinks()
+                && data
+                    .unix_mode()
+                    .is_some_and(|mode| mode & ffi::S_IFMT == ffi::S_IFLNK);
+            let size = data.uncompressed_size;
+            let relative = normalize(&relative);
+            let mut action = if is_dir {
+                PlannedAction::CreateDirectory
+            } else if is_symlink {
+                let mut target = String::new();
+                self.by_index(index)?.read_to_string(&mut target)?;
+                let target = PathBuf::from(target);
+                if check_symlink_target(&relative, &target).is_err() {
+                    plan.problems
+                        .push(PlanProblem::UnsafeSymlinkTarget { index, target });
+                    continue;
+                }
+                PlannedAction::CreateSymlink { target }
+            } else {
+                PlannedAction::WriteFile { size }
+            };
+            if !is_dir {
+                paths.entry(relative.clone()).or_default().push(index);
+            }
+
+            let mut path = directory.join(&relative);
+            if !is_dir {
+                match resolve_existing(path.clone(), options.on_existing()) {
+                    Ok(Some(resolved)) => path = resolved,
+                    Ok(None) => action = PlannedAction::Skip,
+                    Err(ZipError::Io(_)) => {
+                        plan.problems
+                            .push(PlanProblem::AlreadyExists { index, path });
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            if let PlannedAction::WriteFile { size } = action {
+                plan.required_space = plan.required_space.saturating_add(size);
+            }
+            plan.entries.push(PlannedEntry {
+                index,
+                path,
+                action,
+            });
+        }
+
+        // A file where a directory entry, or another file's parent, would be also collides
+        let directories: Vec<PathBuf> = self
+            .shared
+            .files
+            .values()
+            .filter_map(|data| data.enclosed_name().map(|name| (data, normalize(&name))))
+            .flat_map(|(data, name)| {
+                let is_dir = data.file_name.ends_with(['/', '\\']);
+                let parents = name
+                    .ancestors()
+                    .skip(1)
+                    .map(Path::to_path_buf)
+                    .collect::<Vec<_>>();
+                is_dir.then_some(name).into_iter().chain(parents)
+            })
+            .collect();
+        let mut collisions: Vec<_> = paths
+            .into_iter()
+            .filter(|(path, indices)| indices.len() > 1 || directories.contains(path))
+            .collect();
+        collisions.sort_by_key(|(_, indices)| indices[0]);
+        plan.problems.extend(
+            collisions
+                .into_iter()
+                .map(|(path, indices)| PlanProblem::Collision { indices, path }),
+        );
+        Ok(plan)
+    }
+}
+
+/// Resolves the `.` and `..` components of a path that doesn't leave its root.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::Normal(name) => normalized.push(name),
+            _ => {}
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PlanProblem, PlannedAction};
+    use crate::read::{ExtractOptions, OverwritePolicy};
+    use crate::test_util::TempDir;
+    use crate::write::SimpleFileOptions;
+    use crate::ZipWriter;
+    use std::fs;
+    use std::io::{Cursor, Write};
+    use std::path::PathBuf;
+
+    #[test]
+    fn plan() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        writer.add_directory("dir", options).unwrap();
+        writer.start_file("dir/file", options).unwrap();
+        writer.write_all(b"contents").unwrap();
+        writer.start_file("../escape", options).unwrap();
+        writer.add_symlink("link", "dir/file", options).unwrap();
+        writer.add_symlink("bad", "../../etc", options).unwrap();
+        writer.start_file("other/../dir/file", options).unwrap();
+        writer.write_all(b"more").unwrap();
+        writer.start_file("existing", options).unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+
+        let temp = TempDir::new("plan-extract");
+        let directory = temp.path();
+        fs::write(directory.join("existing"), "").unwrap();
+
+        let plan = archive
+            .plan_extract(directory, ExtractOptions::default().symlinks(true))
+            .unwrap();
+        let actions: Vec<_> = plan
+            .entries
+            .iter()
+            .map(|entry| (entry.index, entry.action.clone()))
+            .collect();
+        assert_eq!(
+            actions,
+            [
+                (0, PlannedAction::CreateDirectory),
+                (1, PlannedAction::WriteFile { size: 8 }),
+                (
+                    3,
+                    PlannedAction::CreateSymlink {
+                        target: PathBuf::from("dir/file")
+                    }
+                ),
+                (5, PlannedAction::WriteFile { size: 4 }),
+                (6, PlannedAction::WriteFile { size: 0 }),
+            ]
+        );
+        assert_eq!(plan.required_space, 12);
+        assert_eq!(plan.problems.len(), 3);
+        assert!(matches!(
+            plan.problems[0],
+            PlanProblem::UnsafePath { index: 2, .. }
+        ));
+        assert!(matches!(
+            plan.problems[1],
+            PlanProblem::UnsafeSymlinkTarget { index: 4, .. }
+        ));
+        assert_eq!(
+            plan.problems[2],
+            PlanProblem::Collision {
+                indices: vec![1, 5],
+                path: PathBuf::from("dir/file")
+            }
+        );
+
+        let options = ExtractOptions::default().overwrite_policy(OverwritePolicy::Error);
+        let plan = archive.plan_extract(directory, options).unwrap();
+        assert!(plan.problems.contains(&PlanProblem::AlreadyExists {
+            index: 6,
+            path: directory.join("existing")
+        }));
+        let options = ExtractOptions::default().overwrite_policy(OverwritePolicy::Skip);
+        let plan = archive.plan_extract(directory, options).unwrap();
+        assert_eq!(plan.entries.last().unwrap().action, PlannedAction::Skip);
+        let options = ExtractOptions::default().overwrite_policy(OverwritePolicy::RenameNew);
+        let plan = archive.plan_extract(directory, options).unwrap();
+        assert_eq!(
+            plan.entries.last().unwrap().path,
+            directory.join("existing (1)")
+        );
+
+        assert_eq!(fs::read_dir(directory).unwrap().count(), 1);
+    }
+}
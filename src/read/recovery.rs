@@ -0,0 +1,274 @@
+//! Opening archives whose central directory is missing or damaged
+
+use crate::read::zip_archive::Shared;
+use crate::read::{read_data_descriptor_entry, read_local_header, ZipArchive};
+use crate::result::{ZipError, ZipResult};
+use crate::spec;
+use indexmap::IndexMap;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::sync::OnceLock;
+
+/// Options for [`ZipArchive::new_with_recovery`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecoveryOptions {
+    always_scan: bool,
+    include_truncated: bool,
+}
+
+impl Default for RecoveryOptions {
+    fn default() -> Self {
+        RecoveryOptions {
+            always_scan: false,
+            include_truncated: true,
+        }
+    }
+}
+
+impl RecoveryOptions {
+    /// Set whether the local file headers are scanned even if the central directory can be read.
+    /// This finds entries that a damaged but readable central directory leaves out.
+    ///
+    /// The default is false.
+    #[must_use]
+    pub const fn always_scan(mut self, always_scan: bool) -> Self {
+        self.always_scan = always_scan;
+        self
+    }
+
+    /// Set whether entries whose data runs past the end of the file are kept. Reading them fails,
+    /// but [`crate::recover::salvage`] can recover the data that's there.
+    ///
+    /// The default is true.
+    #[must_use]
+    pub const fn include_truncated(mut self, include_truncated: bool) -> Self {
+        self.include_truncated = include_truncated;
+        self
+    }
+}
+
+impl<R: Read + Seek> ZipArchive<R> {
+    /// Read a ZIP archive like [`ZipArchive::new`], but if its end of central directory record or
+    /// central directory is missing or damaged, such as when a download was interrupted, rebuild
+    /// the directory by scanning the file for local file headers instead.
+    ///
+    /// Recovered entries only have what their local headers record: they have no comments or
+    /// external attributes, and entries whose sizes are in a data descriptor are only found if the
+    /// descriptor has its optional signature. If two entries have the same name, the later one is
+    /// kept.
+    pub fn new_with_recovery(mut reader: R, options: RecoveryOptions) -> ZipResult<ZipArchive<R>> {
+        let footer = match spec::CentralDirectoryEnd::find_and_parse(&mut reader) {
+            Ok(footer) => Some(footer),
+            Err(ZipError::Io(e)) if e.kind() != io::ErrorKind::UnexpectedEof => {
+                return Err(ZipError::Io(e))
+            }
+            Err(_) => None,
+        };
+        let shared = match &footer {
+            Some((footer, cde_start_pos)) if !options.always_scan => {
+                match Self::get_metadata(&mut reader, footer, *cde_start_pos, None, false) {
+                    Ok(shared) => Some(shared),
+                    Err(ZipError::Io(e)) if e.kind() != io::ErrorKind::UnexpectedEof => {
+                        return Err(ZipError::Io(e))
+                    }
+                    Err(_) => None,
+                }
+            }
+            _ => None,
+        };
+        let shared = match shared {
+            Some(shared) => shared,
+            None => scan_local_headers(&mut reader, &options)?,
+        };
+        let comment = footer.map_or_else(Vec::new, |(footer, _)| footer.zip_file_comment);
+        Ok(ZipArchive {
+            reader,
+            shared: shared.into(),
+            comment: comment.into(),
+            registry: None,
+            limits: None,
+            metrics: None,
+        })
+    }
+}
+
+/// Builds the directory from the local file headers found anywhere in the file.
+fn scan_local_headers<R: Read + Seek>(
+    reader: &mut R,
+    options: &RecoveryOptions,
+) -> ZipResult<Shared> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    let mut files = IndexMap::new();
+    let mut search_from = 0;
+    let mut dir_start = 0;
+    while let Some(header_start) = find_local_header(reader, search_from)? {
+        search_from = header_start + 1;
+        reader.seek(SeekFrom::Start(header_start))?;
+        let mut buffered = BufReader::new(&mut *reader);
+        // A signature that doesn't start a valid header is just part of some other data
+        let Ok(Some(mut file)) = read_local_header(&mut buffered) else {
+            continue;
+        };
+        let data_start = buffered.stream_position()?;
+        let data_end = if file.using_data_descriptor {
+            match read_data_descriptor_entry(&mut buffered, &mut file, u64::MAX) {
+                Ok(_) => Some(buffered.stream_position()?),
+                Err(ZipError::Io(e)) if e.kind() != io::ErrorKind::UnexpectedEof => {
+                    return Err(ZipError::Io(e))
+                }
+                Err(_) => {
+                    file.compressed_size = len - data_start;
+                    None
+                }
+            }
+        } else {
+            data_start
+                .checked_add(file.compressed_size)
+                .filter(|&data_end| data_end <= len)
+        };
+        match data_end {
+            Some(data_end) => {
+                search_from = data_end;
+                dir_start = data_end;
+            }
+            None if options.include_truncated => {
+                // The recorded size may itself be damaged, so look for more entries in the data
+                search_from = data_start;
+                dir_start = len;
+            }
+            None => continue,
+        }
+        file.header_start = header_start;
+        file.data_start.get_or_init(|| data_start);
+        files.insert(file.file_name.clone(), file);
+    }
+    if files.is_empty() {
+        return Err(ZipError::InvalidArchive("No local file headers found"));
+    }
+    Ok(Shared {
+        files,
+        offset: 0,
+        dir_start,
+        name_index: OnceLock::new(),
+    })
+}
+
+/// Returns the offset of the next local file header signature at or after `from`.
+fn find_local_header<R: Read + Seek>(reader: &mut R, from: u64) -> ZipResult<Option<u64>> {
+    const SIGNATURE: [u8; 4] = spec::LOCAL_FILE_HEADER_SIGNATURE.to_le_bytes();
+    let mut buf = vec![0; 1 << 16];
+    let mut start = from;
+    loop {
+        reader.seek(SeekFrom::Start(start))?;
+        let mut filled = 0;
+        while filled < buf.len() {
+            match reader.read(&mut buf[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if let Some(i) = buf[..filled].windows(4).position(|w| w == SIGNATURE) {
+            return Ok(Some(start + i as u64));
+        }
+        if filled < buf.len() {
+            return Ok(None);
+        }
+        // Overlap the blocks so that a signature split between them is still found
+        start += (filled - (SIGNATURE.len() - 1)) as u64;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RecoveryOptions;
+    use crate::write::SimpleFileOptions;
+    use crate::CompressionMethod;
+    use crate::{ZipArchive, ZipWriter};
+    use std::io::{Cursor, Read, Write};
+
+    fn archive() -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file("first", options).unwrap();
+        writer.write_all(b"first contents").unwrap();
+        // Stored data that looks like a local header mustn't be mistaken for one
+        writer.start_file("second", options).unwrap();
+        writer.write_all(b"PK\x03\x04 second contents").unwrap();
+        writer.set_comment("comment");
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn names(archive: &ZipArchive<Cursor<Vec<u8>>>) -> Vec<&str> {
+        archive.file_names().collect()
+    }
+
+    fn central_directory_start(bytes: &[u8]) -> usize {
+        bytes
+            .windows(4)
+            .position(|window| window == b"PK\x01\x02")
+            .unwrap()
+    }
+
+    #[test]
+    fn missing_central_directory() {
+        let bytes = archive();
+        let central_directory = central_directory_start(&bytes);
+        let truncated = bytes[..central_directory + 10].to_vec();
+        assert!(ZipArchive::new(Cursor::new(truncated.clone())).is_err());
+        let mut archive =
+            ZipArchive::new_with_recovery(Cursor::new(truncated), RecoveryOptions::default())
+                .unwrap();
+        assert_eq!(names(&archive), ["first", "second"]);
+        let mut contents = String::new();
+        archive
+            .by_name("second")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "PK\x03\x04 second contents");
+
+        // An intact archive is read as usual
+        let archive =
+            ZipArchive::new_with_recovery(Cursor::new(bytes), RecoveryOptions::default()).unwrap();
+        assert_eq!(archive.comment(), b"comment");
+    }
+
+    #[test]
+    fn truncated_entry() {
+        let bytes = archive();
+        let mut readable = ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+        let second_data = readable.by_index(1).unwrap().data_start() as usize;
+        let truncated = bytes[..second_data + 3].to_vec();
+
+        let mut archive = ZipArchive::new_with_recovery(
+            Cursor::new(truncated.clone()),
+            RecoveryOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(names(&archive), ["first", "second"]);
+        let salvaged = crate::recover::salvage(&mut archive, 1).unwrap();
+        assert_eq!(salvaged.data, b"PK\x03");
+
+        let options = RecoveryOptions::default().include_truncated(false);
+        let archive = ZipArchive::new_with_recovery(Cursor::new(truncated), options).unwrap();
+        assert_eq!(names(&archive), ["first"]);
+    }
+
+    #[cfg(feature = "_deflate-any")]
+    #[test]
+    fn data_descriptor() {
+        let bytes = include_bytes!("../../tests/data/data_descriptor.zip");
+        let truncated = bytes[..central_directory_start(bytes)].to_vec();
+        let mut archive =
+            ZipArchive::new_with_recovery(Cursor::new(truncated), RecoveryOptions::default())
+                .unwrap();
+        let mut contents = Vec::new();
+        archive
+            .by_index(0)
+            .unwrap()
+            .read_to_end(&mut contents)
+            .unwrap();
+        assert_eq!(contents.len(), 12);
+    }
+}
@@ -0,0 +1,264 @@
+//! Reading an entry from arbitrary positions in its decompressed contents
+
+use crate::compression::CompressionMethod;
+use crate::read::{find_data_start, ZipArchive};
+use crate::result::{ZipError, ZipResult};
+use std::io::{self, Read, Seek, SeekFrom};
+
+#[cfg(feature = "_deflate-flate2")]
+use flate2::{Decompress, FlushDecompress, Status};
+
+/// An entry of a [`ZipArchive`] that can be read from any position, returned by
+/// [`ZipArchive::by_index_seekable`].
+///
+/// Unlike [`crate::read::ZipFile`], this doesn't check the entry's CRC-32, since the contents
+/// may never be read in full.
+pub struct SeekableZipFile<'a, R> {
+    reader: &'a mut R,
+    data_start: u64,
+    size: u64,
+    position: u64,
+    decoder: Decoder,
+}
+
+enum Decoder {
+    Stored,
+    #[cfg(feature = "_deflate-flate2")]
+    Deflated(Box<Inflater>),
+}
+
+impl<R: Read + Seek> ZipArchive<R> {
+    /// Get a contained file by index, as a reader that can seek to any position in the file's
+    /// decompressed contents. This serves large entries, such as databases or media, straight out
+    /// of the archive without extracting them first.
+    ///
+    /// Seeking within a stored entry is as cheap as seeking the archive. A deflated entry is
+    /// decompressed from the start, skipping ahead, whenever a read follows a seek backward, so
+    /// reads that mostly move forward are the fast case. Other compression methods and encrypted
+    /// entries aren't supported.
+    pub fn by_index_seekable(&mut self, file_number: usize) -> ZipResult<SeekableZipFile<'_, R>> {
+        let (_, data) = self
+            .shared
+            .files
+            .get_index(file_number)
+            .ok_or(ZipError::FileNotFound)?;
+        if data.encrypted {
+            return Err(ZipError::UnsupportedArchive(
+                "Encrypted files can't be read from arbitrary positions",
+            ));
+        }
+        let decoder = match data.compression_method {
+            CompressionMethod::Stored => Decoder::Stored,
+            #[cfg(feature = "_deflate-flate2")]
+            CompressionMethod::Deflated => {
+                Decoder::Deflated(Box::new(Inflater::new(data.compressed_size)))
+            }
+            _ => {
+                return Err(ZipError::UnsupportedArchive(
+                    "Only stored and deflated files can be read from arbitrary positions",
+                ))
+            }
+        };
+        let data_start = find_data_start(data, &mut self.reader)?;
+        Ok(SeekableZipFile {
+            reader: &mut self.reader,
+            data_start,
+            size: data.uncompressed_size,
+            position: 0,
+            decoder,
+        })
+    }
+}
+
+impl<'a, R> SeekableZipFile<'a, R> {
+    /// Get the size of the file, in bytes, when uncompressed
+    pub const fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl<'a, R: Read + Seek> Read for SeekableZipFile<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.size || buf.is_empty() {
+            return Ok(0);
+        }
+        let limit = buf
+            .len()
+            .min((self.size - self.position).try_into().unwrap_or(usize::MAX));
+        let count = match &mut self.decoder {
+            Decoder::Stored => {
+                self.reader
+                    .seek(SeekFrom::Start(self.data_start + self.position))?;
+                self.reader.read(&mut buf[..limit])?
+            }
+            #[cfg(feature = "_deflate-flate2")]
+            Decoder::Deflated(inflater) => {
+                let mut source = Source {
+                    reader: &mut *self.reader,
+                    data_start: self.data_start,
+                };
+                inflater.read_at(&mut source, self.position, &mut buf[..limit])?
+            }
+        };
+        if count == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "File data ends before its recorded size",
+            ));
+        }
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl<'a, R> Seek for SeekableZipFile<'a, R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.size.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position = position.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
+
+/// The compressed data of the entry being inflated
+#[cfg(feature = "_deflate-flate2")]
+struct Source<'r, R> {
+    reader: &'r mut R,
+    data_start: u64,
+}
+
+/// Inflates an entry from its start, keeping its state between reads so that reading on from
+/// where the last read stopped doesn't start over.
+#[cfg(feature = "_deflate-flate2")]
+struct Inflater {
+    state: Decompress,
+    input: Vec<u8>,
+    input_pos: usize,
+    /// How many bytes of compressed data have been read into `input`
+    fetched: u64,
+    compressed_size: u64,
+}
+
+#[cfg(feature = "_deflate-flate2")]
+impl Inflater {
+    const INPUT_SIZE: usize = 32 * 1024;
+
+    fn new(compressed_size: u64) -> Self {
+        Inflater {
+            state: Decompress::new(false),
+            input: Vec::with_capacity(Self::INPUT_SIZE),
+            input_pos: 0,
+            fetched: 0,
+            compressed_size,
+        }
+    }
+
+    /// Reads the decompressed contents at `position` into `buf`.
+    fn read_at<R: Read + Seek>(
+        &mut self,
+        source: &mut Source<'_, R>,
+        position: u64,
+        buf: &mut [u8],
+    ) -> io::Result<usize> {
+        if position < self.state.total_out() {
+            self.state.reset(false);
+            self.input.clear();
+            self.input_pos = 0;
+            self.fetched = 0;
+        }
+        let mut skipped = [0u8; 8 * 1024];
+        while self.state.total_out() < position {
+            let skip = (position - self.state.total_out()).min(skipped.len() as u64) as usize;
+            if self.inflate(source, &mut skipped[..skip])? == 0 {
+                return Ok(0);
+            }
+        }
+        self.inflate(source, buf)
+    }
+
+    fn inflate<R: Read + Seek>(
+        &mut self,
+        source: &mut Source<'_, R>,
+        buf: &mut [u8],
+    ) -> io::Result<usize> {
+        loop {
+            if self.input_pos == self.input.len() && self.fetched < self.compressed_size {
+                let chunk = (self.compressed_size - self.fetched).min(Self::INPUT_SIZE as u64);
+                self.input.resize(chunk as usize, 0);
+                source
+                    .reader
+                    .seek(SeekFrom::Start(source.data_start + self.fetched))?;
+                source.reader.read_exact(&mut self.input)?;
+                self.input_pos = 0;
+                self.fetched += chunk;
+            }
+            let (total_in, total_out) = (self.state.total_in(), self.state.total_out());
+            let status = self
+                .state
+                .decompress(&self.input[self.input_pos..], buf, FlushDecompress::None)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            self.input_pos += (self.state.total_in() - total_in) as usize;
+            let count = (self.state.total_out() - total_out) as usize;
+            let input_exhausted =
+                self.input_pos == self.input.len() && self.fetched == self.compressed_size;
+            if count > 0 || status == Status::StreamEnd || input_exhausted {
+                return Ok(count);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::write::SimpleFileOptions;
+    use crate::{CompressionMethod, ZipWriter};
+    use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+    fn check_seeks(options: SimpleFileOptions) {
+        let data: Vec<u8> = (0..200_000u32)
+            .map(|i| ((i % 251) ^ (i / 1000)) as u8)
+            .collect();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("file", options).unwrap();
+        writer.write_all(&data).unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+
+        let mut file = archive.by_index_seekable(0).unwrap();
+        assert_eq!(file.size(), data.len() as u64);
+        for position in [150_000, 10, 100_000, 199_990] {
+            file.seek(SeekFrom::Start(position)).unwrap();
+            let mut buf = [0; 10];
+            file.read_exact(&mut buf).unwrap();
+            assert_eq!(buf, data[position as usize..][..10]);
+        }
+        let mut rest = Vec::new();
+        file.read_to_end(&mut rest).unwrap();
+        assert!(rest.is_empty());
+        file.seek(SeekFrom::End(-100_000)).unwrap();
+        file.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, data[100_000..]);
+    }
+
+    #[test]
+    fn stored() {
+        check_seeks(SimpleFileOptions::default().compression_method(CompressionMethod::Stored));
+    }
+
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn deflated() {
+        // A low level, since the default one is slow when zopfli is enabled
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .compression_level(Some(1));
+        check_seeks(options);
+    }
+}
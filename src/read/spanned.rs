@@ -0,0 +1,212 @@
+//! Reading archives split into several files, such as `.z01`, `.z02` … `.zip`
+
+use crate::read::{central_header_to_zip_file, zip_archive::Shared, ZipArchive};
+use crate::result::{ZipError, ZipResult};
+use crate::spec;
+use indexmap::IndexMap;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Joins the parts of a spanned or split archive, in order, into one stream that can be read with
+/// [`ZipArchive::new_spanned`].
+#[derive(Debug)]
+pub struct SpannedReader<R> {
+    parts: Vec<R>,
+    /// The offset each part starts at in the joined stream, followed by the total length
+    starts: Vec<u64>,
+    position: u64,
+}
+
+impl<R: Read + Seek> SpannedReader<R> {
+    /// Joins `parts`, which must be in the order of their disk numbers.
+    pub fn new(mut parts: Vec<R>) -> io::Result<Self> {
+        let mut starts = Vec::with_capacity(parts.len() + 1);
+        let mut start = 0u64;
+        for part in &mut parts {
+            starts.push(start);
+            let len = part.seek(SeekFrom::End(0))?;
+            start = start.checked_add(len).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "Parts are too large")
+            })?;
+        }
+        starts.push(start);
+        Ok(SpannedReader {
+            parts,
+            starts,
+            position: 0,
+        })
+    }
+}
+
+impl SpannedReader<File> {
+    /// Opens the parts of a split archive, given the path of its last part, whose extension is
+    /// `.zip`. The other parts are found next to it by replacing the extension with `.z01`,
+    /// `.z02` and so on.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let mut parts = Vec::new();
+        loop {
+            let part = path.with_extension(format!("z{:02}", parts.len() + 1));
+            match File::open(part) {
+                Ok(file) => parts.push(file),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => break,
+                Err(e) => return Err(e),
+            }
+        }
+        parts.push(File::open(path)?);
+        Self::new(parts)
+    }
+}
+
+impl<R> SpannedReader<R> {
+    /// Returns the offset in the joined stream where the part with the given disk number starts.
+    pub fn disk_start(&self, disk: u32) -> Option<u64> {
+        self.starts[..self.parts.len()].get(disk as usize).copied()
+    }
+
+    /// Returns the number of parts.
+    pub fn len(&self) -> usize {
+        self.parts.len()
+    }
+
+    /// Returns whether there are no parts.
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+
+    /// Unwrap and return the parts
+    pub fn into_inner(self) -> Vec<R> {
+        self.parts
+    }
+
+    fn total_len(&self) -> u64 {
+        self.starts[self.parts.len()]
+    }
+}
+
+impl<R: Read + Seek> Read for SpannedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.total_len() || buf.is_empty() {
+            return Ok(0);
+        }
+        // The first part that ends after the position, which skips any empty parts
+        let disk = self.starts[1..].partition_point(|&end| end <= self.position);
+        let remaining = self.starts[disk + 1] - self.position;
+        let part = &mut self.parts[disk];
+        part.seek(SeekFrom::Start(self.position - self.starts[disk]))?;
+        let limit = buf.len().min(remaining.try_into().unwrap_or(usize::MAX));
+        let count = part.read(&mut buf[..limit])?;
+        if count == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Part of the archive is shorter than when it was opened",
+            ));
+        }
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl<R: Read + Seek> Seek for SpannedReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.total_len().checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position = position.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
+
+impl<R: Read + Seek> ZipArchive<SpannedReader<R>> {
+    /// Read a ZIP archive that's split into several parts, such as one written by WinZip or 7-Zip
+    /// to `.z01`, `.z02` … `.zip` files, or spanned across removable disks.
+    ///
+    /// `parts` must hold every part in order, ending with the one that has the end of central
+    /// directory record. Entries and the central directory may cross from one part to the next.
+    pub fn new_spanned(parts: Vec<R>) -> ZipResult<Self> {
+        let mut reader = SpannedReader::new(parts)?;
+        let (footer, cde_start_pos) = spec::CentralDirectoryEnd::find_and_parse(&mut reader)?;
+        let zip64 = Self::get_directory_info_zip64(&mut reader, cde_start_pos)
+            .ok()
+            .and_then(|results| results.into_iter().find_map(Result::ok));
+        let dir_info = match zip64 {
+            Some(dir_info) => dir_info,
+            None => {
+                let mut dir_info = Self::get_directory_info_zip32(&footer, cde_start_pos)?;
+                dir_info.number_of_files = footer.number_of_files as usize;
+                dir_info
+            }
+        };
+        if dir_info.disk_number as usize + 1 != reader.len() {
+            return Err(ZipError::InvalidArchive(
+                "Number of parts doesn't match the spanned archive's disk count",
+            ));
+        }
+        let disk_start = |reader: &SpannedReader<R>, disk: u32| {
+            reader
+                .disk_start(disk)
+                .ok_or(ZipError::InvalidArchive("Disk number is out of range"))
+        };
+        // The footer's offsets are relative to the start of the part they refer to
+        let directory_start = disk_start(&reader, dir_info.disk_with_central_directory)?
+            .checked_add(dir_info.directory_start - dir_info.archive_offset)
+            .ok_or(ZipError::InvalidArchive(
+                "Invalid central directory size or offset",
+            ))?;
+
+        let mut files = IndexMap::new();
+        reader.seek(SeekFrom::Start(directory_start))?;
+        for _ in 0..dir_info.number_of_files {
+            let mut file = central_header_to_zip_file(&mut reader, 0, None)?;
+            file.header_start = disk_start(&reader, file.disk_number)?
+                .checked_add(file.header_start)
+                .ok_or(ZipError::InvalidArchive("Archive header is too large"))?;
+            files.insert(file.file_name.clone(), file);
+        }
+        let shared = Shared {
+            files,
+            offset: 0,
+            dir_start: directory_start,
+            name_index: OnceLock::new(),
+        };
+        Ok(ZipArchive {
+            reader,
+            shared: shared.into(),
+            comment: footer.zip_file_comment.into(),
+            registry: None,
+            limits: None,
+            metrics: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SpannedReader;
+    use std::io::{Cursor, Read, Seek, SeekFrom};
+
+    #[test]
+    fn joins_parts() {
+        let parts = [&b"abc"[..], b"", b"defg", b"h"].map(Cursor::new).to_vec();
+        let mut reader = SpannedReader::new(parts).unwrap();
+        assert_eq!(reader.disk_start(2), Some(3));
+        assert_eq!(reader.disk_start(4), None);
+        let mut joined = String::new();
+        reader.read_to_string(&mut joined).unwrap();
+        assert_eq!(joined, "abcdefgh");
+
+        reader.seek(SeekFrom::End(-3)).unwrap();
+        let mut buf = [0; 2];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"fg");
+    }
+}
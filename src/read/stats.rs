@@ -0,0 +1,136 @@
+//! Totals over an archive's entries, from its central directory
+
+use crate::compression::CompressionMethod;
+use crate::read::ZipArchive;
+
+/// How many of the largest files [`ArchiveStats::largest`] lists
+const LARGEST: usize = 10;
+
+/// Totals over the entries compressed with one method, in [`ArchiveStats::methods`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MethodStats {
+    /// The compression method
+    pub method: CompressionMethod,
+    /// The number of entries that use it
+    pub entries: usize,
+    /// Their total compressed size, in bytes
+    pub compressed_size: u64,
+    /// Their total uncompressed size, in bytes
+    pub uncompressed_size: u64,
+}
+
+/// Totals over an archive's entries, from [`ZipArchive::stats`]
+///
+/// The sizes are those the central directory records, and sums that would overflow saturate.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ArchiveStats {
+    /// The number of entries, including directories
+    pub entries: usize,
+    /// The number of directories
+    pub directories: usize,
+    /// The number of encrypted entries
+    pub encrypted: usize,
+    /// The total compressed size of the entries, in bytes
+    pub compressed_size: u64,
+    /// The total uncompressed size of the entries, in bytes
+    pub uncompressed_size: u64,
+    /// The totals for each compression method used, in order of its first entry
+    pub methods: Vec<MethodStats>,
+    /// The indices of the ten largest files by uncompressed size, largest first
+    pub largest: Vec<usize>,
+}
+
+impl ArchiveStats {
+    /// Returns the total uncompressed size divided by the total compressed size, or 1 if the
+    /// entries are empty.
+    pub fn ratio(&self) -> f64 {
+        match self.compressed_size {
+            0 if self.uncompressed_size == 0 => 1.0,
+            compressed_size => self.uncompressed_size as f64 / compressed_size as f64,
+        }
+    }
+}
+
+impl<R> ZipArchive<R> {
+    /// Totals the entries' sizes, compression methods and encryption, from the central
+    /// directory without touching the reader.
+    pub fn stats(&self) -> ArchiveStats {
+        let mut stats = ArchiveStats::default();
+        let mut files = Vec::new();
+        for (index, data) in self.shared.files.values().enumerate() {
+            stats.entries += 1;
+            if data.file_name.ends_with(['/', '\\']) {
+                stats.directories += 1;
+            } else {
+                files.push((data.uncompressed_size, index));
+            }
+            stats.encrypted += data.encrypted as usize;
+            stats.compressed_size = stats.compressed_size.saturating_add(data.compressed_size);
+            stats.uncompressed_size = stats
+                .uncompressed_size
+                .saturating_add(data.uncompressed_size);
+            let method = match stats
+                .methods
+                .iter_mut()
+                .find(|method| method.method == data.compression_method)
+            {
+                Some(method) => method,
+                None => {
+                    stats.methods.push(MethodStats {
+                        method: data.compression_method,
+                        entries: 0,
+                        compressed_size: 0,
+                        uncompressed_size: 0,
+                    });
+                    stats.methods.last_mut().unwrap()
+                }
+            };
+            method.entries += 1;
+            method.compressed_size = method.compressed_size.saturating_add(data.compressed_size);
+            method.uncompressed_size = method
+                .uncompressed_size
+                .saturating_add(data.uncompressed_size);
+        }
+        // Largest first, and in archive order among equals
+        files.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        stats.largest = files
+            .into_iter()
+            .take(LARGEST)
+            .map(|(_, index)| index)
+            .collect();
+        stats
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::write::SimpleFileOptions;
+    use crate::{CompressionMethod, ZipWriter};
+    use std::io::{Cursor, Write};
+
+    #[test]
+    fn stats() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.add_directory("dir", stored).unwrap();
+        for (name, size) in [("small", 10), ("large", 1000), ("medium", 100)] {
+            writer.start_file(name, stored).unwrap();
+            writer.write_all(&vec![b'x'; size]).unwrap();
+        }
+        let archive = writer.finish_into_readable().unwrap();
+
+        let stats = archive.stats();
+        assert_eq!(stats.entries, 4);
+        assert_eq!(stats.directories, 1);
+        assert_eq!(stats.encrypted, 0);
+        assert_eq!(stats.compressed_size, 1110);
+        assert_eq!(stats.uncompressed_size, 1110);
+        assert_eq!(stats.ratio(), 1.0);
+        assert_eq!(stats.methods.len(), 1);
+        assert_eq!(stats.methods[0].method, CompressionMethod::Stored);
+        assert_eq!(stats.methods[0].entries, 4);
+        assert_eq!(stats.largest, [2, 3, 1]);
+    }
+}
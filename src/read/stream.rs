@@ -1,37 +1,57 @@
 use crate::unstable::LittleEndianReadExt;
 use std::fs;
-use std::io::{self, Read};
+use std::io::{self, BufReader, Read};
 use std::path::{Path, PathBuf};
 
 use super::{
-    central_header_to_zip_file_inner, read_zipfile_from_stream, spec, ZipError, ZipFile,
-    ZipFileData, ZipResult,
+    central_header_to_zip_file_inner, read_data_descriptor_entry, read_local_header_from_stream,
+    spec, stream_zipfile, ZipError, ZipFile, ZipFileData, ZipResult,
 };
 
 /// Stream decoder for zip.
 #[derive(Debug)]
-pub struct ZipStreamReader<R>(R);
+pub struct ZipStreamReader<R> {
+    reader: R,
+    max_buffered_size: u64,
+}
 
 impl<R> ZipStreamReader<R> {
     /// Create a new ZipStreamReader
     pub const fn new(reader: R) -> Self {
-        Self(reader)
+        Self {
+            reader,
+            max_buffered_size: u64::MAX,
+        }
+    }
+
+    /// Set the most compressed bytes of a file with a data descriptor that
+    /// [`ZipStreamReader::visit`] buffers in memory. A file with more fails the visit with
+    /// [`ZipError::UnsupportedArchive`], which guards against streams from untrusted sources
+    /// using up memory.
+    ///
+    /// The default is no limit.
+    #[must_use]
+    pub const fn max_buffered_size(mut self, max_buffered_size: u64) -> Self {
+        self.max_buffered_size = max_buffered_size;
+        self
     }
 }
 
 impl<R: Read> ZipStreamReader<R> {
-    fn parse_central_directory(&mut self) -> ZipResult<Option<ZipStreamFileMetadata>> {
+    fn parse_central_directory(
+        reader: &mut impl Read,
+        signature_read: bool,
+    ) -> ZipResult<Option<ZipStreamFileMetadata>> {
         // Give archive_offset and central_header_start dummy value 0, since
         // they are not used in the output.
         let archive_offset = 0;
         let central_header_start = 0;
 
         // Parse central header
-        let signature = self.0.read_u32_le()?;
-        if signature != spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE {
+        if !signature_read && reader.read_u32_le()? != spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE {
             Ok(None)
         } else {
-            central_header_to_zip_file_inner(&mut self.0, archive_offset, central_header_start)
+            central_header_to_zip_file_inner(reader, archive_offset, central_header_start, None)
                 .map(ZipStreamFileMetadata)
                 .map(Some)
         }
@@ -39,13 +59,30 @@ impl<R: Read> ZipStreamReader<R> {
 
     /// Iteraate over the stream and extract all file and their
     /// metadata.
-    pub fn visit<V: ZipStreamVisitor>(mut self, visitor: &mut V) -> ZipResult<()> {
-        while let Some(mut file) = read_zipfile_from_stream(&mut self.0)? {
-            visitor.visit_file(&mut file)?;
+    ///
+    /// Files whose sizes are stored in a data descriptor after their data, as written by
+    /// streaming writers, are supported as long as the descriptor has its optional signature.
+    /// The compressed data of such files is buffered in memory before `visit_file` is called, up
+    /// to [`ZipStreamReader::max_buffered_size`].
+    pub fn visit<V: ZipStreamVisitor>(self, visitor: &mut V) -> ZipResult<()> {
+        let mut reader = BufReader::new(self.reader);
+        while let Some(mut data) = read_local_header_from_stream(&mut reader)? {
+            if data.using_data_descriptor {
+                let compressed =
+                    read_data_descriptor_entry(&mut reader, &mut data, self.max_buffered_size)?;
+                let mut compressed = io::Cursor::new(compressed);
+                visitor.visit_file(&mut stream_zipfile(data, &mut compressed)?)?;
+            } else {
+                visitor.visit_file(&mut stream_zipfile(data, &mut reader)?)?;
+            }
         }
 
-        while let Some(metadata) = self.parse_central_directory()? {
+        // The signature of the first central directory header was read while looking for
+        // another local header
+        let mut signature_read = true;
+        while let Some(metadata) = Self::parse_central_directory(&mut reader, signature_read)? {
             visitor.visit_additional_metadata(&metadata)?;
+            signature_read = false;
         }
 
         Ok(())
@@ -341,6 +378,48 @@ mod test {
         reader.visit(&mut V::default()).unwrap();
     }
 
+    #[cfg(feature = "_deflate-any")]
+    #[test]
+    fn data_descriptor() {
+        let reader = ZipStreamReader::new(io::Cursor::new(include_bytes!(
+            "../../tests/data/data_descriptor.zip"
+        )));
+
+        #[derive(Default)]
+        struct V {
+            counter: CounterVisitor,
+            contents: Vec<u8>,
+        }
+        impl ZipStreamVisitor for V {
+            fn visit_file(&mut self, file: &mut ZipFile<'_>) -> ZipResult<()> {
+                self.counter.visit_file(file)?;
+                assert_eq!(file.size(), 12);
+                file.read_to_end(&mut self.contents)?;
+                Ok(())
+            }
+            fn visit_additional_metadata(
+                &mut self,
+                metadata: &ZipStreamFileMetadata,
+            ) -> ZipResult<()> {
+                self.counter.visit_additional_metadata(metadata)
+            }
+        }
+
+        let mut visitor = V::default();
+        reader.visit(&mut visitor).unwrap();
+        assert_eq!(visitor.counter, CounterVisitor(1, 1));
+        assert_eq!(visitor.contents.len(), 12);
+
+        let bytes = include_bytes!("../../tests/data/data_descriptor.zip");
+        let reader = ZipStreamReader::new(io::Cursor::new(bytes)).max_buffered_size(14);
+        reader.visit(&mut V::default()).unwrap();
+        let reader = ZipStreamReader::new(io::Cursor::new(bytes)).max_buffered_size(13);
+        assert!(matches!(
+            reader.visit(&mut V::default()),
+            Err(ZipError::UnsupportedArchive(_))
+        ));
+    }
+
     /// test case to ensure we don't preemptively over allocate based on the
     /// declared number of files in the CDE of an invalid zip when the number of
     /// files declared is more than the alleged offset in the CDE
@@ -0,0 +1,344 @@
+//! Checking every entry of an archive, collecting all the problems found
+
+use crate::read::ZipArchive;
+use crate::spec;
+use crate::types::ZipFileData;
+use crate::unstable::LittleEndianReadExt;
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// Options for [`ZipArchive::verify`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerifyOptions {
+    contents: bool,
+    local_headers: bool,
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        VerifyOptions {
+            contents: true,
+            local_headers: true,
+        }
+    }
+}
+
+impl VerifyOptions {
+    /// Set whether each entry is decompressed, to check its CRC-32 and size. This is what takes
+    /// most of the time.
+    ///
+    /// The default is true.
+    #[must_use]
+    pub const fn contents(mut self, contents: bool) -> Self {
+        self.contents = contents;
+        self
+    }
+
+    /// Set whether each entry's local header is compared with its central directory record.
+    ///
+    /// The default is true.
+    #[must_use]
+    pub const fn local_headers(mut self, local_headers: bool) -> Self {
+        self.local_headers = local_headers;
+        self
+    }
+}
+
+/// What is wrong with an entry, in a [`VerifyProblem`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProblemKind {
+    /// A field of the local header disagrees with the central directory, or the local header is
+    /// missing
+    LocalHeaderMismatch(&'static str),
+    /// The decompressed contents don't match the recorded CRC-32
+    Crc32Mismatch {
+        /// The CRC-32 recorded in the central directory
+        recorded: u32,
+        /// The CRC-32 of the decompressed contents
+        actual: u32,
+    },
+    /// The decompressed contents aren't the recorded size
+    SizeMismatch {
+        /// The size recorded in the central directory
+        recorded: u64,
+        /// The size of the decompressed contents
+        actual: u64,
+    },
+    /// The entry couldn't be read, such as because its compressed data is corrupt or its
+    /// compression method isn't supported
+    Unreadable(String),
+}
+
+/// A problem with one entry, found by [`ZipArchive::verify`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifyProblem {
+    /// The entry's index
+    pub index: usize,
+    /// The entry's name
+    pub name: String,
+    /// What is wrong
+    pub kind: ProblemKind,
+}
+
+/// Everything [`ZipArchive::verify`] found
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// The problems, ordered by entry index
+    pub problems: Vec<VerifyProblem>,
+    /// The indices of encrypted entries, whose contents weren't checked
+    pub encrypted: Vec<usize>,
+}
+
+impl VerifyReport {
+    /// Returns true if no problems were found.
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+impl<R: Read + Seek> ZipArchive<R> {
+    /// Checks every entry of the archive, going on past entries with problems to report all of
+    /// them.
+    ///
+    /// Each entry's local header is compared with the central directory, and each entry is
+    /// decompressed to check its CRC-32 and size, as `options` choose. Encrypted entries can't be
+    /// decompressed without a password, so only their local headers are checked.
+    pub fn verify(&mut self, options: VerifyOptions) -> VerifyReport {
+        let mut report = VerifyReport::default();
+        for index in 0..self.len() {
+            let shared = self.shared.clone();
+            let (name, data) = shared.files.get_index(index).unwrap();
+            let mut problems = Vec::new();
+            if options.local_headers {
+                let result = check_local_header(data, &mut self.reader);
+                problems.extend(result.err().map(ProblemKind::LocalHeaderMismatch));
+            }
+            if options.contents && data.encrypted {
+                report.encrypted.push(index);
+            } else if options.contents {
+                problems.extend(self.check_contents(index, data).err());
+            }
+            report
+                .problems
+                .extend(problems.into_iter().map(|kind| VerifyProblem {
+                    index,
+                    name: name.to_string(),
+                    kind,
+                }));
+        }
+        report
+    }
+
+    fn check_contents(&mut self, index: usize, data: &ZipFileData) -> Result<(), ProblemKind> {
+        let unreadable = |e: &dyn std::fmt::Display| ProblemKind::Unreadable(e.to_string());
+        let mut file = self.by_index(index).map_err(|e| unreadable(&e))?;
+        let mut hasher = crc32fast::Hasher::new();
+        let mut size = 0u64;
+        let mut buf = [0; 16 * 1024];
+        let result = loop {
+            match file.read(&mut buf) {
+                Ok(0) => break Ok(()),
+                Ok(count) => {
+                    hasher.update(&buf[..count]);
+                    size += count as u64;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => break Err(e),
+            }
+        };
+        let actual = hasher.finalize();
+        match result {
+            Err(_) if actual != data.crc32 && size == data.uncompressed_size => {
+                Err(ProblemKind::Crc32Mismatch {
+                    recorded: data.crc32,
+                    actual,
+                })
+            }
+            Err(e) => Err(unreadable(&e)),
+            Ok(()) if size != data.uncompressed_size => Err(ProblemKind::SizeMismatch {
+                recorded: data.uncompressed_size,
+                actual: size,
+            }),
+            Ok(()) => Ok(()),
+        }
+    }
+}
+
+/// The fields of a local header that the central directory also records, and its extra field
+pub(crate) struct LocalHeader {
+    signature: u32,
+    flags: u16,
+    method: u16,
+    crc32: u32,
+    compressed_size: u32,
+    uncompressed_size: u32,
+    name: Vec<u8>,
+    pub(crate) extra_field: Vec<u8>,
+}
+
+fn read_local_header(
+    data: &ZipFileData,
+    reader: &mut (impl Read + Seek),
+) -> io::Result<LocalHeader> {
+    reader.seek(SeekFrom::Start(data.header_start))?;
+    let signature = reader.read_u32_le()?;
+    let _version_needed = reader.read_u16_le()?;
+    let flags = reader.read_u16_le()?;
+    let method = reader.read_u16_le()?;
+    let _last_mod_time = reader.read_u16_le()?;
+    let _last_mod_date = reader.read_u16_le()?;
+    let crc32 = reader.read_u32_le()?;
+    let compressed_size = reader.read_u32_le()?;
+    let uncompressed_size = reader.read_u32_le()?;
+    let name_length = reader.read_u16_le()?;
+    let extra_field_length = reader.read_u16_le()?;
+    let mut name = vec![0; name_length as usize];
+    reader.read_exact(&mut name)?;
+    let mut extra_field = vec![0; extra_field_length as usize];
+    reader.read_exact(&mut extra_field)?;
+    Ok(LocalHeader {
+        signature,
+        flags,
+        method,
+        crc32,
+        compressed_size,
+        uncompressed_size,
+        name,
+        extra_field,
+    })
+}
+
+/// Compares the local header of the entry described by `data` with the central directory,
+/// returning it if they agree or what they disagree on otherwise.
+pub(crate) fn check_local_header(
+    data: &ZipFileData,
+    reader: &mut (impl Read + Seek),
+) -> Result<LocalHeader, &'static str> {
+    let header = read_local_header(data, reader)
+        .map_err(|_| "Local header is beyond the end of the archive")?;
+    let LocalHeader {
+        signature,
+        flags,
+        method,
+        crc32,
+        compressed_size,
+        uncompressed_size,
+        ref name,
+        ..
+    } = header;
+    if signature != spec::LOCAL_FILE_HEADER_SIGNATURE {
+        return Err("Local header signature is missing");
+    }
+    // Entries of an archive from ZipWriter::finish_into_readable have no raw name
+    let central_name = match &*data.file_name_raw {
+        [] => data.file_name.as_bytes(),
+        raw => raw,
+    };
+    if name != central_name {
+        return Err("Local header has a different name");
+    }
+    #[allow(deprecated)]
+    let central_method = match data.aes_mode {
+        // The method recorded for AES entries was replaced with the one the AES field gives
+        Some(_) => 99,
+        None => data.compression_method.to_u16(),
+    };
+    if method != central_method {
+        return Err("Local header has a different compression method");
+    }
+    if flags & 1 != data.encrypted as u16 {
+        return Err("Local header disagrees on whether the entry is encrypted");
+    }
+    // With a data descriptor, the local header may leave these out
+    if flags & (1 << 3) == 0 {
+        let zip64 = spec::ZIP64_BYTES_THR as u32;
+        if crc32 != data.crc32 {
+            return Err("Local header has a different CRC-32");
+        }
+        if compressed_size != zip64 && compressed_size as u64 != data.compressed_size {
+            return Err("Local header has a different compressed size");
+        }
+        if uncompressed_size != zip64 && uncompressed_size as u64 != data.uncompressed_size {
+            return Err("Local header has a different uncompressed size");
+        }
+    }
+    Ok(header)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ProblemKind, VerifyOptions};
+    use crate::write::SimpleFileOptions;
+    use crate::{CompressionMethod, ZipArchive, ZipWriter};
+    use std::io::{Cursor, Write};
+
+    #[test]
+    fn finds_every_problem() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        for name in ["good", "corrupt", "renamed"] {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(b"contents").unwrap();
+        }
+        writer
+            .start_file("encrypted", options.with_deprecated_encryption(b"password"))
+            .unwrap();
+        writer.write_all(b"contents").unwrap();
+        let mut bytes = writer.finish().unwrap().into_inner();
+        let mut archive = ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+        assert!(archive.verify(VerifyOptions::default()).is_ok());
+
+        let find = |bytes: &[u8], needle: &[u8]| {
+            bytes
+                .windows(needle.len())
+                .position(|window| window == needle)
+                .unwrap()
+        };
+        let corrupt = find(&bytes, b"corruptcontents") + 7;
+        bytes[corrupt] = b'C';
+        // Only the local header's copy of the name comes first
+        let renamed = find(&bytes, b"renamed");
+        bytes[renamed] = b'R';
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let report = archive.verify(VerifyOptions::default());
+        let problems: Vec<_> = report
+            .problems
+            .iter()
+            .map(|problem| (problem.name.as_str(), &problem.kind))
+            .collect();
+        assert_eq!(problems.len(), 2);
+        assert_eq!(problems[0].0, "corrupt");
+        assert!(matches!(problems[0].1, ProblemKind::Crc32Mismatch { .. }));
+        assert_eq!(
+            problems[1],
+            (
+                "renamed",
+                &ProblemKind::LocalHeaderMismatch("Local header has a different name")
+            )
+        );
+        assert_eq!(report.encrypted, [3]);
+    }
+
+    #[cfg(feature = "conformance")]
+    #[test]
+    fn fixtures() {
+        for fixture in crate::conformance::fixtures() {
+            let report = fixture.archive().unwrap().verify(VerifyOptions::default());
+            assert!(report.is_ok(), "{}: {:?}", fixture.name, report.problems);
+        }
+    }
+
+    #[cfg(feature = "aes-crypto")]
+    #[test]
+    fn aes() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options =
+            SimpleFileOptions::default().with_aes_encryption(crate::AesMode::Aes256, "password");
+        writer.start_file("aes", options).unwrap();
+        writer.write_all(b"contents").unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+        let report = archive.verify(VerifyOptions::default());
+        assert!(report.is_ok(), "{:?}", report.problems);
+    }
+}
@@ -0,0 +1,98 @@
+use lzma_rs::error::Error as XzError;
+use lzma_rs::xz_decompress;
+use std::io::{BufReader, Cursor, Error, ErrorKind, Read, Result, Write};
+
+/// Decoder for XZ-compressed entries.
+///
+/// `lzma-rs` only exposes a one-shot XZ decoder, so the whole entry is decompressed into memory on
+/// the first read and then served from the buffer. Decompression fails as soon as the output
+/// exceeds the size the entry declares, so a crafted entry can't use more memory than that.
+#[derive(Debug)]
+pub struct XzDecoder<R> {
+    compressed_reader: Option<R>,
+    max_size: u64,
+    output: Cursor<Vec<u8>>,
+}
+
+impl<R: Read> XzDecoder<R> {
+    pub fn new(inner: R, max_size: u64) -> Self {
+        XzDecoder {
+            compressed_reader: Some(inner),
+            max_size,
+            output: Cursor::new(Vec::new()),
+        }
+    }
+
+    fn decompress(&mut self) -> Result<()> {
+        if let Some(inner) = self.compressed_reader.take() {
+            let mut output = CappedWriter {
+                data: Vec::new(),
+                max_size: self.max_size,
+            };
+            xz_decompress(&mut BufReader::new(inner), &mut output).map_err(|e| match e {
+                XzError::IoError(e) => e,
+                e => Error::new(ErrorKind::InvalidData, e),
+            })?;
+            self.output = Cursor::new(output.data);
+        }
+        Ok(())
+    }
+
+    /// Returns the compressed reader if it hasn't been consumed yet.
+    pub fn into_inner(self) -> Option<R> {
+        self.compressed_reader
+    }
+}
+
+impl<R: Read> Read for XzDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.decompress()?;
+        self.output.read(buf)
+    }
+}
+
+/// Collects decompressed data, failing once there's more than `max_size` bytes of it.
+struct CappedWriter {
+    data: Vec<u8>,
+    max_size: u64,
+}
+
+impl Write for CappedWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if self.data.len() as u64 + buf.len() as u64 > self.max_size {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "XZ data is larger than the file's uncompressed size",
+            ));
+        }
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::XzDecoder;
+    use std::io::Read;
+
+    /// "hello" compressed by `xz --check=crc32`
+    const HELLO: &[u8] = &[
+        0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00, 0x00, 0x01, 0x69, 0x22, 0xde, 0x36, 0x04, 0xc0, 0x09,
+        0x05, 0x21, 0x01, 0x16, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xbf, 0x79,
+        0x25, 0x67, 0x01, 0x00, 0x04, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x00, 0x00, 0x00, 0x00, 0x86,
+        0xa6, 0x10, 0x36, 0x00, 0x01, 0x21, 0x05, 0x47, 0x54, 0x73, 0xdc, 0x90, 0x42, 0x99, 0x0d,
+        0x01, 0x00, 0x00, 0x00, 0x00, 0x01, 0x59, 0x5a,
+    ];
+
+    #[test]
+    fn size_cap() {
+        let mut output = Vec::new();
+        XzDecoder::new(HELLO, 5).read_to_end(&mut output).unwrap();
+        assert_eq!(output, b"hello");
+        assert!(XzDecoder::new(HELLO, 4).read_to_end(&mut output).is_err());
+    }
+}
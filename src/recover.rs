@@ -0,0 +1,400 @@
+//! Salvaging data from damaged archives
+//!
+//! These functions read as much as possible of entries whose data is corrupt or truncated,
+//! instead of failing at the first invalid byte, and report which parts couldn't be recovered.
+
+use crate::compression::CompressionMethod;
+use crate::read::ZipArchive;
+use crate::result::{ZipError, ZipResult};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+
+mod inflate;
+
+/// A range of an entry's compressed data that couldn't be decoded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DamagedRange {
+    /// The damaged bytes, as offsets from the start of the entry's compressed data.
+    pub compressed: Range<u64>,
+    /// The offset in [`Salvaged::data`] where the data decoded after the damaged range begins.
+    pub output_offset: u64,
+}
+
+/// The data recovered from an entry by [`salvage`].
+#[derive(Clone, Debug)]
+pub struct Salvaged {
+    /// The decoded data. The data decoded before and after each damaged range is joined together,
+    /// because the length of the data that was lost is unknown.
+    ///
+    /// Bytes decoded just before a damaged range may be corrupt, since damage is only detected
+    /// once the data stops being valid.
+    pub data: Vec<u8>,
+    /// The parts of the compressed data that were skipped.
+    pub damaged: Vec<DamagedRange>,
+    /// Ranges of [`Salvaged::data`] that refer back to data lost in a damaged range. They're
+    /// filled with zeroes.
+    pub unknown: Vec<Range<u64>>,
+    /// Whether [`Salvaged::data`] matches the CRC-32 stored in the archive.
+    pub crc32_matches: bool,
+}
+
+impl Salvaged {
+    /// Returns true if nothing was skipped and the data matches its checksum.
+    pub fn is_complete(&self) -> bool {
+        self.damaged.is_empty() && self.unknown.is_empty() && self.crc32_matches
+    }
+}
+
+/// An entry's compressed data, as far as it could be read
+struct RawEntry {
+    method: CompressionMethod,
+    compressed_size: u64,
+    size: u64,
+    crc32: u32,
+    compressed: Vec<u8>,
+}
+
+fn read_raw<R: Read + Seek>(archive: &mut ZipArchive<R>, index: usize) -> ZipResult<RawEntry> {
+    let mut file = archive.by_index_raw(index)?;
+    if file.data.encrypted {
+        return Err(ZipError::UnsupportedArchive(
+            "Encrypted entries can't be salvaged",
+        ));
+    }
+    let method = file.compression();
+    if method != CompressionMethod::STORE && method != CompressionMethod::DEFLATE {
+        return Err(ZipError::UnsupportedArchive(
+            "Only stored and deflated entries can be salvaged",
+        ));
+    }
+    let mut compressed = Vec::new();
+    // A truncated entry is exactly what we're trying to recover from, so only fail on other
+    // errors
+    if let Err(e) = file.read_to_end(&mut compressed) {
+        if e.kind() != io::ErrorKind::UnexpectedEof {
+            return Err(e.into());
+        }
+    }
+    Ok(RawEntry {
+        method,
+        compressed_size: file.compressed_size(),
+        size: file.size(),
+        crc32: file.crc32(),
+        compressed,
+    })
+}
+
+/// Reads as much of an entry's data as possible.
+///
+/// For a deflated entry, whenever the compressed data stops being valid, decoding resumes at the
+/// next bit offset where a plausible deflate block starts. The search for that offset gives up
+/// with an error if nothing plausible is found within 256 KiB, or if the searches for the entry
+/// together take too long. Decoding stops at the entry's recorded uncompressed size. For a
+/// stored entry, a truncated end is
+/// reported as damaged. Other compression methods and encrypted entries aren't supported.
+pub fn salvage<R: Read + Seek>(archive: &mut ZipArchive<R>, index: usize) -> ZipResult<Salvaged> {
+    let raw = read_raw(archive, index)?;
+    let (data, damaged, unknown) = if raw.method == CompressionMethod::STORE {
+        let mut damaged = Vec::new();
+        let available = raw.compressed.len() as u64;
+        if available < raw.compressed_size {
+            damaged.push(DamagedRange {
+                compressed: available..raw.compressed_size,
+                output_offset: available,
+            });
+        }
+        (raw.compressed, damaged, Vec::new())
+    } else {
+        let max_output = usize::try_from(raw.size).unwrap_or(usize::MAX);
+        let mut search_budget = inflate::SEARCH_BUDGET;
+        let inflated =
+            inflate::inflate_with_resync(&raw.compressed, true, max_output, &mut search_budget)?;
+        let damaged = inflated
+            .damaged
+            .into_iter()
+            .map(|(compressed, output_offset)| DamagedRange {
+                compressed,
+                output_offset,
+            })
+            .collect();
+        (inflated.data, damaged, inflated.unknown)
+    };
+    Ok(Salvaged {
+        crc32_matches: crc32fast::hash(&data) == raw.crc32,
+        data,
+        damaged,
+        unknown,
+    })
+}
+
+/// A run of decoded data written by [`carve`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CarvedSegment {
+    /// Where the data was written in the output.
+    pub output: Range<u64>,
+    /// The offset in the entry's compressed data where decoding of this segment began.
+    pub compressed_start: u64,
+    /// Whether `output` is the data's true position. When data after a damaged range can't be
+    /// placed exactly, its position is estimated from the entry's compression ratio.
+    pub exact: bool,
+}
+
+/// The layout of the output written by [`carve`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CarveMap {
+    /// The decoded data, in output order.
+    pub segments: Vec<CarvedSegment>,
+    /// Ranges of the output whose contents are unknown. They were either not written, or are
+    /// filled with zeroes in place of data that referred back to a damaged range.
+    pub holes: Vec<Range<u64>>,
+}
+
+/// Extracts the decodable parts of an entry into `output`, leaving holes where data was lost.
+///
+/// `damaged` lists ranges of the entry's compressed data, as offsets from its start, that are
+/// already known to be bad (such as unreadable sectors); they're skipped without being decoded.
+/// Damage found while decoding is skipped the same way as in [`salvage`].
+///
+/// Each decoded segment is written at its position in the original file, so that the output is
+/// as long as the entry's uncompressed size and unwritten regions can stay sparse. Since the
+/// amount of data lost in a damaged range of a deflated entry is unknown, segments after it are
+/// placed at an estimated position, except that a segment that runs to the end of the entry is
+/// placed so that it ends at the entry's recorded size.
+pub fn carve<R: Read + Seek, W: Write + Seek>(
+    archive: &mut ZipArchive<R>,
+    index: usize,
+    damaged: &[Range<u64>],
+    output: &mut W,
+) -> ZipResult<CarveMap> {
+    let raw = read_raw(archive, index)?;
+    let available = raw.compressed.len() as u64;
+    let mut damaged: Vec<Range<u64>> = damaged
+        .iter()
+        .map(|range| range.start.min(available)..range.end.min(available))
+        .filter(|range| !range.is_empty())
+        .collect();
+    damaged.sort_by_key(|range| range.start);
+
+    // The readable parts of the compressed data, between the known damaged ranges
+    let mut readable = Vec::new();
+    let mut start = 0;
+    for range in damaged {
+        if range.start > start {
+            readable.push(start..range.start);
+        }
+        start = start.max(range.end);
+    }
+    if start < available {
+        readable.push(start..available);
+    }
+
+    // Decoded pieces, with the compressed offset they start at, and unknown ranges relative to
+    // their own data
+    let mut pieces: Vec<(u64, Vec<u8>, Vec<Range<u64>>)> = Vec::new();
+    // The searches in all the pieces share one budget, and together decode no more than the
+    // entry's recorded size
+    let mut search_budget = inflate::SEARCH_BUDGET;
+    let mut max_output = usize::try_from(raw.size).unwrap_or(usize::MAX);
+    for range in readable {
+        let input = &raw.compressed[range.start as usize..range.end as usize];
+        if raw.method == CompressionMethod::STORE {
+            pieces.push((range.start, input.to_vec(), Vec::new()));
+            continue;
+        }
+        let inflated =
+            inflate::inflate_with_resync(input, range.start == 0, max_output, &mut search_budget)?;
+        max_output -= inflated.data.len();
+        let mut bounds = vec![(0, 0)];
+        bounds.extend(
+            inflated
+                .damaged
+                .iter()
+                .map(|(compressed, output_offset)| (compressed.end, *output_offset)),
+        );
+        for (i, &(compressed_start, data_start)) in bounds.iter().enumerate() {
+            let data_end = bounds
+                .get(i + 1)
+                .map_or(inflated.data.len() as u64, |&(_, end)| end);
+            if data_start == data_end {
+                continue;
+            }
+            let unknown = inflated
+                .unknown
+                .iter()
+                .filter(|unknown| unknown.start < data_end && unknown.end > data_start)
+                .map(|unknown| {
+                    unknown.start.max(data_start) - data_start
+                        ..unknown.end.min(data_end) - data_start
+                })
+                .collect();
+            pieces.push((
+                range.start + compressed_start,
+                inflated.data[data_start as usize..data_end as usize].to_vec(),
+                unknown,
+            ));
+        }
+    }
+
+    let ratio = if raw.compressed_size == 0 {
+        1.0
+    } else {
+        raw.size as f64 / raw.compressed_size as f64
+    };
+    let ends_intact = available == raw.compressed_size;
+    let piece_count = pieces.len();
+    let mut map = CarveMap::default();
+    let mut written_end = 0;
+    for (i, (compressed_start, data, unknown)) in pieces.into_iter().enumerate() {
+        let len = data.len() as u64;
+        let (offset, exact) = if raw.method == CompressionMethod::STORE || compressed_start == 0 {
+            (compressed_start, true)
+        } else if i == piece_count - 1 && ends_intact && raw.size >= written_end + len {
+            (raw.size - len, true)
+        } else {
+            let estimate = (compressed_start as f64 * ratio) as u64;
+            (
+                estimate.min(raw.size.saturating_sub(len)).max(written_end),
+                false,
+            )
+        };
+        output.seek(SeekFrom::Start(offset))?;
+        output.write_all(&data)?;
+        map.holes.extend(
+            unknown
+                .into_iter()
+                .map(|range| offset + range.start..offset + range.end),
+        );
+        if offset > written_end {
+            map.holes.push(written_end..offset);
+        }
+        written_end = offset + len;
+        map.segments.push(CarvedSegment {
+            output: offset..written_end,
+            compressed_start,
+            exact,
+        });
+    }
+    if written_end < raw.size {
+        map.holes.push(written_end..raw.size);
+        // Extend the output to its full length, without filling the hole
+        output.seek(SeekFrom::Start(raw.size - 1))?;
+        output.write_all(&[0])?;
+    }
+    map.holes.sort_by_key(|range| range.start);
+    Ok(map)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{carve, salvage};
+    use crate::write::SimpleFileOptions;
+    use crate::{CompressionMethod, ZipArchive, ZipWriter};
+    use std::io::{Cursor, Write};
+    use std::slice;
+
+    #[test]
+    fn salvage_stored() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file("a", options).unwrap();
+        writer.write_all(b"0123456789").unwrap();
+        let mut bytes = writer.finish().unwrap().into_inner();
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+        let salvaged = salvage(&mut archive, 0).unwrap();
+        assert!(salvaged.is_complete());
+        assert_eq!(salvaged.data, b"0123456789");
+
+        let data_start = bytes.windows(10).position(|w| w == b"0123456789").unwrap();
+        bytes[data_start + 3] = b'x';
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let salvaged = salvage(&mut archive, 0).unwrap();
+        assert!(!salvaged.is_complete());
+        assert_eq!(salvaged.data, b"012x456789");
+    }
+
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn salvage_deflated() {
+        let text: Vec<u8> = (0..20_000u32)
+            .map(|i| b"the quick brown fox jumps over the lazy dog "[(i * 7 % 44) as usize])
+            .collect();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .compression_level(Some(6));
+        writer.start_file("a", options).unwrap();
+        writer.write_all(&text).unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+        let salvaged = salvage(&mut archive, 0).unwrap();
+        assert!(salvaged.is_complete());
+        assert_eq!(salvaged.data, text);
+
+        let data_start = archive.by_index(0).unwrap().data_start() as usize;
+        let mut bytes = archive.into_inner().into_inner();
+        bytes[data_start + 20] ^= 0xff;
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let salvaged = salvage(&mut archive, 0).unwrap();
+        assert!(!salvaged.is_complete());
+    }
+
+    #[test]
+    fn carve_stored() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file("a", options).unwrap();
+        writer.write_all(b"0123456789").unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+
+        let mut output = Cursor::new(Vec::new());
+        let map = carve(&mut archive, 0, &[2..4, 7..8], &mut output).unwrap();
+        assert_eq!(map.holes, vec![2..4, 7..8]);
+        assert_eq!(map.segments.len(), 3);
+        assert!(map.segments.iter().all(|segment| segment.exact));
+        assert_eq!(output.into_inner(), b"01\x00\x00456\x0089");
+
+        let mut output = Cursor::new(Vec::new());
+        let map = carve(&mut archive, 0, slice::from_ref(&(8..20)), &mut output).unwrap();
+        assert_eq!(map.holes, vec![8..10]);
+        assert_eq!(output.into_inner(), b"01234567\0\0");
+    }
+
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn carve_deflated() {
+        let text: Vec<u8> = (0..20_000u32)
+            .map(|i| b"the quick brown fox jumps over the lazy dog "[(i * 7 % 44) as usize])
+            .collect();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .compression_level(Some(6));
+        writer.start_file("a", options).unwrap();
+        writer.write_all(&text).unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+
+        let mut output = Cursor::new(Vec::new());
+        let map = carve(&mut archive, 0, &[], &mut output).unwrap();
+        assert!(map.holes.is_empty());
+        assert_eq!(map.segments.len(), 1);
+        assert_eq!(output.into_inner(), text);
+
+        let compressed_size = archive.by_index_raw(0).unwrap().compressed_size();
+        let mut output = Cursor::new(Vec::new());
+        let map = carve(
+            &mut archive,
+            0,
+            slice::from_ref(&(compressed_size / 2..compressed_size)),
+            &mut output,
+        )
+        .unwrap();
+        assert!(map.segments[0].exact);
+        assert_eq!(map.segments[0].output.start, 0);
+        assert!(!map.holes.is_empty());
+        let output = output.into_inner();
+        assert_eq!(output.len(), text.len());
+        let intact = map.holes[0].start as usize;
+        assert!(intact > 0);
+        assert_eq!(output[..intact], text[..intact]);
+    }
+}
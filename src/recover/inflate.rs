@@ -0,0 +1,579 @@
+//! A deflate decoder that can resynchronize after damaged data.
+//!
+//! This trades speed for control: it decodes one bit at a time, so that it can restart at any bit
+//! offset and treat back-references into data that was lost as unknown bytes instead of failing.
+
+use crate::result::{ZipError, ZipResult};
+use std::ops::Range;
+
+/// Marks data that isn't a valid deflate stream. The position is tracked by [`Bits`].
+struct Invalid;
+
+const MAX_BITS: usize = 15;
+
+/// How far past damage a block boundary is searched for, in bytes of compressed data
+const MAX_SEARCH: u64 = 256 * 1024;
+
+/// How much a run of blocks at a candidate boundary is decoded before it's accepted for having
+/// decoded that much, in bytes
+const MAX_ATTEMPT_OUTPUT: usize = 256 * 1024;
+
+/// How much compressed data, in bits, the candidate runs tried while searching for block
+/// boundaries may read in total, which bounds the time recovering one entry takes
+pub(crate) const SEARCH_BUDGET: u64 = 64 * 1024 * 1024 * 8;
+
+/// Reads bits least-significant first, as deflate packs them.
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: u64,
+}
+
+impl Bits<'_> {
+    fn bit(&mut self) -> Result<u32, Invalid> {
+        let byte = *self.data.get((self.pos / 8) as usize).ok_or(Invalid)?;
+        let bit = (byte >> (self.pos % 8)) & 1;
+        self.pos += 1;
+        Ok(bit as u32)
+    }
+
+    fn bits(&mut self, count: u32) -> Result<u32, Invalid> {
+        let mut value = 0;
+        for i in 0..count {
+            value |= self.bit()? << i;
+        }
+        Ok(value)
+    }
+
+    fn align_to_byte(&mut self) {
+        self.pos = (self.pos + 7) / 8 * 8;
+    }
+}
+
+/// A canonical Huffman code, stored as the number of codes of each length and the symbols in
+/// code order.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+    /// Whether every code of up to `MAX_BITS` bits decodes to a symbol
+    complete: bool,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, Invalid> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        let used = lengths.len() as u16 - counts[0];
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left = left * 2 - count as i32;
+            if left < 0 {
+                // Over-subscribed
+                return Err(Invalid);
+            }
+        }
+        let mut offsets = [0u16; MAX_BITS + 1];
+        for len in 1..MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; used as usize];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Ok(Huffman {
+            counts,
+            symbols,
+            complete: left == 0,
+        })
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, Invalid> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for len in 1..=MAX_BITS {
+            code |= bits.bit()? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(Invalid)
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Decoded data, with a flag for every byte that was copied from history that was lost.
+struct Output {
+    data: Vec<u8>,
+    unknown: Vec<bool>,
+    /// How much data may be decoded before decoding stops
+    limit: usize,
+}
+
+impl Output {
+    fn new(limit: usize) -> Self {
+        Output {
+            data: Vec::new(),
+            unknown: Vec::new(),
+            limit,
+        }
+    }
+
+    fn full(&self) -> bool {
+        self.data.len() >= self.limit
+    }
+
+    fn push(&mut self, byte: u8) {
+        self.data.push(byte);
+        self.unknown.push(false);
+    }
+
+    fn copy(&mut self, distance: usize, length: usize) {
+        for _ in 0..length {
+            let pos = self.data.len();
+            if distance > pos {
+                // The referenced byte was before the point where decoding resumed
+                self.data.push(0);
+                self.unknown.push(true);
+            } else {
+                let (byte, unknown) = (self.data[pos - distance], self.unknown[pos - distance]);
+                self.data.push(byte);
+                self.unknown.push(unknown);
+            }
+        }
+    }
+}
+
+/// The kinds of block, which differ in how strongly a successful decode suggests real data.
+#[derive(PartialEq)]
+enum BlockKind {
+    /// Stored blocks are checked by their length's complement, and dynamic blocks by the validity
+    /// of their code tables.
+    Checked,
+    /// Almost any bits decode as the start of a fixed-code block.
+    Fixed,
+}
+
+fn decode_codes(
+    bits: &mut Bits,
+    out: &mut Output,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), Invalid> {
+    loop {
+        if out.full() {
+            return Err(Invalid);
+        }
+        let symbol = literals.decode(bits)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            257..=285 => {
+                let index = symbol - 257;
+                let length =
+                    LENGTH_BASE[index] as usize + bits.bits(LENGTH_EXTRA[index] as u32)? as usize;
+                let dist_symbol = distances.decode(bits)? as usize;
+                if dist_symbol >= DIST_BASE.len() {
+                    return Err(Invalid);
+                }
+                let distance = DIST_BASE[dist_symbol] as usize
+                    + bits.bits(DIST_EXTRA[dist_symbol] as u32)? as usize;
+                out.copy(distance, length);
+            }
+            _ => return Err(Invalid),
+        }
+    }
+}
+
+fn fixed_tables() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    let literals = Huffman::new(&lengths).unwrap_or_else(|_| unreachable!());
+    let distances = Huffman::new(&[5; 30]).unwrap_or_else(|_| unreachable!());
+    (literals, distances)
+}
+
+fn dynamic_tables(bits: &mut Bits) -> Result<(Huffman, Huffman), Invalid> {
+    let literal_count = bits.bits(5)? as usize + 257;
+    let distance_count = bits.bits(5)? as usize + 1;
+    let code_length_count = bits.bits(4)? as usize + 4;
+    if literal_count > 286 || distance_count > 30 {
+        return Err(Invalid);
+    }
+    let mut code_lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[index] = bits.bits(3)? as u8;
+    }
+    let code_length_code = checked_code(&code_lengths)?;
+
+    let mut lengths = vec![0u8; literal_count + distance_count];
+    let mut index = 0;
+    while index < lengths.len() {
+        let symbol = code_length_code.decode(bits)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths[..index].last().ok_or(Invalid)?;
+                (previous, 3 + bits.bits(2)? as usize)
+            }
+            17 => (0, 3 + bits.bits(3)? as usize),
+            _ => (0, 11 + bits.bits(7)? as usize),
+        };
+        let end = index + repeat;
+        lengths.get_mut(index..end).ok_or(Invalid)?.fill(value);
+        index = end;
+    }
+    if lengths[256] == 0 {
+        // No end-of-block code
+        return Err(Invalid);
+    }
+    let literals = checked_code(&lengths[..literal_count])?;
+    let distances = checked_code(&lengths[literal_count..])?;
+    Ok((literals, distances))
+}
+
+/// Builds a code sent in a dynamic block. Encoders only send an incomplete code when it has a
+/// single symbol, and rejecting the others makes it much less likely that random data passes for
+/// a valid block.
+fn checked_code(lengths: &[u8]) -> Result<Huffman, Invalid> {
+    let code = Huffman::new(lengths)?;
+    if code.complete || code.symbols.len() <= 1 {
+        Ok(code)
+    } else {
+        Err(Invalid)
+    }
+}
+
+/// Decodes one block, returning whether it was the final one.
+fn decode_block(bits: &mut Bits, out: &mut Output) -> Result<(bool, BlockKind), Invalid> {
+    let last = bits.bit()? == 1;
+    let kind = match bits.bits(2)? {
+        0 => {
+            bits.align_to_byte();
+            let len = bits.bits(16)?;
+            let complement = bits.bits(16)?;
+            if len != !complement & 0xffff {
+                return Err(Invalid);
+            }
+            for _ in 0..len {
+                if out.full() {
+                    return Err(Invalid);
+                }
+                out.push(bits.bits(8)? as u8);
+            }
+            BlockKind::Checked
+        }
+        1 => {
+            let (literals, distances) = fixed_tables();
+            decode_codes(bits, out, &literals, &distances)?;
+            BlockKind::Fixed
+        }
+        2 => {
+            let (literals, distances) = dynamic_tables(bits)?;
+            decode_codes(bits, out, &literals, &distances)?;
+            BlockKind::Checked
+        }
+        _ => return Err(Invalid),
+    };
+    Ok((last, kind))
+}
+
+/// How a run of blocks ended.
+enum RunEnd {
+    /// The final block ended at this bit position.
+    Final(u64),
+    /// Decoding failed at this bit position.
+    Failed(u64),
+    /// The output reached its limit.
+    Limited,
+}
+
+struct Run {
+    end: RunEnd,
+    /// The bit position decoding stopped at
+    read_to: u64,
+    blocks: usize,
+    checked_blocks: usize,
+}
+
+/// Decodes blocks starting at bit `start` until the final block or an error.
+fn decode_run(input: &[u8], start: u64, out: &mut Output) -> Run {
+    let mut bits = Bits {
+        data: input,
+        pos: start,
+    };
+    let mut blocks = 0;
+    let mut checked_blocks = 0;
+    loop {
+        match decode_block(&mut bits, out) {
+            Ok((last, kind)) => {
+                blocks += 1;
+                if kind == BlockKind::Checked {
+                    checked_blocks += 1;
+                }
+                if last {
+                    return Run {
+                        end: RunEnd::Final(bits.pos),
+                        read_to: bits.pos,
+                        blocks,
+                        checked_blocks,
+                    };
+                }
+            }
+            Err(Invalid) => {
+                return Run {
+                    end: match out.full() {
+                        true => RunEnd::Limited,
+                        false => RunEnd::Failed(bits.pos),
+                    },
+                    read_to: bits.pos,
+                    blocks,
+                    checked_blocks,
+                }
+            }
+        }
+    }
+}
+
+/// The result of decoding a damaged deflate stream.
+pub(crate) struct Inflated {
+    pub(crate) data: Vec<u8>,
+    /// Ranges of compressed bytes that were skipped, and the position in `data` where the output
+    /// resumed after each of them.
+    pub(crate) damaged: Vec<(Range<u64>, u64)>,
+    /// Ranges of `data` that were copied from lost history and so are unknown.
+    pub(crate) unknown: Vec<Range<u64>>,
+}
+
+/// Decodes `input`, skipping to the next plausible block boundary whenever the data is invalid.
+///
+/// If `at_stream_start` is true, the first run of blocks is accepted as is; otherwise `input` is
+/// assumed to begin mid-stream and is searched for a block boundary first. After damage, a run
+/// starting at a candidate bit offset is only accepted if it decodes to the end of the stream,
+/// decodes at least two blocks of which one had checkable structure, or decodes
+/// [`MAX_ATTEMPT_OUTPUT`] bytes without error.
+///
+/// Decoding stops once `max_output` bytes have been decoded. Fails if no run is accepted within
+/// [`MAX_SEARCH`] bytes of where the data became invalid, or if the candidate runs tried read
+/// more than `search_budget` bits in total; what they read is subtracted from it, so one budget
+/// can be shared by several calls.
+pub(crate) fn inflate_with_resync(
+    input: &[u8],
+    at_stream_start: bool,
+    max_output: usize,
+    search_budget: &mut u64,
+) -> ZipResult<Inflated> {
+    let total_bits = input.len() as u64 * 8;
+    let mut out = Output::new(max_output);
+    let mut damaged = Vec::new();
+    // The bit offset where the data became invalid, and the first bit offset to try resuming at
+    let mut failure = if at_stream_start {
+        match decode_run(input, 0, &mut out).end {
+            RunEnd::Failed(failed_at) => Some((failed_at, failed_at + 1)),
+            RunEnd::Final(_) | RunEnd::Limited => None,
+        }
+    } else {
+        Some((0, 0))
+    };
+    while let Some((failed_at, search_from)) = failure {
+        if out.full() {
+            break;
+        }
+        let search_end = total_bits.min(search_from.saturating_add(MAX_SEARCH * 8));
+        let mut resumed = None;
+        for candidate in search_from..search_end {
+            let room = max_output - out.data.len();
+            let mut scratch = Output::new(MAX_ATTEMPT_OUTPUT.min(room));
+            let candidate_run = decode_run(input, candidate, &mut scratch);
+            *search_budget = search_budget
+                .checked_sub(candidate_run.read_to - candidate)
+                .ok_or(ZipError::InvalidArchive(
+                    "Resynchronizing damaged deflate data took more work than the limit allows",
+                ))?;
+            let accepted = match candidate_run.end {
+                RunEnd::Final(end) => candidate_run.blocks > 0 && total_bits - end < 8,
+                RunEnd::Failed(_) => candidate_run.blocks >= 2 && candidate_run.checked_blocks > 0,
+                RunEnd::Limited => true,
+            };
+            if accepted {
+                resumed = Some((candidate, candidate_run, scratch));
+                break;
+            }
+        }
+        let Some((candidate, mut candidate_run, mut scratch)) = resumed else {
+            if search_end < total_bits {
+                return Err(ZipError::InvalidArchive(
+                    "No deflate block boundary found within the search limit",
+                ));
+            }
+            damaged.push((failed_at / 8..input.len() as u64, out.data.len() as u64));
+            break;
+        };
+        if let RunEnd::Limited = candidate_run.end {
+            // The run was only decoded far enough to accept it
+            scratch = Output::new(max_output - out.data.len());
+            candidate_run = decode_run(input, candidate, &mut scratch);
+        }
+        damaged.push((failed_at / 8..(candidate + 7) / 8, out.data.len() as u64));
+        out.data.extend_from_slice(&scratch.data);
+        out.unknown.extend_from_slice(&scratch.unknown);
+        failure = match candidate_run.end {
+            RunEnd::Failed(failed_at) => Some((failed_at, failed_at + 1)),
+            RunEnd::Final(_) | RunEnd::Limited => None,
+        };
+    }
+
+    let mut unknown: Vec<Range<u64>> = Vec::new();
+    for (pos, _) in out.unknown.iter().enumerate().filter(|(_, &u)| u) {
+        let pos = pos as u64;
+        match unknown.last_mut() {
+            Some(range) if range.end == pos => range.end += 1,
+            _ => unknown.push(pos..pos + 1),
+        }
+    }
+    Ok(Inflated {
+        data: out.data,
+        damaged,
+        unknown,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{inflate_with_resync, Inflated, SEARCH_BUDGET};
+    use crate::result::ZipResult;
+
+    fn inflate(input: &[u8], at_stream_start: bool) -> ZipResult<Inflated> {
+        inflate_with_resync(
+            input,
+            at_stream_start,
+            usize::MAX,
+            &mut SEARCH_BUDGET.clone(),
+        )
+    }
+
+    /// "Hello, Hello, Hello!" as one fixed-code block
+    const HELLO: [u8; 12] = [
+        0xf3, 0x48, 0xcd, 0xc9, 0xc9, 0xd7, 0x51, 0xf0, 0x40, 0xa2, 0x14, 0x01,
+    ];
+
+    #[test]
+    fn undamaged() {
+        let inflated = inflate(&HELLO, true).unwrap();
+        assert_eq!(inflated.data, b"Hello, Hello, Hello!");
+        assert!(inflated.damaged.is_empty());
+        assert!(inflated.unknown.is_empty());
+    }
+
+    #[test]
+    fn truncated() {
+        let inflated = inflate(&HELLO[..6], true).unwrap();
+        assert!(b"Hello, Hello, Hello!".starts_with(&inflated.data));
+        assert_eq!(inflated.damaged.len(), 1);
+        assert_eq!(inflated.damaged[0].0.end, 6);
+    }
+
+    #[test]
+    fn limits() {
+        // Stored blocks, more than one attempt is decoded for, found by searching from the start
+        let mut input = Vec::new();
+        for last in [0, 0, 0, 0, 1] {
+            input.push(last);
+            input.extend_from_slice(&u16::MAX.to_le_bytes());
+            input.extend_from_slice(&0u16.to_le_bytes());
+            input.extend_from_slice(&[7; u16::MAX as usize]);
+        }
+        let inflated = inflate(&input, false).unwrap();
+        assert_eq!(inflated.data, [7; 5 * u16::MAX as usize]);
+
+        // Invalid block types everywhere, so no boundary is ever found
+        let inflated = inflate(&[0xff; 1000], true).unwrap();
+        assert_eq!(inflated.damaged.len(), 1);
+        assert!(inflate(&vec![0xff; 2 * super::MAX_SEARCH as usize], true).is_err());
+
+        // The output is capped, and so is the work all the searches may do
+        let inflated = inflate_with_resync(&HELLO, true, 5, &mut SEARCH_BUDGET.clone()).unwrap();
+        assert_eq!(inflated.data, b"Hello");
+        let mut budget = 30_000;
+        assert!(inflate_with_resync(&[0xff; 1000], true, usize::MAX, &mut budget).is_ok());
+        assert!(inflate_with_resync(&[0xff; 1000], true, usize::MAX, &mut budget).is_err());
+    }
+
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn resynchronizes_after_damage() {
+        use flate2::{Compress, Compression, FlushCompress};
+
+        // Full flushes end each chunk on a byte boundary and reset the history, as in an archive
+        // written with resynchronization points
+        let mut state = 1u32;
+        let chunks: Vec<Vec<u8>> = (0..8)
+            .map(|_| {
+                (0..4000)
+                    .map(|_| {
+                        state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                        b"abcdefghijklmnopqrstuvwxyz"[(state >> 16) as usize % 26]
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut compress = Compress::new(Compression::default(), false);
+        let mut compressed = Vec::with_capacity(1 << 16);
+        let mut chunk_ends = Vec::new();
+        for (i, chunk) in chunks.iter().enumerate() {
+            let flush = if i == chunks.len() - 1 {
+                FlushCompress::Finish
+            } else {
+                FlushCompress::Full
+            };
+            compress
+                .compress_vec(chunk, &mut compressed, flush)
+                .unwrap();
+            chunk_ends.push(compressed.len());
+        }
+
+        let damage = chunk_ends[2] + 10..chunk_ends[2] + 40;
+        for byte in &mut compressed[damage.clone()] {
+            *byte ^= 0x5a;
+        }
+        let inflated = inflate(&compressed, true).unwrap();
+        assert_eq!(inflated.damaged.len(), 1);
+        let (range, resumed_at) = inflated.damaged[0].clone();
+        // Damage is only noticed once the data stops decoding, but it must be skipped no later
+        // than the next flush point
+        assert!(range.start >= damage.start as u64);
+        assert!(range.end <= chunk_ends[3] as u64 + 1);
+        assert!(inflated.data.starts_with(&chunks[..3].concat()));
+        assert!(inflated.data.ends_with(&chunks[4..].concat()));
+        assert!(resumed_at as usize <= inflated.data.len() - chunks[4..].concat().len());
+    }
+}
@@ -14,11 +14,17 @@ use std::num::TryFromIntError;
 pub type ZipResult<T> = Result<T, ZipError>;
 
 /// Error type for Zip
+///
+/// Reading a [`crate::read::ZipFile`] can only fail with an [`io::Error`], so errors this crate
+/// detects while reading are wrapped in one, with an [`io::ErrorKind`] to match. Converting that
+/// back with `ZipError::from`, or the `?` operator, unwraps the original error rather than
+/// returning [`ZipError::Io`].
 #[derive(Debug, Display, Error)]
+#[ignore_extra_doc_attributes]
 #[non_exhaustive]
 pub enum ZipError {
     /// i/o error: {0}
-    Io(#[from] io::Error),
+    Io(#[source] io::Error),
 
     /// invalid Zip archive: {0}
     InvalidArchive(&'static str),
@@ -31,6 +37,18 @@ pub enum ZipError {
 
     /// The password provided is incorrect
     InvalidPassword,
+
+    /// Invalid checksum: expected {expected:#010x}, found {actual:#010x}
+    ///
+    /// The file's contents don't match the CRC-32 the archive records for them, because the
+    /// archive is corrupt or, for a file encrypted with ZipCrypto, the password is wrong. The
+    /// ZipCrypto password check only catches 255 out of 256 wrong passwords.
+    ChecksumMismatch {
+        /// The checksum the archive records
+        expected: u32,
+        /// The checksum of the contents read
+        actual: u32,
+    },
 }
 
 impl ZipError {
@@ -56,12 +74,24 @@ impl From<ZipError> for io::Error {
             ZipError::UnsupportedArchive(_) => io::ErrorKind::Unsupported,
             ZipError::FileNotFound => io::ErrorKind::NotFound,
             ZipError::InvalidPassword => io::ErrorKind::InvalidInput,
+            ZipError::ChecksumMismatch { .. } => io::ErrorKind::InvalidData,
         };
 
         io::Error::new(kind, err)
     }
 }
 
+/// Wraps `err` in [`ZipError::Io`], unless it only wraps a [`ZipError`] itself, in which case that
+/// error is returned. Up to 1.2.1, the wrapping error was always returned as [`ZipError::Io`].
+impl From<io::Error> for ZipError {
+    fn from(err: io::Error) -> ZipError {
+        if err.get_ref().is_some_and(|inner| inner.is::<ZipError>()) {
+            return *err.into_inner().unwrap().downcast().unwrap();
+        }
+        ZipError::Io(err)
+    }
+}
+
 /// Error type for time parsing
 #[derive(Debug)]
 pub struct DateTimeRangeError;
@@ -83,3 +113,24 @@ impl fmt::Display for DateTimeRangeError {
 }
 
 impl Error for DateTimeRangeError {}
+
+#[cfg(test)]
+mod test {
+    use super::ZipError;
+    use std::io;
+
+    #[test]
+    fn io_error_conversion() {
+        let err = ZipError::from(io::Error::new(io::ErrorKind::TimedOut, "slow"));
+        match err {
+            ZipError::Io(err) => {
+                assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+                assert_eq!(err.to_string(), "slow");
+            }
+            err => panic!("unexpected error {err:?}"),
+        }
+
+        let err = ZipError::from(io::Error::from(ZipError::InvalidArchive("bad")));
+        assert!(matches!(err, ZipError::InvalidArchive("bad")));
+    }
+}
@@ -11,6 +11,10 @@ pub const CENTRAL_DIRECTORY_HEADER_SIGNATURE: u32 = 0x02014b50;
 pub(crate) const CENTRAL_DIRECTORY_END_SIGNATURE: u32 = 0x06054b50;
 pub const ZIP64_CENTRAL_DIRECTORY_END_SIGNATURE: u32 = 0x06064b50;
 pub(crate) const ZIP64_CENTRAL_DIRECTORY_END_LOCATOR_SIGNATURE: u32 = 0x07064b50;
+pub(crate) const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x08074b50;
+
+/// The size of an end of central directory record with the longest possible comment, rounded up
+pub(crate) const MAX_HEADER_AND_COMMENT_SIZE: u64 = 66000;
 
 pub const ZIP64_BYTES_THR: u64 = u32::MAX as u64;
 pub const ZIP64_ENTRY_THR: usize = u16::MAX as usize;
@@ -53,17 +57,28 @@ impl CentralDirectoryEnd {
     }
 
     pub fn find_and_parse<T: Read + Seek>(reader: &mut T) -> ZipResult<(CentralDirectoryEnd, u64)> {
+        Self::find_and_parse_within(reader, MAX_HEADER_AND_COMMENT_SIZE)
+    }
+
+    /// Like [`CentralDirectoryEnd::find_and_parse`], but searches the last `search_window` bytes
+    /// of the file, which can be more than a maximal comment when other data follows the archive.
+    pub fn find_and_parse_within<T: Read + Seek>(
+        reader: &mut T,
+        search_window: u64,
+    ) -> ZipResult<(CentralDirectoryEnd, u64)> {
         const HEADER_SIZE: u64 = 22;
-        const MAX_HEADER_AND_COMMENT_SIZE: u64 = 66000;
         const BYTES_BETWEEN_MAGIC_AND_COMMENT_SIZE: u64 = HEADER_SIZE - 6;
         let file_length = reader.seek(io::SeekFrom::End(0))?;
 
-        let search_upper_bound = file_length.saturating_sub(MAX_HEADER_AND_COMMENT_SIZE);
+        let search_upper_bound = file_length.saturating_sub(search_window);
 
         if file_length < HEADER_SIZE {
             return Err(ZipError::InvalidArchive("Invalid zip header"));
         }
 
+        // A comment can hold something that looks like a record, so one whose comment runs to
+        // the end of the file is preferred over the last one that parses
+        let mut fallback = None;
         let mut pos = file_length - HEADER_SIZE;
         while pos >= search_upper_bound {
             let mut have_signature = false;
@@ -75,7 +90,10 @@ impl CentralDirectoryEnd {
                 ))?;
                 let cde_start_pos = reader.seek(io::SeekFrom::Start(pos))?;
                 if let Ok(end_header) = CentralDirectoryEnd::parse(reader) {
-                    return Ok((end_header, cde_start_pos));
+                    if reader.stream_position()? == file_length {
+                        return Ok((end_header, cde_start_pos));
+                    }
+                    fallback.get_or_insert((end_header, cde_start_pos));
                 }
             }
             pos = match pos.checked_sub(if have_signature {
@@ -87,7 +105,7 @@ impl CentralDirectoryEnd {
                 None => break,
             };
         }
-        Err(ZipError::InvalidArchive(
+        fallback.ok_or(ZipError::InvalidArchive(
             "Could not find central directory end",
         ))
     }
@@ -100,7 +118,9 @@ impl CentralDirectoryEnd {
         writer.write_u16_le(self.number_of_files)?;
         writer.write_u32_le(self.central_directory_size)?;
         writer.write_u32_le(self.central_directory_offset)?;
-        writer.write_u16_le(self.zip_file_comment.len() as u16)?;
+        let comment_length = u16::try_from(self.zip_file_comment.len())
+            .map_err(|_| ZipError::InvalidArchive("Archive comment is too long"))?;
+        writer.write_u16_le(comment_length)?;
         writer.write_all(&self.zip_file_comment)?;
         Ok(())
     }
@@ -0,0 +1,167 @@
+//! Rewriting archives with different compression
+
+use crate::compression::CompressionMethod;
+use crate::read::{glob_matches, ZipArchive};
+use crate::result::ZipResult;
+use crate::spec;
+use crate::write::{SimpleFileOptions, ZipWriter};
+use std::io::{self, Read, Seek, Write};
+
+/// Which entries a rule of a [`Recipe`] applies to
+#[derive(Clone, Debug)]
+enum Selector {
+    Method(CompressionMethod),
+    Glob(Vec<String>),
+}
+
+/// Rules for [`recompress`], saying how to compress which entries
+///
+/// Each entry is compressed as the first rule that matches it says, and entries that match no
+/// rule are copied as they are.
+#[derive(Clone, Debug, Default)]
+pub struct Recipe {
+    rules: Vec<(Selector, CompressionMethod, Option<i64>)>,
+}
+
+impl Recipe {
+    /// Recompress the entries compressed with `from` with `to`, at `level` or the method's
+    /// default level.
+    #[must_use]
+    pub fn method(
+        mut self,
+        from: CompressionMethod,
+        to: CompressionMethod,
+        level: Option<i64>,
+    ) -> Self {
+        self.rules.push((Selector::Method(from), to, level));
+        self
+    }
+
+    /// Recompress the entries whose names match the glob `pattern` with `to`, at `level` or the
+    /// method's default level.
+    ///
+    /// Patterns are written as for [`ZipArchive::extract_glob`], so `**/*.png` matches every PNG
+    /// file.
+    #[must_use]
+    pub fn glob(mut self, pattern: &str, to: CompressionMethod, level: Option<i64>) -> Self {
+        let pattern = pattern.split('/').map(str::to_owned).collect();
+        self.rules.push((Selector::Glob(pattern), to, level));
+        self
+    }
+
+    /// Returns the method and level to recompress an entry with, if a rule matches it.
+    fn target(
+        &self,
+        name: &str,
+        method: CompressionMethod,
+    ) -> Option<(CompressionMethod, Option<i64>)> {
+        let name: Vec<&str> = name.trim_end_matches('/').split('/').collect();
+        self.rules
+            .iter()
+            .find(|(selector, _, _)| match selector {
+                Selector::Method(from) => *from == method,
+                Selector::Glob(pattern) => {
+                    let pattern: Vec<&str> = pattern.iter().map(String::as_str).collect();
+                    glob_matches(&pattern, &name)
+                }
+            })
+            .map(|&(_, to, level)| (to, level))
+    }
+}
+
+/// Writes every entry of `src` to `dst`, in order, recompressing those that `recipe` says to and
+/// copying the rest without decompressing them.
+///
+/// Recompressed entries keep their names, modification times, Unix permissions and extended
+/// timestamps. Directories and encrypted entries are always copied as they are, as are entries
+/// already compressed with the method a rule gives, unless it also gives a level.
+pub fn recompress<R, W>(
+    src: &mut ZipArchive<R>,
+    dst: &mut ZipWriter<W>,
+    recipe: Recipe,
+) -> ZipResult<()>
+where
+    R: Read + Seek,
+    W: Write + Seek,
+{
+    for index in 0..src.len() {
+        let file = src.by_index_raw(index)?;
+        let target = match recipe.target(file.name(), file.compression()) {
+            _ if file.is_dir() || file.data.encrypted => None,
+            Some((to, None)) if to == file.compression() => None,
+            target => target,
+        };
+        let Some((method, level)) = target else {
+            dst.raw_copy_file(file)?;
+            continue;
+        };
+        drop(file);
+
+        let mut file = src.by_index(index)?;
+        let mut options = SimpleFileOptions::default()
+            .compression_method(method)
+            .compression_level(level)
+            .last_modified_time(file.last_modified())
+            .large_file(file.size() > spec::ZIP64_BYTES_THR);
+        if let Some(mode) = file.unix_mode() {
+            options = options.unix_permissions(mode);
+        }
+        if let Some(timestamp) = file.extended_timestamp() {
+            options = options.extended_timestamp(timestamp);
+        }
+        dst.start_file(file.name(), options)?;
+        io::copy(&mut file, dst)?;
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "deflate"))]
+mod test {
+    use super::{recompress, Recipe};
+    use crate::write::SimpleFileOptions;
+    use crate::{CompressionMethod, ZipArchive, ZipWriter};
+    use std::io::{Cursor, Read, Write};
+
+    #[test]
+    fn recompress_by_recipe() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        for (name, options) in [
+            ("a.txt", deflated),
+            ("img/b.png", deflated),
+            ("c.bin", stored),
+        ] {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(&[b'x'; 1000]).unwrap();
+        }
+        let mut src = writer.finish_into_readable().unwrap();
+
+        let recipe = Recipe::default()
+            .glob("**/*.png", CompressionMethod::Stored, None)
+            .method(
+                CompressionMethod::Stored,
+                CompressionMethod::Deflated,
+                Some(9),
+            );
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        recompress(&mut src, &mut writer, recipe).unwrap();
+        let mut dst = ZipArchive::new(writer.finish().unwrap()).unwrap();
+
+        for (name, method) in [
+            ("a.txt", CompressionMethod::Deflated),
+            ("img/b.png", CompressionMethod::Stored),
+            ("c.bin", CompressionMethod::Deflated),
+        ] {
+            let mut file = dst.by_name(name).unwrap();
+            assert_eq!(file.compression(), method, "{name}");
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents).unwrap();
+            assert_eq!(contents, [b'x'; 1000]);
+        }
+        assert_eq!(
+            dst.by_name("a.txt").unwrap().compressed_size(),
+            src.by_name("a.txt").unwrap().compressed_size()
+        );
+    }
+}
@@ -9,11 +9,13 @@ use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
 use {crate::read::ZipFile, crate::write::FileOptions};
 
 pub(crate) mod ffi {
+    pub const S_IFMT: u32 = 0o0170000;
     pub const S_IFDIR: u32 = 0o0040000;
     pub const S_IFREG: u32 = 0o0100000;
+    pub const S_IFLNK: u32 = 0o0120000;
 }
 
-use crate::extra_fields::ExtraField;
+use crate::extra_fields::{Expiry, ExtendedTimestamp, ExtraField, UnixOwner};
 use crate::result::DateTimeRangeError;
 use crate::types::ffi::S_IFDIR;
 use crate::CompressionMethod;
@@ -339,6 +341,8 @@ pub struct ZipFileData {
     pub file_comment: Box<str>,
     /// Specifies where the local header of the file starts
     pub header_start: u64,
+    /// The number of the disk, in a spanned archive, that the local header is on
+    pub disk_number: u32,
     /// Specifies where the extra data of the file starts
     pub extra_data_start: Option<u64>,
     /// Specifies where the central header of the file starts
@@ -430,6 +434,27 @@ impl ZipFileData {
         }
     }
 
+    pub(crate) fn unix_owner(&self) -> Option<UnixOwner> {
+        self.extra_fields.iter().find_map(|field| match field {
+            ExtraField::UnixOwner(owner) => Some(*owner),
+            _ => None,
+        })
+    }
+
+    pub(crate) fn extended_timestamp(&self) -> Option<ExtendedTimestamp> {
+        self.extra_fields.iter().find_map(|field| match field {
+            ExtraField::ExtendedTimestamp(timestamp) => Some(*timestamp),
+            _ => None,
+        })
+    }
+
+    pub(crate) fn expiry(&self) -> Option<Expiry> {
+        self.extra_fields.iter().find_map(|field| match field {
+            ExtraField::Expiry(expiry) => Some(*expiry),
+            _ => None,
+        })
+    }
+
     /// PKZIP version needed to open this file (from APPNOTE 4.4.3.2).
     pub fn version_needed(&self) -> u16 {
         let compression_version: u16 = match self.compression_method {
@@ -442,6 +467,8 @@ impl ZipFileData {
             CompressionMethod::Deflate64 => 21,
             #[cfg(feature = "lzma")]
             CompressionMethod::Lzma => 63,
+            #[cfg(feature = "xz")]
+            CompressionMethod::Xz => 63,
             // APPNOTE doesn't specify a version for Zstandard
             _ => DEFAULT_VERSION as u16,
         };
@@ -560,6 +587,7 @@ mod test {
             central_extra_field: None,
             file_comment: String::with_capacity(0).into_boxed_str(),
             header_start: 0,
+            disk_number: 0,
             extra_data_start: None,
             data_start: OnceLock::new(),
             central_header_start: 0,
@@ -2,8 +2,13 @@
 
 #[cfg(feature = "aes-crypto")]
 use crate::aes::AesWriter;
-use crate::compression::CompressionMethod;
-use crate::read::{find_content, ZipArchive, ZipFile, ZipFileReader};
+use crate::comment_metadata;
+use crate::compression::{CompressionMethod, CompressionRegistry, CustomWriter};
+use crate::extra_fields::{Expiry, ExtendedTimestamp, ExtraField, UnixOwner};
+use crate::read::{
+    find_apk_signing_block, find_content, find_entry_end, is_apk_signing_block, ZipArchive,
+    ZipFile, ZipFileReader,
+};
 use crate::result::{ZipError, ZipResult};
 use crate::spec;
 #[cfg(feature = "aes-crypto")]
@@ -11,30 +16,25 @@ use crate::types::AesMode;
 use crate::types::{ffi, AesVendorVersion, DateTime, System, ZipFileData, DEFAULT_VERSION};
 #[cfg(any(feature = "_deflate-any", feature = "bzip2", feature = "zstd",))]
 use core::num::NonZeroU64;
-use crc32fast::Hasher;
 use indexmap::IndexMap;
+use std::collections::BTreeMap;
 use std::default::Default;
 use std::io;
 use std::io::prelude::*;
 use std::io::{BufReader, SeekFrom};
 use std::marker::PhantomData;
 use std::mem;
+#[cfg(feature = "_deflate-flate2")]
+use std::num::NonZeroUsize;
 use std::str::{from_utf8, Utf8Error};
 use std::sync::{Arc, OnceLock};
 
-#[cfg(any(
-    feature = "deflate",
-    feature = "deflate-zlib",
-    feature = "deflate-zlib-ng"
-))]
+#[cfg(feature = "_deflate-flate2")]
 use flate2::{write::DeflateEncoder, Compression};
 
 #[cfg(feature = "bzip2")]
 use bzip2::write::BzEncoder;
 
-#[cfg(feature = "time")]
-use time::OffsetDateTime;
-
 #[cfg(feature = "deflate-zopfli")]
 use zopfli::Options;
 
@@ -45,6 +45,51 @@ use std::path::Path;
 #[cfg(feature = "zstd")]
 use zstd::stream::write::Encoder as ZstdEncoder;
 
+mod checksum;
+use checksum::Checksum;
+
+mod defaults;
+pub use defaults::Defaults;
+
+mod extension_options;
+use extension_options::has_compressed_extension;
+pub use extension_options::ExtensionOptions;
+
+mod merge;
+pub use merge::ConflictPolicy;
+
+mod recursive;
+pub use recursive::AddOptions;
+
+mod split;
+use split::SplitLayout;
+pub use split::SplitWriter;
+
+mod stream;
+pub use stream::StreamWriter;
+
+#[cfg(feature = "_deflate-flate2")]
+mod parallel_deflate;
+#[cfg(feature = "_deflate-flate2")]
+use parallel_deflate::ParallelDeflateEncoder;
+
+/// What [`ZipWriter`] does when an entry is added with the name of one already in the archive,
+/// as set with [`ZipWriter::set_duplicate_name_policy`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DuplicateNamePolicy {
+    /// Fail to add the entry. This is the default.
+    #[default]
+    Error,
+    /// Add the entry as well, so the archive holds both. Extractors disagree about which one
+    /// wins, and the writer's own lookups by name, such as [`ZipWriter::remove_file`], find the
+    /// first.
+    Allow,
+    /// Remove the entry already in the archive, as [`ZipWriter::remove_file`] does, so the one
+    /// added last is the only one with that name.
+    ReplaceLast,
+}
+
 enum MaybeEncrypted<W> {
     Unencrypted(W),
     #[cfg(feature = "aes-crypto")]
@@ -72,12 +117,10 @@ impl<W: Write> Write for MaybeEncrypted<W> {
 enum GenericZipWriter<W: Write + Seek> {
     Closed,
     Storer(MaybeEncrypted<W>),
-    #[cfg(any(
-        feature = "deflate",
-        feature = "deflate-zlib",
-        feature = "deflate-zlib-ng"
-    ))]
+    #[cfg(feature = "_deflate-flate2")]
     Deflater(DeflateEncoder<MaybeEncrypted<W>>),
+    #[cfg(feature = "_deflate-flate2")]
+    ParallelDeflater(ParallelDeflateEncoder<MaybeEncrypted<W>>),
     #[cfg(feature = "deflate-zopfli")]
     ZopfliDeflater(zopfli::DeflateEncoder<MaybeEncrypted<W>>),
     #[cfg(feature = "deflate-zopfli")]
@@ -86,6 +129,7 @@ enum GenericZipWriter<W: Write + Seek> {
     Bzip2(BzEncoder<MaybeEncrypted<W>>),
     #[cfg(feature = "zstd")]
     Zstd(ZstdEncoder<'static, MaybeEncrypted<W>>),
+    Custom(CustomWriter<MaybeEncrypted<W>>),
 }
 
 // Put the struct declaration in a private module to convince rustdoc to display ZipWriter nicely
@@ -127,12 +171,26 @@ pub(crate) mod zip_writer {
         pub(super) writing_raw: bool,
         pub(super) comment: Vec<u8>,
         pub(super) flush_on_finish_file: bool,
+        pub(super) incompressible_sample: Option<IncompressibleSample>,
+        pub(super) discard_tail: bool,
+        pub(super) progress: Option<Box<dyn Progress + Send>>,
+        pub(super) progress_bytes: u64,
+        pub(super) metrics: Option<Arc<dyn Metrics>>,
+        pub(super) duplicate_names: DuplicateNamePolicy,
+        pub(super) defaults: Defaults,
+        pub(super) split: Option<Arc<SplitLayout>>,
+        pub(super) streaming: bool,
+        pub(super) registry: Option<Arc<CompressionRegistry>>,
+        pub(super) force_zip64: bool,
+        pub(super) apk_signing_block: Option<Vec<u8>>,
     }
 }
 #[doc(inline)]
 pub use self::sealed::FileOptionExtension;
+use crate::metrics::Metrics;
+use crate::progress::{Progress, ProgressStatus};
 use crate::result::ZipError::InvalidArchive;
-#[cfg(feature = "lzma")]
+#[cfg(any(feature = "lzma", feature = "xz"))]
 use crate::result::ZipError::UnsupportedArchive;
 use crate::spec::path_to_string;
 use crate::unstable::LittleEndianWriteExt;
@@ -143,11 +201,18 @@ pub use zip_writer::ZipWriter;
 
 #[derive(Default)]
 struct ZipWriterStats {
-    hasher: Hasher,
+    hasher: Checksum,
     start: u64,
     bytes_written: u64,
 }
 
+/// Uncompressed data kept for [`FileOptions::store_if_incompressible`], in case the file has to be
+/// restarted as `Stored`.
+struct IncompressibleSample {
+    sample_size: usize,
+    data: Vec<u8>,
+}
+
 struct ZipRawValues {
     crc32: u32,
     compressed_size: u64,
@@ -221,14 +286,23 @@ impl<'a> arbitrary::Arbitrary<'a> for EncryptWith<'a> {
 pub struct FileOptions<'k, T: FileOptionExtension> {
     pub(crate) compression_method: CompressionMethod,
     pub(crate) compression_level: Option<i64>,
-    pub(crate) last_modified_time: DateTime,
+    pub(crate) last_modified_time: Option<DateTime>,
     pub(crate) permissions: Option<u32>,
+    pub(crate) unix_owner: Option<UnixOwner>,
+    pub(crate) expiry: Option<Expiry>,
+    pub(crate) extended_timestamp: Option<ExtendedTimestamp>,
     pub(crate) large_file: bool,
+    checksum_thread: bool,
     encrypt_with: Option<EncryptWith<'k>>,
     extended_options: T,
     alignment: u16,
     #[cfg(feature = "deflate-zopfli")]
     pub(super) zopfli_buffer_size: Option<usize>,
+    /// `None` when left to the writer's [`Defaults`]
+    incompressible_sample_size: Option<Option<usize>>,
+    store_compressed_formats: Option<bool>,
+    #[cfg(feature = "_deflate-flate2")]
+    compression_threads: Option<NonZeroUsize>,
 }
 /// Simple File Options. Can be copied and good for simple writing zip files
 pub type SimpleFileOptions = FileOptions<'static, ()>;
@@ -251,7 +325,7 @@ impl<'a> arbitrary::Arbitrary<'a> for FileOptions<'a, ExtendedFileOptions> {
             } else {
                 None
             },
-            last_modified_time: DateTime::arbitrary(u)?,
+            last_modified_time: Option::<DateTime>::arbitrary(u)?,
             permissions: Option::<u32>::arbitrary(u)?,
             large_file: bool::arbitrary(u)?,
             encrypt_with: Option::<EncryptWith>::arbitrary(u)?,
@@ -309,11 +383,11 @@ impl<'k, T: FileOptionExtension> FileOptions<'k, T> {
 
     /// Set the last modified time
     ///
-    /// The default is the current timestamp if the 'time' feature is enabled, and 1980-01-01
-    /// otherwise
+    /// The default is taken from the writer's [`Defaults`]: the current timestamp if the 'time'
+    /// feature is enabled, and 1980-01-01 otherwise, unless they've been changed
     #[must_use]
     pub const fn last_modified_time(mut self, mod_time: DateTime) -> Self {
-        self.last_modified_time = mod_time;
+        self.last_modified_time = Some(mod_time);
         self
     }
 
@@ -332,6 +406,37 @@ impl<'k, T: FileOptionExtension> FileOptions<'k, T> {
         self
     }
 
+    /// Set the UID and GID of the new file's owner, which are stored in an Info-ZIP New Unix
+    /// extra field (0x7875).
+    ///
+    /// By default, no owner is stored.
+    #[must_use]
+    pub const fn unix_owner(mut self, uid: u32, gid: u32) -> Self {
+        self.unix_owner = Some(UnixOwner::new(uid, gid));
+        self
+    }
+
+    /// Set the modification, access and creation times stored in the new file's Info-ZIP
+    /// extended timestamp extra field (0x5455), which hold whole seconds in UTC.
+    ///
+    /// This doesn't change the MS-DOS timestamp set by [`FileOptions::last_modified_time`].
+    /// By default, no extended timestamp is stored.
+    #[must_use]
+    pub const fn extended_timestamp(mut self, timestamp: ExtendedTimestamp) -> Self {
+        self.extended_timestamp = Some(timestamp);
+        self
+    }
+
+    /// Set when the new file was created and how long it stays fresh, which are stored in an
+    /// extra field of this crate's own (0x4c54) that [`crate::read::ZipFile::is_expired`] checks.
+    ///
+    /// By default, files don't expire.
+    #[must_use]
+    pub const fn expiry(mut self, expiry: Expiry) -> Self {
+        self.expiry = Some(expiry);
+        self
+    }
+
     /// Set whether the new file's compressed and uncompressed size is less than 4 GiB.
     ///
     /// If set to `false` and the file exceeds the limit, an I/O error is thrown and the file is
@@ -373,6 +478,17 @@ impl<'k, T: FileOptionExtension> FileOptions<'k, T> {
         self
     }
 
+    /// Set whether the new file's CRC-32 is computed on a separate thread, which overlaps hashing
+    /// with compression and I/O. Each write is copied to send it to the thread, so this only pays
+    /// off for large files.
+    ///
+    /// The default is false.
+    #[must_use]
+    pub const fn checksum_thread(mut self, checksum_thread: bool) -> Self {
+        self.checksum_thread = checksum_thread;
+        self
+    }
+
     /// Returns the compression level currently set.
     pub const fn get_compression_level(&self) -> Option<i64> {
         self.compression_level
@@ -383,6 +499,43 @@ impl<'k, T: FileOptionExtension> FileOptions<'k, T> {
         self.alignment = alignment;
         self
     }
+
+    /// Checks how well the first `sample_size` bytes of the file compress, and switches the file
+    /// to `CompressionMethod::Stored` if compression saved less than 5% of them. The file is
+    /// restarted with the sampled data, so callers don't need to do anything differently.
+    ///
+    /// Files shorter than `sample_size` are checked when they're finished. The check is skipped
+    /// for encrypted and `Stored` files, and for files compressed with Zopfli. `None` disables
+    /// the check. If this isn't called, the writer's [`Defaults::store_if_incompressible`]
+    /// applies, which disables it unless set.
+    #[must_use]
+    pub const fn store_if_incompressible(mut self, sample_size: Option<usize>) -> Self {
+        self.incompressible_sample_size = Some(sample_size);
+        self
+    }
+
+    /// Stores the file instead of compressing it if its name ends in the extension of a format
+    /// that's already compressed, such as `.jpg`, `.mp4` or `.gz`, since compressing it again
+    /// would spend time for little or no gain. If this isn't called, the writer's
+    /// [`Defaults::store_compressed_formats`] applies, which is off unless set.
+    #[must_use]
+    pub const fn store_compressed_formats(mut self, store: bool) -> Self {
+        self.store_compressed_formats = Some(store);
+        self
+    }
+
+    /// Sets the number of threads used to deflate the file. With more than one, the data is split
+    /// into chunks of 128 KiB that are compressed independently and joined into one stream, like
+    /// `pigz` does, at the cost of slightly worse compression.
+    ///
+    /// This only affects files compressed with `CompressionMethod::Deflated` at levels that don't
+    /// use Zopfli. The default, `None`, compresses on the writing thread.
+    #[must_use]
+    #[cfg(feature = "_deflate-flate2")]
+    pub const fn compression_threads(mut self, threads: Option<NonZeroUsize>) -> Self {
+        self.compression_threads = threads;
+        self
+    }
 }
 impl<'k> FileOptions<'k, ExtendedFileOptions> {
     /// Adds an extra data field.
@@ -442,17 +595,22 @@ impl<'k, T: FileOptionExtension> Default for FileOptions<'k, T> {
         Self {
             compression_method: Default::default(),
             compression_level: None,
-            #[cfg(feature = "time")]
-            last_modified_time: OffsetDateTime::now_utc().try_into().unwrap_or_default(),
-            #[cfg(not(feature = "time"))]
-            last_modified_time: DateTime::default(),
+            last_modified_time: None,
             permissions: None,
+            unix_owner: None,
+            expiry: None,
+            extended_timestamp: None,
+            checksum_thread: false,
             large_file: false,
             encrypt_with: None,
             extended_options: T::default(),
             alignment: 1,
             #[cfg(feature = "deflate-zopfli")]
             zopfli_buffer_size: Some(1 << 15),
+            incompressible_sample_size: None,
+            store_compressed_formats: None,
+            #[cfg(feature = "_deflate-flate2")]
+            compression_threads: None,
         }
     }
 }
@@ -482,6 +640,17 @@ impl<W: Write + Seek> Write for ZipWriter<W> {
                             "Large file option has not been set",
                         ));
                     }
+                    if let Some(sample) = &mut self.incompressible_sample {
+                        sample.data.extend_from_slice(&buf[0..count]);
+                        if sample.data.len() >= sample.sample_size {
+                            self.check_incompressible()?;
+                        }
+                    }
+                    self.progress_bytes += count as u64;
+                    if let Some(metrics) = &self.metrics {
+                        metrics.uncompressed_bytes_written(count as u64);
+                    }
+                    self.report_progress();
                 }
                 write_result
             }
@@ -514,7 +683,19 @@ impl<A: Read + Write + Seek> ZipWriter<A> {
     /// Initializes the archive from an existing ZIP archive, making it ready for append.
     pub fn new_append(mut readwriter: A) -> ZipResult<ZipWriter<A>> {
         let (footer, cde_start_pos) = spec::CentralDirectoryEnd::find_and_parse(&mut readwriter)?;
-        let metadata = ZipArchive::get_metadata(&mut readwriter, &footer, cde_start_pos)?;
+        let metadata =
+            ZipArchive::get_metadata(&mut readwriter, &footer, cde_start_pos, None, false)?;
+        // Keep the APK Signing Block in memory, and write new entries over it
+        let apk_signing_block = match find_apk_signing_block(&mut readwriter, metadata.dir_start)? {
+            Some(block_start) => {
+                let mut block = vec![0; (metadata.dir_start - block_start) as usize];
+                readwriter.seek(SeekFrom::Start(block_start))?;
+                readwriter.read_exact(&mut block)?;
+                readwriter.seek(SeekFrom::Start(block_start))?;
+                Some(block)
+            }
+            None => None,
+        };
 
         Ok(ZipWriter {
             inner: Storer(MaybeEncrypted::Unencrypted(readwriter)),
@@ -524,6 +705,18 @@ impl<A: Read + Write + Seek> ZipWriter<A> {
             comment: footer.zip_file_comment,
             writing_raw: true, // avoid recomputing the last file's header
             flush_on_finish_file: false,
+            incompressible_sample: None,
+            discard_tail: false,
+            progress: None,
+            progress_bytes: 0,
+            metrics: None,
+            duplicate_names: DuplicateNamePolicy::Error,
+            defaults: Defaults::preserve_host(),
+            split: None,
+            streaming: false,
+            registry: None,
+            force_zip64: false,
+            apk_signing_block,
         })
     }
 
@@ -577,8 +770,13 @@ impl<A: Read + Write + Seek> ZipWriter<A> {
             let mut options = FileOptions::<ExtendedFileOptions> {
                 compression_method: src_data.compression_method,
                 compression_level: src_data.compression_level,
-                last_modified_time: src_data.last_modified_time,
+                last_modified_time: Some(src_data.last_modified_time),
                 permissions: src_data.unix_mode(),
+                // Copied with the rest of the extra data
+                unix_owner: None,
+                expiry: None,
+                extended_timestamp: None,
+                checksum_thread: false,
                 large_file: src_data.large_file,
                 encrypt_with: None,
                 extended_options: ExtendedFileOptions {
@@ -588,29 +786,42 @@ impl<A: Read + Write + Seek> ZipWriter<A> {
                 alignment: 1,
                 #[cfg(feature = "deflate-zopfli")]
                 zopfli_buffer_size: None,
+                incompressible_sample_size: None,
+                store_compressed_formats: None,
+                #[cfg(feature = "_deflate-flate2")]
+                compression_threads: None,
             };
             if let Some(perms) = src_data.unix_mode() {
                 options = options.unix_permissions(perms);
             }
-            Self::normalize_options(&mut options);
+            Self::normalize_options(&mut options, self.defaults);
             self.start_entry(dest_name, options, Some(raw_values))?;
         } else {
             let mut options = FileOptions::<()> {
                 compression_method: src_data.compression_method,
                 compression_level: src_data.compression_level,
-                last_modified_time: src_data.last_modified_time,
+                last_modified_time: Some(src_data.last_modified_time),
                 permissions: src_data.unix_mode(),
+                // Copied with the rest of the extra data
+                unix_owner: None,
+                expiry: None,
+                extended_timestamp: None,
+                checksum_thread: false,
                 large_file: src_data.large_file,
                 encrypt_with: None,
                 extended_options: (),
                 alignment: 1,
                 #[cfg(feature = "deflate-zopfli")]
                 zopfli_buffer_size: None,
+                incompressible_sample_size: None,
+                store_compressed_formats: None,
+                #[cfg(feature = "_deflate-flate2")]
+                compression_threads: None,
             };
             if let Some(perms) = src_data.unix_mode() {
                 options = options.unix_permissions(perms);
             }
-            Self::normalize_options(&mut options);
+            Self::normalize_options(&mut options, self.defaults);
             self.start_entry(dest_name, options, Some(raw_values))?;
         }
 
@@ -636,6 +847,75 @@ impl<A: Read + Write + Seek> ZipWriter<A> {
         self.deep_copy_file(&path_to_string(src_path), &path_to_string(dest_path))
     }
 
+    /// Moves every entry's header and data backward to close the gaps between them, such as those
+    /// left by aborted files or by entries written over when appending, and returns the number of
+    /// bytes reclaimed. Data before the first entry is kept.
+    ///
+    /// Since a [`Write`] + [`Seek`] writer can't be truncated, the reclaimed bytes remain at the
+    /// end of the underlying storage, and finishing overwrites them with zeros, so that no reader
+    /// finds an old central directory there. After [`ZipWriter::finish`], the archive ends at the
+    /// returned writer's stream position, and the bytes beyond it must be cut off, for example
+    /// with [`std::fs::File::set_len`]: readers only search the last 64 KiB or so of a file for
+    /// the end of the central directory, so behind a longer run of zeros they don't find it.
+    ///
+    /// Aborting a file afterwards with [`ZipWriter::abort_file`] gives the bytes back, and the
+    /// central directory is written at the very end again, so nothing has to be cut off.
+    pub fn compact(&mut self) -> ZipResult<u64> {
+        self.finish_file()?;
+        let writer = self.inner.get_plain();
+        let old_end = writer.stream_position()?;
+
+        // Entries whose data is shared, or that otherwise overlap, move together as one block
+        let mut extents = Vec::with_capacity(self.files.len());
+        for file in self.files.values() {
+            extents.push((file.header_start, find_entry_end(file, writer)?));
+        }
+        extents.sort_unstable();
+        let mut blocks: Vec<(u64, u64)> = Vec::new();
+        for (start, end) in extents {
+            match blocks.last_mut() {
+                Some(block) if start < block.1 => block.1 = block.1.max(end),
+                _ => blocks.push((start, end)),
+            }
+        }
+
+        let mut write_position = blocks.first().map_or(old_end, |block| block.0);
+        let mut moves = Vec::with_capacity(blocks.len());
+        let mut buffer = vec![0; 1 << 16];
+        for (start, end) in blocks {
+            let delta = start - write_position;
+            if delta > 0 {
+                let mut read_position = start;
+                while read_position < end {
+                    let chunk = (end - read_position).min(buffer.len() as u64) as usize;
+                    writer.seek(SeekFrom::Start(read_position))?;
+                    writer.read_exact(&mut buffer[..chunk])?;
+                    writer.seek(SeekFrom::Start(read_position - delta))?;
+                    writer.write_all(&buffer[..chunk])?;
+                    read_position += chunk as u64;
+                }
+            }
+            moves.push((start..end, delta));
+            write_position = end - delta;
+        }
+        for file in self.files.values_mut() {
+            let Some((_, delta)) = moves
+                .iter()
+                .find(|(block, _)| block.contains(&file.header_start))
+            else {
+                continue;
+            };
+            file.header_start -= delta;
+            if let Some(data_start) = file.data_start.take() {
+                file.data_start.get_or_init(|| data_start - delta);
+            }
+        }
+        let new_end = write_position;
+        writer.seek(SeekFrom::Start(new_end))?;
+        self.discard_tail = true;
+        Ok(old_end.max(new_end) - new_end)
+    }
+
     /// Write the zip file into the backing stream, then produce a readable archive of that data.
     ///
     /// This method avoids parsing the central directory records at the end of the stream for
@@ -665,7 +945,10 @@ impl<A: Read + Write + Seek> ZipWriter<A> {
         let inner = mem::replace(&mut self.inner, Closed).unwrap();
         let comment = mem::take(&mut self.comment);
         let files = mem::take(&mut self.files);
-        let archive = ZipArchive::from_finalized_writer(files, comment, inner, central_start)?;
+        let mut archive = ZipArchive::from_finalized_writer(files, comment, inner, central_start)?;
+        if let Some(registry) = self.registry.take() {
+            archive.set_compression_registry(registry);
+        }
         Ok(archive)
     }
 }
@@ -685,7 +968,119 @@ impl<W: Write + Seek> ZipWriter<W> {
             writing_raw: false,
             comment: Vec::new(),
             flush_on_finish_file: false,
+            incompressible_sample: None,
+            discard_tail: false,
+            progress: None,
+            progress_bytes: 0,
+            metrics: None,
+            duplicate_names: DuplicateNamePolicy::Error,
+            defaults: Defaults::preserve_host(),
+            split: None,
+            streaming: false,
+            registry: None,
+            force_zip64: false,
+            apk_signing_block: None,
+        }
+    }
+
+    /// Writes `prefix` before the archive's entries, such as the stub of a self-extracting
+    /// archive or a shebang line that makes the archive executable. Entries' offsets are recorded
+    /// from the start of the file, prefix included, as `zip -A` does, so readers that know nothing
+    /// of the prefix still find them.
+    ///
+    /// This must be called before any entries are written.
+    pub fn with_prefix(mut self, prefix: &[u8]) -> ZipResult<Self> {
+        if !self.files.is_empty() || self.writing_to_file {
+            return Err(ZipError::Io(io::Error::new(
+                io::ErrorKind::Other,
+                "The prefix must be written before any entries",
+            )));
         }
+        self.inner.get_plain().write_all(prefix)?;
+        Ok(self)
+    }
+
+    /// Set the last-modified time and permissions of entries whose [`FileOptions`] don't set them.
+    /// The default is [`Defaults::preserve_host`].
+    pub fn set_defaults(&mut self, defaults: Defaults) {
+        self.defaults = defaults;
+    }
+
+    /// Set whether every entry is written as with [`FileOptions::large_file`], and the archive
+    /// ends with Zip64 records even if nothing needs them. This suits archives streamed with
+    /// [`ZipWriter::new_stream`] whose files may reach 4 GiB, since their sizes can't be known
+    /// when they're started.
+    ///
+    /// Without it, the Zip64 records at the end are still added once the archive holds more than
+    /// 65,535 entries or its central directory starts beyond 4 GiB.
+    ///
+    /// This setting is false by default.
+    pub fn set_force_zip64(&mut self, force_zip64: bool) {
+        self.force_zip64 = force_zip64;
+    }
+
+    /// Returns the APK Signing Block that will be written before the central directory, if any.
+    /// [`ZipWriter::new_append`] keeps the block of the archive it opens.
+    pub fn apk_signing_block(&self) -> Option<&[u8]> {
+        self.apk_signing_block.as_deref()
+    }
+
+    /// Set the APK Signing Block written between the last entry and the central directory, or
+    /// leave it out with `None`. The block must be whole, from its leading size field to its
+    /// magic number, as [`ZipArchive::apk_signing_block`] returns it.
+    ///
+    /// The signatures in a kept block cover the archive's other contents, so they're no longer
+    /// valid once entries are added or changed; the archive needs signing again.
+    pub fn set_apk_signing_block(&mut self, block: Option<Vec<u8>>) -> ZipResult<()> {
+        if block
+            .as_deref()
+            .is_some_and(|block| !is_apk_signing_block(block))
+        {
+            return Err(InvalidArchive("Not an APK Signing Block"));
+        }
+        self.apk_signing_block = block;
+        Ok(())
+    }
+
+    /// Reports the progress of writing to `progress` when each file is started, and after each
+    /// write to it. The byte counts are of the uncompressed data written, and the totals are
+    /// unknown.
+    pub fn set_progress<P: Progress + Send + 'static>(&mut self, progress: P) {
+        self.progress = Some(Box::new(progress));
+    }
+
+    /// Reports the bytes written to this writer's files, before and after compression, to
+    /// `metrics`.
+    pub fn set_metrics(&mut self, metrics: Arc<dyn Metrics>) {
+        self.metrics = Some(metrics);
+    }
+
+    /// Set what happens when an entry is added with the name of one already in the archive.
+    pub fn set_duplicate_name_policy(&mut self, policy: DuplicateNamePolicy) {
+        self.duplicate_names = policy;
+    }
+
+    /// Compress files with the methods in `registry`, as well as those this crate implements,
+    /// when they're chosen with [`FileOptions::compression_method`].
+    pub fn set_compression_registry(&mut self, registry: Arc<CompressionRegistry>) {
+        self.registry = Some(registry);
+    }
+
+    fn report_progress(&mut self) {
+        let Some(progress) = &mut self.progress else {
+            return;
+        };
+        let Some((name, _)) = self.files.last() else {
+            return;
+        };
+        progress.update(&ProgressStatus {
+            entry_index: self.files.len() - 1,
+            entry_name: name,
+            entry_bytes: self.stats.bytes_written,
+            bytes: self.progress_bytes,
+            total_entries: None,
+            total_bytes: None,
+        });
     }
 
     /// Returns true if a file is currently open for writing.
@@ -694,6 +1089,9 @@ impl<W: Write + Seek> ZipWriter<W> {
     }
 
     /// Set ZIP archive comment.
+    ///
+    /// Finishing the archive fails if the comment is longer than the 65,535 bytes a comment can
+    /// hold.
     pub fn set_comment<S>(&mut self, comment: S)
     where
         S: Into<String>,
@@ -704,11 +1102,69 @@ impl<W: Write + Seek> ZipWriter<W> {
     /// Set ZIP archive comment.
     ///
     /// This sets the raw bytes of the comment. The comment
-    /// is typically expected to be encoded in UTF-8
+    /// is typically expected to be encoded in UTF-8, and finishing the archive fails if it's
+    /// longer than 65,535 bytes.
     pub fn set_raw_comment(&mut self, comment: Vec<u8>) {
         self.comment = comment;
     }
 
+    /// Set the comment of the file being written, or of the last one added.
+    ///
+    /// Comments that aren't ASCII are flagged as UTF-8, and also stored in an Info-ZIP Unicode
+    /// Comment extra field (0x6375) if [`Defaults::unicode_extra_fields`] is set. Fails if no file
+    /// has been added yet, or if the comment is longer than the 65,535 bytes a comment can hold.
+    pub fn set_file_comment<S>(&mut self, comment: S) -> ZipResult<()>
+    where
+        S: Into<Box<str>>,
+    {
+        let comment = comment.into();
+        if comment.len() > u16::MAX as usize {
+            return Err(InvalidArchive("File comment is too long"));
+        }
+        let writes_unicode_extra_field = self.defaults.writes_unicode_extra_fields();
+        let Some((_, file)) = self.files.last_mut() else {
+            return Err(ZipError::Io(io::Error::new(
+                io::ErrorKind::Other,
+                "No file has been added to comment on",
+            )));
+        };
+        if let Some(central_extra_field) = &mut file.central_extra_field {
+            let central_extra_field = Arc::make_mut(central_extra_field);
+            *central_extra_field = without_extra_field_block(central_extra_field, 0x6375);
+        }
+        if writes_unicode_extra_field && !comment.is_ascii() {
+            let field = unicode_extra_field(0x6375, comment.as_bytes(), &comment);
+            if file.extra_field_len() + file.central_extra_field_len() + field.len()
+                > u16::MAX as usize
+            {
+                return Err(InvalidArchive("Extra data field is too large"));
+            }
+            Arc::make_mut(
+                file.central_extra_field
+                    .get_or_insert_with(Default::default),
+            )
+            .extend_from_slice(&field);
+        }
+        file.file_comment = comment;
+        Ok(())
+    }
+
+    /// Set the ZIP archive comment to a JSON object holding `metadata`, such as build provenance
+    /// tags, which [`ZipArchive::comment_metadata`] reads back.
+    ///
+    /// This replaces any other comment, and fails if the object doesn't fit in the 65,535 bytes
+    /// a comment can hold.
+    pub fn set_comment_metadata(&mut self, metadata: &BTreeMap<String, String>) -> ZipResult<()> {
+        self.comment = comment_metadata::encode(metadata)?;
+        Ok(())
+    }
+
+    /// Get the metadata in the ZIP archive comment, if it's a JSON object whose values are all
+    /// strings, as written by [`ZipWriter::set_comment_metadata`].
+    pub fn get_comment_metadata(&self) -> Option<BTreeMap<String, String>> {
+        comment_metadata::decode(&self.comment)
+    }
+
     /// Get ZIP archive comment.
     pub fn get_comment(&mut self) -> Result<&str, Utf8Error> {
         from_utf8(self.get_raw_comment())
@@ -733,7 +1189,20 @@ impl<W: Write + Seek> ZipWriter<W> {
         S: Into<Box<str>>,
     {
         self.finish_file()?;
+        if self.streaming && options.alignment > 1 {
+            return Err(ZipError::UnsupportedArchive(
+                "Files can't be aligned in a streamed archive",
+            ));
+        }
+        #[cfg(feature = "aes-crypto")]
+        if self.streaming && matches!(options.encrypt_with, Some(EncryptWith::Aes { .. })) {
+            return Err(ZipError::UnsupportedArchive(
+                "Files can't be encrypted with AES in a streamed archive",
+            ));
+        }
 
+        // A streamed file records its sizes and CRC-32 after its data, unless they are known now
+        let using_data_descriptor = self.streaming && raw_values.is_none();
         let raw_values = raw_values.unwrap_or(ZipRawValues {
             crc32: 0,
             compressed_size: 0,
@@ -743,6 +1212,27 @@ impl<W: Write + Seek> ZipWriter<W> {
         #[allow(unused_mut)]
         let mut extra_field = options.extended_options.extra_data().cloned();
 
+        // Extra fields this crate writes itself. The central header repeats the local extra data,
+        // so these only need adding once.
+        let mut own_fields = Vec::new();
+        if let Some(timestamp) = options.extended_timestamp {
+            own_fields.extend_from_slice(&timestamp.to_bytes());
+        }
+        if let Some(owner) = options.unix_owner {
+            own_fields.extend_from_slice(&owner.to_bytes());
+        }
+        if let Some(expiry) = options.expiry {
+            own_fields.extend_from_slice(&expiry.to_bytes());
+        }
+        if !own_fields.is_empty() {
+            let field = extra_field.get_or_insert_with(Default::default);
+            if field.len() + own_fields.len() > u16::MAX as usize {
+                let _ = self.abort_file();
+                return Err(InvalidArchive("Extra data field is too large"));
+            }
+            Arc::make_mut(field).extend_from_slice(&own_fields);
+        }
+
         // Write AES encryption extra data.
         #[allow(unused_mut)]
         let mut aes_extra_data_start = 0;
@@ -785,32 +1275,47 @@ impl<W: Write + Seek> ZipWriter<W> {
                 ),
                 _ => (options.compression_method, None),
             };
+            let name: Box<str> = name.into();
+            let mut central_extra_field = options.extended_options.central_extra_data().cloned();
+            if self.defaults.writes_unicode_extra_fields() && !name.is_ascii() {
+                Arc::make_mut(central_extra_field.get_or_insert_with(Default::default))
+                    .extend_from_slice(&unicode_extra_field(0x7075, name.as_bytes(), &name));
+            }
             let file = ZipFileData {
                 system: System::Unix,
                 version_made_by: DEFAULT_VERSION,
                 encrypted: options.encrypt_with.is_some(),
-                using_data_descriptor: false,
+                using_data_descriptor,
                 compression_method,
                 compression_level: options.compression_level,
-                last_modified_time: options.last_modified_time,
+                last_modified_time: options
+                    .last_modified_time
+                    .unwrap_or_else(|| self.defaults.timestamp()),
                 crc32: raw_values.crc32,
                 compressed_size: raw_values.compressed_size,
                 uncompressed_size: raw_values.uncompressed_size,
-                file_name: name.into(),
+                file_name: name,
                 file_name_raw: vec![].into_boxed_slice(), // Never used for saving
                 extra_field,
-                central_extra_field: options.extended_options.central_extra_data().cloned(),
+                central_extra_field,
                 file_comment: String::with_capacity(0).into_boxed_str(),
                 header_start,
+                disk_number: 0,
                 extra_data_start: None,
                 data_start: OnceLock::new(),
                 central_header_start: 0,
                 external_attributes: permissions << 16,
-                large_file: options.large_file,
+                large_file: options.large_file || self.force_zip64,
                 aes_mode,
                 aes_extra_data_start,
 
-                extra_fields: Vec::new(),
+                extra_fields: options
+                    .extended_timestamp
+                    .map(ExtraField::ExtendedTimestamp)
+                    .into_iter()
+                    .chain(options.unix_owner.map(ExtraField::UnixOwner))
+                    .chain(options.expiry.map(ExtraField::Expiry))
+                    .collect(),
             };
             let index = self.insert_file_data(file)?;
             let file = &mut self.files[index];
@@ -823,7 +1328,12 @@ impl<W: Write + Seek> ZipWriter<W> {
                 1u16 << 11
             } else {
                 0
-            } | if file.encrypted { 1u16 << 0 } else { 0 };
+            } | if file.encrypted { 1u16 << 0 } else { 0 }
+                | if file.using_data_descriptor {
+                    1u16 << 3
+                } else {
+                    0
+                };
             writer.write_u16_le(flag)?;
             // Compression method
             #[allow(deprecated)]
@@ -930,16 +1440,34 @@ impl<W: Write + Seek> ZipWriter<W> {
             file.data_start.get_or_init(|| header_end);
             self.writing_to_file = true;
             self.stats.bytes_written = 0;
-            self.stats.hasher = Hasher::new();
+            self.stats.hasher = if options.checksum_thread {
+                Checksum::spawn()
+            } else {
+                Checksum::default()
+            };
         }
+        self.report_progress();
         Ok(())
     }
 
     fn insert_file_data(&mut self, file: ZipFileData) -> ZipResult<usize> {
-        if self.files.contains_key(&file.file_name) {
-            return Err(InvalidArchive("Duplicate filename"));
+        let mut name = file.file_name.to_owned();
+        if self.files.contains_key(&name) {
+            match self.duplicate_names {
+                DuplicateNamePolicy::Error => return Err(InvalidArchive("Duplicate filename")),
+                DuplicateNamePolicy::Allow => {
+                    // Entries are keyed by name, so further ones get keys that no lookup by
+                    // name finds
+                    name = (self.files.len()..)
+                        .map(|n| format!("{name}\0{n}").into_boxed_str())
+                        .find(|key| !self.files.contains_key(key))
+                        .unwrap();
+                }
+                DuplicateNamePolicy::ReplaceLast => {
+                    self.files.shift_remove(&name);
+                }
+            }
         }
-        let name = file.file_name.to_owned();
         self.files.insert(name.clone(), file);
         Ok(self.files.get_index_of(&name).unwrap())
     }
@@ -948,12 +1476,15 @@ impl<W: Write + Seek> ZipWriter<W> {
         if !self.writing_to_file {
             return Ok(());
         }
+        self.check_incompressible()?;
 
         let make_plain_writer = self.inner.prepare_next_writer(
             Stored,
             None,
             #[cfg(feature = "deflate-zopfli")]
             None,
+            #[cfg(feature = "_deflate-flate2")]
+            None,
         )?;
         self.inner.switch_to(make_plain_writer)?;
         self.switch_to_non_encrypting_writer()?;
@@ -969,8 +1500,11 @@ impl<W: Write + Seek> ZipWriter<W> {
             let file_end = writer.stream_position()?;
             debug_assert!(file_end >= self.stats.start);
             file.compressed_size = file_end - self.stats.start;
+            if let Some(metrics) = &self.metrics {
+                metrics.compressed_bytes_written(file.compressed_size);
+            }
 
-            file.crc32 = self.stats.hasher.clone().finalize();
+            file.crc32 = self.stats.hasher.finalize()?;
             if let Some(aes_mode) = &mut file.aes_mode {
                 // We prefer using AE-1 which provides an extra CRC check, but for small files we
                 // switch to AE-2 to prevent being able to use the CRC value to to reconstruct the
@@ -985,9 +1519,13 @@ impl<W: Write + Seek> ZipWriter<W> {
                 }
             }
 
-            update_aes_extra_data(writer, file)?;
-            update_local_file_header(writer, file)?;
-            writer.seek(SeekFrom::Start(file_end))?;
+            if file.using_data_descriptor {
+                write_data_descriptor(writer, file)?;
+            } else {
+                update_aes_extra_data(writer, file)?;
+                update_local_file_header(writer, file)?;
+                writer.seek(SeekFrom::Start(file_end))?;
+            }
         }
         if self.flush_on_finish_file {
             if let Err(e) = writer.flush() {
@@ -1007,7 +1545,14 @@ impl<W: Write + Seek> ZipWriter<W> {
                 self.inner = Storer(MaybeEncrypted::Unencrypted(writer.finish()?));
             }
             Storer(MaybeEncrypted::ZipCrypto(writer)) => {
-                let crc32 = self.stats.hasher.clone().finalize();
+                let mut crc32 = self.stats.hasher.finalize()?;
+                if let Some((_, file)) = self.files.last() {
+                    if file.using_data_descriptor {
+                        // Info-ZIP checks the password against the modification time instead, so
+                        // that the header can be written before the CRC-32 is known
+                        crc32 = (file.last_modified_time.timepart() as u32) << 16;
+                    }
+                }
                 self.inner = Storer(MaybeEncrypted::Unencrypted(writer.finish(crc32)?))
             }
             Storer(MaybeEncrypted::Unencrypted(w)) => {
@@ -1018,15 +1563,57 @@ impl<W: Write + Seek> ZipWriter<W> {
         Ok(())
     }
 
+    /// If the current file's sample compressed poorly, restarts it as `Stored` using the sampled
+    /// data. See [`FileOptions::store_if_incompressible`].
+    fn check_incompressible(&mut self) -> ZipResult<()> {
+        let Some(sample) = self.incompressible_sample.take() else {
+            return Ok(());
+        };
+        let Some(compressed_end) = self.inner.unencrypted_position()? else {
+            return Ok(());
+        };
+        let compressed_size = compressed_end - self.stats.start;
+        let sample_size = sample.data.len() as u64;
+        if compressed_size.saturating_mul(100) < sample_size.saturating_mul(95) {
+            return Ok(());
+        }
+        let make_plain_writer = self.inner.prepare_next_writer(
+            Stored,
+            None,
+            #[cfg(feature = "deflate-zopfli")]
+            None,
+            #[cfg(feature = "_deflate-flate2")]
+            None,
+        )?;
+        self.inner.switch_to(make_plain_writer)?;
+        let writer = self.inner.get_plain();
+        let file = &mut self.files.last_mut().ok_or(ZipError::FileNotFound)?.1;
+        file.compression_method = Stored;
+        file.compression_level = None;
+        writer.seek(SeekFrom::Start(file.header_start + 4))?;
+        writer.write_u16_le(file.version_needed())?;
+        writer.seek(SeekFrom::Start(file.header_start + 8))?;
+        #[allow(deprecated)]
+        writer.write_u16_le(Stored.to_u16())?;
+        writer.seek(SeekFrom::Start(self.stats.start))?;
+        writer.write_all(&sample.data)?;
+        Ok(())
+    }
+
     /// Removes the file currently being written from the archive if there is one, or else removes
     /// the file most recently written.
     pub fn abort_file(&mut self) -> ZipResult<()> {
+        self.incompressible_sample = None;
+        // The aborted data may lie past the end left by `compact`, so the footer goes at the end
+        self.discard_tail = false;
         let (_, last_file) = self.files.pop().ok_or(ZipError::FileNotFound)?;
         let make_plain_writer = self.inner.prepare_next_writer(
             Stored,
             None,
             #[cfg(feature = "deflate-zopfli")]
             None,
+            #[cfg(feature = "_deflate-flate2")]
+            None,
         )?;
         self.inner.switch_to(make_plain_writer)?;
         self.switch_to_non_encrypting_writer()?;
@@ -1040,7 +1627,7 @@ impl<W: Write + Seek> ZipWriter<W> {
                     .is_some_and(|start| start < last_file_start)
             }),
         };
-        if rewind_safe {
+        if rewind_safe && !self.streaming {
             self.inner
                 .get_plain()
                 .seek(SeekFrom::Start(last_file.header_start))?;
@@ -1061,19 +1648,53 @@ impl<W: Write + Seek> ZipWriter<W> {
     where
         S: Into<Box<str>>,
     {
-        Self::normalize_options(&mut options);
-        let make_new_self = self.inner.prepare_next_writer(
-            options.compression_method,
-            options.compression_level,
-            #[cfg(feature = "deflate-zopfli")]
-            options.zopfli_buffer_size,
-        )?;
+        let name = name.into();
+        Self::normalize_options(&mut options, self.defaults);
+        if options
+            .store_compressed_formats
+            .unwrap_or(self.defaults.stores_compressed_formats())
+            && has_compressed_extension(&name)
+        {
+            options.compression_method = Stored;
+            options.compression_level = None;
+        }
+        let custom = self
+            .registry
+            .as_ref()
+            .and_then(|registry| registry.get(options.compression_method));
+        let make_new_self: SwitchWriterFunction<W> = match custom {
+            Some(method) => {
+                let encoder = method.encoder(options.compression_level)?;
+                Box::new(|bare| GenericZipWriter::Custom(CustomWriter::new(encoder, bare)))
+            }
+            None => self.inner.prepare_next_writer(
+                options.compression_method,
+                options.compression_level,
+                #[cfg(feature = "deflate-zopfli")]
+                options.zopfli_buffer_size,
+                #[cfg(feature = "_deflate-flate2")]
+                options.compression_threads,
+            )?,
+        };
+        let incompressible_sample = options
+            .incompressible_sample_size
+            .unwrap_or(self.defaults.incompressible_sample_size())
+            .filter(|_| {
+                options.compression_method != Stored
+                    && options.encrypt_with.is_none()
+                    && !self.streaming
+            })
+            .map(|sample_size| IncompressibleSample {
+                sample_size,
+                data: Vec::with_capacity(sample_size),
+            });
         self.start_entry(name, options, None)?;
         if let Err(e) = self.inner.switch_to(make_new_self) {
             self.abort_file().unwrap();
             return Err(e);
         }
         self.writing_raw = false;
+        self.incompressible_sample = incompressible_sample;
         Ok(())
     }
 
@@ -1086,6 +1707,9 @@ impl<W: Write + Seek> ZipWriter<W> {
     /// decompression or decryption. This is more performant than the equivalent operation of
     /// calling [`Self::raw_copy_file()`] for each entry from the `source` archive in sequence.
     ///
+    /// Entries whose names are already in this archive replace the existing ones; use
+    /// [`Self::merge_archive_with_policy()`] to skip them or fail instead.
+    ///
     ///```
     /// # fn main() -> Result<(), zip::result::ZipError> {
     /// use std::io::{Cursor, prelude::*};
@@ -1118,33 +1742,22 @@ impl<W: Write + Seek> ZipWriter<W> {
     /// # Ok(())
     /// # }
     ///```
-    pub fn merge_archive<R>(&mut self, mut source: ZipArchive<R>) -> ZipResult<()>
+    pub fn merge_archive<R>(&mut self, source: ZipArchive<R>) -> ZipResult<()>
     where
         R: Read + io::Seek,
     {
-        self.finish_file()?;
-
-        /* Ensure we accept the file contents on faith (and avoid overwriting the data).
-         * See raw_copy_file_rename(). */
-        self.writing_to_file = true;
-        self.writing_raw = true;
-
-        let writer = self.inner.get_plain();
-        /* Get the file entries from the source archive. */
-        let new_files = source.merge_contents(writer)?;
-
-        /* These file entries are now ours! */
-        self.files.extend(new_files);
-
-        Ok(())
+        self.merge_archive_with_policy(source, ConflictPolicy::Replace)
     }
 
-    fn normalize_options<T: FileOptionExtension>(options: &mut FileOptions<T>) {
+    fn normalize_options<T: FileOptionExtension>(options: &mut FileOptions<T>, defaults: Defaults) {
         if options.permissions.is_none() {
-            options.permissions = Some(0o644);
+            options.permissions = Some(defaults.file_mode());
         }
-        if !options.last_modified_time.is_valid() {
-            options.last_modified_time = FileOptions::<T>::default().last_modified_time;
+        if options
+            .last_modified_time
+            .is_some_and(|mod_time| !mod_time.is_valid())
+        {
+            options.last_modified_time = None;
         }
         *options.permissions.as_mut().unwrap() |= ffi::S_IFREG;
     }
@@ -1199,7 +1812,7 @@ impl<W: Write + Seek> ZipWriter<W> {
         if let Some(perms) = file.unix_mode() {
             options = options.unix_permissions(perms);
         }
-        Self::normalize_options(&mut options);
+        Self::normalize_options(&mut options, self.defaults);
 
         let raw_values = ZipRawValues {
             crc32: file.crc32(),
@@ -1269,7 +1882,7 @@ impl<W: Write + Seek> ZipWriter<W> {
         S: Into<String>,
     {
         if options.permissions.is_none() {
-            options.permissions = Some(0o755);
+            options.permissions = Some(self.defaults.directory_mode());
         }
         *options.permissions.as_mut().unwrap() |= 0o40000;
         options.compression_method = Stored;
@@ -1282,7 +1895,12 @@ impl<W: Write + Seek> ZipWriter<W> {
             _ => name_as_string + "/",
         };
 
-        self.start_entry(name_with_slash, options, None)?;
+        let raw_values = ZipRawValues {
+            crc32: 0,
+            compressed_size: 0,
+            uncompressed_size: 0,
+        };
+        self.start_entry(name_with_slash, options, Some(raw_values))?;
         self.writing_to_file = false;
         self.switch_to_non_encrypting_writer()?;
         Ok(())
@@ -1334,7 +1952,7 @@ impl<W: Write + Seek> ZipWriter<W> {
         T: Into<Box<str>>,
     {
         if options.permissions.is_none() {
-            options.permissions = Some(0o777);
+            options.permissions = Some(self.defaults.symlink_mode());
         }
         *options.permissions.as_mut().unwrap() |= 0o120000;
         // The symlink target is stored as file content. And compressing the target path
@@ -1376,11 +1994,22 @@ impl<W: Write + Seek> ZipWriter<W> {
             let footer_end = writer.stream_position()?;
             let file_end = writer.seek(SeekFrom::End(0))?;
             if footer_end < file_end {
-                // Data from an aborted file is past the end of the footer, so rewrite the footer at
-                // the actual end.
-                let central_and_footer_size = footer_end - central_start;
-                writer.seek(SeekFrom::End(-(central_and_footer_size as i64)))?;
-                self.write_central_and_footer()?;
+                if self.discard_tail {
+                    // The data past the footer was left behind by `compact`, and is meant to be
+                    // truncated. Until it is, it mustn't hold an older footer that readers would
+                    // find first
+                    writer.seek(SeekFrom::Start(footer_end))?;
+                    io::copy(&mut io::repeat(0).take(file_end - footer_end), writer)?;
+                    writer.seek(SeekFrom::Start(footer_end))?;
+                    self.discard_tail = false;
+                } else {
+                    // Data from an aborted file is past the end of the footer, so rewrite the
+                    // footer at the actual end.
+                    let block_size = self.apk_signing_block.as_ref().map_or(0, Vec::len);
+                    let central_and_footer_size = footer_end - central_start + block_size as u64;
+                    writer.seek(SeekFrom::End(-(central_and_footer_size as i64)))?;
+                    self.write_central_and_footer()?;
+                }
             }
             central_start
         };
@@ -1390,33 +2019,58 @@ impl<W: Write + Seek> ZipWriter<W> {
 
     fn write_central_and_footer(&mut self) -> Result<u64, ZipError> {
         let writer = self.inner.get_plain();
+        // In a split archive, offsets are relative to the part they're in
+        let split = self.split.as_deref();
+        let locate = |position| split.map_or(Ok((0, position)), |split| split.locate(position));
 
+        if let Some(block) = &self.apk_signing_block {
+            writer.write_all(block)?;
+        }
         let central_start = writer.stream_position()?;
+        let mut record_disks = Vec::new();
         for file in self.files.values() {
-            write_central_directory_header(writer, file)?;
+            if split.is_some() {
+                record_disks.push(locate(writer.stream_position()?)?.0);
+            }
+            let (disk_number, header_start) = locate(file.header_start)?;
+            write_central_directory_header(writer, file, disk_number as u16, header_start)?;
+        }
+        let central_end = writer.stream_position()?;
+        let central_size = central_end - central_start;
+        if let Some(split) = split {
+            split.seal(central_end);
         }
-        let central_size = writer.stream_position()? - central_start;
+        let (central_disk, central_offset) = locate(central_start)?;
+        let (last_disk, end_offset) = locate(central_end)?;
+        let files_on_last_disk = match split {
+            Some(_) => record_disks
+                .into_iter()
+                .filter(|&disk| disk == last_disk)
+                .count(),
+            None => self.files.len(),
+        };
 
-        if self.files.len() > spec::ZIP64_ENTRY_THR
-            || central_size.max(central_start) > spec::ZIP64_BYTES_THR
+        if self.force_zip64
+            || self.files.len() > spec::ZIP64_ENTRY_THR
+            || central_size.max(central_offset) > spec::ZIP64_BYTES_THR
         {
             let zip64_footer = spec::Zip64CentralDirectoryEnd {
                 version_made_by: DEFAULT_VERSION as u16,
                 version_needed_to_extract: DEFAULT_VERSION as u16,
-                disk_number: 0,
-                disk_with_central_directory: 0,
-                number_of_files_on_this_disk: self.files.len() as u64,
+                disk_number: last_disk,
+                disk_with_central_directory: central_disk,
+                number_of_files_on_this_disk: files_on_last_disk as u64,
                 number_of_files: self.files.len() as u64,
                 central_directory_size: central_size,
-                central_directory_offset: central_start,
+                central_directory_offset: central_offset,
             };
 
             zip64_footer.write(writer)?;
 
             let zip64_footer = spec::Zip64CentralDirectoryEndLocator {
-                disk_with_central_directory: 0,
-                end_of_central_directory_offset: central_start + central_size,
-                number_of_disks: 1,
+                disk_with_central_directory: last_disk,
+                end_of_central_directory_offset: end_offset,
+                number_of_disks: last_disk + 1,
             };
 
             zip64_footer.write(writer)?;
@@ -1424,13 +2078,13 @@ impl<W: Write + Seek> ZipWriter<W> {
 
         let number_of_files = self.files.len().min(spec::ZIP64_ENTRY_THR) as u16;
         let footer = spec::CentralDirectoryEnd {
-            disk_number: 0,
-            disk_with_central_directory: 0,
+            disk_number: last_disk as u16,
+            disk_with_central_directory: central_disk as u16,
             zip_file_comment: self.comment.clone(),
-            number_of_files_on_this_disk: number_of_files,
+            number_of_files_on_this_disk: files_on_last_disk.min(spec::ZIP64_ENTRY_THR) as u16,
             number_of_files,
             central_directory_size: central_size.min(spec::ZIP64_BYTES_THR) as u32,
-            central_directory_offset: central_start.min(spec::ZIP64_BYTES_THR) as u32,
+            central_directory_offset: central_offset.min(spec::ZIP64_BYTES_THR) as u32,
         };
 
         footer.write(writer)?;
@@ -1467,6 +2121,53 @@ impl<W: Write + Seek> ZipWriter<W> {
     ) -> ZipResult<()> {
         self.shallow_copy_file(&path_to_string(src_path), &path_to_string(dest_path))
     }
+
+    /// Removes an entry from the central directory. Its header and data are left in place as a
+    /// gap, which [`ZipWriter::compact`] can reclaim.
+    pub fn remove_file(&mut self, name: &str) -> ZipResult<()> {
+        self.finish_file()?;
+        self.files
+            .shift_remove(name)
+            .ok_or(ZipError::FileNotFound)?;
+        Ok(())
+    }
+
+    /// Renames an entry in the central directory, keeping its position. As with
+    /// [`ZipWriter::shallow_copy_file`], the local-file header still has the original name, which
+    /// [ZipArchive] ignores in favor of the central directory.
+    pub fn rename_file(&mut self, old_name: &str, new_name: &str) -> ZipResult<()> {
+        self.finish_file()?;
+        self.index_by_name(old_name)?;
+        if self.files.contains_key(new_name) {
+            return Err(InvalidArchive("Duplicate filename"));
+        }
+        let new_name: Box<str> = new_name.into();
+        self.files = mem::take(&mut self.files)
+            .into_iter()
+            .map(|(name, mut data)| {
+                if &*name == old_name {
+                    data.file_name = new_name.clone();
+                    (new_name.clone(), data)
+                } else {
+                    (name, data)
+                }
+            })
+            .collect();
+        Ok(())
+    }
+
+    /// Like `rename_file`, but uses Path arguments.
+    ///
+    /// This function ensures that the '/' path separator is used and normalizes `.` and `..`. It
+    /// ignores any `..` or Windows drive letter that would produce a path outside the ZIP file's
+    /// root.
+    pub fn rename_file_from_path<T: AsRef<Path>, U: AsRef<Path>>(
+        &mut self,
+        old_path: T,
+        new_path: U,
+    ) -> ZipResult<()> {
+        self.rename_file(&path_to_string(old_path), &path_to_string(new_path))
+    }
 }
 
 impl<W: Write + Seek> Drop for ZipWriter<W> {
@@ -1487,6 +2188,7 @@ impl<W: Write + Seek> GenericZipWriter<W> {
         compression: CompressionMethod,
         compression_level: Option<i64>,
         #[cfg(feature = "deflate-zopfli")] zopfli_buffer_size: Option<usize>,
+        #[cfg(feature = "_deflate-flate2")] compression_threads: Option<NonZeroUsize>,
     ) -> ZipResult<SwitchWriterFunction<W>> {
         if let Closed = self {
             return Err(
@@ -1552,17 +2254,20 @@ impl<W: Write + Seek> GenericZipWriter<W> {
                         }
                     }
 
-                    #[cfg(any(
-                        feature = "deflate",
-                        feature = "deflate-zlib",
-                        feature = "deflate-zlib-ng",
-                    ))]
+                    #[cfg(feature = "_deflate-flate2")]
                     {
-                        Ok(Box::new(move |bare| {
-                            GenericZipWriter::Deflater(DeflateEncoder::new(
+                        Ok(Box::new(move |bare| match compression_threads {
+                            Some(threads) if threads.get() > 1 => {
+                                GenericZipWriter::ParallelDeflater(ParallelDeflateEncoder::new(
+                                    bare,
+                                    Compression::new(level),
+                                    threads,
+                                ))
+                            }
+                            _ => GenericZipWriter::Deflater(DeflateEncoder::new(
                                 bare,
                                 Compression::new(level),
-                            ))
+                            )),
                         }))
                     }
                 }
@@ -1606,6 +2311,10 @@ impl<W: Write + Seek> GenericZipWriter<W> {
                 CompressionMethod::Lzma => {
                     Err(UnsupportedArchive("LZMA isn't supported for compression"))
                 }
+                #[cfg(feature = "xz")]
+                CompressionMethod::Xz => {
+                    Err(UnsupportedArchive("XZ isn't supported for compression"))
+                }
                 CompressionMethod::Unsupported(..) => {
                     Err(ZipError::UnsupportedArchive("Unsupported compression"))
                 }
@@ -1616,12 +2325,10 @@ impl<W: Write + Seek> GenericZipWriter<W> {
     fn switch_to(&mut self, make_new_self: SwitchWriterFunction<W>) -> ZipResult<()> {
         let bare = match mem::replace(self, Closed) {
             Storer(w) => w,
-            #[cfg(any(
-                feature = "deflate",
-                feature = "deflate-zlib",
-                feature = "deflate-zlib-ng"
-            ))]
+            #[cfg(feature = "_deflate-flate2")]
             GenericZipWriter::Deflater(w) => w.finish()?,
+            #[cfg(feature = "_deflate-flate2")]
+            GenericZipWriter::ParallelDeflater(w) => w.finish()?,
             #[cfg(feature = "deflate-zopfli")]
             GenericZipWriter::ZopfliDeflater(w) => w.finish()?,
             #[cfg(feature = "deflate-zopfli")]
@@ -1633,6 +2340,7 @@ impl<W: Write + Seek> GenericZipWriter<W> {
             GenericZipWriter::Bzip2(w) => w.finish()?,
             #[cfg(feature = "zstd")]
             GenericZipWriter::Zstd(w) => w.finish()?,
+            GenericZipWriter::Custom(w) => w.finish()?,
             Closed => {
                 return Err(io::Error::new(
                     io::ErrorKind::BrokenPipe,
@@ -1648,12 +2356,10 @@ impl<W: Write + Seek> GenericZipWriter<W> {
     fn ref_mut(&mut self) -> Option<&mut dyn Write> {
         match self {
             Storer(ref mut w) => Some(w as &mut dyn Write),
-            #[cfg(any(
-                feature = "deflate",
-                feature = "deflate-zlib",
-                feature = "deflate-zlib-ng"
-            ))]
+            #[cfg(feature = "_deflate-flate2")]
             GenericZipWriter::Deflater(ref mut w) => Some(w as &mut dyn Write),
+            #[cfg(feature = "_deflate-flate2")]
+            GenericZipWriter::ParallelDeflater(ref mut w) => Some(w as &mut dyn Write),
             #[cfg(feature = "deflate-zopfli")]
             GenericZipWriter::ZopfliDeflater(w) => Some(w as &mut dyn Write),
             #[cfg(feature = "deflate-zopfli")]
@@ -1662,10 +2368,43 @@ impl<W: Write + Seek> GenericZipWriter<W> {
             GenericZipWriter::Bzip2(ref mut w) => Some(w as &mut dyn Write),
             #[cfg(feature = "zstd")]
             GenericZipWriter::Zstd(ref mut w) => Some(w as &mut dyn Write),
+            GenericZipWriter::Custom(ref mut w) => Some(w as &mut dyn Write),
             Closed => None,
         }
     }
 
+    /// Flushes any compressor, and returns the position of the underlying writer if it reflects
+    /// all the data written so far.
+    fn unencrypted_position(&mut self) -> io::Result<Option<u64>> {
+        if let Some(w) = self.ref_mut() {
+            w.flush()?;
+        }
+        let inner = match self {
+            Closed => return Ok(None),
+            Storer(w) => w,
+            #[cfg(feature = "_deflate-flate2")]
+            GenericZipWriter::Deflater(w) => w.get_mut(),
+            #[cfg(feature = "_deflate-flate2")]
+            GenericZipWriter::ParallelDeflater(w) => w.get_mut(),
+            // Zopfli holds back the last chunk written even when flushed, so the position would
+            // understate the compressed size
+            #[cfg(feature = "deflate-zopfli")]
+            GenericZipWriter::ZopfliDeflater(_) | GenericZipWriter::BufferedZopfliDeflater(_) => {
+                return Ok(None)
+            }
+            #[cfg(feature = "bzip2")]
+            GenericZipWriter::Bzip2(w) => w.get_mut(),
+            #[cfg(feature = "zstd")]
+            GenericZipWriter::Zstd(w) => w.get_mut(),
+            // A custom encoder may hold back data until it's finished
+            GenericZipWriter::Custom(_) => return Ok(None),
+        };
+        match inner {
+            MaybeEncrypted::Unencrypted(w) => Ok(Some(w.stream_position()?)),
+            _ => Ok(None),
+        }
+    }
+
     const fn is_closed(&self) -> bool {
         matches!(*self, GenericZipWriter::Closed)
     }
@@ -1687,10 +2426,7 @@ impl<W: Write + Seek> GenericZipWriter<W> {
 
 #[cfg(feature = "_deflate-any")]
 fn deflate_compression_level_range() -> std::ops::RangeInclusive<i64> {
-    let min = if cfg!(feature = "deflate")
-        || cfg!(feature = "deflate-zlib")
-        || cfg!(feature = "deflate-zlib-ng")
-    {
+    let min = if cfg!(feature = "_deflate-flate2") {
         Compression::none().level() as i64
     } else {
         Compression::best().level() as i64 + 1
@@ -1790,11 +2526,38 @@ fn update_local_file_header<T: Write + Seek>(writer: &mut T, file: &ZipFileData)
     Ok(())
 }
 
-fn write_central_directory_header<T: Write>(writer: &mut T, file: &ZipFileData) -> ZipResult<()> {
+/// Writes the data descriptor that follows the data of a streamed file.
+fn write_data_descriptor<T: Write>(writer: &mut T, file: &ZipFileData) -> ZipResult<()> {
+    writer.write_u32_le(spec::DATA_DESCRIPTOR_SIGNATURE)?;
+    writer.write_u32_le(file.crc32)?;
+    if file.large_file {
+        writer.write_u64_le(file.compressed_size)?;
+        writer.write_u64_le(file.uncompressed_size)?;
+    } else {
+        if file.compressed_size > spec::ZIP64_BYTES_THR {
+            return Err(ZipError::Io(io::Error::new(
+                io::ErrorKind::Other,
+                "Large file option has not been set",
+            )));
+        }
+        writer.write_u32_le(file.compressed_size as u32)?;
+        writer.write_u32_le(file.uncompressed_size as u32)?;
+    }
+    Ok(())
+}
+
+/// Writes the central directory record of `file`, whose local header is at `header_start` in the
+/// part with the given disk number.
+fn write_central_directory_header<T: Write>(
+    writer: &mut T,
+    file: &ZipFileData,
+    disk_number: u16,
+    header_start: u64,
+) -> ZipResult<()> {
     // buffer zip64 extra field to determine its variable length
     let mut zip64_extra_field = [0; 28];
     let zip64_extra_field_length =
-        write_central_zip64_extra_field(&mut zip64_extra_field.as_mut(), file)?;
+        write_central_zip64_extra_field(&mut zip64_extra_field.as_mut(), file, header_start)?;
 
     // central file header signature
     writer.write_u32_le(spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE)?;
@@ -1804,11 +2567,16 @@ fn write_central_directory_header<T: Write>(writer: &mut T, file: &ZipFileData)
     // version needed to extract
     writer.write_u16_le(file.version_needed())?;
     // general purpose bit flag
-    let flag = if !file.file_name.is_ascii() {
+    let flag = if !file.file_name.is_ascii() || !file.file_comment.is_ascii() {
         1u16 << 11
     } else {
         0
-    } | if file.encrypted { 1u16 << 0 } else { 0 };
+    } | if file.encrypted { 1u16 << 0 } else { 0 }
+        | if file.using_data_descriptor {
+            1u16 << 3
+        } else {
+            0
+        };
     writer.write_u16_le(flag)?;
     // compression method
     #[allow(deprecated)]
@@ -1831,15 +2599,15 @@ fn write_central_directory_header<T: Write>(writer: &mut T, file: &ZipFileData)
             + file.central_extra_field_len() as u16,
     )?;
     // file comment length
-    writer.write_u16_le(0)?;
+    writer.write_u16_le(file.file_comment.len() as u16)?;
     // disk number start
-    writer.write_u16_le(0)?;
+    writer.write_u16_le(disk_number)?;
     // internal file attributes
     writer.write_u16_le(0)?;
     // external file attributes
     writer.write_u32_le(file.external_attributes)?;
     // relative offset of local header
-    writer.write_u32_le(file.header_start.min(spec::ZIP64_BYTES_THR) as u32)?;
+    writer.write_u32_le(header_start.min(spec::ZIP64_BYTES_THR) as u32)?;
     // file name
     writer.write_all(file.file_name.as_bytes())?;
     // zip64 extra field
@@ -1852,7 +2620,7 @@ fn write_central_directory_header<T: Write>(writer: &mut T, file: &ZipFileData)
         writer.write_all(central_extra_field)?;
     }
     // file comment
-    // <none>
+    writer.write_all(file.file_comment.as_bytes())?;
 
     Ok(())
 }
@@ -1890,6 +2658,32 @@ fn validate_extra_data(header_id: u16, data: &[u8]) -> ZipResult<()> {
     Ok(())
 }
 
+/// Builds an Info-ZIP Unicode extra field, which gives `unicode` as the UTF-8 form of
+/// `header_value`, the field of the header it replaces.
+fn unicode_extra_field(header_id: u16, header_value: &[u8], unicode: &str) -> Vec<u8> {
+    let mut field = Vec::with_capacity(9 + unicode.len());
+    field.extend_from_slice(&header_id.to_le_bytes());
+    field.extend_from_slice(&((5 + unicode.len()) as u16).to_le_bytes());
+    field.push(1);
+    field.extend_from_slice(&crc32fast::hash(header_value).to_le_bytes());
+    field.extend_from_slice(unicode.as_bytes());
+    field
+}
+
+/// Returns the blocks of an extra field other than those with the given header ID.
+fn without_extra_field_block(mut extra_field: &[u8], header_id: u16) -> Vec<u8> {
+    let mut kept = Vec::with_capacity(extra_field.len());
+    while let [id_low, id_high, len_low, len_high, ..] = *extra_field {
+        let len = (4 + u16::from_le_bytes([len_low, len_high]) as usize).min(extra_field.len());
+        if u16::from_le_bytes([id_low, id_high]) != header_id {
+            kept.extend_from_slice(&extra_field[..len]);
+        }
+        extra_field = &extra_field[len..];
+    }
+    kept.extend_from_slice(extra_field);
+    kept
+}
+
 fn write_local_zip64_extra_field<T: Write>(writer: &mut T, file: &ZipFileData) -> ZipResult<()> {
     // This entry in the Local header MUST include BOTH original
     // and compressed file size fields.
@@ -1915,7 +2709,11 @@ fn update_local_zip64_extra_field<T: Write + Seek>(
     Ok(())
 }
 
-fn write_central_zip64_extra_field<T: Write>(writer: &mut T, file: &ZipFileData) -> ZipResult<u16> {
+fn write_central_zip64_extra_field<T: Write>(
+    writer: &mut T,
+    file: &ZipFileData,
+    header_start: u64,
+) -> ZipResult<u16> {
     // The order of the fields in the zip64 extended
     // information record is fixed, but the fields MUST
     // only appear if the corresponding Local or Central
@@ -1923,14 +2721,14 @@ fn write_central_zip64_extra_field<T: Write>(writer: &mut T, file: &ZipFileData)
     let mut size = 0;
     let uncompressed_size = file.uncompressed_size > spec::ZIP64_BYTES_THR;
     let compressed_size = file.compressed_size > spec::ZIP64_BYTES_THR;
-    let header_start = file.header_start > spec::ZIP64_BYTES_THR;
+    let header_start_too_large = header_start > spec::ZIP64_BYTES_THR;
     if uncompressed_size {
         size += 8;
     }
     if compressed_size {
         size += 8;
     }
-    if header_start {
+    if header_start_too_large {
         size += 8;
     }
     if size > 0 {
@@ -1944,8 +2742,8 @@ fn write_central_zip64_extra_field<T: Write>(writer: &mut T, file: &ZipFileData)
         if compressed_size {
             writer.write_u64_le(file.compressed_size)?;
         }
-        if header_start {
-            writer.write_u64_le(file.header_start)?;
+        if header_start_too_large {
+            writer.write_u64_le(header_start)?;
         }
         // Excluded fields:
         // u32: disk start number
@@ -1964,9 +2762,9 @@ const EXTRA_FIELD_MAPPING: [u16; 49] = [
 
 #[cfg(test)]
 mod test {
-    use super::{FileOptions, ZipWriter};
+    use super::{DuplicateNamePolicy, FileOptions, ZipWriter};
     use crate::compression::CompressionMethod;
-    use crate::result::ZipResult;
+    use crate::result::{ZipError, ZipResult};
     use crate::types::DateTime;
     use crate::write::SimpleFileOptions;
     use crate::CompressionMethod::Stored;
@@ -1994,6 +2792,90 @@ mod test {
         assert_eq!(options.permissions, Some(0o777));
     }
 
+    #[test]
+    fn comment_metadata() {
+        let mut metadata = std::collections::BTreeMap::new();
+        metadata.insert("commit".to_string(), "0123abc".to_string());
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_comment_metadata(&metadata).unwrap();
+        assert_eq!(writer.get_comment().unwrap(), r#"{"commit":"0123abc"}"#);
+        let bytes = writer.finish().unwrap().into_inner();
+        let archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(archive.comment_metadata(), Some(metadata));
+    }
+
+    #[test]
+    fn unix_owner() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().unix_owner(1000, 100);
+        writer.start_file("owned", options).unwrap();
+        writer.add_directory("dir/", options).unwrap();
+        writer
+            .start_file("unowned", SimpleFileOptions::default())
+            .unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        for name in ["owned", "dir/"] {
+            let owner = archive.by_name(name).unwrap().unix_owner().unwrap();
+            assert_eq!((owner.uid(), owner.gid()), (1000, 100));
+        }
+        assert!(archive.by_name("unowned").unwrap().unix_owner().is_none());
+    }
+
+    #[test]
+    fn expiry() {
+        use crate::extra_fields::Expiry;
+        use std::time::{Duration, SystemTime};
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let expired = Expiry::new(SystemTime::UNIX_EPOCH, Duration::from_secs(60));
+        let fresh = Expiry::new(SystemTime::now(), Duration::from_secs(3600));
+        let options = SimpleFileOptions::default().unix_owner(1, 2);
+        writer
+            .start_file("expired", options.expiry(expired))
+            .unwrap();
+        writer.start_file("fresh", options.expiry(fresh)).unwrap();
+        writer.start_file("forever", options).unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let file = archive.by_name("expired").unwrap();
+        assert_eq!(file.expiry(), Some(expired));
+        assert!(file.is_expired());
+        assert_eq!(file.unix_owner().unwrap().gid(), 2);
+        drop(file);
+        assert!(!archive.by_name("fresh").unwrap().is_expired());
+        assert!(!archive.by_name("forever").unwrap().is_expired());
+    }
+
+    #[test]
+    fn extended_timestamp() {
+        use crate::extra_fields::ExtendedTimestamp;
+        use std::time::{Duration, SystemTime};
+
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1714635025);
+        let accessed = modified + Duration::from_secs(60);
+        let timestamp = ExtendedTimestamp::new(Some(modified), Some(accessed), None).unwrap();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().extended_timestamp(timestamp);
+        writer.start_file("file", options).unwrap();
+        writer
+            .start_file("plain", SimpleFileOptions::default())
+            .unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let file = archive.by_name("file").unwrap();
+        let timestamp = file.extended_timestamp().unwrap();
+        assert_eq!(timestamp.modified(), Some(modified));
+        assert_eq!(timestamp.accessed(), Some(accessed));
+        assert_eq!(timestamp.created(), None);
+        drop(file);
+        assert!(archive
+            .by_name("plain")
+            .unwrap()
+            .extended_timestamp()
+            .is_none());
+    }
+
     #[test]
     fn write_zip_dir() {
         let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
@@ -2105,14 +2987,22 @@ mod test {
         let options = FileOptions {
             compression_method: CompressionMethod::Stored,
             compression_level: None,
-            last_modified_time: DateTime::default(),
+            last_modified_time: Some(DateTime::default()),
             permissions: Some(33188),
+            unix_owner: None,
+            expiry: None,
+            extended_timestamp: None,
+            checksum_thread: false,
             large_file: false,
             encrypt_with: None,
             extended_options: (),
             alignment: 1,
             #[cfg(feature = "deflate-zopfli")]
             zopfli_buffer_size: None,
+            incompressible_sample_size: None,
+            store_compressed_formats: None,
+            #[cfg(feature = "_deflate-flate2")]
+            compression_threads: None,
         };
         writer.start_file("mimetype", options).unwrap();
         writer
@@ -2142,14 +3032,22 @@ mod test {
         let options = FileOptions {
             compression_method: CompressionMethod::default(),
             compression_level: None,
-            last_modified_time: DateTime::default(),
+            last_modified_time: Some(DateTime::default()),
             permissions: Some(33188),
+            unix_owner: None,
+            expiry: None,
+            extended_timestamp: None,
+            checksum_thread: false,
             large_file: false,
             encrypt_with: None,
             extended_options: (),
             alignment: 0,
             #[cfg(feature = "deflate-zopfli")]
             zopfli_buffer_size: None,
+            incompressible_sample_size: None,
+            store_compressed_formats: None,
+            #[cfg(feature = "_deflate-flate2")]
+            compression_threads: None,
         };
         writer.start_file(RT_TEST_FILENAME, options).unwrap();
         writer.write_all(RT_TEST_TEXT.as_ref()).unwrap();
@@ -2193,14 +3091,22 @@ mod test {
         let options = FileOptions {
             compression_method: CompressionMethod::default(),
             compression_level: None,
-            last_modified_time: DateTime::default(),
+            last_modified_time: Some(DateTime::default()),
             permissions: Some(33188),
+            unix_owner: None,
+            expiry: None,
+            extended_timestamp: None,
+            checksum_thread: false,
             large_file: false,
             encrypt_with: None,
             extended_options: (),
             alignment: 0,
             #[cfg(feature = "deflate-zopfli")]
             zopfli_buffer_size: None,
+            incompressible_sample_size: None,
+            store_compressed_formats: None,
+            #[cfg(feature = "_deflate-flate2")]
+            compression_threads: None,
         };
         writer.start_file(RT_TEST_FILENAME, options).unwrap();
         writer.write_all(RT_TEST_TEXT.as_ref()).unwrap();
@@ -2249,6 +3155,38 @@ mod test {
             .expect_err("Expected duplicate filename not to be allowed");
     }
 
+    #[test]
+    fn duplicate_name_policy() {
+        let write = |policy| {
+            let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+            writer.set_duplicate_name_policy(policy);
+            for contents in [b"first", b"other"] {
+                writer.start_file("a", SimpleFileOptions::default())?;
+                writer.write_all(contents)?;
+            }
+            writer.start_file("b", SimpleFileOptions::default())?;
+            writer.finish()
+        };
+        assert!(write(DuplicateNamePolicy::Error).is_err());
+
+        let bytes = write(DuplicateNamePolicy::ReplaceLast)
+            .unwrap()
+            .into_inner();
+        let mut archive = ZipArchive::new(io::Cursor::new(bytes)).unwrap();
+        assert_eq!(archive.file_names().collect::<Vec<_>>(), ["a", "b"]);
+        let mut contents = String::new();
+        archive
+            .by_name("a")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "other");
+
+        let bytes = write(DuplicateNamePolicy::Allow).unwrap().into_inner();
+        let central_headers = bytes.windows(4).filter(|w| w == b"PK\x01\x02").count();
+        assert_eq!(central_headers, 3);
+    }
+
     #[test]
     fn test_filename_looks_like_zip64_locator() {
         let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
@@ -2498,4 +3436,293 @@ mod test {
         let _ = ZipArchive::new(first_writer.finish()?)?;
         Ok(())
     }
+
+    #[cfg(feature = "_deflate-any")]
+    #[test]
+    fn store_if_incompressible() -> ZipResult<()> {
+        // xorshift output doesn't compress
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let noise: Vec<u8> = (0..100_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        let text = b"All work and no play makes Jack a dull boy. ".repeat(2000);
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .compression_level(Some(6))
+            .store_if_incompressible(Some(1 << 14));
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("noise", options)?;
+        // Write in pieces so the sample fills up partway through
+        for chunk in noise.chunks(5000) {
+            writer.write_all(chunk)?;
+        }
+        writer.start_file("text", options)?;
+        writer.write_all(&text)?;
+        writer.start_file("short_noise", options)?;
+        writer.write_all(&noise[..1000])?;
+        let mut archive = writer.finish_into_readable()?;
+        for (name, expected_method, expected_content) in [
+            ("noise", Stored, &noise[..]),
+            ("text", CompressionMethod::Deflated, &text[..]),
+            ("short_noise", Stored, &noise[..1000]),
+        ] {
+            let mut file = archive.by_name(name)?;
+            assert_eq!(file.compression(), expected_method, "{name}");
+            let mut content = Vec::new();
+            file.read_to_end(&mut content)?;
+            assert_eq!(content, expected_content);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "_deflate-any")]
+    #[test]
+    fn store_compressed_formats() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_defaults(super::Defaults::preserve_host().store_compressed_formats(true));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        for name in ["photo.JPG", "notes.txt", "archive.tar.gz", "gz"] {
+            writer.start_file(name, options)?;
+            writer.write_all(b"contents")?;
+        }
+        writer.start_file("overridden.png", options.store_compressed_formats(false))?;
+        writer.write_all(b"contents")?;
+        let mut archive = writer.finish_into_readable()?;
+        for (name, expected_method) in [
+            ("photo.JPG", Stored),
+            ("notes.txt", CompressionMethod::Deflated),
+            ("archive.tar.gz", Stored),
+            ("gz", CompressionMethod::Deflated),
+            ("overridden.png", CompressionMethod::Deflated),
+        ] {
+            assert_eq!(
+                archive.by_name(name)?.compression(),
+                expected_method,
+                "{name}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn unicode_path_extra_field() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_defaults(super::Defaults::preserve_host().unicode_extra_fields(true));
+        writer.start_file("caf\u{e9}", SimpleFileOptions::default())?;
+        let mut bytes = writer.finish()?.into_inner();
+
+        // Clear the UTF-8 flag, so the name is only known to be UTF-8 from the extra field
+        let central_header = bytes
+            .windows(4)
+            .position(|window| window == b"PK\x01\x02")
+            .unwrap();
+        bytes[central_header + 9] &= !(1 << 3);
+        let archive = ZipArchive::new(Cursor::new(bytes))?;
+        assert_eq!(archive.name_for_index(0), Some("caf\u{e9}"));
+        Ok(())
+    }
+
+    #[test]
+    fn comments() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_defaults(super::Defaults::preserve_host().unicode_extra_fields(true));
+        assert!(writer.set_file_comment("too early").is_err());
+        writer.start_file("a", SimpleFileOptions::default())?;
+        writer.set_file_comment("first")?;
+        writer.set_file_comment("caf\u{e9}")?;
+        writer.start_file("b", SimpleFileOptions::default())?;
+        assert!(writer.set_file_comment("x".repeat(0x10000)).is_err());
+        // A comment that holds an end of central directory record, as long as a comment can be
+        let mut comment = b"PK\x05\x06".to_vec();
+        comment.resize(u16::MAX as usize, b'x');
+        comment[20..22].fill(0);
+        writer.set_raw_comment(comment.clone());
+        let mut bytes = writer.finish()?.into_inner();
+
+        let archive = ZipArchive::new(Cursor::new(bytes.clone()))?;
+        assert_eq!(archive.comment(), comment);
+        assert!(archive.comment_str().is_ok());
+        let mut archive = archive;
+        assert_eq!(archive.by_name("a")?.comment(), "caf\u{e9}");
+        assert_eq!(archive.by_name("b")?.comment(), "");
+        let central_extra = archive.by_name("a")?.extra_data().unwrap().to_vec();
+        assert_eq!(central_extra.windows(2).filter(|id| id == b"uc").count(), 1);
+
+        // Clear the UTF-8 flag, so the comment is only known to be UTF-8 from the extra field
+        let central_header = bytes
+            .windows(4)
+            .position(|window| window == b"PK\x01\x02")
+            .unwrap();
+        bytes[central_header + 9] &= !(1 << 3);
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        assert_eq!(archive.by_name("a")?.comment(), "caf\u{e9}");
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_raw_comment(vec![b'x'; 0x10000]);
+        assert!(writer.finish().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn compact() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(Stored);
+        for (name, content) in [("a", b"first"), ("b", b"other"), ("c", b"third")] {
+            writer.start_file(name, options)?;
+            writer.write_all(content)?;
+        }
+        writer.shallow_copy_file("c", "d")?;
+        let removed_size = writer.files["c"].header_start - writer.files["b"].header_start;
+        writer.remove_file("b")?;
+
+        assert_eq!(writer.compact()?, removed_size);
+        assert_eq!(writer.compact()?, 0);
+        let mut inner = writer.finish()?;
+        let end = inner.position() as usize;
+        inner.get_mut().truncate(end);
+
+        let mut archive = ZipArchive::new(inner)?;
+        assert_eq!(archive.layout()?.wasted(), 0);
+        for (name, expected) in [("a", b"first"), ("c", b"third"), ("d", b"third")] {
+            let mut content = Vec::new();
+            archive.by_name(name)?.read_to_end(&mut content)?;
+            assert_eq!(&content, expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn compact_appended() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(Stored);
+        for name in ["a", "b", "c"] {
+            writer.start_file(name, options)?;
+            writer.write_all(&[b'x'; 100])?;
+        }
+        let mut writer = ZipWriter::new_append(writer.finish()?)?;
+        writer.remove_file("b")?;
+        assert!(writer.compact()? > 0);
+        let inner = writer.finish()?;
+        // Without truncating, the old central directory mustn't be found in the stale tail
+        let end = inner.position() as usize;
+        assert!(inner.get_ref()[end..].iter().all(|&byte| byte == 0));
+        let archive = ZipArchive::new(inner)?;
+        assert_eq!(archive.file_names().collect::<Vec<_>>(), ["a", "c"]);
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for name in ["a", "b", "c"] {
+            writer.start_file(name, options)?;
+            writer.write_all(&[b'x'; 100])?;
+        }
+        writer.remove_file("b")?;
+        assert!(writer.compact()? > 0);
+        writer.start_file("d", options)?;
+        writer.abort_file()?;
+        let bytes = writer.finish()?.into_inner();
+        let footer = bytes.len() - 22;
+        assert_eq!(&bytes[footer..footer + 4], b"PK\x05\x06");
+        assert_eq!(ZipArchive::new(Cursor::new(bytes))?.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn with_prefix() -> ZipResult<()> {
+        let prefix = b"#!/bin/sh\nexec unzip -o \"$0\"\n";
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new())).with_prefix(prefix)?;
+        writer.start_file("file", SimpleFileOptions::default())?;
+        writer.write_all(b"contents")?;
+        assert!(writer.with_prefix(b"late").is_err());
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new())).with_prefix(prefix)?;
+        writer.start_file("file", SimpleFileOptions::default())?;
+        writer.write_all(b"contents")?;
+        let bytes = writer.finish()?.into_inner();
+        assert!(bytes.starts_with(prefix));
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        assert_eq!(archive.by_index(0)?.header_start(), prefix.len() as u64);
+        let mut contents = String::new();
+        archive.by_name("file")?.read_to_string(&mut contents)?;
+        assert_eq!(contents, "contents");
+        Ok(())
+    }
+
+    #[test]
+    fn remove_and_rename() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        for (name, content) in [("a", b"first"), ("b", b"other"), ("c", b"third")] {
+            writer.start_file(name, options)?;
+            writer.write_all(content)?;
+        }
+        let mut writer = ZipWriter::new_append(writer.finish()?)?;
+        writer.remove_file("b")?;
+        assert!(matches!(
+            writer.remove_file("b"),
+            Err(ZipError::FileNotFound)
+        ));
+        assert!(matches!(
+            writer.rename_file("a", "c"),
+            Err(ZipError::InvalidArchive("Duplicate filename"))
+        ));
+        writer.rename_file("a", "z")?;
+
+        let mut archive = writer.finish_into_readable()?;
+        assert_eq!(archive.file_names().collect::<Vec<_>>(), ["z", "c"]);
+        for (name, expected) in [("z", b"first"), ("c", b"third")] {
+            let mut content = Vec::new();
+            archive.by_name(name)?.read_to_end(&mut content)?;
+            assert_eq!(&content, expected);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn compression_threads() -> ZipResult<()> {
+        let data: Vec<u8> = (0..600_000u32).map(|i| (i % 7 * i / 3000) as u8).collect();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .compression_level(Some(6))
+            .compression_threads(std::num::NonZeroUsize::new(4));
+        writer.start_file("parallel", options)?;
+        writer.write_all(&data)?;
+        let mut archive = writer.finish_into_readable()?;
+        let mut file = archive.by_name("parallel")?;
+        assert!(file.compressed_size() < data.len() as u64 / 2);
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)?;
+        assert_eq!(content, data);
+        Ok(())
+    }
+
+    #[test]
+    fn checksum_thread() -> ZipResult<()> {
+        let data: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Stored)
+            .checksum_thread(true);
+        writer.start_file("plain", options)?;
+        writer.write_all(&data)?;
+        writer.start_file("encrypted", options.with_deprecated_encryption(b"password"))?;
+        writer.write_all(&data)?;
+        let mut archive = writer.finish_into_readable()?;
+        for (name, password) in [("plain", None), ("encrypted", Some(&b"password"[..]))] {
+            let mut file = match password {
+                None => archive.by_name(name)?,
+                Some(password) => archive.by_name_decrypt(name, password)?,
+            };
+            assert_eq!(file.crc32(), crc32fast::hash(&data));
+            let mut content = Vec::new();
+            file.read_to_end(&mut content)?;
+            assert_eq!(content, data);
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,86 @@
+//! The CRC-32 of the file being written, optionally computed on a separate thread
+
+use crc32fast::Hasher;
+use std::io;
+use std::mem;
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::thread::{self, JoinHandle};
+
+/// How many written chunks may be waiting for the hashing thread before writes block
+const QUEUED_CHUNKS: usize = 16;
+
+pub(crate) enum Checksum {
+    Inline(Hasher),
+    Thread {
+        sender: SyncSender<Vec<u8>>,
+        handle: JoinHandle<Hasher>,
+    },
+}
+
+impl Default for Checksum {
+    fn default() -> Self {
+        Checksum::Inline(Hasher::new())
+    }
+}
+
+impl Checksum {
+    /// Starts a thread that hashes the chunks passed to [`Checksum::update`].
+    pub(crate) fn spawn() -> Self {
+        let (sender, receiver) = sync_channel::<Vec<u8>>(QUEUED_CHUNKS);
+        let handle = thread::spawn(move || {
+            let mut hasher = Hasher::new();
+            for chunk in receiver {
+                hasher.update(&chunk);
+            }
+            hasher
+        });
+        Checksum::Thread { sender, handle }
+    }
+
+    pub(crate) fn update(&mut self, buf: &[u8]) {
+        match self {
+            Checksum::Inline(hasher) => hasher.update(buf),
+            Checksum::Thread { sender, .. } => {
+                // If the thread is gone, finalize() reports why
+                let _ = sender.send(buf.to_vec());
+            }
+        }
+    }
+
+    /// Returns the CRC-32 of everything passed to [`Checksum::update`], waiting for the hashing
+    /// thread to catch up if there is one. Nothing more can be hashed on that thread afterwards.
+    pub(crate) fn finalize(&mut self) -> io::Result<u32> {
+        let hasher = match mem::take(self) {
+            Checksum::Inline(hasher) => hasher,
+            Checksum::Thread { sender, handle } => {
+                drop(sender);
+                handle
+                    .join()
+                    .map_err(|_| io::Error::new(io::ErrorKind::Other, "Checksum thread panicked"))?
+            }
+        };
+        let crc32 = hasher.clone().finalize();
+        *self = Checksum::Inline(hasher);
+        Ok(crc32)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Checksum;
+
+    #[test]
+    fn matches_inline() {
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i * 7) as u8).collect();
+        let mut inline = Checksum::default();
+        let mut threaded = Checksum::spawn();
+        for chunk in data.chunks(1000) {
+            inline.update(chunk);
+            threaded.update(chunk);
+        }
+        let expected = crc32fast::hash(&data);
+        assert_eq!(inline.finalize().unwrap(), expected);
+        assert_eq!(threaded.finalize().unwrap(), expected);
+        assert_eq!(threaded.finalize().unwrap(), expected);
+    }
+}
@@ -0,0 +1,165 @@
+//! Settings for entries whose [`FileOptions`](super::FileOptions) leave them unset
+
+use crate::types::DateTime;
+#[cfg(feature = "time")]
+use time::OffsetDateTime;
+
+/// The last-modified time, Unix permissions, store-vs-compress checks and Unicode extra fields a
+/// [`ZipWriter`](super::ZipWriter) gives entries whose [`FileOptions`](super::FileOptions) don't
+/// set them, set with
+/// [`ZipWriter::set_defaults`](super::ZipWriter::set_defaults).
+///
+/// [`Defaults::portable`] makes the output depend only on the files written, so two machines
+/// produce identical archives from identical input. [`Defaults::preserve_host`], which is the
+/// default, records when each entry was written according to the host's clock.
+///
+/// ```
+/// use std::io::Write;
+/// use zip::write::{Defaults, SimpleFileOptions};
+/// use zip::ZipWriter;
+///
+/// # fn main() -> zip::result::ZipResult<()> {
+/// let mut zip = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+/// zip.set_defaults(Defaults::portable());
+/// zip.start_file("hello.txt", SimpleFileOptions::default())?;
+/// zip.write_all(b"Hello, world!")?;
+/// let mut archive = zip.finish_into_readable()?;
+/// let file = archive.by_index(0)?;
+/// assert_eq!(file.last_modified().datepart(), zip::DateTime::default().datepart());
+/// assert_eq!(file.unix_mode(), Some(0o100644));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Defaults {
+    last_modified_time: Option<DateTime>,
+    file_permissions: u32,
+    directory_permissions: u32,
+    symlink_permissions: u32,
+    incompressible_sample_size: Option<usize>,
+    store_compressed_formats: bool,
+    unicode_extra_fields: bool,
+}
+
+impl Defaults {
+    /// 1980-01-01 00:00:00, the earliest time an MS-DOS timestamp can hold, and permissions of
+    /// `0o644` for files, `0o755` for directories and `0o777` for symlinks.
+    pub const fn portable() -> Self {
+        Defaults {
+            last_modified_time: Some(DateTime::from_msdos(0x0021, 0)),
+            file_permissions: 0o644,
+            directory_permissions: 0o755,
+            symlink_permissions: 0o777,
+            incompressible_sample_size: None,
+            store_compressed_formats: false,
+            unicode_extra_fields: false,
+        }
+    }
+
+    /// The time each entry is started, if the `time` feature is enabled, and the same permissions
+    /// as [`Defaults::portable`].
+    ///
+    /// Without the `time` feature the host's clock can't be read, so this uses 1980-01-01 as well.
+    pub const fn preserve_host() -> Self {
+        Defaults {
+            last_modified_time: None,
+            ..Self::portable()
+        }
+    }
+
+    /// Set the last-modified time.
+    #[must_use]
+    pub const fn last_modified_time(mut self, mod_time: DateTime) -> Self {
+        self.last_modified_time = Some(mod_time);
+        self
+    }
+
+    /// Set the permissions of files. Only the permission bits (`0o777`) are kept.
+    #[must_use]
+    pub const fn file_permissions(mut self, mode: u32) -> Self {
+        self.file_permissions = mode & 0o777;
+        self
+    }
+
+    /// Set the permissions of directories. Only the permission bits (`0o777`) are kept.
+    #[must_use]
+    pub const fn directory_permissions(mut self, mode: u32) -> Self {
+        self.directory_permissions = mode & 0o777;
+        self
+    }
+
+    /// Set the permissions of symlinks. Only the permission bits (`0o777`) are kept.
+    #[must_use]
+    pub const fn symlink_permissions(mut self, mode: u32) -> Self {
+        self.symlink_permissions = mode & 0o777;
+        self
+    }
+
+    /// Set the sample size used to check whether files compress, as with
+    /// [`FileOptions::store_if_incompressible`](super::FileOptions::store_if_incompressible).
+    /// The default is `None`, which disables the check.
+    #[must_use]
+    pub const fn store_if_incompressible(mut self, sample_size: Option<usize>) -> Self {
+        self.incompressible_sample_size = sample_size;
+        self
+    }
+
+    /// Set whether to store files whose extension marks them as already compressed, as with
+    /// [`FileOptions::store_compressed_formats`](super::FileOptions::store_compressed_formats).
+    /// The default is `false`.
+    #[must_use]
+    pub const fn store_compressed_formats(mut self, store: bool) -> Self {
+        self.store_compressed_formats = store;
+        self
+    }
+
+    /// Set whether files with non-ASCII names also get an Info-ZIP Unicode Path extra field
+    /// (0x7075), and those with non-ASCII comments an Info-ZIP Unicode Comment extra field
+    /// (0x6375), for tools that ignore the UTF-8 flag but read those fields. The default is
+    /// `false`.
+    #[must_use]
+    pub const fn unicode_extra_fields(mut self, unicode_extra_fields: bool) -> Self {
+        self.unicode_extra_fields = unicode_extra_fields;
+        self
+    }
+
+    pub(crate) fn timestamp(&self) -> DateTime {
+        match self.last_modified_time {
+            Some(mod_time) => mod_time,
+            #[cfg(feature = "time")]
+            None => OffsetDateTime::now_utc().try_into().unwrap_or_default(),
+            #[cfg(not(feature = "time"))]
+            None => DateTime::default(),
+        }
+    }
+
+    pub(crate) const fn file_mode(&self) -> u32 {
+        self.file_permissions
+    }
+
+    pub(crate) const fn directory_mode(&self) -> u32 {
+        self.directory_permissions
+    }
+
+    pub(crate) const fn symlink_mode(&self) -> u32 {
+        self.symlink_permissions
+    }
+
+    pub(crate) const fn incompressible_sample_size(&self) -> Option<usize> {
+        self.incompressible_sample_size
+    }
+
+    pub(crate) const fn stores_compressed_formats(&self) -> bool {
+        self.store_compressed_formats
+    }
+
+    pub(crate) const fn writes_unicode_extra_fields(&self) -> bool {
+        self.unicode_extra_fields
+    }
+}
+
+impl Default for Defaults {
+    fn default() -> Self {
+        Self::preserve_host()
+    }
+}
@@ -0,0 +1,196 @@
+//! Default [`SimpleFileOptions`] chosen by file extension
+
+use super::SimpleFileOptions;
+use crate::compression::CompressionMethod;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Extensions of formats that are already compressed, so deflating them again wastes CPU time and
+/// usually makes the entry slightly larger.
+pub(crate) const STORED_EXTENSIONS: &[&str] = &[
+    "7z", "apk", "avif", "br", "bz2", "docx", "epub", "flac", "gif", "gz", "heic", "jar", "jpeg",
+    "jpg", "lz", "lz4", "lzma", "m4a", "mkv", "mov", "mp3", "mp4", "odt", "ogg", "opus", "png",
+    "pptx", "rar", "tbz2", "tgz", "txz", "webm", "webp", "whl", "woff", "woff2", "xlsx", "xz",
+    "zip", "zst",
+];
+
+/// Whether `name` ends in one of the [`STORED_EXTENSIONS`].
+pub(crate) fn has_compressed_extension(name: &str) -> bool {
+    let Some((_, extension)) = name.rsplit_once('.') else {
+        return false;
+    };
+    STORED_EXTENSIONS
+        .iter()
+        .any(|known| known.eq_ignore_ascii_case(extension))
+}
+
+/// Extensions of text formats, which usually compress well enough to be worth the highest
+/// compression level.
+#[cfg(feature = "_deflate-any")]
+const TEXT_EXTENSIONS: &[&str] = &[
+    "c", "cfg", "cpp", "css", "csv", "h", "htm", "html", "ini", "java", "js", "json", "log", "md",
+    "py", "rs", "svg", "toml", "ts", "tsv", "txt", "xml", "yaml", "yml",
+];
+
+/// A table mapping file extensions to the [`SimpleFileOptions`] used for files with that extension.
+///
+/// Extensions are matched case-insensitively and without the leading dot. Files whose extension
+/// isn't in the table use the fallback options.
+///
+/// ```
+/// use std::io::Write;
+/// use zip::write::{ExtensionOptions, SimpleFileOptions};
+/// use zip::{CompressionMethod, ZipWriter};
+///
+/// # fn main() -> zip::result::ZipResult<()> {
+/// let mut table = ExtensionOptions::default();
+/// table.insert("dat", SimpleFileOptions::default().compression_method(CompressionMethod::Stored));
+///
+/// let mut zip = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+/// for name in ["photo.PNG", "readme.txt", "blob.dat"] {
+///     zip.start_file(name, table.options_for(name))?;
+///     zip.write_all(b"...")?;
+/// }
+/// let archive = zip.finish_into_readable()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct ExtensionOptions {
+    by_extension: HashMap<Box<str>, SimpleFileOptions>,
+    fallback: SimpleFileOptions,
+}
+
+impl ExtensionOptions {
+    /// Creates a table with no extensions, so every file uses `fallback`.
+    pub fn empty(fallback: SimpleFileOptions) -> Self {
+        ExtensionOptions {
+            by_extension: HashMap::new(),
+            fallback,
+        }
+    }
+
+    /// Creates a table like [`ExtensionOptions::default`], built on `base` instead of the default
+    /// options: every entry, and the fallback, keeps the settings of `base` other than the
+    /// compression method and level. Files in already-compressed formats are stored, and text
+    /// files use the highest deflate level if `base` uses deflate.
+    pub fn with_base(base: SimpleFileOptions) -> Self {
+        let mut table = ExtensionOptions::empty(base);
+        let stored = base
+            .compression_method(CompressionMethod::Stored)
+            .compression_level(None);
+        for ext in STORED_EXTENSIONS {
+            table.insert(ext, stored);
+        }
+        #[cfg(feature = "_deflate-any")]
+        if base.compression_method == CompressionMethod::Deflated {
+            // The best non-zopfli level, unless zopfli is the only deflate backend
+            let level = 9.max(*super::deflate_compression_level_range().start());
+            let text = base.compression_level(Some(level));
+            for ext in TEXT_EXTENSIONS {
+                table.insert(ext, text);
+            }
+        }
+        table
+    }
+
+    /// Sets the options used for files with the given extension, replacing any previous entry.
+    pub fn insert(&mut self, extension: &str, options: SimpleFileOptions) -> &mut Self {
+        self.by_extension
+            .insert(extension.to_ascii_lowercase().into_boxed_str(), options);
+        self
+    }
+
+    /// Removes the entry for the given extension, so those files use the fallback options.
+    pub fn remove(&mut self, extension: &str) -> Option<SimpleFileOptions> {
+        self.by_extension
+            .remove(extension.to_ascii_lowercase().as_str())
+    }
+
+    /// Sets the options used for files whose extension isn't in the table.
+    pub fn set_fallback(&mut self, fallback: SimpleFileOptions) -> &mut Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Returns the options used for files whose extension isn't in the table.
+    pub const fn fallback(&self) -> SimpleFileOptions {
+        self.fallback
+    }
+
+    /// Returns the options to use for the file at `path`.
+    pub fn options_for<P: AsRef<Path>>(&self, path: P) -> SimpleFileOptions {
+        path.as_ref()
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| self.by_extension.get(ext.to_ascii_lowercase().as_str()))
+            .copied()
+            .unwrap_or(self.fallback)
+    }
+}
+
+impl Default for ExtensionOptions {
+    /// Stores files in already-compressed formats, and uses the highest deflate level for text.
+    fn default() -> Self {
+        ExtensionOptions::with_base(SimpleFileOptions::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ExtensionOptions;
+    use crate::write::SimpleFileOptions;
+    use crate::CompressionMethod;
+
+    #[test]
+    fn lookup_by_extension() {
+        let mut table = ExtensionOptions::default();
+        assert_eq!(
+            table.options_for("a/b.MP4").compression_method,
+            CompressionMethod::Stored
+        );
+        assert_eq!(
+            table.options_for("no_extension").compression_method,
+            CompressionMethod::default()
+        );
+        #[cfg(feature = "deflate")]
+        assert_eq!(table.options_for("notes.txt").compression_level, Some(9));
+
+        table.remove("mp4");
+        assert_eq!(
+            table.options_for("a/b.mp4").compression_method,
+            CompressionMethod::default()
+        );
+        table.set_fallback(
+            SimpleFileOptions::default().compression_method(CompressionMethod::Stored),
+        );
+        assert_eq!(
+            table.options_for("a/b.mp4").compression_method,
+            CompressionMethod::Stored
+        );
+    }
+    #[test]
+    fn with_base() {
+        let base = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Stored)
+            .unix_permissions(0o755);
+        let table = ExtensionOptions::with_base(base);
+        for name in ["a.png", "b.txt", "c"] {
+            let options = table.options_for(name);
+            assert_eq!(options.compression_method, CompressionMethod::Stored);
+            assert_eq!(options.permissions, Some(0o755));
+        }
+
+        #[cfg(feature = "deflate")]
+        {
+            let base = base.compression_method(CompressionMethod::Deflated);
+            let table = ExtensionOptions::with_base(base);
+            assert_eq!(
+                table.options_for("a.png").compression_method,
+                CompressionMethod::Stored
+            );
+            assert_eq!(table.options_for("b.txt").compression_level, Some(9));
+            assert_eq!(table.options_for("b.txt").permissions, Some(0o755));
+        }
+    }
+}
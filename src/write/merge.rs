@@ -0,0 +1,120 @@
+//! Merging archives whose entries share names
+
+use crate::read::ZipArchive;
+use crate::result::{ZipError, ZipResult};
+use crate::write::ZipWriter;
+use indexmap::map::Entry;
+use std::io::{Read, Seek, Write};
+
+/// What [`ZipWriter::merge_archive_with_policy`] does with an entry whose name is already in the
+/// archive being written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConflictPolicy {
+    /// Fail the merge, before anything is copied.
+    Error,
+    /// Keep the entry already in the archive.
+    Skip,
+    /// Replace the entry already in the archive with the merged one, keeping its position in the
+    /// central directory. This is what [`ZipWriter::merge_archive`] does.
+    Replace,
+}
+
+impl<W: Write + Seek> ZipWriter<W> {
+    /// Copies all the entries of `source` into this archive, like [`ZipWriter::merge_archive`],
+    /// handling entries whose names are already in this archive as `policy` says.
+    ///
+    /// The entries' data is copied in bulk without being decompressed, so the data of entries
+    /// that lose a conflict, whichever side they came from, stays in the archive even though
+    /// nothing refers to it.
+    pub fn merge_archive_with_policy<R: Read + Seek>(
+        &mut self,
+        mut source: ZipArchive<R>,
+        policy: ConflictPolicy,
+    ) -> ZipResult<()> {
+        self.finish_file()?;
+        if policy == ConflictPolicy::Error
+            && source
+                .file_names()
+                .any(|name| self.files.contains_key(name))
+        {
+            return Err(ZipError::InvalidArchive("Duplicate filename"));
+        }
+
+        /* Ensure we accept the file contents on faith (and avoid overwriting the data).
+         * See raw_copy_file_rename(). */
+        self.writing_to_file = true;
+        self.writing_raw = true;
+
+        let new_files = source.merge_contents(self.inner.get_plain())?;
+        for (name, file) in new_files {
+            match self.files.entry(name) {
+                Entry::Occupied(_) if policy == ConflictPolicy::Skip => {}
+                Entry::Occupied(mut entry) => {
+                    entry.insert(file);
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(file);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ConflictPolicy;
+    use crate::write::SimpleFileOptions;
+    use crate::{ZipArchive, ZipWriter};
+    use std::io::{Cursor, Read, Write};
+
+    fn shard(files: &[(&str, &str)]) -> ZipArchive<Cursor<Vec<u8>>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in files {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+        writer.finish_into_readable().unwrap()
+    }
+
+    fn merged(policy: ConflictPolicy) -> Option<Vec<(String, String)>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .merge_archive_with_policy(shard(&[("a", "first"), ("b", "first")]), policy)
+            .unwrap();
+        writer
+            .merge_archive_with_policy(shard(&[("b", "second"), ("c", "second")]), policy)
+            .ok()?;
+        let mut archive = writer.finish_into_readable().unwrap();
+        let entries = (0..archive.len())
+            .map(|i| {
+                let mut file = archive.by_index(i).unwrap();
+                let mut contents = String::new();
+                file.read_to_string(&mut contents).unwrap();
+                (file.name().to_owned(), contents)
+            })
+            .collect();
+        Some(entries)
+    }
+
+    #[test]
+    fn conflicts() {
+        let entries = |list: &[(&str, &str)]| {
+            list.iter()
+                .map(|(name, contents)| (name.to_string(), contents.to_string()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(merged(ConflictPolicy::Error), None);
+        assert_eq!(
+            merged(ConflictPolicy::Skip).unwrap(),
+            entries(&[("a", "first"), ("b", "first"), ("c", "second")])
+        );
+        assert_eq!(
+            merged(ConflictPolicy::Replace).unwrap(),
+            entries(&[("a", "first"), ("b", "second"), ("c", "second")])
+        );
+    }
+}
@@ -0,0 +1,239 @@
+//! A deflate encoder that compresses chunks of its input on several threads
+
+use flate2::{Compress, Compression, FlushCompress, Status};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::mem;
+use std::num::NonZeroUsize;
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// The amount of input each thread compresses at once
+const CHUNK_SIZE: usize = 128 * 1024;
+
+/// A chunk for a worker thread to compress, and where to send the result
+struct Job {
+    data: Vec<u8>,
+    last: bool,
+    result: SyncSender<io::Result<Vec<u8>>>,
+}
+
+/// Splits its input into chunks and deflates each one independently, as pigz does.
+///
+/// Every chunk but the last ends with a sync flush, which byte-aligns the output without marking
+/// the final block, so the compressed chunks concatenate into a single valid deflate stream.
+/// Matches can't reach back across a chunk boundary, which costs a little compression.
+///
+/// Chunks are compressed by worker threads that live as long as the encoder, while compressed
+/// chunks are written in order as they complete. At most two chunks per thread are in flight, so
+/// a slow writer holds back the input rather than letting it pile up in memory.
+pub(crate) struct ParallelDeflateEncoder<W: Write> {
+    inner: W,
+    level: Compression,
+    threads: usize,
+    /// Queues chunks for the workers, once they're started. Dropping it stops them.
+    jobs: Option<SyncSender<Job>>,
+    /// The results of the chunks in flight, in input order
+    pending: VecDeque<Receiver<io::Result<Vec<u8>>>>,
+    current: Vec<u8>,
+}
+
+impl<W: Write> ParallelDeflateEncoder<W> {
+    pub(crate) fn new(inner: W, level: Compression, threads: NonZeroUsize) -> Self {
+        ParallelDeflateEncoder {
+            inner,
+            level,
+            threads: threads.get(),
+            jobs: None,
+            pending: VecDeque::with_capacity(2 * threads.get()),
+            current: Vec::with_capacity(CHUNK_SIZE),
+        }
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Starts the worker threads, which take chunks from a shared queue until it's closed.
+    fn start_workers(&mut self) -> io::Result<SyncSender<Job>> {
+        let (jobs, queue) = mpsc::sync_channel::<Job>(self.threads);
+        let queue = Arc::new(Mutex::new(queue));
+        for _ in 0..self.threads {
+            let queue = queue.clone();
+            let level = self.level;
+            thread::Builder::new()
+                .name("zip-deflate".to_owned())
+                .spawn(move || loop {
+                    // Only hold the lock while waiting, so the other workers can compress
+                    let job = match queue.lock() {
+                        Ok(queue) => queue.recv(),
+                        Err(_) => return,
+                    };
+                    let Ok(job) = job else {
+                        return;
+                    };
+                    // If the encoder was dropped, nobody is waiting for the result
+                    let _ = job.result.send(compress_chunk(&job.data, level, job.last));
+                })?;
+        }
+        Ok(jobs)
+    }
+
+    /// Hands a chunk to the workers, first writing any chunks that are done, and waiting for the
+    /// oldest one if too many are in flight.
+    fn submit(&mut self, data: Vec<u8>, last: bool) -> io::Result<()> {
+        self.write_completed()?;
+        if self.pending.len() >= 2 * self.threads {
+            self.write_next()?;
+        }
+        let jobs = match self.jobs.take() {
+            Some(jobs) => jobs,
+            None => self.start_workers()?,
+        };
+        let (result, receiver) = mpsc::sync_channel(1);
+        // Workers only go away by panicking
+        jobs.send(Job { data, last, result })
+            .map_err(|_| worker_panicked())?;
+        self.jobs = Some(jobs);
+        self.pending.push_back(receiver);
+        Ok(())
+    }
+
+    /// Writes the oldest chunk in flight, waiting for it to be compressed.
+    fn write_next(&mut self) -> io::Result<()> {
+        if let Some(receiver) = self.pending.pop_front() {
+            // The worker drops the sender without sending if it panics
+            let output = receiver.recv().map_err(|_| worker_panicked())??;
+            self.inner.write_all(&output)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the chunks in flight that are already compressed, up to the first that isn't.
+    fn write_completed(&mut self) -> io::Result<()> {
+        while let Some(receiver) = self.pending.front() {
+            let output = match receiver.try_recv() {
+                Ok(output) => output?,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Err(worker_panicked()),
+            };
+            self.pending.pop_front();
+            self.inner.write_all(&output)?;
+        }
+        Ok(())
+    }
+
+    fn write_all_pending(&mut self) -> io::Result<()> {
+        while !self.pending.is_empty() {
+            self.write_next()?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        // An empty final chunk still makes the final block
+        let last = mem::take(&mut self.current);
+        self.submit(last, true)?;
+        self.write_all_pending()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for ParallelDeflateEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(CHUNK_SIZE - self.current.len());
+        self.current.extend_from_slice(&buf[..len]);
+        if self.current.len() == CHUNK_SIZE {
+            let next = Vec::with_capacity(CHUNK_SIZE);
+            let chunk = mem::replace(&mut self.current, next);
+            self.submit(chunk, false)?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.current.is_empty() {
+            let chunk = mem::replace(&mut self.current, Vec::with_capacity(CHUNK_SIZE));
+            self.submit(chunk, false)?;
+        }
+        self.write_all_pending()?;
+        self.inner.flush()
+    }
+}
+
+fn worker_panicked() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "A deflate worker thread panicked")
+}
+
+fn compress_chunk(data: &[u8], level: Compression, last: bool) -> io::Result<Vec<u8>> {
+    let mut compress = Compress::new(level, false);
+    let mut output = Vec::with_capacity(data.len() / 2 + 64);
+    let flush = if last {
+        FlushCompress::Finish
+    } else {
+        FlushCompress::Sync
+    };
+    loop {
+        if output.len() == output.capacity() {
+            output.reserve(output.capacity());
+        }
+        let consumed = compress.total_in() as usize;
+        let status = compress
+            .compress_vec(&data[consumed..], &mut output, flush)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        let done = if last {
+            status == Status::StreamEnd
+        } else {
+            // The flush is complete once the compressor stops filling the whole output buffer
+            compress.total_in() as usize == data.len() && output.len() < output.capacity()
+        };
+        if done {
+            return Ok(output);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ParallelDeflateEncoder, CHUNK_SIZE};
+    use flate2::read::DeflateDecoder;
+    use flate2::Compression;
+    use std::io::{Read, Write};
+    use std::num::NonZeroUsize;
+    use std::sync::mpsc;
+
+    #[test]
+    fn round_trip() {
+        let data: Vec<u8> = (0..CHUNK_SIZE * 5 + 123)
+            .map(|i| (i % 251) as u8 ^ (i / 1000) as u8)
+            .collect();
+        for len in [0, 10, CHUNK_SIZE, data.len()] {
+            let threads = NonZeroUsize::new(3).unwrap();
+            let mut encoder =
+                ParallelDeflateEncoder::new(Vec::new(), Compression::default(), threads);
+            encoder.write_all(&data[..len / 2]).unwrap();
+            encoder.flush().unwrap();
+            encoder.write_all(&data[len / 2..len]).unwrap();
+            let compressed = encoder.finish().unwrap();
+
+            let mut decompressed = Vec::new();
+            DeflateDecoder::new(&compressed[..])
+                .read_to_end(&mut decompressed)
+                .unwrap();
+            assert_eq!(decompressed, &data[..len]);
+        }
+    }
+
+    #[test]
+    fn worker_panic() {
+        let threads = NonZeroUsize::new(2).unwrap();
+        let mut encoder = ParallelDeflateEncoder::new(Vec::new(), Compression::default(), threads);
+        encoder.write_all(&[0; CHUNK_SIZE]).unwrap();
+        // A worker that panics drops the sender without sending a result
+        let (sender, receiver) = mpsc::sync_channel(1);
+        drop(sender);
+        encoder.pending.push_back(receiver);
+        assert!(encoder.flush().is_err());
+    }
+}
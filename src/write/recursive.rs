@@ -0,0 +1,310 @@
+//! Adding a directory tree from the file system
+
+use crate::extra_fields::ExtendedTimestamp;
+use crate::read::glob_matches;
+use crate::result::ZipResult;
+use crate::write::{FileOptionExtension, FileOptions, ZipWriter};
+#[cfg(feature = "time")]
+use crate::DateTime;
+use std::fs::{self, File, Metadata};
+use std::io::{self, Seek, Write};
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+#[cfg(feature = "time")]
+use time::OffsetDateTime;
+
+/// Options for [`ZipWriter::add_directory_recursive`]
+#[derive(Clone, Debug)]
+pub struct AddOptions<'k, T: FileOptionExtension> {
+    file_options: FileOptions<'k, T>,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    follow_symlinks: bool,
+    preserve_metadata: bool,
+}
+
+impl<'k, T: FileOptionExtension> Default for AddOptions<'k, T> {
+    fn default() -> Self {
+        AddOptions {
+            file_options: FileOptions::default(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            follow_symlinks: false,
+            preserve_metadata: true,
+        }
+    }
+}
+
+impl<'k, T: FileOptionExtension> AddOptions<'k, T> {
+    /// Set the options every entry is written with, before the permissions and timestamps of its
+    /// file are applied.
+    #[must_use]
+    pub fn file_options(mut self, file_options: FileOptions<'k, T>) -> Self {
+        self.file_options = file_options;
+        self
+    }
+
+    /// Only add the entries whose paths, relative to the directory being added, match the glob
+    /// `pattern`, or one of the other include patterns. Directories that don't match are still
+    /// walked for entries that do. With no include patterns, everything is added.
+    ///
+    /// Patterns are written as for [`crate::ZipArchive::extract_glob`], with `/` separators.
+    #[must_use]
+    pub fn include(mut self, pattern: &str) -> Self {
+        self.include.push(pattern.to_owned());
+        self
+    }
+
+    /// Leave out the entries whose relative paths match the glob `pattern`. A directory that's
+    /// left out isn't walked, so nothing in it is added either.
+    #[must_use]
+    pub fn exclude(mut self, pattern: &str) -> Self {
+        self.exclude.push(pattern.to_owned());
+        self
+    }
+
+    /// Set whether symlinks are followed, adding what they point to, rather than stored as
+    /// symlinks. A symlink to a directory that's already being walked is skipped, so loops end.
+    ///
+    /// The default is false.
+    #[must_use]
+    pub const fn follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Set whether each entry gets its file's Unix permissions, on Unix, and last-modified time.
+    /// The time is stored in an extended timestamp extra field, and with the `time` feature as
+    /// the entry's MS-DOS time too.
+    ///
+    /// The default is true. Turn it off, along with [`super::Defaults::portable`], to make the
+    /// archive depend only on the files' contents.
+    #[must_use]
+    pub const fn preserve_metadata(mut self, preserve_metadata: bool) -> Self {
+        self.preserve_metadata = preserve_metadata;
+        self
+    }
+}
+
+/// The state of one call to [`ZipWriter::add_directory_recursive`]
+struct Walk<'a, 'k, T: FileOptionExtension> {
+    options: &'a AddOptions<'k, T>,
+    include: Vec<Vec<&'a str>>,
+    exclude: Vec<Vec<&'a str>>,
+    prefix: &'a str,
+    /// The canonical paths of the directories being walked, when following symlinks
+    ancestors: Vec<PathBuf>,
+}
+
+impl<W: Write + Seek> ZipWriter<W> {
+    /// Add the contents of the directory `src_dir`, and of every directory in it, under the
+    /// directory `prefix` of the archive, or at its root if `prefix` is empty. `src_dir` itself
+    /// isn't added as an entry.
+    ///
+    /// Files, directories and symlinks are added in order of name within each directory, so
+    /// the archive doesn't depend on the order the file system lists them in.
+    pub fn add_directory_recursive<P: AsRef<Path>, T: FileOptionExtension + Clone>(
+        &mut self,
+        src_dir: P,
+        prefix: &str,
+        options: AddOptions<'_, T>,
+    ) -> ZipResult<()> {
+        let mut walk = Walk {
+            options: &options,
+            include: split_patterns(&options.include),
+            exclude: split_patterns(&options.exclude),
+            prefix: prefix.trim_end_matches('/'),
+            ancestors: Vec::new(),
+        };
+        self.add_tree(&mut walk, src_dir.as_ref(), &mut Vec::new())
+    }
+
+    fn add_tree<T: FileOptionExtension + Clone>(
+        &mut self,
+        walk: &mut Walk<'_, '_, T>,
+        dir: &Path,
+        relative: &mut Vec<String>,
+    ) -> ZipResult<()> {
+        if walk.options.follow_symlinks {
+            walk.ancestors.push(dir.canonicalize()?);
+        }
+        let mut children = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+        children.sort_by_key(|child| child.file_name());
+        for child in children {
+            let path = child.path();
+            relative.push(child.file_name().to_string_lossy().into_owned());
+            let components: Vec<&str> = relative.iter().map(String::as_str).collect();
+            let matches = |patterns: &[Vec<&str>]| {
+                patterns
+                    .iter()
+                    .any(|pattern| glob_matches(pattern, &components))
+            };
+            if !matches(&walk.exclude) {
+                let included = walk.include.is_empty() || matches(&walk.include);
+                let name = match walk.prefix {
+                    "" => relative.join("/"),
+                    prefix => format!("{prefix}/{}", relative.join("/")),
+                };
+                let metadata = match walk.options.follow_symlinks {
+                    true => fs::metadata(&path)?,
+                    false => fs::symlink_metadata(&path)?,
+                };
+                let mut options = walk.options.file_options.clone();
+                if walk.options.preserve_metadata {
+                    options = with_metadata(options, &metadata);
+                }
+                if metadata.is_dir() {
+                    // Following a symlink back up the tree would never end
+                    let is_loop = walk.options.follow_symlinks
+                        && walk.ancestors.contains(&path.canonicalize()?);
+                    if !is_loop {
+                        if included {
+                            self.add_directory(name, options)?;
+                        }
+                        self.add_tree(walk, &path, relative)?;
+                    }
+                } else if metadata.is_symlink() {
+                    if included {
+                        let target = fs::read_link(&path)?;
+                        let target = target.to_string_lossy().replace(MAIN_SEPARATOR, "/");
+                        self.add_symlink(name, target, options)?;
+                    }
+                } else if included {
+                    self.start_file(name, options)?;
+                    io::copy(&mut File::open(&path)?, self)?;
+                }
+            }
+            relative.pop();
+        }
+        if walk.options.follow_symlinks {
+            walk.ancestors.pop();
+        }
+        Ok(())
+    }
+}
+
+/// Splits glob patterns into their `/`-separated components.
+fn split_patterns(patterns: &[String]) -> Vec<Vec<&str>> {
+    patterns
+        .iter()
+        .map(|pattern| pattern.split('/').collect())
+        .collect()
+}
+
+/// Applies a file's permissions and last-modified time to the options of its entry.
+fn with_metadata<'k, T: FileOptionExtension>(
+    mut options: FileOptions<'k, T>,
+    metadata: &Metadata,
+) -> FileOptions<'k, T> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        options = options.unix_permissions(metadata.permissions().mode());
+    }
+    if let Ok(modified) = metadata.modified() {
+        #[cfg(feature = "time")]
+        if let Ok(time) = DateTime::try_from(OffsetDateTime::from(modified)) {
+            options = options.last_modified_time(time);
+        }
+        if let Ok(timestamp) = ExtendedTimestamp::new(Some(modified), None, None) {
+            options = options.extended_timestamp(timestamp);
+        }
+    }
+    options
+}
+
+#[cfg(test)]
+mod test {
+    use super::AddOptions;
+    use crate::test_util::TempDir;
+    use crate::write::SimpleFileOptions;
+    use crate::ZipWriter;
+    use std::fs;
+    use std::io::{Cursor, Read};
+
+    #[test]
+    fn add_directory_recursive() {
+        let temp = TempDir::new("add-recursive");
+        let directory = temp.path();
+        fs::create_dir_all(directory.join("src/nested")).unwrap();
+        fs::create_dir_all(directory.join("target")).unwrap();
+        fs::write(directory.join("README.md"), "readme").unwrap();
+        fs::write(directory.join("src/lib.rs"), "lib").unwrap();
+        fs::write(directory.join("src/nested/mod.rs"), "mod").unwrap();
+        fs::write(directory.join("src/notes.txt"), "notes").unwrap();
+        fs::write(directory.join("target/out.rs"), "out").unwrap();
+        fs::write(directory.join("src/run.rs"), "run").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let permissions = fs::Permissions::from_mode(0o755);
+            fs::set_permissions(directory.join("src/run.rs"), permissions).unwrap();
+        }
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .add_directory_recursive(directory, "", AddOptions::<()>::default())
+            .unwrap();
+        let options = AddOptions::default()
+            .file_options(SimpleFileOptions::default())
+            .include("**/*.rs")
+            .exclude("target");
+        writer
+            .add_directory_recursive(directory, "filtered/", options)
+            .unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+
+        let names: Vec<&str> = archive.file_names().collect();
+        let filtered = [
+            "filtered/src/lib.rs",
+            "filtered/src/nested/mod.rs",
+            "filtered/src/run.rs",
+        ];
+        assert_eq!(names[names.len() - filtered.len()..], filtered);
+        assert!(names.contains(&"README.md"));
+        assert!(names.contains(&"src/nested/"));
+        assert!(names.contains(&"target/out.rs"));
+
+        let mut file = archive.by_name("src/nested/mod.rs").unwrap();
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "mod");
+        assert!(file.extended_timestamp().unwrap().modified().is_some());
+        drop(file);
+        #[cfg(unix)]
+        assert_eq!(
+            archive.by_name("src/run.rs").unwrap().unix_mode(),
+            Some(0o100755)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks() {
+        let temp = TempDir::new("add-recursive-links");
+        let directory = temp.path();
+        fs::create_dir_all(directory.join("dir")).unwrap();
+        fs::write(directory.join("dir/file"), "contents").unwrap();
+        std::os::unix::fs::symlink("dir/file", directory.join("link")).unwrap();
+        std::os::unix::fs::symlink("..", directory.join("dir/loop")).unwrap();
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .add_directory_recursive(directory, "stored", AddOptions::<()>::default())
+            .unwrap();
+        let options = AddOptions::<()>::default().follow_symlinks(true);
+        writer
+            .add_directory_recursive(directory, "followed", options)
+            .unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+
+        assert!(archive.by_name("stored/link").unwrap().is_symlink());
+        assert!(archive.by_name("stored/dir/loop").unwrap().is_symlink());
+        let mut file = archive.by_name("followed/link").unwrap();
+        assert!(!file.is_symlink());
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "contents");
+        drop(file);
+        assert!(archive.by_name("followed/dir/loop/").is_err());
+    }
+}
@@ -0,0 +1,253 @@
+//! Writing archives split into parts of a fixed size, such as `.z01`, `.z02` … `.zip`
+
+use crate::result::{ZipError, ZipResult};
+use crate::unstable::LittleEndianWriteExt;
+use crate::write::ZipWriter;
+use std::fs::{self, File};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// The smallest part size that the ZIP specification allows
+const MIN_PART_SIZE: u64 = 64 * 1024;
+
+/// Starts the first part of a split archive
+const SPANNING_SIGNATURE: u32 = 0x08074b50;
+
+/// Replaces [`SPANNING_SIGNATURE`] in a split archive that turned out to fit in one part
+const SINGLE_PART_MARKER: u32 = 0x30304b50;
+
+/// Where each position in the archive ends up, shared by [`SplitWriter`] and the [`ZipWriter`]
+/// writing to it.
+#[derive(Debug)]
+pub(crate) struct SplitLayout {
+    part_size: u64,
+    /// Where the last part starts, once the end of the archive is being written
+    last_part_start: AtomicU64,
+}
+
+impl SplitLayout {
+    /// Returns the disk number and offset within its part of a position in the archive.
+    pub(crate) fn locate(&self, position: u64) -> ZipResult<(u32, u64)> {
+        let last_part_start = self.last_part_start.load(Ordering::Relaxed);
+        let start = if position >= last_part_start {
+            last_part_start
+        } else {
+            position - position % self.part_size
+        };
+        let disk = start / self.part_size;
+        if disk >= u16::MAX as u64 {
+            return Err(ZipError::InvalidArchive("Split archive has too many parts"));
+        }
+        Ok((disk as u32, position - start))
+    }
+
+    /// Keeps everything from `position` onward in the part it's in, since the records that end
+    /// the archive mustn't be split.
+    pub(crate) fn seal(&self, position: u64) {
+        let start = position - position % self.part_size;
+        let _ = self.last_part_start.compare_exchange(
+            u64::MAX,
+            start,
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        );
+    }
+
+    fn last_disk(&self) -> Option<u32> {
+        match self.last_part_start.load(Ordering::Relaxed) {
+            u64::MAX => None,
+            start => Some((start / self.part_size) as u32),
+        }
+    }
+}
+
+/// Writes an archive to files of a fixed size, for [`ZipWriter::new_split`].
+///
+/// The parts are named after the archive's path with the extensions `.z01`, `.z02` and so on,
+/// except for the last, which is written to the path itself once the archive is finished.
+#[derive(Debug)]
+pub struct SplitWriter {
+    path: PathBuf,
+    layout: Arc<SplitLayout>,
+    parts: Vec<File>,
+    last_part_named: bool,
+    position: u64,
+    len: u64,
+}
+
+impl SplitWriter {
+    /// Returns the number of parts written so far.
+    pub fn len(&self) -> usize {
+        self.parts.len()
+    }
+
+    /// Returns whether no parts have been written.
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+
+    fn numbered_path(&self, disk: u32) -> PathBuf {
+        self.path.with_extension(format!("z{:02}", disk + 1))
+    }
+
+    fn part(&mut self, disk: u32) -> io::Result<&mut File> {
+        let last_disk = self.layout.last_disk();
+        while self.parts.len() <= disk as usize {
+            let new_disk = self.parts.len() as u32;
+            let path = if last_disk == Some(new_disk) {
+                self.last_part_named = true;
+                self.path.clone()
+            } else {
+                self.numbered_path(new_disk)
+            };
+            self.parts.push(File::create(path)?);
+        }
+        if last_disk == Some(disk) && !self.last_part_named {
+            fs::rename(self.numbered_path(disk), &self.path)?;
+            self.last_part_named = true;
+            if disk == 0 {
+                // The archive fits in one part, so it isn't really split
+                let part = &mut self.parts[0];
+                part.seek(SeekFrom::Start(0))?;
+                part.write_u32_le(SINGLE_PART_MARKER)?;
+            }
+        }
+        Ok(&mut self.parts[disk as usize])
+    }
+}
+
+impl Write for SplitWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let (disk, offset) = self.layout.locate(self.position)?;
+        let limit = if self.layout.last_disk() == Some(disk) {
+            buf.len()
+        } else {
+            buf.len().min(
+                (self.layout.part_size - offset)
+                    .try_into()
+                    .unwrap_or(usize::MAX),
+            )
+        };
+        let part = self.part(disk)?;
+        part.seek(SeekFrom::Start(offset))?;
+        let count = part.write(&buf[..limit])?;
+        self.position += count as u64;
+        self.len = self.len.max(self.position);
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.parts.iter_mut().try_for_each(File::flush)
+    }
+}
+
+impl Seek for SplitWriter {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position = position.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
+
+impl ZipWriter<SplitWriter> {
+    /// Initializes an archive that's split into parts of `part_size` bytes, which must be at least
+    /// 64 KiB, as WinZip and 7-Zip do for archives that have to fit on removable media or under a
+    /// size limit. The parts can be read back with [`crate::ZipArchive::new_spanned`].
+    ///
+    /// `path` is where the last part is written when the archive is finished, and should end in
+    /// `.zip`; the other parts are named by replacing the extension with `.z01`, `.z02` and so on.
+    /// The last part may be larger than `part_size`, because the records that end the archive
+    /// aren't split.
+    pub fn new_split<P: AsRef<Path>>(path: P, part_size: u64) -> ZipResult<Self> {
+        if part_size < MIN_PART_SIZE {
+            return Err(ZipError::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Parts of a split archive must be at least 64 KiB",
+            )));
+        }
+        let layout = Arc::new(SplitLayout {
+            part_size,
+            last_part_start: AtomicU64::new(u64::MAX),
+        });
+        let mut inner = SplitWriter {
+            path: path.as_ref().to_path_buf(),
+            layout: layout.clone(),
+            parts: Vec::new(),
+            last_part_named: false,
+            position: 0,
+            len: 0,
+        };
+        // Offsets in the first part count from the start of this signature
+        inner.write_u32_le(SPANNING_SIGNATURE)?;
+        let mut writer = ZipWriter::new(inner);
+        writer.split = Some(layout);
+        Ok(writer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::read::SpannedReader;
+    use crate::test_util::TempDir;
+    use crate::write::SimpleFileOptions;
+    use crate::{CompressionMethod, ZipArchive, ZipWriter};
+    use std::fs;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn split_round_trip() {
+        let temp = TempDir::new("split-round-trip");
+        let directory = temp.path();
+        let path = directory.join("archive.zip");
+        let part_size = 64 * 1024;
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        let contents: Vec<Vec<u8>> = (0..5u8).map(|i| vec![i; 50_000]).collect();
+
+        let mut writer = ZipWriter::new_split(&path, part_size).unwrap();
+        for (i, content) in contents.iter().enumerate() {
+            writer.start_file(format!("file{i}"), options).unwrap();
+            writer.write_all(content).unwrap();
+        }
+        let parts = writer.finish().unwrap().len();
+        assert_eq!(parts, 4);
+        for disk in 1..parts {
+            let part = path.with_extension(format!("z{disk:02}"));
+            assert_eq!(fs::metadata(part).unwrap().len(), part_size);
+        }
+
+        let mut archive =
+            ZipArchive::new_spanned(SpannedReader::open(&path).unwrap().into_inner()).unwrap();
+        assert_eq!(archive.len(), contents.len());
+        for (i, content) in contents.iter().enumerate() {
+            let mut read = Vec::new();
+            let mut file = archive.by_name(&format!("file{i}")).unwrap();
+            file.read_to_end(&mut read).unwrap();
+            assert_eq!(&read, content);
+        }
+
+        // An archive that fits in one part can be read as usual
+        let mut writer = ZipWriter::new_split(&path, part_size).unwrap();
+        writer.start_file("small", options).unwrap();
+        writer.write_all(b"contents").unwrap();
+        assert_eq!(writer.finish().unwrap().len(), 1);
+        let mut archive = ZipArchive::new(fs::File::open(&path).unwrap()).unwrap();
+        let mut read = String::new();
+        archive
+            .by_name("small")
+            .unwrap()
+            .read_to_string(&mut read)
+            .unwrap();
+        assert_eq!(read, "contents");
+    }
+}
@@ -0,0 +1,174 @@
+//! Writing archives to sinks that can't seek, such as pipes and sockets
+
+use crate::write::ZipWriter;
+use std::io::{self, Seek, SeekFrom, Write};
+
+/// Gives a writer that can't seek the position tracking that [`ZipWriter`] needs, for
+/// [`ZipWriter::new_stream`].
+///
+/// Seeking anywhere other than the current position fails.
+#[derive(Debug)]
+pub struct StreamWriter<W> {
+    inner: W,
+    position: u64,
+}
+
+impl<W> StreamWriter<W> {
+    /// Returns a reference to the wrapped writer.
+    pub const fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Unwrap and return the wrapped writer
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for StreamWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let count = self.inner.write(buf)?;
+        self.position += count as u64;
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W> Seek for StreamWriter<W> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match pos {
+            SeekFrom::Start(offset) if offset == self.position => Ok(self.position),
+            SeekFrom::Current(0) | SeekFrom::End(0) => Ok(self.position),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Can't seek in an archive that's being streamed",
+            )),
+        }
+    }
+}
+
+impl<W: Write> ZipWriter<StreamWriter<W>> {
+    /// Initializes an archive that's written strictly in order, to a writer that can't seek, such
+    /// as standard output or a socket.
+    ///
+    /// Since the sizes and CRC-32 of a file aren't known until it's finished, each file's local
+    /// header leaves them out and a data descriptor after the file's data records them instead.
+    /// Set [`crate::write::FileOptions::large_file`] on files that may reach 4 GiB, or
+    /// [`ZipWriter::set_force_zip64`] if any might, because the descriptor's size fields are chosen
+    /// when the file is started.
+    ///
+    /// Files can't be aligned or encrypted with AES, because both need the header rewritten, and
+    /// [`crate::write::FileOptions::store_if_incompressible`] is ignored. An aborted file's data
+    /// stays in the stream, although nothing refers to it.
+    pub fn new_stream(inner: W) -> Self {
+        let mut writer = ZipWriter::new(StreamWriter { inner, position: 0 });
+        writer.streaming = true;
+        writer
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::read::stream::{ZipStreamFileMetadata, ZipStreamReader, ZipStreamVisitor};
+    use crate::read::ZipFile;
+    use crate::result::ZipResult;
+    use crate::write::SimpleFileOptions;
+    use crate::{CompressionMethod, ZipArchive, ZipWriter};
+    use std::io::{Cursor, Read, Write};
+
+    #[test]
+    fn stream_round_trip() {
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        let mut writer = ZipWriter::new_stream(Vec::new());
+        writer.start_file("stored", options).unwrap();
+        writer.write_all(b"stored contents").unwrap();
+        writer.add_directory("directory", options).unwrap();
+        writer
+            .start_file("large", options.large_file(true))
+            .unwrap();
+        writer.write_all(b"large contents").unwrap();
+        writer.add_symlink("link", "stored", options).unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let mut archive = ZipArchive::new(Cursor::new(&bytes)).unwrap();
+        let mut contents = String::new();
+        archive
+            .by_name("large")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "large contents");
+
+        // The data descriptors let a streaming reader find where each file ends
+        struct Collect(Vec<(String, Vec<u8>)>);
+        impl ZipStreamVisitor for Collect {
+            fn visit_file(&mut self, file: &mut ZipFile<'_>) -> ZipResult<()> {
+                let mut contents = Vec::new();
+                file.read_to_end(&mut contents)?;
+                self.0.push((file.name().to_owned(), contents));
+                Ok(())
+            }
+            fn visit_additional_metadata(&mut self, _: &ZipStreamFileMetadata) -> ZipResult<()> {
+                Ok(())
+            }
+        }
+        let mut collect = Collect(Vec::new());
+        ZipStreamReader::new(Cursor::new(&bytes))
+            .visit(&mut collect)
+            .unwrap();
+        let expected = [
+            ("stored", &b"stored contents"[..]),
+            ("directory/", b""),
+            ("large", b"large contents"),
+            ("link", b"stored"),
+        ];
+        assert_eq!(collect.0.len(), expected.len());
+        for ((name, contents), (expected_name, expected_contents)) in collect.0.iter().zip(expected)
+        {
+            assert_eq!(name, expected_name);
+            assert_eq!(contents, expected_contents);
+        }
+    }
+
+    #[test]
+    fn stream_force_zip64() {
+        let mut writer = ZipWriter::new_stream(Vec::new());
+        writer.set_force_zip64(true);
+        writer
+            .start_file("file", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"contents").unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+        assert!(bytes.windows(4).any(|window| window == b"PK\x06\x06"));
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let mut contents = String::new();
+        archive
+            .by_name("file")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "contents");
+    }
+
+    #[test]
+    fn stream_encrypted() {
+        let mut writer = ZipWriter::new_stream(Vec::new());
+        let options = SimpleFileOptions::default().with_deprecated_encryption(b"password");
+        writer.start_file("encrypted", options).unwrap();
+        writer.write_all(b"encrypted contents").unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let mut contents = String::new();
+        archive
+            .by_name_decrypt("encrypted", b"password")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "encrypted contents");
+    }
+}
@@ -115,6 +115,24 @@ fn aes256_deflated_roundtrip() {
     test_extract_encrypted_file(&mut archive, "test.txt", "some password", "other password");
 }
 
+#[test]
+fn aes256_deflated_finish_into_readable() {
+    // The archive is read from the writer's own records, which must use the method in the AES
+    // extra field as an archive parsed from bytes does
+    let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+    zip.start_file(
+        "test.txt",
+        SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .with_aes_encryption(AesMode::Aes256, "some password"),
+    )
+    .unwrap();
+    zip.write_all(SECRET_CONTENT.as_bytes()).unwrap();
+
+    let mut archive = zip.finish_into_readable().unwrap();
+    test_extract_encrypted_file(&mut archive, "test.txt", "some password", "other password");
+}
+
 fn test_extract_encrypted_file<R: io::Read + io::Seek>(
     archive: &mut ZipArchive<R>,
     file_name: &str,
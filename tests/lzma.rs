@@ -19,3 +19,26 @@ fn decompress_lzma() {
         .expect("couldn't read encrypted and compressed file");
     assert_eq!("Hello world\n", String::from_utf8(content).unwrap());
 }
+
+#[test]
+fn decompress_lzma_from_python() {
+    // CPython's zipfile writes the ZIP LZMA header, and no end-of-stream marker
+    let mut archive = ZipArchive::new(io::Cursor::new(include_bytes!("data/lzma_python.zip")))
+        .expect("couldn't open test zip file");
+
+    for (name, expected) in [
+        ("short.txt", b"hi".to_vec()),
+        (
+            "lorem.txt",
+            b"Lorem ipsum dolor sit amet, consectetur adipiscing elit. ".repeat(40),
+        ),
+    ] {
+        let mut content = Vec::new();
+        archive
+            .by_name(name)
+            .expect("couldn't find file in archive")
+            .read_to_end(&mut content)
+            .expect("couldn't decompress file");
+        assert_eq!(content, expected);
+    }
+}
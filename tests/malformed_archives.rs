@@ -0,0 +1,63 @@
+//! Reading truncated and corrupted archives must fail with an error rather than panic.
+
+use std::io::{self, Read, Seek, SeekFrom};
+use zip::read::read_zipfile_from_stream;
+use zip::ZipArchive;
+
+const MAX_BYTES_TO_READ: u64 = 1 << 20;
+
+fn read_all(data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut archive = ZipArchive::new(io::Cursor::new(data))?;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?.take(MAX_BYTES_TO_READ);
+        io::copy(&mut file, &mut io::sink())?;
+    }
+    for i in 0..archive.len() {
+        if let Ok(file) = archive.by_index_decrypt(i, b"helloworld") {
+            io::copy(&mut file.take(MAX_BYTES_TO_READ), &mut io::sink())?;
+        }
+    }
+    let mut reader = archive.into_inner();
+    reader.seek(SeekFrom::Start(0))?;
+    while let Ok(Some(file)) = read_zipfile_from_stream(&mut reader) {
+        io::copy(&mut file.take(MAX_BYTES_TO_READ), &mut io::sink())?;
+    }
+    Ok(())
+}
+
+const ARCHIVES: &[&[u8]] = &[
+    include_bytes!("data/aes_archive.zip"),
+    include_bytes!("data/comment_garbage.zip"),
+    include_bytes!("data/data_descriptor.zip"),
+    include_bytes!("data/deflate64_issue_25.zip"),
+    include_bytes!("data/extended_timestamp.zip"),
+    include_bytes!("data/files_and_dirs.zip"),
+    include_bytes!("data/lzma.zip"),
+    include_bytes!("data/mimetype.zip"),
+    include_bytes!("data/xz.zip"),
+    include_bytes!("data/zip64_demo.zip"),
+];
+
+#[test]
+fn truncated() {
+    for archive in ARCHIVES {
+        for len in 0..archive.len() {
+            let _ = read_all(&archive[..len]);
+        }
+    }
+}
+
+#[test]
+fn corrupted() {
+    for archive in ARCHIVES {
+        let mut data = archive.to_vec();
+        for i in 0..data.len() {
+            let original = data[i];
+            for byte in [0x00, 0xff, original ^ 0x80] {
+                data[i] = byte;
+                let _ = read_all(&data);
+            }
+            data[i] = original;
+        }
+    }
+}